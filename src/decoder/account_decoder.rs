@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Arc, RwLock};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use solana_sdk::bs58;
+
+use crate::decoder::address_lookup_table::{AddressLookupTable, AddressLookupTableDecoder};
+use crate::decoder::openbook_market_state::{MarketState, OpenBookMarketDecoder};
+use crate::decoder::raydium_amm_v4_state::{AmmInfo, RaydiumAmmV4AccountDecoder};
+use crate::decoder::spl_token_accounts::{Mint, SplTokenAccountDecoder, TokenAccount};
+use crate::decoder::token_2022::{Token2022Account, Token2022AccountDecoder, Token2022Mint};
+use crate::models::solana::solana_program_notification::ProgramAccountData;
+
+/// Typed view of an account's data, produced by the decoder registered for the account owner.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum DecodedAccount {
+    SplTokenMint(Mint),
+    SplTokenAccount(TokenAccount),
+    Token2022Mint(Token2022Mint),
+    Token2022Account(Token2022Account),
+    // Boxed, pool and market states are several times the size of the other accounts
+    RaydiumAmmV4(Box<AmmInfo>),
+    OpenBookMarket(Box<MarketState>),
+    AddressLookupTable(AddressLookupTable),
+}
+
+/// Decodes the raw data of accounts owned by a single program.
+pub trait AccountDecoder {
+    fn owner(&self) -> &str;
+
+    // Ok(None) for accounts of the program this decoder does not model (ie. a Raydium open orders account)
+    fn decode(&self, data: &[u8]) -> Result<Option<DecodedAccount>, Box<dyn Error>>;
+}
+
+/// Account decoders keyed by owner program. Register new ones on `ACCOUNT_DECODERS` to decode more programs.
+pub struct AccountDecoderRegistry {
+    decoders: HashMap<String, Arc<dyn AccountDecoder + Send + Sync>>,
+}
+
+impl AccountDecoderRegistry {
+    pub fn new() -> Self {
+        Self { decoders: HashMap::new() }
+    }
+
+    /// SPL Token, Token-2022, Raydium AMM v4, OpenBook and address lookup tables
+    pub fn with_defaults() -> Self {
+        let mut registry = Self::new();
+        registry.register(SplTokenAccountDecoder);
+        registry.register(Token2022AccountDecoder);
+        registry.register(RaydiumAmmV4AccountDecoder);
+        registry.register(OpenBookMarketDecoder);
+        registry.register(AddressLookupTableDecoder);
+        registry
+    }
+
+    /// Replaces any decoder already registered for the same owner.
+    pub fn register<D: AccountDecoder + Send + Sync + 'static>(&mut self, decoder: D) {
+        self.decoders.insert(decoder.owner().to_string(), Arc::new(decoder));
+    }
+
+    /// None when there is no decoder for the owner, the node already parsed the data (jsonParsed) or the decoder does not model the account.
+    pub fn decode(&self, owner: &str, data: &ProgramAccountData) -> Result<Option<DecodedAccount>, Box<dyn Error>> {
+        let Some(decoder) = self.decoders.get(owner) else {
+            return Ok(None);
+        };
+        match raw_account_data(data)? {
+            Some(bytes) => decoder.decode(&bytes),
+            None => Ok(None),
+        }
+    }
+}
+
+lazy_static! {
+    /// Used while deserializing notifications, which happens far from anything we could pass a registry to.
+    pub static ref ACCOUNT_DECODERS: RwLock<AccountDecoderRegistry> = RwLock::new(AccountDecoderRegistry::with_defaults());
+}
+
+/// Raw bytes of account data as sent by the node, None for jsonParsed data.
+pub fn raw_account_data(data: &ProgramAccountData) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+    match data {
+        ProgramAccountData::Encoded(encoded) => {
+            let payload = encoded.first().map(String::as_str).unwrap_or_default();
+            let encoding = encoded.get(1).map(String::as_str).unwrap_or("base58");
+            decode_payload(payload, encoding).map(Some)
+        }
+        // Legacy "binary" encoding, a bare base58 string
+        ProgramAccountData::Binary(payload) => decode_payload(payload, "base58").map(Some),
+        ProgramAccountData::ParsedJson { .. } => Ok(None),
+    }
+}
+
+pub fn decode_payload(payload: &str, encoding: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    match encoding {
+        "base58" => Ok(bs58::decode(payload).into_vec()?),
+        "base64" => Ok(STANDARD.decode(payload)?),
+        "base64+zstd" => {
+            let compressed = STANDARD.decode(payload)?;
+            Ok(zstd::decode_all(compressed.as_slice())?)
+        }
+        encoding => Err(format!("unsupported account data encoding {}", encoding).into()),
+    }
+}
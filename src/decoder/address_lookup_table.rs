@@ -0,0 +1,73 @@
+// https://github.com/anza-xyz/agave/blob/master/sdk/program/src/address_lookup_table/state.rs
+use std::error::Error;
+
+use serde::{Deserialize, Serialize};
+
+use crate::decoder::account_decoder::{AccountDecoder, DecodedAccount};
+use crate::decoder::byte_reader::ByteReader;
+
+pub const ADDRESS_LOOKUP_TABLE_PROGRAM_ID: &str = "AddressLookupTab1e1111111111111111111111111";
+
+// The metadata is padded to 56 bytes whether the authority is set or not, the addresses follow
+pub const LOOKUP_TABLE_META_SIZE: usize = 56;
+// ProgramState::LookupTable, 0 being an uninitialized table
+const LOOKUP_TABLE_TAG: u32 = 1;
+
+/// An address lookup table: the accounts v0 messages point to by index instead of listing them.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AddressLookupTable {
+    // u64::MAX while the table is active
+    pub deactivation_slot: u64,
+    pub last_extended_slot: u64,
+    pub last_extended_slot_start_index: u8,
+    // None once the table is frozen
+    pub authority: Option<String>,
+    pub addresses: Vec<String>,
+}
+
+impl AddressLookupTable {
+    pub fn unpack(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let mut reader = ByteReader::new(data);
+        let tag = reader.u32()?;
+        if tag != LOOKUP_TABLE_TAG {
+            return Err(format!("not an initialized lookup table (state {})", tag).into());
+        }
+        let deactivation_slot = reader.u64()?;
+        let last_extended_slot = reader.u64()?;
+        let last_extended_slot_start_index = reader.u8()?;
+        let authority = reader.option_pubkey()?;
+
+        let addresses_data = data.get(LOOKUP_TABLE_META_SIZE..).unwrap_or_default();
+        if addresses_data.len() % 32 != 0 {
+            return Err(format!("lookup table addresses take {} bytes, not a multiple of 32", addresses_data.len()).into());
+        }
+        let mut addresses_reader = ByteReader::new(addresses_data);
+        let addresses = (0..addresses_data.len() / 32).map(|_| addresses_reader.pubkey()).collect::<Result<Vec<String>, _>>()?;
+
+        Ok(AddressLookupTable { deactivation_slot, last_extended_slot, last_extended_slot_start_index, authority, addresses })
+    }
+
+    /// The addresses at `indexes`, in that order. Fails when an index is past the end of the table (extended since it was read).
+    pub fn lookup(&self, indexes: &[u8]) -> Result<Vec<String>, Box<dyn Error>> {
+        indexes.iter()
+            .map(|index| self.addresses.get(*index as usize)
+                .cloned()
+                .ok_or_else(|| format!("lookup table index {} out of its {} addresses", index, self.addresses.len()).into()))
+            .collect()
+    }
+}
+
+pub struct AddressLookupTableDecoder;
+
+impl AccountDecoder for AddressLookupTableDecoder {
+    fn owner(&self) -> &str {
+        ADDRESS_LOOKUP_TABLE_PROGRAM_ID
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Option<DecodedAccount>, Box<dyn Error>> {
+        if data.len() < LOOKUP_TABLE_META_SIZE {
+            return Ok(None);
+        }
+        Ok(Some(DecodedAccount::AddressLookupTable(AddressLookupTable::unpack(data)?)))
+    }
+}
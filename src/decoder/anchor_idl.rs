@@ -0,0 +1,435 @@
+// https://www.anchor-lang.com/docs/idl, both the legacy (< 0.30) and the current IDL spec
+use std::error::Error;
+use std::io::Read;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use solana_sdk::hash::hash;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::decoder::byte_reader::ByteReader;
+use crate::decoder::instruction_decoder::{DecodedInstruction, InstructionDecoder};
+
+/// Discriminator of the self-CPI Anchor programs emit events with (`emit_cpi!`), not an instruction of the IDL
+pub const EVENT_IX_TAG: [u8; 8] = [0xe4, 0x45, 0xa5, 0x2e, 0x51, 0xcb, 0x9a, 0x1d];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+// Nested defined types deeper than this are rejected, a malformed IDL could recurse forever
+const MAX_TYPE_DEPTH: usize = 32;
+
+/**
+An Anchor IDL: the instructions of a program with their discriminator, accounts and Borsh encoded args, plus the types they use.
+Legacy IDLs carry no discriminator, it is derived from the instruction name as Anchor does (`sha256("global:<snake_case_name>")[..8]`),
+and from the event name for the events (`sha256("event:<Name>")[..8]`).
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AnchorIdl {
+    pub name: String,
+    // Program id, legacy IDLs only have it under metadata once deployed
+    pub address: Option<String>,
+    pub instructions: Vec<IdlInstruction>,
+    pub types: Vec<IdlTypeDef>,
+    pub events: Vec<IdlEvent>,
+    pub errors: Vec<IdlErrorCode>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct RawIdl {
+    #[serde(default)]
+    address: Option<String>,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    metadata: Option<Value>,
+    instructions: Vec<IdlInstruction>,
+    #[serde(default)]
+    types: Vec<IdlTypeDef>,
+    #[serde(default)]
+    events: Vec<IdlEvent>,
+    #[serde(default)]
+    errors: Vec<IdlErrorCode>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IdlInstruction {
+    pub name: String,
+    #[serde(default)]
+    pub discriminator: Option<Vec<u8>>,
+    pub accounts: Vec<IdlAccountItem>,
+    pub args: Vec<IdlField>,
+}
+
+/// A single account or a group of accounts (an Anchor `Accounts` struct nested in another one)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum IdlAccountItem {
+    Composite {
+        name: String,
+        accounts: Vec<IdlAccountItem>,
+    },
+    Single {
+        name: String,
+        #[serde(default, alias = "isMut")]
+        writable: bool,
+        #[serde(default, alias = "isSigner")]
+        signer: bool,
+        #[serde(default, alias = "isOptional")]
+        optional: bool,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IdlField {
+    pub name: String,
+    // "u64", {"vec": ...}, {"defined": ...}..., decoded by `AnchorIdl::decode_type`
+    #[serde(rename = "type")]
+    pub field_type: Value,
+}
+
+/// An event the program emits (`emit!` logs it as `Program data:`, `emit_cpi!` passes it to a self-CPI)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IdlEvent {
+    pub name: String,
+    #[serde(default)]
+    pub discriminator: Option<Vec<u8>>,
+    // Legacy IDLs only, the current spec declares the event struct among the types under the event name
+    #[serde(default)]
+    pub fields: Option<Vec<Value>>,
+}
+
+/// A custom error of the program (`#[error_code]`), 6000 and up
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IdlErrorCode {
+    pub code: u32,
+    pub name: String,
+    #[serde(default)]
+    pub msg: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IdlTypeDef {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub body: IdlTypeDefBody,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum IdlTypeDefBody {
+    Struct {
+        #[serde(default)]
+        fields: Option<Vec<Value>>,
+    },
+    Enum {
+        variants: Vec<IdlEnumVariant>,
+    },
+    Type {
+        alias: Value,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IdlEnumVariant {
+    pub name: String,
+    #[serde(default)]
+    pub fields: Option<Vec<Value>>,
+}
+
+/// An account of an instruction, named after the IDL. Composite accounts are named `group.account`, accounts past the ones
+/// of the IDL are `remaining`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NamedAccount {
+    pub name: String,
+    pub pubkey: String,
+    pub writable: bool,
+    pub signer: bool,
+}
+
+/// An instruction of an Anchor program decoded through its IDL
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AnchorInstruction {
+    pub program_id: String,
+    // Name of the program in the IDL
+    pub program: String,
+    pub name: String,
+    pub accounts: Vec<NamedAccount>,
+    // Object keyed by arg name. u128/i128 are strings, pubkeys base58, bytes base58.
+    pub args: Value,
+}
+
+/// An event of an Anchor program decoded through its IDL
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AnchorEvent {
+    // Name of the program in the IDL
+    pub program: String,
+    pub name: String,
+    // Object keyed by field name, same encoding as `AnchorInstruction::args`
+    pub data: Value,
+}
+
+impl AnchorIdl {
+    pub fn from_json(json: &str) -> Result<Self, Box<dyn Error>> {
+        let raw: RawIdl = serde_json::from_str(json)?;
+        let metadata = raw.metadata.unwrap_or(Value::Null);
+        let name = raw.name
+            .or_else(|| metadata["name"].as_str().map(String::from))
+            .unwrap_or_else(|| "unknown".to_string());
+        let address = raw.address.or_else(|| metadata["address"].as_str().map(String::from));
+        Ok(Self { name, address, instructions: raw.instructions, types: raw.types, events: raw.events, errors: raw.errors })
+    }
+
+    /// Data of the on-chain IDL account: the 8-byte discriminator, the authority, a u32 length and the compressed JSON IDL.
+    /// Anchor compresses it with zlib, zstd is also accepted.
+    pub fn from_account_data(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let mut reader = ByteReader::new(data);
+        reader.skip(8)?;
+        let _authority = reader.pubkey()?;
+        let len = reader.u32()? as usize;
+        let compressed = reader.bytes(len)?;
+
+        let json = if compressed.starts_with(&ZSTD_MAGIC) {
+            zstd::decode_all(compressed)?
+        } else {
+            let mut json = Vec::new();
+            flate2::read::ZlibDecoder::new(compressed).read_to_end(&mut json)?;
+            json
+        };
+        Self::from_json(std::str::from_utf8(&json)?)
+    }
+
+    /// Where Anchor keeps the IDL of `program_id`: `create_with_seed(find_program_address([], program), "anchor:idl", program)`
+    pub fn address_of(program_id: &str) -> Result<Pubkey, Box<dyn Error>> {
+        let program = Pubkey::from_str(program_id)?;
+        let (base, _) = Pubkey::find_program_address(&[], &program);
+        Ok(Pubkey::create_with_seed(&base, "anchor:idl", &program)?)
+    }
+
+    pub fn discriminator(instruction: &IdlInstruction) -> Vec<u8> {
+        match &instruction.discriminator {
+            Some(discriminator) => discriminator.clone(),
+            None => hash(format!("global:{}", snake_case(&instruction.name)).as_bytes()).to_bytes()[..8].to_vec(),
+        }
+    }
+
+    pub fn error(&self, code: u32) -> Option<&IdlErrorCode> {
+        self.errors.iter().find(|error| error.code == code)
+    }
+
+    pub fn event_discriminator(event: &IdlEvent) -> Vec<u8> {
+        match &event.discriminator {
+            Some(discriminator) => discriminator.clone(),
+            None => hash(format!("event:{}", event.name).as_bytes()).to_bytes()[..8].to_vec(),
+        }
+    }
+
+    /// Decodes the payload of a `Program data:` log, or the data of an `emit_cpi!` self-CPI (prefixed by `EVENT_IX_TAG`).
+    /// Ok(None) when no event of the IDL matches its discriminator.
+    pub fn decode_event(&self, data: &[u8]) -> Result<Option<AnchorEvent>, Box<dyn Error>> {
+        let data = data.strip_prefix(&EVENT_IX_TAG[..]).unwrap_or(data);
+        let Some((event, discriminator_len)) = self.events.iter()
+            .map(|event| (event, Self::event_discriminator(event)))
+            .find(|(_, discriminator)| !discriminator.is_empty() && data.starts_with(discriminator))
+            .map(|(event, discriminator)| (event, discriminator.len())) else {
+            return Ok(None);
+        };
+
+        let mut reader = ByteReader::new(&data[discriminator_len..]);
+        let decoded = match &event.fields {
+            Some(fields) => self.decode_fields(fields, &mut reader, 0),
+            None => self.decode_defined(&event.name, &mut reader, 0),
+        };
+        Ok(Some(AnchorEvent {
+            program: self.name.clone(),
+            name: event.name.clone(),
+            data: decoded.map_err(|e| format!("event {}: {}", event.name, e))?,
+        }))
+    }
+
+    /// Ok(None) when no instruction of the IDL matches the discriminator of `data` (or it is an event self-CPI).
+    pub fn decode_instruction(&self, program_id: &str, data: &[u8], accounts: &[String]) -> Result<Option<AnchorInstruction>, Box<dyn Error>> {
+        if data.starts_with(&EVENT_IX_TAG) {
+            return Ok(None);
+        }
+        let Some((instruction, discriminator_len)) = self.instructions.iter()
+            .map(|instruction| (instruction, Self::discriminator(instruction)))
+            .find(|(_, discriminator)| !discriminator.is_empty() && data.starts_with(discriminator))
+            .map(|(instruction, discriminator)| (instruction, discriminator.len())) else {
+            return Ok(None);
+        };
+
+        let mut reader = ByteReader::new(&data[discriminator_len..]);
+        let mut args = Map::new();
+        for arg in &instruction.args {
+            let value = self.decode_type(&arg.field_type, &mut reader, 0)
+                .map_err(|e| format!("{}.{}: {}", instruction.name, arg.name, e))?;
+            args.insert(arg.name.clone(), value);
+        }
+
+        let mut named = Vec::new();
+        flatten_accounts(&instruction.accounts, "", &mut named);
+        let accounts = accounts.iter().enumerate()
+            .map(|(index, pubkey)| match named.get(index) {
+                Some((name, writable, signer)) => NamedAccount { name: name.clone(), pubkey: pubkey.clone(), writable: *writable, signer: *signer },
+                None => NamedAccount { name: "remaining".to_string(), pubkey: pubkey.clone(), writable: false, signer: false },
+            })
+            .collect();
+
+        Ok(Some(AnchorInstruction {
+            program_id: program_id.to_string(),
+            program: self.name.clone(),
+            name: instruction.name.clone(),
+            accounts,
+            args: Value::Object(args),
+        }))
+    }
+
+    /// Borsh decodes a value of the IDL type `field_type` into JSON
+    pub fn decode_type(&self, field_type: &Value, reader: &mut ByteReader, depth: usize) -> Result<Value, Box<dyn Error>> {
+        if depth > MAX_TYPE_DEPTH {
+            return Err("type nested too deep".into());
+        }
+        if let Some(primitive) = field_type.as_str() {
+            return Ok(match primitive {
+                "bool" => json!(reader.bool()?),
+                "u8" => json!(reader.u8()?),
+                "i8" => json!(reader.u8()? as i8),
+                "u16" => json!(reader.u16()?),
+                "i16" => json!(reader.u16()? as i16),
+                "u32" => json!(reader.u32()?),
+                "i32" => json!(reader.u32()? as i32),
+                "u64" => json!(reader.u64()?),
+                "i64" => json!(reader.u64()? as i64),
+                "u128" => json!(reader.u128()?.to_string()),
+                "i128" => json!((reader.u128()? as i128).to_string()),
+                "f32" => json!(f32::from_bits(reader.u32()?)),
+                "f64" => json!(f64::from_bits(reader.u64()?)),
+                "string" => json!(reader.string()?),
+                "bytes" => {
+                    let len = reader.u32()? as usize;
+                    json!(solana_sdk::bs58::encode(reader.bytes(len)?).into_string())
+                }
+                "publicKey" | "pubkey" => json!(reader.pubkey()?),
+                other => return Err(format!("unsupported type {}", other).into()),
+            });
+        }
+
+        if let Some(inner) = field_type.get("vec") {
+            let len = reader.u32()? as usize;
+            // Every element takes at least a byte, a bogus length fails here instead of allocating
+            if len > reader.remaining() {
+                return Err(format!("vec of {} elements with {} bytes left", len, reader.remaining()).into());
+            }
+            return (0..len).map(|_| self.decode_type(inner, reader, depth + 1)).collect::<Result<Vec<Value>, _>>().map(Value::Array);
+        }
+        if let Some(inner) = field_type.get("option") {
+            return match reader.u8()? {
+                0 => Ok(Value::Null),
+                _ => self.decode_type(inner, reader, depth + 1),
+            };
+        }
+        if let Some(inner) = field_type.get("coption") {
+            return match reader.u32()? {
+                0 => Ok(Value::Null),
+                _ => self.decode_type(inner, reader, depth + 1),
+            };
+        }
+        if let Some(array) = field_type.get("array").and_then(Value::as_array) {
+            let (Some(inner), Some(len)) = (array.first(), array.get(1).and_then(Value::as_u64)) else {
+                return Err(format!("unsupported array {}", field_type).into());
+            };
+            return (0..len).map(|_| self.decode_type(inner, reader, depth + 1)).collect::<Result<Vec<Value>, _>>().map(Value::Array);
+        }
+        if let Some(defined) = field_type.get("defined") {
+            // "Name" in legacy IDLs, {"name": "Name"} in the current spec
+            let name = defined.as_str().or_else(|| defined["name"].as_str()).ok_or_else(|| format!("unsupported defined type {}", defined))?;
+            return self.decode_defined(name, reader, depth + 1);
+        }
+        Err(format!("unsupported type {}", field_type).into())
+    }
+
+    fn decode_defined(&self, name: &str, reader: &mut ByteReader, depth: usize) -> Result<Value, Box<dyn Error>> {
+        let type_def = self.types.iter()
+            .find(|type_def| type_def.name == name)
+            .ok_or_else(|| format!("type {} not in the IDL", name))?;
+        match &type_def.body {
+            IdlTypeDefBody::Struct { fields } => self.decode_fields(fields.as_deref().unwrap_or_default(), reader, depth),
+            IdlTypeDefBody::Enum { variants } => {
+                let tag = reader.u8()? as usize;
+                let variant = variants.get(tag).ok_or_else(|| format!("variant {} out of the {} variants of {}", tag, variants.len(), name))?;
+                match &variant.fields {
+                    Some(fields) if !fields.is_empty() => {
+                        let mut object = Map::new();
+                        object.insert(variant.name.clone(), self.decode_fields(fields, reader, depth)?);
+                        Ok(Value::Object(object))
+                    }
+                    _ => Ok(json!(variant.name)),
+                }
+            }
+            IdlTypeDefBody::Type { alias } => self.decode_type(alias, reader, depth),
+        }
+    }
+
+    /// Named fields ({"name", "type"}) decode to an object, tuple fields (bare types) to an array
+    fn decode_fields(&self, fields: &[Value], reader: &mut ByteReader, depth: usize) -> Result<Value, Box<dyn Error>> {
+        let named = fields.iter().all(|field| field.get("name").is_some() && field.get("type").is_some());
+        if named {
+            let mut object = Map::new();
+            for field in fields {
+                let name = field["name"].as_str().unwrap_or_default().to_string();
+                object.insert(name, self.decode_type(&field["type"], reader, depth)?);
+            }
+            Ok(Value::Object(object))
+        } else {
+            fields.iter().map(|field| self.decode_type(field, reader, depth)).collect::<Result<Vec<Value>, _>>().map(Value::Array)
+        }
+    }
+}
+
+/// Decodes the instructions of an Anchor program through its IDL, registered by the `IdlRegistry` for every IDL it loads.
+pub struct AnchorInstructionDecoder {
+    program_id: String,
+    idl: Arc<AnchorIdl>,
+}
+
+impl AnchorInstructionDecoder {
+    pub fn new(program_id: &str, idl: Arc<AnchorIdl>) -> Self {
+        Self { program_id: program_id.to_string(), idl }
+    }
+}
+
+impl InstructionDecoder for AnchorInstructionDecoder {
+    fn program_id(&self) -> &str {
+        &self.program_id
+    }
+
+    fn decode(&self, data: &[u8], accounts: &[String]) -> Result<Option<DecodedInstruction>, Box<dyn Error>> {
+        Ok(self.idl.decode_instruction(&self.program_id, data, accounts)?.map(DecodedInstruction::Anchor))
+    }
+}
+
+/// (name, writable, signer) in the order the accounts are passed
+fn flatten_accounts(items: &[IdlAccountItem], prefix: &str, accounts: &mut Vec<(String, bool, bool)>) {
+    for item in items {
+        match item {
+            IdlAccountItem::Composite { name, accounts: nested } => flatten_accounts(nested, &format!("{}{}.", prefix, name), accounts),
+            IdlAccountItem::Single { name, writable, signer, .. } => accounts.push((format!("{}{}", prefix, name), *writable, *signer)),
+        }
+    }
+}
+
+/// camelCase instruction names of legacy IDLs back to the snake_case the discriminator is derived from
+fn snake_case(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (index, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if index > 0 {
+                snake.push('_');
+            }
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
@@ -0,0 +1,96 @@
+use std::error::Error;
+
+use solana_sdk::pubkey::Pubkey;
+
+/// Little-endian cursor over raw account or instruction data, as laid out by `#[repr(C)]`/`Pack` programs.
+pub struct ByteReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, offset: 0 }
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.data.len().saturating_sub(self.offset)
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], Box<dyn Error>> {
+        if self.remaining() < len {
+            return Err(format!("expected {} more bytes at offset {}, only {} left", len, self.offset, self.remaining()).into());
+        }
+        let bytes = &self.data[self.offset..self.offset + len];
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    pub fn skip(&mut self, len: usize) -> Result<(), Box<dyn Error>> {
+        self.bytes(len).map(|_| ())
+    }
+
+    pub fn u8(&mut self) -> Result<u8, Box<dyn Error>> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool, Box<dyn Error>> {
+        Ok(self.u8()? != 0)
+    }
+
+    pub fn u16(&mut self) -> Result<u16, Box<dyn Error>> {
+        Ok(u16::from_le_bytes(self.bytes(2)?.try_into()?))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, Box<dyn Error>> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into()?))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, Box<dyn Error>> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into()?))
+    }
+
+    pub fn u128(&mut self) -> Result<u128, Box<dyn Error>> {
+        Ok(u128::from_le_bytes(self.bytes(16)?.try_into()?))
+    }
+
+    /// Base58 encoded pubkey
+    pub fn pubkey(&mut self) -> Result<String, Box<dyn Error>> {
+        let bytes: [u8; 32] = self.bytes(32)?.try_into()?;
+        Ok(Pubkey::new_from_array(bytes).to_string())
+    }
+
+    /// `COption<Pubkey>` as packed by the SPL programs: a 4 byte tag followed by the (always present) pubkey
+    pub fn coption_pubkey(&mut self) -> Result<Option<String>, Box<dyn Error>> {
+        let tag = self.u32()?;
+        let pubkey = self.pubkey()?;
+        Ok(if tag == 1 { Some(pubkey) } else { None })
+    }
+
+    pub fn coption_u64(&mut self) -> Result<Option<u64>, Box<dyn Error>> {
+        let tag = self.u32()?;
+        let value = self.u64()?;
+        Ok(if tag == 1 { Some(value) } else { None })
+    }
+
+    /// `COption<Pubkey>` as packed in SPL Token instructions: a 1 byte tag, the pubkey only follows when it is set
+    pub fn option_pubkey(&mut self) -> Result<Option<String>, Box<dyn Error>> {
+        match self.u8()? {
+            0 => Ok(None),
+            1 => self.pubkey().map(Some),
+            tag => Err(format!("invalid option tag {} at offset {}", tag, self.offset - 1).into()),
+        }
+    }
+
+    /// `OptionalNonZeroPubkey` of the Token-2022 extensions: 32 bytes, all zeros meaning None
+    pub fn optional_nonzero_pubkey(&mut self) -> Result<Option<String>, Box<dyn Error>> {
+        let bytes: [u8; 32] = self.bytes(32)?.try_into()?;
+        Ok(if bytes == [0u8; 32] { None } else { Some(Pubkey::new_from_array(bytes).to_string()) })
+    }
+
+    /// Borsh string: a u32 length followed by the utf-8 bytes
+    pub fn string(&mut self) -> Result<String, Box<dyn Error>> {
+        let len = self.u32()? as usize;
+        Ok(String::from_utf8(self.bytes(len)?.to_vec())?)
+    }
+}
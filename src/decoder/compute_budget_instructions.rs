@@ -0,0 +1,178 @@
+// https://github.com/anza-xyz/agave/blob/master/sdk/src/compute_budget.rs
+use std::error::Error;
+
+use serde::{Deserialize, Serialize};
+
+use crate::decoder::byte_reader::ByteReader;
+use crate::decoder::instruction_decoder::{DecodedInstruction, InstructionDecoder};
+use crate::models::solana::compute_budget::ComputeBudget;
+use crate::models::solana::solana_encoded_transaction::EncodedTransactionWithMeta;
+
+pub const COMPUTE_BUDGET_PROGRAM_ID: &str = "ComputeBudget111111111111111111111111111111";
+
+// Limit of an instruction when the transaction does not set one, and of the whole transaction
+pub const DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT: u32 = 200_000;
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+pub const LAMPORTS_PER_SIGNATURE: u64 = 5_000;
+const MICRO_LAMPORTS_PER_LAMPORT: u128 = 1_000_000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ComputeBudgetInstruction {
+    // Deprecated, still accepted by old clients: a limit and a flat fee in lamports
+    RequestUnits { units: u32, additional_fee: u32 },
+    RequestHeapFrame { bytes: u32 },
+    SetComputeUnitLimit { units: u32 },
+    // Micro-lamports per compute unit
+    SetComputeUnitPrice { micro_lamports: u64 },
+    SetLoadedAccountsDataSizeLimit { bytes: u32 },
+}
+
+impl ComputeBudgetInstruction {
+    /// Decodes the instruction data, the first byte being the instruction tag. Ok(None) for unknown tags.
+    pub fn unpack(data: &[u8]) -> Result<Option<Self>, Box<dyn Error>> {
+        let mut reader = ByteReader::new(data);
+        let instruction = match reader.u8()? {
+            0 => ComputeBudgetInstruction::RequestUnits { units: reader.u32()?, additional_fee: reader.u32()? },
+            1 => ComputeBudgetInstruction::RequestHeapFrame { bytes: reader.u32()? },
+            2 => ComputeBudgetInstruction::SetComputeUnitLimit { units: reader.u32()? },
+            3 => ComputeBudgetInstruction::SetComputeUnitPrice { micro_lamports: reader.u64()? },
+            4 => ComputeBudgetInstruction::SetLoadedAccountsDataSizeLimit { bytes: reader.u32()? },
+            _ => return Ok(None),
+        };
+        Ok(Some(instruction))
+    }
+}
+
+pub struct ComputeBudgetInstructionDecoder;
+
+impl InstructionDecoder for ComputeBudgetInstructionDecoder {
+    fn program_id(&self) -> &str {
+        COMPUTE_BUDGET_PROGRAM_ID
+    }
+
+    fn decode(&self, data: &[u8], _accounts: &[String]) -> Result<Option<DecodedInstruction>, Box<dyn Error>> {
+        Ok(ComputeBudgetInstruction::unpack(data)?.map(DecodedInstruction::ComputeBudget))
+    }
+}
+
+/// Compute unit limit and price the transaction set (or the runtime defaults), the priority fee they make and the units consumed.
+/// Only outer instructions count, the runtime ignores ComputeBudget instructions invoked through a CPI.
+pub fn compute_budget(transaction: &EncodedTransactionWithMeta) -> Result<ComputeBudget, Box<dyn Error>> {
+    let mut requested_unit_limit = None;
+    let mut unit_price = 0;
+    let mut other_instructions: u32 = 0;
+    for instruction in transaction.instructions()?.iter().filter(|instruction| instruction.inner_index.is_none()) {
+        if instruction.program_id != COMPUTE_BUDGET_PROGRAM_ID {
+            other_instructions += 1;
+            continue;
+        }
+        match ComputeBudgetInstruction::unpack(&instruction.data)? {
+            Some(ComputeBudgetInstruction::SetComputeUnitLimit { units }) => requested_unit_limit = Some(units),
+            Some(ComputeBudgetInstruction::SetComputeUnitPrice { micro_lamports }) => unit_price = micro_lamports,
+            Some(ComputeBudgetInstruction::RequestUnits { units, additional_fee }) => {
+                requested_unit_limit = Some(units);
+                // The flat fee spread over the units, as the runtime converts it
+                if units > 0 {
+                    unit_price = (additional_fee as u128 * MICRO_LAMPORTS_PER_LAMPORT / units as u128) as u64;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let unit_limit = requested_unit_limit
+        .unwrap_or_else(|| other_instructions.saturating_mul(DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT))
+        .min(MAX_COMPUTE_UNIT_LIMIT);
+    // Rounded up to the next lamport
+    let priority_fee = (unit_price as u128 * unit_limit as u128).div_ceil(MICRO_LAMPORTS_PER_LAMPORT) as u64;
+    Ok(ComputeBudget {
+        signature: transaction.signature().unwrap_or_default().to_string(),
+        slot: transaction.slot,
+        unit_limit,
+        requested_unit_limit,
+        unit_price,
+        priority_fee,
+        base_fee: transaction.transaction.signatures.len() as u64 * LAMPORTS_PER_SIGNATURE,
+        fee: transaction.meta.as_ref().map(|meta| meta.fee),
+        units_consumed: transaction.meta.as_ref().and_then(|meta| meta.compute_units_consumed),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use solana_sdk::bs58;
+
+    use super::*;
+
+    /// A transaction of a single signer calling `instructions` (program, data), with no accounts
+    fn transaction(instructions: &[(&str, Vec<u8>)], compute_units_consumed: u64) -> EncodedTransactionWithMeta {
+        let mut keys = vec!["9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM".to_string()];
+        let instructions: Vec<serde_json::Value> = instructions.iter()
+            .map(|(program_id, data)| {
+                let index = keys.iter().position(|key| key == program_id).unwrap_or_else(|| {
+                    keys.push(program_id.to_string());
+                    keys.len() - 1
+                });
+                json!({"programIdIndex": index, "accounts": [], "data": bs58::encode(data).into_string()})
+            })
+            .collect();
+        serde_json::from_value(json!({
+            "slot": 270_000_000,
+            "blockTime": null,
+            "version": "legacy",
+            "meta": {"err": null, "fee": 12_500, "preBalances": [], "postBalances": [], "computeUnitsConsumed": compute_units_consumed},
+            "transaction": {
+                "signatures": ["5h6xBEauJ3PK6SWCZ1PGjBvj8vDdWG3KpwATGy1ARAXFSDwt8GFXM7W5Ncn16wmqokgpiKRLuS83KUxyZyv2sUYv"],
+                "message": {
+                    "header": {"numRequiredSignatures": 1, "numReadonlySignedAccounts": 0, "numReadonlyUnsignedAccounts": 1},
+                    "accountKeys": keys,
+                    "recentBlockhash": "EtWTRABZaYq6iMfeYKouRu166VU2xqa1wcaWoxPkrZBG",
+                    "instructions": instructions,
+                },
+            },
+        })).unwrap()
+    }
+
+    #[test]
+    fn unpacks_compute_budget_instructions() {
+        // 300k units, 25k micro-lamports per unit
+        assert_eq!(ComputeBudgetInstruction::unpack(&[2, 0xe0, 0x93, 0x04, 0x00]).unwrap(),
+                   Some(ComputeBudgetInstruction::SetComputeUnitLimit { units: 300_000 }));
+        assert_eq!(ComputeBudgetInstruction::unpack(&[3, 0xa8, 0x61, 0, 0, 0, 0, 0, 0]).unwrap(),
+                   Some(ComputeBudgetInstruction::SetComputeUnitPrice { micro_lamports: 25_000 }));
+        assert_eq!(ComputeBudgetInstruction::unpack(&[9]).unwrap(), None);
+        assert!(ComputeBudgetInstruction::unpack(&[3, 0xa8, 0x61]).is_err());
+    }
+
+    #[test]
+    fn priority_fee_of_the_requested_limit() {
+        let transaction = transaction(&[
+            (COMPUTE_BUDGET_PROGRAM_ID, [&[2u8][..], &300_000u32.to_le_bytes()].concat()),
+            (COMPUTE_BUDGET_PROGRAM_ID, [&[3u8][..], &25_000u64.to_le_bytes()].concat()),
+            ("JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4", vec![0xe5, 0x17, 0xcb, 0x97]),
+        ], 180_000);
+        let budget = compute_budget(&transaction).unwrap();
+        assert_eq!(budget.unit_limit, 300_000);
+        assert_eq!(budget.requested_unit_limit, Some(300_000));
+        assert_eq!(budget.unit_price, 25_000);
+        assert_eq!(budget.priority_fee, 7_500);
+        assert_eq!(budget.base_fee, 5_000);
+        assert_eq!(budget.units_consumed, Some(180_000));
+    }
+
+    #[test]
+    fn default_limit_per_instruction() {
+        let transaction = transaction(&[
+            (COMPUTE_BUDGET_PROGRAM_ID, [&[3u8][..], &1_001u64.to_le_bytes()].concat()),
+            ("11111111111111111111111111111111", vec![2, 0, 0, 0, 0x40, 0x42, 0x0f, 0, 0, 0, 0, 0]),
+            ("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr", b"gm".to_vec()),
+        ], 600);
+        let budget = compute_budget(&transaction).unwrap();
+        assert_eq!(budget.unit_limit, 400_000);
+        assert_eq!(budget.requested_unit_limit, None);
+        // 400.4 lamports, rounded up
+        assert_eq!(budget.priority_fee, 401);
+    }
+}
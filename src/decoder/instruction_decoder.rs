@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Arc, RwLock};
+
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+use crate::decoder::anchor_idl::AnchorInstruction;
+use crate::decoder::compute_budget_instructions::{ComputeBudgetInstruction, ComputeBudgetInstructionDecoder};
+use crate::decoder::raydium_amm_v4_instructions::{RaydiumAmmInstruction, RaydiumAmmV4InstructionDecoder};
+use crate::decoder::spl_token_accounts::SPL_TOKEN_PROGRAM_ID;
+use crate::decoder::spl_token_instructions::{SplTokenInstructionDecoder, TokenInstruction};
+use crate::decoder::token_2022::TOKEN_2022_PROGRAM_ID;
+use crate::models::solana::solana_encoded_transaction::ResolvedInstruction;
+
+/// Typed view of an instruction, produced by the decoder registered for its program.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum DecodedInstruction {
+    SplToken(TokenInstruction),
+    RaydiumAmmV4(RaydiumAmmInstruction),
+    Anchor(AnchorInstruction),
+    ComputeBudget(ComputeBudgetInstruction),
+}
+
+/// Decodes the raw data of the instructions of a single program.
+pub trait InstructionDecoder {
+    fn program_id(&self) -> &str;
+
+    // Ok(None) for instructions of the program this decoder does not model
+    fn decode(&self, data: &[u8], accounts: &[String]) -> Result<Option<DecodedInstruction>, Box<dyn Error>>;
+}
+
+/// Instruction decoders keyed by program id. Register new ones on `INSTRUCTION_DECODERS` to decode more programs.
+pub struct InstructionDecoderRegistry {
+    decoders: HashMap<String, Arc<dyn InstructionDecoder + Send + Sync>>,
+}
+
+impl InstructionDecoderRegistry {
+    pub fn new() -> Self {
+        Self { decoders: HashMap::new() }
+    }
+
+    /// SPL Token and Token-2022, which share the instructions of the original program, Raydium AMM v4 and ComputeBudget
+    pub fn with_defaults() -> Self {
+        let mut registry = Self::new();
+        registry.register(SplTokenInstructionDecoder::new(SPL_TOKEN_PROGRAM_ID));
+        registry.register(SplTokenInstructionDecoder::new(TOKEN_2022_PROGRAM_ID));
+        registry.register(RaydiumAmmV4InstructionDecoder);
+        registry.register(ComputeBudgetInstructionDecoder);
+        registry
+    }
+
+    /// Replaces any decoder already registered for the same program.
+    pub fn register<D: InstructionDecoder + Send + Sync + 'static>(&mut self, decoder: D) {
+        self.decoders.insert(decoder.program_id().to_string(), Arc::new(decoder));
+    }
+
+    pub fn decoder_for(&self, program_id: &str) -> Option<Arc<dyn InstructionDecoder + Send + Sync>> {
+        self.decoders.get(program_id).cloned()
+    }
+
+    /// None when there is no decoder for the program or the decoder does not model the instruction.
+    pub fn decode(&self, instruction: &ResolvedInstruction) -> Result<Option<DecodedInstruction>, Box<dyn Error>> {
+        match self.decoders.get(&instruction.program_id) {
+            Some(decoder) => decoder.decode(&instruction.data, &instruction.accounts),
+            None => Ok(None),
+        }
+    }
+}
+
+lazy_static! {
+    /// Shared by every transaction summary and endpoint, decoders can be registered at startup.
+    pub static ref INSTRUCTION_DECODERS: RwLock<InstructionDecoderRegistry> = RwLock::new(InstructionDecoderRegistry::with_defaults());
+}
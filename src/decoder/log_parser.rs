@@ -0,0 +1,223 @@
+// https://github.com/anza-xyz/agave/blob/master/program-runtime/src/stable_log.rs
+use crate::models::solana::program_logs::{InvocationStatus, ProgramDataLog, ProgramInvocation, ProgramLogs};
+
+const LOG_PREFIX: &str = "Program log: ";
+const DATA_PREFIX: &str = "Program data: ";
+const RETURN_PREFIX: &str = "Program return: ";
+const TRUNCATED: &str = "Log truncated";
+
+/// Rebuilds the CPI tree of a transaction from its runtime logs:
+///
+/// * `Program <id> invoke [<depth>]` opens an invocation
+/// * `Program log: <message>`, `Program data: <base64>...` and `Program return: <id> <base64>` belong to the open one
+/// * `Program <id> consumed <n> of <m> compute units` sets its compute usage
+/// * `Program <id> success` and `Program <id> failed: <error>` close it
+///
+/// Invocations still open when the logs end are `Incomplete`. Events are left undecoded, see `IdlRegistry::decode_events`.
+pub fn parse_logs(logs: &[String]) -> ProgramLogs {
+    let mut parsed = ProgramLogs::default();
+    // Open invocations, the innermost last
+    let mut stack: Vec<ProgramInvocation> = Vec::new();
+
+    for line in logs {
+        if line == TRUNCATED {
+            parsed.truncated = true;
+            continue;
+        }
+        if let Some(message) = line.strip_prefix(LOG_PREFIX) {
+            match stack.last_mut() {
+                Some(invocation) => invocation.logs.push(message.to_string()),
+                None => parsed.unparsed.push(line.clone()),
+            }
+            continue;
+        }
+        if let Some(data) = line.strip_prefix(DATA_PREFIX) {
+            match stack.last_mut() {
+                Some(invocation) => invocation.data.push(ProgramDataLog {
+                    data: data.split_whitespace().map(String::from).collect(),
+                    event: None,
+                }),
+                None => parsed.unparsed.push(line.clone()),
+            }
+            continue;
+        }
+        if let Some(returned) = line.strip_prefix(RETURN_PREFIX) {
+            match (stack.last_mut(), returned.split_once(' ')) {
+                (Some(invocation), Some((_, data))) => invocation.return_data = Some(data.to_string()),
+                _ => parsed.unparsed.push(line.clone()),
+            }
+            continue;
+        }
+
+        let Some(rest) = line.strip_prefix("Program ") else {
+            // Runtime messages outside of the grammar (ie. "Program is not deployed" variants), kept on the open invocation
+            match stack.last_mut() {
+                Some(invocation) => invocation.logs.push(line.clone()),
+                None => parsed.unparsed.push(line.clone()),
+            }
+            continue;
+        };
+        let (program_id, event) = rest.split_once(' ').unwrap_or((rest, ""));
+
+        if let Some(depth) = event.strip_prefix("invoke [").and_then(|depth| depth.strip_suffix(']')) {
+            let depth = depth.parse().unwrap_or(stack.len() + 1);
+            let outer_index = match stack.first() {
+                Some(outer) => outer.outer_index,
+                None => parsed.invocations.len(),
+            };
+            stack.push(ProgramInvocation::new(program_id, depth, outer_index));
+        } else if let Some(units) = event.strip_prefix("consumed ").and_then(|units| units.strip_suffix(" compute units")) {
+            if let (Some(invocation), Some((consumed, limit))) = (stack.last_mut(), units.split_once(" of ")) {
+                invocation.compute_consumed = consumed.parse().ok();
+                invocation.compute_limit = limit.parse().ok();
+            }
+        } else if event == "success" || event.starts_with("failed") {
+            // A close for another program than the open one means logs are missing, the invocations above it close with it
+            while stack.len() > 1 && stack.last().is_some_and(|invocation| invocation.program_id != program_id) {
+                close(&mut stack, &mut parsed);
+            }
+            if let Some(invocation) = stack.last_mut() {
+                if event == "success" {
+                    invocation.status = InvocationStatus::Success;
+                } else {
+                    invocation.status = InvocationStatus::Failed;
+                    invocation.error = event.strip_prefix("failed: ").map(String::from);
+                }
+            }
+            close(&mut stack, &mut parsed);
+        } else {
+            match stack.last_mut() {
+                Some(invocation) => invocation.logs.push(line.clone()),
+                None => parsed.unparsed.push(line.clone()),
+            }
+        }
+    }
+
+    while !stack.is_empty() {
+        close(&mut stack, &mut parsed);
+    }
+    parsed
+}
+
+/// Pops the innermost open invocation into its caller, or into the roots for an outer instruction
+fn close(stack: &mut Vec<ProgramInvocation>, parsed: &mut ProgramLogs) {
+    let Some(invocation) = stack.pop() else {
+        return;
+    };
+    match stack.last_mut() {
+        Some(caller) => caller.invocations.push(invocation),
+        None => parsed.invocations.push(invocation),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JUPITER: &str = "JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4";
+    const WHIRLPOOL: &str = "whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc";
+    const TOKEN: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+    const COMPUTE_BUDGET: &str = "ComputeBudget111111111111111111111111111111";
+
+    fn logs(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn rebuilds_the_cpi_tree() {
+        let parsed = parse_logs(&logs(&[
+            &format!("Program {} invoke [1]", COMPUTE_BUDGET),
+            &format!("Program {} success", COMPUTE_BUDGET),
+            &format!("Program {} invoke [1]", JUPITER),
+            "Program log: Instruction: Route",
+            &format!("Program {} invoke [2]", WHIRLPOOL),
+            "Program log: Instruction: Swap",
+            &format!("Program {} invoke [3]", TOKEN),
+            "Program log: Instruction: Transfer",
+            &format!("Program {} consumed 4645 of 180000 compute units", TOKEN),
+            &format!("Program {} success", TOKEN),
+            "Program data: 4f4a2b== AAEC",
+            &format!("Program {} consumed 40000 of 190000 compute units", WHIRLPOOL),
+            &format!("Program {} success", WHIRLPOOL),
+            &format!("Program return: {} AQAAAAAAAAA=", JUPITER),
+            &format!("Program {} consumed 60000 of 199850 compute units", JUPITER),
+            &format!("Program {} success", JUPITER),
+        ]));
+
+        assert!(!parsed.truncated);
+        assert!(parsed.unparsed.is_empty());
+        assert_eq!(parsed.invocations.len(), 2);
+        assert_eq!(parsed.invocations[0].compute_consumed, None);
+        assert_eq!(parsed.compute_consumed(), 60000);
+
+        let route = &parsed.invocations[1];
+        assert_eq!((route.program_id.as_str(), route.depth, route.outer_index), (JUPITER, 1, 1));
+        assert_eq!(route.logs, vec!["Instruction: Route"]);
+        assert_eq!(route.return_data.as_deref(), Some("AQAAAAAAAAA="));
+
+        let swap = &route.invocations[0];
+        assert_eq!((swap.program_id.as_str(), swap.depth, swap.outer_index), (WHIRLPOOL, 2, 1));
+        assert_eq!(swap.data[0].data, vec!["4f4a2b==", "AAEC"]);
+        assert_eq!((swap.compute_consumed, swap.compute_limit), (Some(40000), Some(190000)));
+        assert_eq!(swap.invocations[0].program_id, TOKEN);
+        assert_eq!(swap.invocations[0].depth, 3);
+
+        let order: Vec<&str> = parsed.flatten().iter().map(|invocation| invocation.program_id.as_str()).collect();
+        assert_eq!(order, vec![COMPUTE_BUDGET, JUPITER, WHIRLPOOL, TOKEN]);
+        assert!(parsed.logged(TOKEN, "Transfer"));
+        assert!(parsed.failed_invocation().is_none());
+    }
+
+    #[test]
+    fn finds_the_failing_frame() {
+        let parsed = parse_logs(&logs(&[
+            &format!("Program {} invoke [1]", JUPITER),
+            &format!("Program {} invoke [2]", WHIRLPOOL),
+            "Program log: AnchorError occurred. Error Code: AmountOutBelowMinimum.",
+            &format!("Program {} consumed 20000 of 190000 compute units", WHIRLPOOL),
+            &format!("Program {} failed: custom program error: 0x1794", WHIRLPOOL),
+            &format!("Program {} consumed 30000 of 200000 compute units", JUPITER),
+            &format!("Program {} failed: custom program error: 0x1794", JUPITER),
+        ]));
+
+        let failed = parsed.failed_invocation().unwrap();
+        assert_eq!(failed.program_id, WHIRLPOOL);
+        assert_eq!(failed.error.as_deref(), Some("custom program error: 0x1794"));
+        assert_eq!(parsed.invocations[0].status, InvocationStatus::Failed);
+        // A failed invocation doesn't count as having logged
+        assert!(!parsed.logged(WHIRLPOOL, "AnchorError"));
+    }
+
+    #[test]
+    fn truncated_logs_leave_invocations_incomplete() {
+        let parsed = parse_logs(&logs(&[
+            &format!("Program {} invoke [1]", JUPITER),
+            &format!("Program {} invoke [2]", WHIRLPOOL),
+            "Log truncated",
+        ]));
+
+        assert!(parsed.truncated);
+        let route = &parsed.invocations[0];
+        assert_eq!(route.status, InvocationStatus::Incomplete);
+        assert_eq!(route.invocations[0].status, InvocationStatus::Incomplete);
+        assert_eq!(route.invocations[0].program_id, WHIRLPOOL);
+    }
+
+    #[test]
+    fn missing_close_logs_close_the_inner_invocations() {
+        let parsed = parse_logs(&logs(&[
+            "Program log: before any invocation",
+            &format!("Program {} invoke [1]", JUPITER),
+            &format!("Program {} invoke [2]", WHIRLPOOL),
+            &format!("Program {} success", JUPITER),
+            &format!("Program {} invoke [1]", TOKEN),
+            &format!("Program {} success", TOKEN),
+        ]));
+
+        assert_eq!(parsed.unparsed, vec!["Program log: before any invocation"]);
+        assert_eq!(parsed.invocations.len(), 2);
+        assert_eq!(parsed.invocations[0].status, InvocationStatus::Success);
+        assert_eq!(parsed.invocations[0].invocations[0].status, InvocationStatus::Incomplete);
+        assert_eq!(parsed.invocations[1].outer_index, 1);
+    }
+}
@@ -1 +1,16 @@
-pub mod tx_decoder;
\ No newline at end of file
+pub mod tx_decoder;
+pub mod byte_reader;
+pub mod account_decoder;
+pub mod anchor_idl;
+pub mod instruction_decoder;
+pub mod spl_token_accounts;
+pub mod spl_token_instructions;
+pub mod token_2022;
+pub mod raydium_amm_v4_state;
+pub mod raydium_amm_v4_instructions;
+pub mod compute_budget_instructions;
+pub mod swap_extractor;
+pub mod log_parser;
+pub mod transaction_error;
+pub mod openbook_market_state;
+pub mod address_lookup_table;
@@ -0,0 +1,92 @@
+// https://github.com/openbook-dex/program/blob/master/dex/src/state.rs (MarketState, serum v3 layout)
+use std::error::Error;
+
+use serde::{Deserialize, Serialize};
+
+use crate::decoder::account_decoder::{AccountDecoder, DecodedAccount};
+use crate::decoder::byte_reader::ByteReader;
+
+pub const OPENBOOK_PROGRAM_ID: &str = "srmqPvymJeFKQ4zGQed1GFppgkRHL9kaELCbyksJtPX";
+
+// 5 bytes "serum" head padding + 376 bytes of state + 7 bytes "padding" tail
+pub const MARKET_STATE_LEN: usize = 388;
+const HEAD_PADDING: &[u8] = b"serum";
+// AccountFlag::Initialized | AccountFlag::Market
+const MARKET_ACCOUNT_FLAGS: u64 = 0b11;
+
+/// An OpenBook (serum v3) market, Raydium AMM v4 pools point to one of these.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MarketState {
+    pub account_flags: u64,
+    pub own_address: String,
+    pub vault_signer_nonce: u64,
+    pub coin_mint: String,
+    pub pc_mint: String,
+    pub coin_vault: String,
+    pub coin_deposits_total: u64,
+    pub coin_fees_accrued: u64,
+    pub pc_vault: String,
+    pub pc_deposits_total: u64,
+    pub pc_fees_accrued: u64,
+    pub pc_dust_threshold: u64,
+    pub request_queue: String,
+    pub event_queue: String,
+    pub bids: String,
+    pub asks: String,
+    pub coin_lot_size: u64,
+    pub pc_lot_size: u64,
+    pub fee_rate_bps: u64,
+    pub referrer_rebates_accrued: u64,
+}
+
+impl MarketState {
+    pub fn unpack(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let mut reader = ByteReader::new(data);
+        if reader.bytes(HEAD_PADDING.len())? != HEAD_PADDING {
+            return Err("market account does not start with the serum padding".into());
+        }
+
+        Ok(MarketState {
+            account_flags: reader.u64()?,
+            own_address: reader.pubkey()?,
+            vault_signer_nonce: reader.u64()?,
+            coin_mint: reader.pubkey()?,
+            pc_mint: reader.pubkey()?,
+            coin_vault: reader.pubkey()?,
+            coin_deposits_total: reader.u64()?,
+            coin_fees_accrued: reader.u64()?,
+            pc_vault: reader.pubkey()?,
+            pc_deposits_total: reader.u64()?,
+            pc_fees_accrued: reader.u64()?,
+            pc_dust_threshold: reader.u64()?,
+            request_queue: reader.pubkey()?,
+            event_queue: reader.pubkey()?,
+            bids: reader.pubkey()?,
+            asks: reader.pubkey()?,
+            coin_lot_size: reader.u64()?,
+            pc_lot_size: reader.u64()?,
+            fee_rate_bps: reader.u64()?,
+            referrer_rebates_accrued: reader.u64()?,
+        })
+    }
+}
+
+/// Market accounts of the OpenBook program. Queues, order books and open orders share the owner and are skipped.
+pub struct OpenBookMarketDecoder;
+
+impl AccountDecoder for OpenBookMarketDecoder {
+    fn owner(&self) -> &str {
+        OPENBOOK_PROGRAM_ID
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Option<DecodedAccount>, Box<dyn Error>> {
+        if data.len() != MARKET_STATE_LEN {
+            return Ok(None);
+        }
+        let market = MarketState::unpack(data)?;
+        if market.account_flags & MARKET_ACCOUNT_FLAGS != MARKET_ACCOUNT_FLAGS {
+            return Ok(None);
+        }
+        Ok(Some(DecodedAccount::OpenBookMarket(Box::new(market))))
+    }
+}
@@ -0,0 +1,676 @@
+// https://github.com/raydium-io/raydium-amm/blob/master/program/src/instruction.rs
+use std::collections::HashMap;
+use std::error::Error;
+
+use serde::{Deserialize, Serialize};
+
+use crate::decoder::byte_reader::ByteReader;
+use crate::decoder::instruction_decoder::{DecodedInstruction, InstructionDecoder};
+use crate::decoder::raydium_amm_v4_state::RAYDIUM_AMM_V4_PROGRAM_ID;
+use crate::decoder::spl_token_accounts::SPL_TOKEN_PROGRAM_ID;
+use crate::decoder::spl_token_instructions::TokenInstruction;
+use crate::decoder::token_2022::TOKEN_2022_PROGRAM_ID;
+use crate::models::raydium::raydium_amm_event::{LiquidityChange, LiquidityChangeKind, PoolCreated, RaydiumAmmEvent, Swap, SwapSide};
+use crate::models::solana::solana_encoded_transaction::{EncodedTransactionWithMeta, ResolvedInstruction, TokenBalanceChange};
+
+// Log line of initialize2, the only way to spot a pool creation among the program logs without fetching the transaction
+pub const INITIALIZE2_LOG: &str = "initialize2";
+
+/// Raydium AMM v4 instruction with its accounts resolved. Coin is the base token, pc the quote token, amounts are raw.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RaydiumAmmInstruction {
+    Initialize2 {
+        amm: String,
+        lp_mint: String,
+        coin_mint: String,
+        pc_mint: String,
+        pool_coin_vault: String,
+        pool_pc_vault: String,
+        serum_market: String,
+        creator: String,
+        nonce: u8,
+        // Unix timestamp (seconds) from which swaps are allowed
+        open_time: u64,
+        init_pc_amount: u64,
+        init_coin_amount: u64,
+    },
+    Deposit {
+        amm: String,
+        lp_mint: String,
+        pool_coin_vault: String,
+        pool_pc_vault: String,
+        owner: String,
+        max_coin_amount: u64,
+        max_pc_amount: u64,
+        // 0 when max_coin_amount is the amount to match, 1 for max_pc_amount
+        base_side: u64,
+    },
+    Withdraw {
+        amm: String,
+        lp_mint: String,
+        pool_coin_vault: String,
+        pool_pc_vault: String,
+        owner: String,
+        // LP tokens burnt
+        amount: u64,
+    },
+    SwapBaseIn {
+        amm: String,
+        pool_coin_vault: String,
+        pool_pc_vault: String,
+        user_source: String,
+        user_destination: String,
+        owner: String,
+        amount_in: u64,
+        minimum_amount_out: u64,
+    },
+    SwapBaseOut {
+        amm: String,
+        pool_coin_vault: String,
+        pool_pc_vault: String,
+        user_source: String,
+        user_destination: String,
+        owner: String,
+        max_amount_in: u64,
+        amount_out: u64,
+    },
+}
+
+impl RaydiumAmmInstruction {
+    /// Decodes the instruction data, the first byte being the instruction tag. Ok(None) for the admin and orderbook instructions.
+    pub fn unpack(data: &[u8], accounts: &[String]) -> Result<Option<Self>, Box<dyn Error>> {
+        let mut reader = ByteReader::new(data);
+        let account = |index: usize| -> Result<String, Box<dyn Error>> {
+            accounts.get(index)
+                .cloned()
+                .ok_or_else(|| format!("raydium instruction expects an account at position {}, got {} accounts", index, accounts.len()).into())
+        };
+        // Counted from the end, the accounts before them vary between program versions
+        let from_end = |offset: usize| -> Result<String, Box<dyn Error>> {
+            match accounts.len().checked_sub(offset) {
+                Some(index) => account(index),
+                None => Err(format!("raydium instruction expects at least {} accounts, got {}", offset, accounts.len()).into()),
+            }
+        };
+
+        let instruction = match reader.u8()? {
+            1 => RaydiumAmmInstruction::Initialize2 {
+                nonce: reader.u8()?,
+                open_time: reader.u64()?,
+                init_pc_amount: reader.u64()?,
+                init_coin_amount: reader.u64()?,
+                amm: account(4)?,
+                lp_mint: account(7)?,
+                coin_mint: account(8)?,
+                pc_mint: account(9)?,
+                pool_coin_vault: account(10)?,
+                pool_pc_vault: account(11)?,
+                serum_market: account(16)?,
+                creator: account(17)?,
+            },
+            3 => RaydiumAmmInstruction::Deposit {
+                max_coin_amount: reader.u64()?,
+                max_pc_amount: reader.u64()?,
+                base_side: reader.u64()?,
+                amm: account(1)?,
+                lp_mint: account(5)?,
+                pool_coin_vault: account(6)?,
+                pool_pc_vault: account(7)?,
+                owner: account(12)?,
+            },
+            4 => RaydiumAmmInstruction::Withdraw {
+                amount: reader.u64()?,
+                amm: account(1)?,
+                lp_mint: account(5)?,
+                pool_coin_vault: account(6)?,
+                pool_pc_vault: account(7)?,
+                // Followed by the event queue, bids and asks
+                owner: from_end(4)?,
+            },
+            // 18 accounts, 17 since the target orders account was made optional: the vaults move one position up
+            9 | 11 => {
+                let shift = if accounts.len() >= 18 { 0 } else { 1 };
+                let first = reader.u64()?;
+                let second = reader.u64()?;
+                let (amm, pool_coin_vault, pool_pc_vault) = (account(1)?, account(5 - shift)?, account(6 - shift)?);
+                let (user_source, user_destination, owner) = (from_end(3)?, from_end(2)?, from_end(1)?);
+                if data[0] == 9 {
+                    RaydiumAmmInstruction::SwapBaseIn {
+                        amm, pool_coin_vault, pool_pc_vault, user_source, user_destination, owner,
+                        amount_in: first,
+                        minimum_amount_out: second,
+                    }
+                } else {
+                    RaydiumAmmInstruction::SwapBaseOut {
+                        amm, pool_coin_vault, pool_pc_vault, user_source, user_destination, owner,
+                        max_amount_in: first,
+                        amount_out: second,
+                    }
+                }
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(instruction))
+    }
+}
+
+pub struct RaydiumAmmV4InstructionDecoder;
+
+impl InstructionDecoder for RaydiumAmmV4InstructionDecoder {
+    fn program_id(&self) -> &str {
+        RAYDIUM_AMM_V4_PROGRAM_ID
+    }
+
+    fn decode(&self, data: &[u8], accounts: &[String]) -> Result<Option<DecodedInstruction>, Box<dyn Error>> {
+        Ok(RaydiumAmmInstruction::unpack(data, accounts)?.map(DecodedInstruction::RaydiumAmmV4))
+    }
+}
+
+/// The pool creations, swaps and liquidity changes of a transaction, from its Raydium AMM v4 instructions (outer and inner).
+/// Amounts that moved come from the token instructions each AMM instruction invoked, so several swaps on the same pool in one
+/// transaction each carry their own. Failed transactions have none.
+///
+/// An instruction that can't be decoded, or whose vaults are not in the token balances, is skipped without failing the others.
+pub fn amm_events(transaction: &EncodedTransactionWithMeta) -> Result<Vec<RaydiumAmmEvent>, Box<dyn Error>> {
+    let Some(meta) = transaction.meta.as_ref() else {
+        return Ok(Vec::new());
+    };
+    if meta.err.is_some() {
+        return Ok(Vec::new());
+    }
+
+    let context = EventContext {
+        signature: transaction.signature().unwrap_or_default().to_string(),
+        transaction,
+        deltas: transaction.token_balance_changes(),
+    };
+    let instructions = transaction.instructions()?;
+    let mut events = Vec::new();
+    for (position, instruction) in instructions.iter().enumerate() {
+        if instruction.program_id != RAYDIUM_AMM_V4_PROGRAM_ID {
+            continue;
+        }
+        let event = RaydiumAmmInstruction::unpack(&instruction.data, &instruction.accounts)
+            .and_then(|decoded| match decoded {
+                Some(decoded) => context.event(decoded, TokenFlows::invoked_by(&instructions, position)).map(Some),
+                None => Ok(None),
+            });
+        match event {
+            Ok(Some(event)) => events.push(event),
+            Ok(None) => {}
+            Err(e) => eprintln!("[[RAYDIUM AMM]] Skipping instruction {}.{:?} of {}: {}",
+                                instruction.outer_index, instruction.inner_index, context.signature, e),
+        }
+    }
+    Ok(events)
+}
+
+/// What the events of one transaction share
+struct EventContext<'a> {
+    signature: String,
+    transaction: &'a EncodedTransactionWithMeta,
+    deltas: HashMap<String, TokenBalanceChange>,
+}
+
+impl EventContext<'_> {
+    /// `flows` is None when the instructions invoked by the AMM instruction are unknown, so are the amounts of the event
+    fn event(&self, decoded: RaydiumAmmInstruction, flows: Option<TokenFlows>) -> Result<RaydiumAmmEvent, Box<dyn Error>> {
+        let event = match decoded {
+            RaydiumAmmInstruction::Initialize2 {
+                amm, lp_mint, coin_mint, pc_mint, pool_coin_vault, pool_pc_vault, serum_market, creator, open_time,
+                init_pc_amount, init_coin_amount, ..
+            } => RaydiumAmmEvent::PoolCreated(PoolCreated {
+                signature: self.signature.clone(),
+                slot: self.transaction.slot,
+                block_time: self.transaction.block_time,
+                pool: amm,
+                // The vaults are created by the instruction, their post balance is the liquidity actually added
+                base_amount: self.deltas.get(&pool_coin_vault).map_or(init_coin_amount, |delta| delta.post),
+                quote_amount: self.deltas.get(&pool_pc_vault).map_or(init_pc_amount, |delta| delta.post),
+                base_mint: coin_mint,
+                quote_mint: pc_mint,
+                lp_mint,
+                base_vault: pool_coin_vault,
+                quote_vault: pool_pc_vault,
+                market: serum_market,
+                creator,
+                open_time,
+            }),
+            RaydiumAmmInstruction::SwapBaseIn { amm, pool_coin_vault, pool_pc_vault, owner, amount_in, minimum_amount_out, .. } => {
+                let vaults = self.vaults(&pool_coin_vault, &pool_pc_vault, flows.as_ref())?;
+                self.swap_event(amm, owner, vaults, amount_in, minimum_amount_out)
+            }
+            RaydiumAmmInstruction::SwapBaseOut { amm, pool_coin_vault, pool_pc_vault, owner, max_amount_in, amount_out, .. } => {
+                let vaults = self.vaults(&pool_coin_vault, &pool_pc_vault, flows.as_ref())?;
+                self.swap_event(amm, owner, vaults, amount_out, max_amount_in)
+            }
+            RaydiumAmmInstruction::Deposit { amm, lp_mint, pool_coin_vault, pool_pc_vault, owner, .. } => {
+                let vaults = self.vaults(&pool_coin_vault, &pool_pc_vault, flows.as_ref())?;
+                let lp_amount = flows.as_ref().map(|flows| flows.minted(&lp_mint));
+                self.liquidity_event(amm, owner, lp_mint, LiquidityChangeKind::Deposit, vaults, lp_amount)
+            }
+            RaydiumAmmInstruction::Withdraw { amm, lp_mint, pool_coin_vault, pool_pc_vault, owner, .. } => {
+                let vaults = self.vaults(&pool_coin_vault, &pool_pc_vault, flows.as_ref())?;
+                let lp_amount = flows.as_ref().map(|flows| flows.minted(&lp_mint));
+                self.liquidity_event(amm, owner, lp_mint, LiquidityChangeKind::Withdraw, vaults, lp_amount)
+            }
+        };
+        Ok(event)
+    }
+
+    /// Mints of the pool vaults from the token balances, and what the instruction moved in (positive) or out of them
+    fn vaults(&self, base_vault: &str, quote_vault: &str, flows: Option<&TokenFlows>) -> Result<PoolVaults, Box<dyn Error>> {
+        let mint = |vault: &str| -> Result<String, Box<dyn Error>> {
+            self.deltas.get(vault)
+                .map(|delta| delta.mint.clone())
+                .ok_or_else(|| format!("vault {} not in the token balances", vault).into())
+        };
+        Ok(PoolVaults {
+            base_mint: mint(base_vault)?,
+            quote_mint: mint(quote_vault)?,
+            base_flow: flows.map(|flows| flows.account(base_vault)),
+            quote_flow: flows.map(|flows| flows.account(quote_vault)),
+        })
+    }
+
+    fn swap_event(&self, pool: String, trader: String, vaults: PoolVaults, amount_specified: u64, other_amount_threshold: u64) -> RaydiumAmmEvent {
+        RaydiumAmmEvent::Swap(Swap {
+            signature: self.signature.clone(),
+            slot: self.transaction.slot,
+            block_time: self.transaction.block_time,
+            pool,
+            trader,
+            side: match vaults.base_flow {
+                Some(flow) if flow < 0 => Some(SwapSide::Buy),
+                Some(flow) if flow > 0 => Some(SwapSide::Sell),
+                _ => None,
+            },
+            base_amount: vaults.base_flow.map(moved),
+            quote_amount: vaults.quote_flow.map(moved),
+            base_mint: vaults.base_mint,
+            quote_mint: vaults.quote_mint,
+            amount_specified,
+            other_amount_threshold,
+        })
+    }
+
+    fn liquidity_event(&self, pool: String, owner: String, lp_mint: String, kind: LiquidityChangeKind, vaults: PoolVaults,
+                       lp_amount: Option<i128>) -> RaydiumAmmEvent {
+        RaydiumAmmEvent::LiquidityChange(LiquidityChange {
+            signature: self.signature.clone(),
+            slot: self.transaction.slot,
+            block_time: self.transaction.block_time,
+            pool,
+            owner,
+            kind,
+            base_mint: vaults.base_mint,
+            quote_mint: vaults.quote_mint,
+            lp_mint,
+            base_amount: vaults.base_flow.map(moved),
+            quote_amount: vaults.quote_flow.map(moved),
+            lp_amount: lp_amount.map(moved),
+        })
+    }
+}
+
+struct PoolVaults {
+    base_mint: String,
+    quote_mint: String,
+    // Into the vault when positive, None when unknown
+    base_flow: Option<i128>,
+    quote_flow: Option<i128>,
+}
+
+/// Net amounts moved by the token instructions an AMM instruction invoked: per token account (transfers, mints and burns) and
+/// per mint (minted when positive, burnt when negative).
+#[derive(Default)]
+struct TokenFlows {
+    accounts: HashMap<String, i128>,
+    mints: HashMap<String, i128>,
+}
+
+impl TokenFlows {
+    /// The flows of the instructions invoked by `instructions[position]`. None for an inner instruction without a stack height
+    /// (before v1.14): what it invoked can't be told apart from what its parent invoked.
+    fn invoked_by(instructions: &[ResolvedInstruction], position: usize) -> Option<Self> {
+        let parent = &instructions[position];
+        // Every inner instruction of the same outer instruction was invoked by the outer one
+        let height = match parent.inner_index {
+            None => None,
+            Some(_) => Some(parent.stack_height?),
+        };
+
+        let mut flows = TokenFlows::default();
+        let invoked = instructions[position + 1..].iter()
+            .take_while(|instruction| instruction.outer_index == parent.outer_index)
+            .take_while(|instruction| height.is_none_or(|height| instruction.stack_height.is_some_and(|invoked| invoked > height)))
+            .filter(|instruction| instruction.program_id == SPL_TOKEN_PROGRAM_ID || instruction.program_id == TOKEN_2022_PROGRAM_ID);
+        for instruction in invoked {
+            // Not a transfer, mint or burn as far as the amounts are concerned
+            let Ok(Some(token_instruction)) = TokenInstruction::unpack(&instruction.data, &instruction.accounts) else {
+                continue;
+            };
+            match token_instruction {
+                TokenInstruction::Transfer { source, destination, amount, .. }
+                | TokenInstruction::TransferChecked { source, destination, amount, .. } => {
+                    *flows.accounts.entry(source).or_default() -= amount as i128;
+                    *flows.accounts.entry(destination).or_default() += amount as i128;
+                }
+                TokenInstruction::MintTo { mint, account, amount, .. } | TokenInstruction::MintToChecked { mint, account, amount, .. } => {
+                    *flows.accounts.entry(account).or_default() += amount as i128;
+                    *flows.mints.entry(mint).or_default() += amount as i128;
+                }
+                TokenInstruction::Burn { account, mint, amount, .. } | TokenInstruction::BurnChecked { account, mint, amount, .. } => {
+                    *flows.accounts.entry(account).or_default() -= amount as i128;
+                    *flows.mints.entry(mint).or_default() -= amount as i128;
+                }
+                _ => {}
+            }
+        }
+        Some(flows)
+    }
+
+    fn account(&self, account: &str) -> i128 {
+        self.accounts.get(account).copied().unwrap_or_default()
+    }
+
+    fn minted(&self, mint: &str) -> i128 {
+        self.mints.get(mint).copied().unwrap_or_default()
+    }
+}
+
+fn moved(flow: i128) -> u64 {
+    flow.unsigned_abs() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+    use solana_sdk::bs58;
+
+    use super::*;
+
+    // Accounts of the SOL/USDC pool on mainnet
+    const AMM: &str = "58oQChx4yWmvKdwLLZzBi4ChoCc2fqCUWBkwMihLYQo2";
+    const AMM_AUTHORITY: &str = "5Q544fKrFoe6tsEbD7S8EmxGTJYAKtTVhAW5Q5pge4j1";
+    const AMM_OPEN_ORDERS: &str = "HmiHHzq4Fym9e1D4qzLS6LDDM3tNsCTBPDWHTLZ763jY";
+    const AMM_TARGET_ORDERS: &str = "CZza3Ej4Mc58MnxWA385itCC9jCo3L1D7zc3LKy1bZMR";
+    const LP_MINT: &str = "8HoQnePLqPj4M7PUDzfw8e3Ymdwgc7NLGnaTUapubyvu";
+    const COIN_VAULT: &str = "DQyrAcCrDXQ7NeoqGgDCZwBvWDcYmFCjSb9JtteuvPpz";
+    const PC_VAULT: &str = "HLmqeL62xR1QoZ1HKKbXRrdN1p3phKpxRMb2VVopvBBz";
+    const MARKET: &str = "8BnEgHoWFysVcuFFX7QztDmzuH8r5ZFvyP3sYwn1XTh6";
+    const OPENBOOK: &str = "srmqPvymJeFKQ4zGQed1GFppgkRHL9kaELCbyksJtPX";
+    const WSOL: &str = "So11111111111111111111111111111111111111112";
+    const USDC: &str = "EPjFWdd5AufqSSqeM2qMi4SbwY4qjSi8BpRxKeuUXx3Z";
+    const TOKEN_PROGRAM: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+    const USER: &str = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+    const USER_SOURCE: &str = "3ZZbNBnGu6qPpTzr6sHQgCE4BkNjGnSBbFhuwaJ4e8Pk";
+    const USER_DESTINATION: &str = "7Xe3gQxZX9RQu2wh2a1QjAYFGHtP2U8hRZSZqQGkmQZJ";
+
+    fn accounts(addresses: &[&str]) -> Vec<String> {
+        addresses.iter().map(|address| address.to_string()).collect()
+    }
+
+    fn data(tag: u8, fields: &[u64]) -> Vec<u8> {
+        let mut data = vec![tag];
+        fields.iter().for_each(|field| data.extend_from_slice(&field.to_le_bytes()));
+        data
+    }
+
+    // token program, amm, authority, open orders, target orders, coin vault, pc vault, market program, market, bids, asks,
+    // event queue, market coin vault, market pc vault, vault signer, user source, user destination, owner
+    fn swap_accounts() -> Vec<String> {
+        accounts(&[
+            TOKEN_PROGRAM, AMM, AMM_AUTHORITY, AMM_OPEN_ORDERS, AMM_TARGET_ORDERS, COIN_VAULT, PC_VAULT, OPENBOOK, MARKET,
+            "14ivtgssEBoBjuZJtSAPKYgpUK7DmnSwuPMqJoVTSgKJ", "CEQdAFKdycHugujQg9k2wbmxjcpdYZyVLfV9WerTnafJ",
+            "5KKsLVU6TcbVDK4BS6K1DGDxnh4Q9xjYJ8XaDCG5t8ht", "36c6YqAwyGKQG66XEp2dJc5JqjaBNv7sVghEtJv4c7u6",
+            "8CFo8bL8mZQK8abbFyypFMwEDd8tVJjHTTojMLgQTUSZ", "F8Vyqk3unwxkXukZFQeYyGmFfTG3CAX4v24iyrjEYBJV",
+            USER_SOURCE, USER_DESTINATION, USER,
+        ])
+    }
+
+    #[test]
+    fn unpacks_initialize2() {
+        // token program, associated token program, system program, rent, amm, authority, open orders, lp mint, coin mint,
+        // pc mint, coin vault, pc vault, target orders, config, fee destination, market program, market, creator, creator's
+        // coin, pc and lp token accounts
+        let accounts = accounts(&[
+            TOKEN_PROGRAM, "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL", "11111111111111111111111111111111",
+            "SysvarRent111111111111111111111111111111111", AMM, AMM_AUTHORITY, AMM_OPEN_ORDERS, LP_MINT, WSOL, USDC,
+            COIN_VAULT, PC_VAULT, AMM_TARGET_ORDERS, "9DCxsMizn3H1hprZ7xWe6LDzeUeZBksYFpBWBtSf1PQX",
+            "7YttLkHDoNj9wyDur5pM1ejNaAvT9X4eqaYcHQqtj2G5", OPENBOOK, MARKET, USER, USER_SOURCE, USER_DESTINATION,
+            "Bf6i2GmZ7SdXrXZxaSsMTyW7qnjYvPxjw3q1jT2qDUnB",
+        ]);
+        // The nonce is a single byte between the tag and the amounts
+        let mut data = data(1, &[1_718_726_400, 2_000_000_000_000, 13_000_000_000_000]);
+        data.insert(1, 254);
+
+        let instruction = RaydiumAmmInstruction::unpack(&data, &accounts).unwrap();
+        assert_eq!(instruction, Some(RaydiumAmmInstruction::Initialize2 {
+            amm: AMM.to_string(),
+            lp_mint: LP_MINT.to_string(),
+            coin_mint: WSOL.to_string(),
+            pc_mint: USDC.to_string(),
+            pool_coin_vault: COIN_VAULT.to_string(),
+            pool_pc_vault: PC_VAULT.to_string(),
+            serum_market: MARKET.to_string(),
+            creator: USER.to_string(),
+            nonce: 254,
+            open_time: 1_718_726_400,
+            init_pc_amount: 2_000_000_000_000,
+            init_coin_amount: 13_000_000_000_000,
+        }));
+    }
+
+    #[test]
+    fn unpacks_swaps_with_and_without_target_orders() {
+        let with_target_orders = swap_accounts();
+        let mut without_target_orders = with_target_orders.clone();
+        without_target_orders.remove(4);
+
+        for accounts in [with_target_orders, without_target_orders] {
+            let instruction = RaydiumAmmInstruction::unpack(&data(9, &[1_000_000_000, 140_000_000]), &accounts).unwrap();
+            assert_eq!(instruction, Some(RaydiumAmmInstruction::SwapBaseIn {
+                amm: AMM.to_string(),
+                pool_coin_vault: COIN_VAULT.to_string(),
+                pool_pc_vault: PC_VAULT.to_string(),
+                user_source: USER_SOURCE.to_string(),
+                user_destination: USER_DESTINATION.to_string(),
+                owner: USER.to_string(),
+                amount_in: 1_000_000_000,
+                minimum_amount_out: 140_000_000,
+            }), "{} accounts", accounts.len());
+
+            let instruction = RaydiumAmmInstruction::unpack(&data(11, &[150_000_000, 1_000_000_000]), &accounts).unwrap();
+            assert!(matches!(instruction, Some(RaydiumAmmInstruction::SwapBaseOut { max_amount_in: 150_000_000, amount_out: 1_000_000_000, .. })));
+        }
+    }
+
+    #[test]
+    fn unpacks_deposit() {
+        // token program, amm, authority, open orders, target orders, lp mint, coin vault, pc vault, market, user coin, user pc,
+        // user lp, owner, event queue
+        let accounts = accounts(&[
+            TOKEN_PROGRAM, AMM, AMM_AUTHORITY, AMM_OPEN_ORDERS, AMM_TARGET_ORDERS, LP_MINT, COIN_VAULT, PC_VAULT, MARKET,
+            USER_SOURCE, USER_DESTINATION, "Bf6i2GmZ7SdXrXZxaSsMTyW7qnjYvPxjw3q1jT2qDUnB", USER,
+            "5KKsLVU6TcbVDK4BS6K1DGDxnh4Q9xjYJ8XaDCG5t8ht",
+        ]);
+        let instruction = RaydiumAmmInstruction::unpack(&data(3, &[5_000_000_000, 720_000_000, 0]), &accounts).unwrap();
+        assert_eq!(instruction, Some(RaydiumAmmInstruction::Deposit {
+            amm: AMM.to_string(),
+            lp_mint: LP_MINT.to_string(),
+            pool_coin_vault: COIN_VAULT.to_string(),
+            pool_pc_vault: PC_VAULT.to_string(),
+            owner: USER.to_string(),
+            max_coin_amount: 5_000_000_000,
+            max_pc_amount: 720_000_000,
+            base_side: 0,
+        }));
+    }
+
+    #[test]
+    fn unpacks_withdraw_owner_from_the_end() {
+        // token program, amm, authority, open orders, target orders, lp mint, coin vault, pc vault, withdraw queue, temp lp,
+        // market program, market, market coin vault, market pc vault, vault signer, user lp, user coin, user pc, owner,
+        // event queue, bids, asks
+        let with_queue = accounts(&[
+            TOKEN_PROGRAM, AMM, AMM_AUTHORITY, AMM_OPEN_ORDERS, AMM_TARGET_ORDERS, LP_MINT, COIN_VAULT, PC_VAULT,
+            "G7mw1d83ismcQJKkzt62Ug4noXCjVhu3eV7U5EMfHXqD", "HhE5LZHXVQFgPbEGdtbEpDYmdfELc8EGrvC8KUXcLsTv", OPENBOOK, MARKET,
+            "36c6YqAwyGKQG66XEp2dJc5JqjaBNv7sVghEtJv4c7u6", "8CFo8bL8mZQK8abbFyypFMwEDd8tVJjHTTojMLgQTUSZ",
+            "F8Vyqk3unwxkXukZFQeYyGmFfTG3CAX4v24iyrjEYBJV", "Bf6i2GmZ7SdXrXZxaSsMTyW7qnjYvPxjw3q1jT2qDUnB", USER_SOURCE,
+            USER_DESTINATION, USER, "5KKsLVU6TcbVDK4BS6K1DGDxnh4Q9xjYJ8XaDCG5t8ht",
+            "14ivtgssEBoBjuZJtSAPKYgpUK7DmnSwuPMqJoVTSgKJ", "CEQdAFKdycHugujQg9k2wbmxjcpdYZyVLfV9WerTnafJ",
+        ]);
+        // Newer clients leave out the withdraw queue and temp lp accounts
+        let mut without_queue = with_queue.clone();
+        without_queue.drain(8..10);
+
+        for accounts in [with_queue, without_queue] {
+            let instruction = RaydiumAmmInstruction::unpack(&data(4, &[25_000_000]), &accounts).unwrap();
+            assert_eq!(instruction, Some(RaydiumAmmInstruction::Withdraw {
+                amm: AMM.to_string(),
+                lp_mint: LP_MINT.to_string(),
+                pool_coin_vault: COIN_VAULT.to_string(),
+                pool_pc_vault: PC_VAULT.to_string(),
+                owner: USER.to_string(),
+                amount: 25_000_000,
+            }), "{} accounts", accounts.len());
+        }
+    }
+
+    #[test]
+    fn skips_admin_instructions_and_rejects_missing_accounts() {
+        // SetParams
+        assert_eq!(RaydiumAmmInstruction::unpack(&[6, 0], &swap_accounts()).unwrap(), None);
+        assert!(RaydiumAmmInstruction::unpack(&data(9, &[1, 1]), &swap_accounts()[..3]).is_err());
+        assert!(RaydiumAmmInstruction::unpack(&[9, 1, 2], &swap_accounts()).is_err());
+    }
+
+    // Two SOL/USDC swaps in one transaction, the vault balances only show their sum
+    fn swaps_transaction(instructions: Vec<Value>, inner_instructions: Vec<Value>) -> EncodedTransactionWithMeta {
+        let keys = keys();
+        let index = |address: &str| keys.iter().position(|key| key == address).unwrap();
+        let balance = |address: &str, mint: &str, amount: u64, decimals: u8| json!({
+            "accountIndex": index(address),
+            "mint": mint,
+            "owner": AMM_AUTHORITY,
+            "programId": TOKEN_PROGRAM,
+            "uiTokenAmount": {"amount": amount.to_string(), "decimals": decimals, "uiAmount": null, "uiAmountString": ""},
+        });
+        serde_json::from_value(json!({
+            "slot": 270_000_000,
+            "blockTime": 1_718_726_500,
+            "version": 0,
+            "meta": {
+                "err": null,
+                "fee": 5000,
+                "preBalances": [],
+                "postBalances": [],
+                "preTokenBalances": [balance(COIN_VAULT, WSOL, 50_000_000_000_000, 9), balance(PC_VAULT, USDC, 7_000_000_000_000, 6)],
+                "postTokenBalances": [balance(COIN_VAULT, WSOL, 50_003_000_000_000, 9), balance(PC_VAULT, USDC, 6_999_577_000_000, 6)],
+                "innerInstructions": inner_instructions,
+            },
+            "transaction": {
+                "signatures": ["5h6xBEauJ3PK6SWCZ1PGjBvj8vDdWG3KpwATGy1ARAXFSDwt8GFXM7W5Ncn16wmqokgpiKRLuS83KUxyZyv2sUYv"],
+                "message": {
+                    "header": {"numRequiredSignatures": 1, "numReadonlySignedAccounts": 0, "numReadonlyUnsignedAccounts": 0},
+                    "accountKeys": keys,
+                    "recentBlockhash": "EtWTRABZaYq6iMfeYKouRu166VU2xqa1wcaWoxPkrZBG",
+                    "instructions": instructions,
+                },
+            },
+        })).unwrap()
+    }
+
+    // The swap accounts followed by the AMM program and a pool missing from the token balances
+    fn keys() -> Vec<String> {
+        let mut keys = swap_accounts();
+        keys.extend(accounts(&[RAYDIUM_AMM_V4_PROGRAM_ID, "7JuwJuNU88gurFnyWeiyGKbFmExMWcmRZntn9imEzdny"]));
+        keys
+    }
+
+    fn swap(accounts: Vec<usize>, data: Vec<u8>, stack_height: Option<u32>) -> Value {
+        json!({
+            "programIdIndex": keys().len() - 2,
+            "accounts": accounts,
+            "data": bs58::encode(data).into_string(),
+            "stackHeight": stack_height,
+        })
+    }
+
+    fn transfer(source: &str, destination: &str, authority: &str, amount: u64, stack_height: Option<u32>) -> Value {
+        let keys = keys();
+        let index = |address: &str| keys.iter().position(|key| key == address).unwrap();
+        json!({
+            "programIdIndex": index(TOKEN_PROGRAM),
+            "accounts": [index(source), index(destination), index(authority)],
+            "data": bs58::encode(data(3, &[amount])).into_string(),
+            "stackHeight": stack_height,
+        })
+    }
+
+    #[test]
+    fn each_swap_gets_the_amounts_it_transferred() {
+        let accounts: Vec<usize> = (0..swap_accounts().len()).collect();
+        let transaction = swaps_transaction(
+            vec![swap(accounts.clone(), data(9, &[1_000_000_000, 140_000_000]), None), swap(accounts, data(9, &[2_000_000_000, 280_000_000]), None)],
+            vec![
+                json!({"index": 0, "instructions": [
+                    transfer(USER_SOURCE, COIN_VAULT, USER, 1_000_000_000, Some(2)),
+                    transfer(PC_VAULT, USER_DESTINATION, AMM_AUTHORITY, 141_000_000, Some(2)),
+                ]}),
+                json!({"index": 1, "instructions": [
+                    transfer(USER_SOURCE, COIN_VAULT, USER, 2_000_000_000, Some(2)),
+                    transfer(PC_VAULT, USER_DESTINATION, AMM_AUTHORITY, 282_000_000, Some(2)),
+                ]}),
+            ],
+        );
+
+        let events = amm_events(&transaction).unwrap();
+        let [RaydiumAmmEvent::Swap(first), RaydiumAmmEvent::Swap(second)] = events.as_slice() else {
+            panic!("expected two swaps, got {:?}", events);
+        };
+        assert_eq!((first.pool.as_str(), first.trader.as_str(), first.side), (AMM, USER, Some(SwapSide::Sell)));
+        assert_eq!((first.base_mint.as_str(), first.base_amount), (WSOL, Some(1_000_000_000)));
+        assert_eq!((first.quote_mint.as_str(), first.quote_amount), (USDC, Some(141_000_000)));
+        assert_eq!((first.amount_specified, first.other_amount_threshold), (1_000_000_000, 140_000_000));
+        assert_eq!((second.base_amount, second.quote_amount), (Some(2_000_000_000), Some(282_000_000)));
+    }
+
+    #[test]
+    fn skips_only_the_instructions_it_cannot_read() {
+        let accounts: Vec<usize> = (0..swap_accounts().len()).collect();
+        // Same swap on a pool whose coin vault is not in the token balances
+        let mut unknown_vault = accounts.clone();
+        unknown_vault[5] = keys().len() - 1;
+        let transaction = swaps_transaction(
+            vec![
+                swap(accounts.clone(), data(9, &[1_000_000_000, 140_000_000]), None),
+                swap(accounts.clone(), vec![9, 1, 2], None),
+                swap(unknown_vault, data(9, &[1_000_000_000, 140_000_000]), None),
+                // A router invoking the AMM, from before stack heights were recorded
+                json!({"programIdIndex": 7, "accounts": [], "data": ""}),
+            ],
+            vec![
+                json!({"index": 0, "instructions": [
+                    transfer(USER_SOURCE, COIN_VAULT, USER, 1_000_000_000, Some(2)),
+                    transfer(PC_VAULT, USER_DESTINATION, AMM_AUTHORITY, 141_000_000, Some(2)),
+                ]}),
+                json!({"index": 3, "instructions": [
+                    swap(accounts, data(9, &[2_000_000_000, 280_000_000]), None),
+                    transfer(USER_SOURCE, COIN_VAULT, USER, 2_000_000_000, None),
+                    transfer(PC_VAULT, USER_DESTINATION, AMM_AUTHORITY, 282_000_000, None),
+                ]}),
+            ],
+        );
+
+        let events = amm_events(&transaction).unwrap();
+        let [RaydiumAmmEvent::Swap(outer), RaydiumAmmEvent::Swap(routed)] = events.as_slice() else {
+            panic!("expected two swaps, got {:?}", events);
+        };
+        assert_eq!((outer.base_amount, outer.quote_amount), (Some(1_000_000_000), Some(141_000_000)));
+        assert_eq!((routed.side, routed.base_amount, routed.quote_amount), (None, None, None));
+        assert_eq!(routed.amount_specified, 2_000_000_000);
+    }
+}
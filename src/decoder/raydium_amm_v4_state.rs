@@ -0,0 +1,169 @@
+// https://github.com/raydium-io/raydium-amm/blob/master/program/src/state.rs
+use std::error::Error;
+
+use serde::{Deserialize, Serialize};
+
+use crate::decoder::account_decoder::{AccountDecoder, DecodedAccount};
+use crate::decoder::byte_reader::ByteReader;
+
+pub const RAYDIUM_AMM_V4_PROGRAM_ID: &str = "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8";
+
+pub const AMM_INFO_LEN: usize = 752;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AmmFees {
+    pub min_separate_numerator: u64,
+    pub min_separate_denominator: u64,
+    pub trade_fee_numerator: u64,
+    pub trade_fee_denominator: u64,
+    pub pnl_numerator: u64,
+    pub pnl_denominator: u64,
+    pub swap_fee_numerator: u64,
+    pub swap_fee_denominator: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AmmStateData {
+    pub need_take_pnl_coin: u64,
+    pub need_take_pnl_pc: u64,
+    pub total_pnl_pc: u64,
+    pub total_pnl_coin: u64,
+    // Unix timestamp (seconds) from which swaps are allowed, launches usually set it a few minutes ahead
+    pub pool_open_time: u64,
+    pub orderbook_to_init_time: u64,
+    pub swap_coin_in_amount: u128,
+    pub swap_pc_out_amount: u128,
+    pub swap_acc_pc_fee: u64,
+    pub swap_pc_in_amount: u128,
+    pub swap_coin_out_amount: u128,
+    pub swap_acc_coin_fee: u64,
+}
+
+/// Pool state of a Raydium AMM v4 pool. Coin is the base token, pc ("price currency") the quote token.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AmmInfo {
+    pub status: u64,
+    pub nonce: u64,
+    pub order_num: u64,
+    pub depth: u64,
+    pub coin_decimals: u64,
+    pub pc_decimals: u64,
+    pub state: u64,
+    pub reset_flag: u64,
+    pub min_size: u64,
+    pub vol_max_cut_ratio: u64,
+    pub amount_wave_ratio: u64,
+    pub coin_lot_size: u64,
+    pub pc_lot_size: u64,
+    pub min_price_multiplier: u64,
+    pub max_price_multiplier: u64,
+    pub sys_decimal_value: u64,
+    pub fees: AmmFees,
+    pub state_data: AmmStateData,
+    pub coin_vault: String,
+    pub pc_vault: String,
+    pub coin_vault_mint: String,
+    pub pc_vault_mint: String,
+    pub lp_mint: String,
+    pub open_orders: String,
+    pub market: String,
+    pub market_program: String,
+    pub target_orders: String,
+    pub amm_owner: String,
+    pub lp_amount: u64,
+    pub client_order_id: u64,
+    pub recent_epoch: u64,
+}
+
+impl AmmInfo {
+    pub fn unpack(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let mut reader = ByteReader::new(data);
+        let status = reader.u64()?;
+        let nonce = reader.u64()?;
+        let order_num = reader.u64()?;
+        let depth = reader.u64()?;
+        let coin_decimals = reader.u64()?;
+        let pc_decimals = reader.u64()?;
+        let state = reader.u64()?;
+        let reset_flag = reader.u64()?;
+        let min_size = reader.u64()?;
+        let vol_max_cut_ratio = reader.u64()?;
+        let amount_wave_ratio = reader.u64()?;
+        let coin_lot_size = reader.u64()?;
+        let pc_lot_size = reader.u64()?;
+        let min_price_multiplier = reader.u64()?;
+        let max_price_multiplier = reader.u64()?;
+        let sys_decimal_value = reader.u64()?;
+
+        let fees = AmmFees {
+            min_separate_numerator: reader.u64()?,
+            min_separate_denominator: reader.u64()?,
+            trade_fee_numerator: reader.u64()?,
+            trade_fee_denominator: reader.u64()?,
+            pnl_numerator: reader.u64()?,
+            pnl_denominator: reader.u64()?,
+            swap_fee_numerator: reader.u64()?,
+            swap_fee_denominator: reader.u64()?,
+        };
+
+        let need_take_pnl_coin = reader.u64()?;
+        let need_take_pnl_pc = reader.u64()?;
+        let total_pnl_pc = reader.u64()?;
+        let total_pnl_coin = reader.u64()?;
+        let pool_open_time = reader.u64()?;
+        reader.skip(16)?; // padding: [u64; 2]
+        let state_data = AmmStateData {
+            need_take_pnl_coin,
+            need_take_pnl_pc,
+            total_pnl_pc,
+            total_pnl_coin,
+            pool_open_time,
+            orderbook_to_init_time: reader.u64()?,
+            swap_coin_in_amount: reader.u128()?,
+            swap_pc_out_amount: reader.u128()?,
+            swap_acc_pc_fee: reader.u64()?,
+            swap_pc_in_amount: reader.u128()?,
+            swap_coin_out_amount: reader.u128()?,
+            swap_acc_coin_fee: reader.u64()?,
+        };
+
+        let coin_vault = reader.pubkey()?;
+        let pc_vault = reader.pubkey()?;
+        let coin_vault_mint = reader.pubkey()?;
+        let pc_vault_mint = reader.pubkey()?;
+        let lp_mint = reader.pubkey()?;
+        let open_orders = reader.pubkey()?;
+        let market = reader.pubkey()?;
+        let market_program = reader.pubkey()?;
+        let target_orders = reader.pubkey()?;
+        reader.skip(64)?; // padding1: [u64; 8]
+        let amm_owner = reader.pubkey()?;
+        let lp_amount = reader.u64()?;
+        let client_order_id = reader.u64()?;
+        let recent_epoch = reader.u64()?;
+
+        Ok(AmmInfo {
+            status, nonce, order_num, depth, coin_decimals, pc_decimals, state, reset_flag, min_size, vol_max_cut_ratio,
+            amount_wave_ratio, coin_lot_size, pc_lot_size, min_price_multiplier, max_price_multiplier, sys_decimal_value,
+            fees, state_data,
+            coin_vault, pc_vault, coin_vault_mint, pc_vault_mint, lp_mint, open_orders, market, market_program, target_orders,
+            amm_owner, lp_amount, client_order_id, recent_epoch,
+        })
+    }
+}
+
+/// Pool state accounts of Raydium AMM v4. Open orders / target orders accounts owned by the program are left undecoded.
+pub struct RaydiumAmmV4AccountDecoder;
+
+impl AccountDecoder for RaydiumAmmV4AccountDecoder {
+    fn owner(&self) -> &str {
+        RAYDIUM_AMM_V4_PROGRAM_ID
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Option<DecodedAccount>, Box<dyn Error>> {
+        if data.len() != AMM_INFO_LEN {
+            return Ok(None);
+        }
+        AmmInfo::unpack(data).map(|amm| Some(DecodedAccount::RaydiumAmmV4(Box::new(amm))))
+    }
+}
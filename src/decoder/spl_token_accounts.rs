@@ -0,0 +1,95 @@
+// https://github.com/solana-labs/solana-program-library/blob/master/token/program/src/state.rs
+use std::error::Error;
+
+use serde::{Deserialize, Serialize};
+
+use crate::decoder::account_decoder::{AccountDecoder, DecodedAccount};
+use crate::decoder::byte_reader::ByteReader;
+
+pub const SPL_TOKEN_PROGRAM_ID: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+/// Wrapped SOL: its token accounts hold their balance as lamports, on top of the rent
+pub const NATIVE_MINT: &str = "So11111111111111111111111111111111111111112";
+
+pub const MINT_LEN: usize = 82;
+pub const TOKEN_ACCOUNT_LEN: usize = 165;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Mint {
+    pub mint_authority: Option<String>,
+    pub supply: u64,
+    pub decimals: u8,
+    pub is_initialized: bool,
+    pub freeze_authority: Option<String>,
+}
+
+impl Mint {
+    pub fn unpack(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let mut reader = ByteReader::new(data);
+        Ok(Mint {
+            mint_authority: reader.coption_pubkey()?,
+            supply: reader.u64()?,
+            decimals: reader.u8()?,
+            is_initialized: reader.bool()?,
+            freeze_authority: reader.coption_pubkey()?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenAccountState {
+    Uninitialized,
+    Initialized,
+    Frozen,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TokenAccount {
+    pub mint: String,
+    pub owner: String,
+    pub amount: u64,
+    pub delegate: Option<String>,
+    pub state: TokenAccountState,
+    // Rent-exempt reserve of wrapped SOL accounts
+    pub is_native: Option<u64>,
+    pub delegated_amount: u64,
+    pub close_authority: Option<String>,
+}
+
+impl TokenAccount {
+    pub fn unpack(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let mut reader = ByteReader::new(data);
+        Ok(TokenAccount {
+            mint: reader.pubkey()?,
+            owner: reader.pubkey()?,
+            amount: reader.u64()?,
+            delegate: reader.coption_pubkey()?,
+            state: match reader.u8()? {
+                0 => TokenAccountState::Uninitialized,
+                1 => TokenAccountState::Initialized,
+                2 => TokenAccountState::Frozen,
+                state => return Err(format!("invalid token account state {}", state).into()),
+            },
+            is_native: reader.coption_u64()?,
+            delegated_amount: reader.u64()?,
+            close_authority: reader.coption_pubkey()?,
+        })
+    }
+}
+
+/// Mints and token accounts of the SPL Token program, told apart by their size. Multisigs are not decoded.
+pub struct SplTokenAccountDecoder;
+
+impl AccountDecoder for SplTokenAccountDecoder {
+    fn owner(&self) -> &str {
+        SPL_TOKEN_PROGRAM_ID
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Option<DecodedAccount>, Box<dyn Error>> {
+        match data.len() {
+            MINT_LEN => Mint::unpack(data).map(|mint| Some(DecodedAccount::SplTokenMint(mint))),
+            TOKEN_ACCOUNT_LEN => TokenAccount::unpack(data).map(|account| Some(DecodedAccount::SplTokenAccount(account))),
+            _ => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,233 @@
+// https://github.com/solana-labs/solana-program-library/blob/master/token/program/src/instruction.rs
+use std::error::Error;
+
+use serde::{Deserialize, Serialize};
+
+use crate::decoder::byte_reader::ByteReader;
+use crate::decoder::instruction_decoder::{DecodedInstruction, InstructionDecoder};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthorityType {
+    MintTokens,
+    FreezeAccount,
+    AccountOwner,
+    CloseAccount,
+    // Token-2022 authorities (transfer fee config, close mint...)
+    Other(u8),
+}
+
+impl AuthorityType {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => AuthorityType::MintTokens,
+            1 => AuthorityType::FreezeAccount,
+            2 => AuthorityType::AccountOwner,
+            3 => AuthorityType::CloseAccount,
+            other => AuthorityType::Other(other),
+        }
+    }
+}
+
+/// SPL Token instruction with its accounts resolved. Amounts are raw, in base units of the mint.
+/// Multisig signers following the authority are not kept.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenInstruction {
+    InitializeMint { mint: String, decimals: u8, mint_authority: String, freeze_authority: Option<String> },
+    // Same as InitializeMint without the rent sysvar
+    InitializeMint2 { mint: String, decimals: u8, mint_authority: String, freeze_authority: Option<String> },
+    InitializeAccount { account: String, mint: String, owner: String },
+    // The owner comes in the data instead of the accounts
+    InitializeAccount2 { account: String, mint: String, owner: String },
+    // Same as InitializeAccount2 without the rent sysvar
+    InitializeAccount3 { account: String, mint: String, owner: String },
+    Transfer { source: String, destination: String, authority: String, amount: u64 },
+    TransferChecked { source: String, mint: String, destination: String, authority: String, amount: u64, decimals: u8 },
+    Approve { source: String, delegate: String, owner: String, amount: u64 },
+    Revoke { source: String, owner: String },
+    SetAuthority { account: String, current_authority: String, authority_type: AuthorityType, new_authority: Option<String> },
+    MintTo { mint: String, account: String, mint_authority: String, amount: u64 },
+    MintToChecked { mint: String, account: String, mint_authority: String, amount: u64, decimals: u8 },
+    Burn { account: String, mint: String, authority: String, amount: u64 },
+    BurnChecked { account: String, mint: String, authority: String, amount: u64, decimals: u8 },
+    CloseAccount { account: String, destination: String, owner: String },
+    FreezeAccount { account: String, mint: String, freeze_authority: String },
+    ThawAccount { account: String, mint: String, freeze_authority: String },
+}
+
+impl TokenInstruction {
+    /// Decodes the instruction data, the first byte being the instruction tag. Ok(None) for the instructions not modeled
+    /// (multisig initialization, ApproveChecked, SyncNative, Token-2022 extension instructions...).
+    pub fn unpack(data: &[u8], accounts: &[String]) -> Result<Option<Self>, Box<dyn Error>> {
+        let mut reader = ByteReader::new(data);
+        let account = |index: usize| -> Result<String, Box<dyn Error>> {
+            accounts.get(index)
+                .cloned()
+                .ok_or_else(|| format!("token instruction expects an account at position {}, got {} accounts", index, accounts.len()).into())
+        };
+
+        let instruction = match reader.u8()? {
+            0 => TokenInstruction::InitializeMint {
+                decimals: reader.u8()?,
+                mint_authority: reader.pubkey()?,
+                freeze_authority: reader.option_pubkey()?,
+                mint: account(0)?,
+            },
+            1 => TokenInstruction::InitializeAccount { account: account(0)?, mint: account(1)?, owner: account(2)? },
+            3 => TokenInstruction::Transfer {
+                amount: reader.u64()?,
+                source: account(0)?,
+                destination: account(1)?,
+                authority: account(2)?,
+            },
+            4 => TokenInstruction::Approve {
+                amount: reader.u64()?,
+                source: account(0)?,
+                delegate: account(1)?,
+                owner: account(2)?,
+            },
+            5 => TokenInstruction::Revoke { source: account(0)?, owner: account(1)? },
+            6 => TokenInstruction::SetAuthority {
+                authority_type: AuthorityType::from_u8(reader.u8()?),
+                new_authority: reader.option_pubkey()?,
+                account: account(0)?,
+                current_authority: account(1)?,
+            },
+            7 => TokenInstruction::MintTo {
+                amount: reader.u64()?,
+                mint: account(0)?,
+                account: account(1)?,
+                mint_authority: account(2)?,
+            },
+            8 => TokenInstruction::Burn {
+                amount: reader.u64()?,
+                account: account(0)?,
+                mint: account(1)?,
+                authority: account(2)?,
+            },
+            9 => TokenInstruction::CloseAccount { account: account(0)?, destination: account(1)?, owner: account(2)? },
+            10 => TokenInstruction::FreezeAccount { account: account(0)?, mint: account(1)?, freeze_authority: account(2)? },
+            11 => TokenInstruction::ThawAccount { account: account(0)?, mint: account(1)?, freeze_authority: account(2)? },
+            12 => TokenInstruction::TransferChecked {
+                amount: reader.u64()?,
+                decimals: reader.u8()?,
+                source: account(0)?,
+                mint: account(1)?,
+                destination: account(2)?,
+                authority: account(3)?,
+            },
+            14 => TokenInstruction::MintToChecked {
+                amount: reader.u64()?,
+                decimals: reader.u8()?,
+                mint: account(0)?,
+                account: account(1)?,
+                mint_authority: account(2)?,
+            },
+            15 => TokenInstruction::BurnChecked {
+                amount: reader.u64()?,
+                decimals: reader.u8()?,
+                account: account(0)?,
+                mint: account(1)?,
+                authority: account(2)?,
+            },
+            16 => TokenInstruction::InitializeAccount2 { owner: reader.pubkey()?, account: account(0)?, mint: account(1)? },
+            18 => TokenInstruction::InitializeAccount3 { owner: reader.pubkey()?, account: account(0)?, mint: account(1)? },
+            20 => TokenInstruction::InitializeMint2 {
+                decimals: reader.u8()?,
+                mint_authority: reader.pubkey()?,
+                freeze_authority: reader.option_pubkey()?,
+                mint: account(0)?,
+            },
+            _ => return Ok(None),
+        };
+        Ok(Some(instruction))
+    }
+}
+
+/// `amount` in base units to a display amount
+pub fn ui_amount(amount: u64, decimals: u8) -> f64 {
+    amount as f64 / 10f64.powi(decimals as i32)
+}
+
+/// Registered for both SPL Token and Token-2022, which kept the instruction layout of the original program.
+pub struct SplTokenInstructionDecoder {
+    program_id: String,
+}
+
+impl SplTokenInstructionDecoder {
+    pub fn new(program_id: &str) -> Self {
+        Self { program_id: program_id.to_string() }
+    }
+}
+
+impl InstructionDecoder for SplTokenInstructionDecoder {
+    fn program_id(&self) -> &str {
+        &self.program_id
+    }
+
+    fn decode(&self, data: &[u8], accounts: &[String]) -> Result<Option<DecodedInstruction>, Box<dyn Error>> {
+        Ok(TokenInstruction::unpack(data, accounts)?.map(DecodedInstruction::SplToken))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::*;
+
+    const USDC: &str = "EPjFWdd5AufqSSqeM2qMi4SbwY4qjSi8BpRxKeuUXx3Z";
+    const SOURCE: &str = "3ZZbNBnGu6qPpTzr6sHQgCE4BkNjGnSBbFhuwaJ4e8Pk";
+    const DESTINATION: &str = "7Xe3gQxZX9RQu2wh2a1QjAYFGHtP2U8hRZSZqQGkmQZJ";
+    const OWNER: &str = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+
+    fn accounts(addresses: &[&str]) -> Vec<String> {
+        addresses.iter().map(|address| address.to_string()).collect()
+    }
+
+    #[test]
+    fn unpacks_transfer_checked() {
+        // 25 USDC
+        let data = [&[12u8][..], &25_000_000u64.to_le_bytes(), &[6]].concat();
+        let instruction = TokenInstruction::unpack(&data, &accounts(&[SOURCE, USDC, DESTINATION, OWNER])).unwrap();
+        assert_eq!(instruction, Some(TokenInstruction::TransferChecked {
+            source: SOURCE.to_string(),
+            mint: USDC.to_string(),
+            destination: DESTINATION.to_string(),
+            authority: OWNER.to_string(),
+            amount: 25_000_000,
+            decimals: 6,
+        }));
+        assert_eq!(ui_amount(25_000_000, 6), 25.0);
+    }
+
+    #[test]
+    fn unpacks_optional_authorities() {
+        let mint_authority = Pubkey::new_from_array([7; 32]);
+        let data = [&[20u8, 9][..], mint_authority.as_ref(), &[0]].concat();
+        assert_eq!(TokenInstruction::unpack(&data, &accounts(&[USDC])).unwrap(), Some(TokenInstruction::InitializeMint2 {
+            mint: USDC.to_string(),
+            decimals: 9,
+            mint_authority: mint_authority.to_string(),
+            freeze_authority: None,
+        }));
+
+        // Revoking the mint authority
+        assert_eq!(TokenInstruction::unpack(&[6, 0, 0], &accounts(&[USDC, OWNER])).unwrap(), Some(TokenInstruction::SetAuthority {
+            account: USDC.to_string(),
+            current_authority: OWNER.to_string(),
+            authority_type: AuthorityType::MintTokens,
+            new_authority: None,
+        }));
+        assert!(TokenInstruction::unpack(&[6, 0, 2], &accounts(&[USDC, OWNER])).is_err());
+    }
+
+    #[test]
+    fn skips_instructions_not_modeled() {
+        // SyncNative
+        assert_eq!(TokenInstruction::unpack(&[17], &accounts(&[SOURCE])).unwrap(), None);
+        // Transfer missing its authority
+        assert!(TokenInstruction::unpack(&[&[3u8][..], &1u64.to_le_bytes()].concat(), &accounts(&[SOURCE, DESTINATION])).is_err());
+    }
+}
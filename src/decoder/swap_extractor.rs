@@ -0,0 +1,531 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+
+use lazy_static::lazy_static;
+
+use crate::decoder::instruction_decoder::{DecodedInstruction, INSTRUCTION_DECODERS};
+use crate::decoder::raydium_amm_v4_instructions::RaydiumAmmInstruction;
+use crate::decoder::raydium_amm_v4_state::RAYDIUM_AMM_V4_PROGRAM_ID;
+use crate::decoder::spl_token_instructions::TokenInstruction;
+use crate::models::birdeye::amm_providers_response::{AmmProvider, AmmProvidersResponse};
+use crate::models::solana::normalized_swap::{NormalizedSwap, SwapLeg};
+use crate::models::solana::solana_encoded_transaction::{EncodedTransactionWithMeta, ResolvedInstruction, TokenBalanceChange};
+
+/// Bundled with the binary
+const AMM_PROVIDERS: &str = include_str!("../../public/birdeye/amm_providers.json");
+
+pub const JUPITER_SOURCE: &str = "jupiter";
+/// Jupiter aggregator v2, v3, v4 and v6. Routers, their swaps are made of the AMM instructions they invoke.
+pub const JUPITER_PROGRAM_IDS: [&str; 4] = [
+    "JUP2jxvXaqu7NQY1GmNF4m1vodw12LVXYxbFL2uJvfo",
+    "JUP3c2Uh3WA4Ng34tw6kPd2G4C5BB21Xo36Je1s32Ph",
+    "JUP4Fb2cqiRUcaTHdrPC8h2gNsA2ETXiPDD33WcGuJB",
+    "JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4",
+];
+// Account of the Jupiter IDL signing for the trader's token accounts
+const JUPITER_TRADER_ACCOUNT: &str = "userTransferAuthority";
+
+/// An SPL Token transfer invoked by an AMM instruction
+struct TokenTransfer {
+    source: String,
+    destination: String,
+    authority: String,
+    amount: u64,
+    // Only known from the instruction for TransferChecked
+    mint: Option<String>,
+}
+
+/**
+Rebuilds the swaps of a transaction whichever DEX they went through. Every instruction is tagged with the AMM source of its program
+(or of the AMM instruction invoking it) from amm_providers.json, each AMM instruction becomes a `SwapLeg` built from the token
+transfers it invoked (mints and owners from the token balances), and the legs of a Jupiter route are collapsed into the net trade.
+ */
+pub struct SwapExtractor {
+    sources: HashMap<String, String>, // program id / AMM source
+}
+
+impl SwapExtractor {
+    /// From the bundled amm_providers.json
+    pub fn new() -> Self {
+        let providers: AmmProvidersResponse = serde_json::from_str(AMM_PROVIDERS).expect("public/birdeye/amm_providers.json must be valid");
+        Self::from_providers(providers.data)
+    }
+
+    pub fn from_providers(providers: Vec<AmmProvider>) -> Self {
+        let mut sources = HashMap::new();
+        for provider in providers {
+            for program_id in provider.program_ids {
+                // serum/serum_swap and openbook/openbook_swap share their program, the first one listed wins
+                sources.entry(program_id).or_insert_with(|| provider.source.clone());
+            }
+        }
+        Self { sources }
+    }
+
+    /// AMM source of the program, `JUPITER_SOURCE` for the Jupiter routers
+    pub fn source_of(&self, program_id: &str) -> Option<&str> {
+        if JUPITER_PROGRAM_IDS.contains(&program_id) {
+            return Some(JUPITER_SOURCE);
+        }
+        self.sources.get(program_id).map(String::as_str)
+    }
+
+    /// Same order as `instructions` (as returned by `EncodedTransactionWithMeta::instructions`). An instruction takes the source of
+    /// its program, or of the closest instruction invoking it that has one: the token transfers of a Raydium swap are tagged raydium.
+    pub fn tag(&self, instructions: &[ResolvedInstruction]) -> Vec<Option<String>> {
+        let parents = parents(instructions);
+        (0..instructions.len())
+            .map(|index| {
+                let mut current = Some(index);
+                while let Some(position) = current {
+                    if let Some(source) = self.source_of(&instructions[position].program_id) {
+                        return Some(source.to_string());
+                    }
+                    current = parents[position];
+                }
+                None
+            })
+            .collect()
+    }
+
+    /// Failed transactions have none.
+    pub fn extract(&self, transaction: &EncodedTransactionWithMeta) -> Result<Vec<NormalizedSwap>, Box<dyn Error>> {
+        let Some(meta) = transaction.meta.as_ref() else {
+            return Ok(Vec::new());
+        };
+        if meta.err.is_some() {
+            return Ok(Vec::new());
+        }
+
+        let instructions = transaction.instructions()?;
+        let parents = parents(&instructions);
+        let changes = transaction.token_balance_changes();
+        let decoded: Vec<Option<DecodedInstruction>> = {
+            let decoders = INSTRUCTION_DECODERS.read().unwrap();
+            // An instruction that fails to decode can't be a transfer or a leg, it is not fatal to the other swaps
+            instructions.iter().map(|instruction| decoders.decode(instruction).ok().flatten()).collect()
+        };
+        let transfers: Vec<Option<TokenTransfer>> = decoded.iter().map(token_transfer).collect();
+
+        // (index of the AMM instruction, leg, trader)
+        let mut legs: Vec<(usize, SwapLeg, String)> = Vec::new();
+        for (index, instruction) in instructions.iter().enumerate() {
+            let Some(source) = self.source_of(&instruction.program_id) else {
+                continue;
+            };
+            // Routers are not legs, and an AMM invoked by another AMM (ie. Raydium settling on OpenBook) is part of the outer one's leg
+            if source == JUPITER_SOURCE || legs.iter().any(|(leg_index, _, _)| is_ancestor(&parents, *leg_index, index)) {
+                continue;
+            }
+
+            let invoked: Vec<&TokenTransfer> = (index + 1..instructions.len())
+                .take_while(|position| is_ancestor(&parents, index, *position))
+                .filter_map(|position| transfers[position].as_ref())
+                .collect();
+            let pool = match &decoded[index] {
+                Some(DecodedInstruction::RaydiumAmmV4(raydium)) if instruction.program_id == RAYDIUM_AMM_V4_PROGRAM_ID => raydium_pool(raydium),
+                _ => None,
+            };
+            if let Some((leg, trader)) = leg(source, instruction, &invoked, &changes, pool) {
+                legs.push((index, leg, trader));
+            }
+        }
+
+        // Legs under the same Jupiter instruction are one route, keyed by that instruction. The others are direct swaps.
+        let mut routes: BTreeMap<usize, Vec<(SwapLeg, String)>> = BTreeMap::new();
+        let mut swaps = Vec::new();
+        let fee_payer = transaction.account_keys().fee_payer().unwrap_or_default().to_string();
+        for (index, leg, trader) in legs {
+            match router_of(&instructions, &parents, index) {
+                Some(router) => routes.entry(router).or_default().push((leg, trader)),
+                None => swaps.push(NormalizedSwap {
+                    signature: transaction.signature().unwrap_or_default().to_string(),
+                    slot: transaction.slot,
+                    block_time: transaction.block_time,
+                    trader,
+                    source: leg.source.clone(),
+                    program_id: leg.program_id.clone(),
+                    input_mint: leg.input_mint.clone(),
+                    input_amount: leg.input_amount,
+                    output_mint: leg.output_mint.clone(),
+                    output_amount: leg.output_amount,
+                    legs: vec![leg],
+                }),
+            }
+        }
+
+        for (router, legs) in routes {
+            let trader = match &decoded[router] {
+                Some(DecodedInstruction::Anchor(route)) => route.accounts.iter()
+                    .find(|account| account.name == JUPITER_TRADER_ACCOUNT)
+                    .map(|account| account.pubkey.clone()),
+                _ => None,
+            };
+            let trader = trader.unwrap_or_else(|| fee_payer.clone());
+            if let Some(swap) = collapse_route(transaction, &instructions[router], trader, legs) {
+                swaps.push(swap);
+            }
+        }
+        Ok(swaps)
+    }
+}
+
+lazy_static! {
+    pub static ref SWAP_EXTRACTOR: SwapExtractor = SwapExtractor::new();
+}
+
+fn token_transfer(decoded: &Option<DecodedInstruction>) -> Option<TokenTransfer> {
+    match decoded {
+        Some(DecodedInstruction::SplToken(TokenInstruction::Transfer { source, destination, authority, amount })) => Some(TokenTransfer {
+            source: source.clone(),
+            destination: destination.clone(),
+            authority: authority.clone(),
+            amount: *amount,
+            mint: None,
+        }),
+        Some(DecodedInstruction::SplToken(TokenInstruction::TransferChecked { source, mint, destination, authority, amount, .. })) => Some(TokenTransfer {
+            source: source.clone(),
+            destination: destination.clone(),
+            authority: authority.clone(),
+            amount: *amount,
+            mint: Some(mint.clone()),
+        }),
+        _ => None,
+    }
+}
+
+fn raydium_pool(instruction: &RaydiumAmmInstruction) -> Option<String> {
+    match instruction {
+        RaydiumAmmInstruction::SwapBaseIn { amm, .. } | RaydiumAmmInstruction::SwapBaseOut { amm, .. } => Some(amm.clone()),
+        _ => None,
+    }
+}
+
+/// The leg of an AMM instruction and its trader. The first transfer it invoked is the input, the last one of another mint the output.
+/// None for liquidity changes: a swap pays back the owner of the input, a deposit pays the pool and a withdrawal takes from it.
+fn leg(source: &str, instruction: &ResolvedInstruction, transfers: &[&TokenTransfer], changes: &HashMap<String, TokenBalanceChange>,
+       pool: Option<String>) -> Option<(SwapLeg, String)> {
+    let mint_of = |transfer: &TokenTransfer| -> Option<String> {
+        transfer.mint.clone()
+            .or_else(|| changes.get(&transfer.source).map(|change| change.mint.clone()))
+            .or_else(|| changes.get(&transfer.destination).map(|change| change.mint.clone()))
+    };
+    let owner_of = |account: &str| -> Option<String> { changes.get(account).and_then(|change| change.owner.clone()) };
+
+    let input = transfers.first()?;
+    let input_mint = mint_of(input)?;
+    let (output, output_mint) = transfers.iter().rev()
+        .filter_map(|transfer| mint_of(transfer).map(|mint| (transfer, mint)))
+        .find(|(_, mint)| *mint != input_mint)?;
+
+    let trader = owner_of(&input.source).unwrap_or_else(|| input.authority.clone());
+    if let Some(receiver) = owner_of(&output.destination) {
+        if receiver != trader {
+            return None;
+        }
+    }
+
+    let leg = SwapLeg {
+        source: source.to_string(),
+        program_id: instruction.program_id.clone(),
+        pool: pool.or_else(|| owner_of(&output.source)).or_else(|| Some(output.authority.clone())),
+        input_mint,
+        input_amount: input.amount,
+        output_mint,
+        output_amount: output.amount,
+        outer_index: instruction.outer_index,
+        inner_index: instruction.inner_index,
+    };
+    Some((leg, trader))
+}
+
+/// The net trade of a route: the mint the legs consumed the most of in, the mint they produced the most of out. Intermediate mints
+/// of multi-hop routes net out, split routes add up.
+fn collapse_route(transaction: &EncodedTransactionWithMeta, router: &ResolvedInstruction, trader: String, legs: Vec<(SwapLeg, String)>) -> Option<NormalizedSwap> {
+    let mut flows: HashMap<String, i128> = HashMap::new();
+    for (leg, _) in &legs {
+        *flows.entry(leg.input_mint.clone()).or_default() -= leg.input_amount as i128;
+        *flows.entry(leg.output_mint.clone()).or_default() += leg.output_amount as i128;
+    }
+    let (input_mint, input_flow) = flows.iter().min_by_key(|(_, flow)| **flow).map(|(mint, flow)| (mint.clone(), *flow))?;
+    let (output_mint, output_flow) = flows.iter().max_by_key(|(_, flow)| **flow).map(|(mint, flow)| (mint.clone(), *flow))?;
+    if input_flow >= 0 || output_flow <= 0 {
+        return None;
+    }
+
+    Some(NormalizedSwap {
+        signature: transaction.signature().unwrap_or_default().to_string(),
+        slot: transaction.slot,
+        block_time: transaction.block_time,
+        trader,
+        source: JUPITER_SOURCE.to_string(),
+        program_id: router.program_id.clone(),
+        input_mint,
+        input_amount: input_flow.unsigned_abs() as u64,
+        output_mint,
+        output_amount: output_flow as u64,
+        legs: legs.into_iter().map(|(leg, _)| leg).collect(),
+    })
+}
+
+/// The instruction that invoked each instruction, None for the outer ones. Inner instructions without a stack height (before
+/// v1.14) are taken as invoked by the outer instruction.
+fn parents(instructions: &[ResolvedInstruction]) -> Vec<Option<usize>> {
+    let mut parents = vec![None; instructions.len()];
+    // (index, stack height) of the instructions still executing
+    let mut stack: Vec<(usize, u32)> = Vec::new();
+    for (index, instruction) in instructions.iter().enumerate() {
+        let height = match instruction.inner_index {
+            None => {
+                stack.clear();
+                1
+            }
+            Some(_) => instruction.stack_height.unwrap_or(2),
+        };
+        while stack.last().is_some_and(|(_, last_height)| *last_height >= height) {
+            stack.pop();
+        }
+        parents[index] = stack.last().map(|(position, _)| *position);
+        stack.push((index, height));
+    }
+    parents
+}
+
+fn is_ancestor(parents: &[Option<usize>], ancestor: usize, index: usize) -> bool {
+    let mut current = parents[index];
+    while let Some(position) = current {
+        if position == ancestor {
+            return true;
+        }
+        current = parents[position];
+    }
+    false
+}
+
+/// The closest Jupiter instruction invoking the instruction at `index`
+fn router_of(instructions: &[ResolvedInstruction], parents: &[Option<usize>], index: usize) -> Option<usize> {
+    let mut current = parents[index];
+    while let Some(position) = current {
+        if JUPITER_PROGRAM_IDS.contains(&instructions[position].program_id.as_str()) {
+            return Some(position);
+        }
+        current = parents[position];
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+    use solana_sdk::bs58;
+
+    use super::*;
+
+    const USER: &str = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+    const USER_USDC: &str = "3ZZbNBnGu6qPpTzr6sHQgCE4BkNjGnSBbFhuwaJ4e8Pk";
+    const USER_BONK: &str = "Bf6i2GmZ7SdXrXZxaSsMTyW7qnjYvPxjw3q1jT2qDUnB";
+    const USER_WSOL: &str = "7Xe3gQxZX9RQu2wh2a1QjAYFGHtP2U8hRZSZqQGkmQZJ";
+    const WSOL: &str = "So11111111111111111111111111111111111111112";
+    const USDC: &str = "EPjFWdd5AufqSSqeM2qMi4SbwY4qjSi8BpRxKeuUXx3Z";
+    const BONK: &str = "DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263";
+    const TOKEN_PROGRAM: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+    const WHIRLPOOL_PROGRAM: &str = "whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc";
+    const WHIRLPOOL: &str = "83v8iPyZihDEjDdY8RdZddyZNyUtXngz69Lgo9Kt5d6d";
+    const WHIRLPOOL_USDC_VAULT: &str = "2WLWEuKDgkDUccTpbwYp1GToYktiSB1cXvreHUwiSUVP";
+    const WHIRLPOOL_BONK_VAULT: &str = "Fvsi5DHp8jgyDsAUq6aq4Y9MBZMoV2jHkUGVPAFV5Lpq";
+    // Accounts of the SOL/USDC pool on mainnet
+    const AMM: &str = "58oQChx4yWmvKdwLLZzBi4ChoCc2fqCUWBkwMihLYQo2";
+    const AMM_AUTHORITY: &str = "5Q544fKrFoe6tsEbD7S8EmxGTJYAKtTVhAW5Q5pge4j1";
+    const COIN_VAULT: &str = "DQyrAcCrDXQ7NeoqGgDCZwBvWDcYmFCjSb9JtteuvPpz";
+    const PC_VAULT: &str = "HLmqeL62xR1QoZ1HKKbXRrdN1p3phKpxRMb2VVopvBBz";
+
+    /// Builds a transaction of USER from its instructions, account keys are added as they are used
+    #[derive(Default)]
+    struct TransactionBuilder {
+        keys: Vec<String>,
+        instructions: Vec<Value>,
+        inner: Vec<Value>,
+        pre_token_balances: Vec<Value>,
+        post_token_balances: Vec<Value>,
+    }
+
+    impl TransactionBuilder {
+        fn new() -> Self {
+            let mut builder = Self::default();
+            builder.key(USER);
+            builder
+        }
+
+        fn key(&mut self, pubkey: &str) -> usize {
+            match self.keys.iter().position(|key| key == pubkey) {
+                Some(index) => index,
+                None => {
+                    self.keys.push(pubkey.to_string());
+                    self.keys.len() - 1
+                }
+            }
+        }
+
+        fn compiled(&mut self, program_id: &str, accounts: &[&str], data: &[u8], stack_height: u32) -> Value {
+            json!({
+                "programIdIndex": self.key(program_id),
+                "accounts": accounts.iter().map(|account| self.key(account)).collect::<Vec<usize>>(),
+                "data": bs58::encode(data).into_string(),
+                "stackHeight": stack_height,
+            })
+        }
+
+        fn outer(mut self, program_id: &str, accounts: &[&str], data: &[u8]) -> Self {
+            let instruction = self.compiled(program_id, accounts, data, 1);
+            self.instructions.push(instruction);
+            self.inner.push(json!({"index": self.instructions.len() - 1, "instructions": []}));
+            self
+        }
+
+        /// Invoked by the last outer instruction
+        fn invoke(mut self, program_id: &str, accounts: &[&str], data: &[u8], stack_height: u32) -> Self {
+            let instruction = self.compiled(program_id, accounts, data, stack_height);
+            self.inner.last_mut().unwrap()["instructions"].as_array_mut().unwrap().push(instruction);
+            self
+        }
+
+        fn transfer(self, source: &str, destination: &str, authority: &str, amount: u64, stack_height: u32) -> Self {
+            let data = [&[3u8][..], &amount.to_le_bytes()].concat();
+            self.invoke(TOKEN_PROGRAM, &[source, destination, authority], &data, stack_height)
+        }
+
+        fn balance(mut self, account: &str, mint: &str, owner: &str, pre: u64, post: u64) -> Self {
+            let index = self.key(account);
+            for (balances, amount) in [(&mut self.pre_token_balances, pre), (&mut self.post_token_balances, post)] {
+                balances.push(json!({
+                    "accountIndex": index,
+                    "mint": mint,
+                    "owner": owner,
+                    "uiTokenAmount": {"amount": amount.to_string(), "decimals": 6, "uiAmount": null, "uiAmountString": ""},
+                }));
+            }
+            self
+        }
+
+        fn build(self, err: Option<Value>) -> EncodedTransactionWithMeta {
+            serde_json::from_value(json!({
+                "slot": 280_000_000,
+                "blockTime": 1_722_000_000,
+                "version": "legacy",
+                "meta": {
+                    "err": err,
+                    "fee": 5000,
+                    "preBalances": vec![0; self.keys.len()],
+                    "postBalances": vec![0; self.keys.len()],
+                    "innerInstructions": self.inner,
+                    "preTokenBalances": self.pre_token_balances,
+                    "postTokenBalances": self.post_token_balances,
+                },
+                "transaction": {
+                    "signatures": ["sig"],
+                    "message": {
+                        "header": {"numRequiredSignatures": 1, "numReadonlySignedAccounts": 0, "numReadonlyUnsignedAccounts": 0},
+                        "accountKeys": self.keys,
+                        "recentBlockhash": "11111111111111111111111111111111",
+                        "instructions": self.instructions,
+                    },
+                },
+            })).unwrap()
+        }
+    }
+
+    // token program, amm, authority, open orders, target orders, coin vault, pc vault, market program, market, bids, asks,
+    // event queue, market coin vault, market pc vault, vault signer, user source, user destination, owner
+    fn raydium_swap_accounts<'a>(user_source: &'a str, user_destination: &'a str) -> Vec<&'a str> {
+        vec![
+            TOKEN_PROGRAM, AMM, AMM_AUTHORITY, "HmiHHzq4Fym9e1D4qzLS6LDDM3tNsCTBPDWHTLZ763jY",
+            "CZza3Ej4Mc58MnxWA385itCC9jCo3L1D7zc3LKy1bZMR", COIN_VAULT, PC_VAULT, "srmqPvymJeFKQ4zGQed1GFppgkRHL9kaELCbyksJtPX",
+            "8BnEgHoWFysVcuFFX7QztDmzuH8r5ZFvyP3sYwn1XTh6", "14ivtgssEBoBjuZJtSAPKYgpUK7DmnSwuPMqJoVTSgKJ",
+            "CEQdAFKdycHugujQg9k2wbmxjcpdYZyVLfV9WerTnafJ", "5KKsLVU6TcbVDK4BS6K1DGDxnh4Q9xjYJ8XaDCG5t8ht",
+            "36c6YqAwyGKQG66XEp2dJc5JqjaBNv7sVghEtJv4c7u6", "8CFo8bL8mZQK8abbFyypFMwEDd8tVJjHTTojMLgQTUSZ",
+            "F8Vyqk3unwxkXukZFQeYyGmFfTG3CAX4v24iyrjEYBJV", user_source, user_destination, USER,
+        ]
+    }
+
+    fn swap_base_in(amount_in: u64, minimum_amount_out: u64) -> Vec<u8> {
+        [&[9u8][..], &amount_in.to_le_bytes(), &minimum_amount_out.to_le_bytes()].concat()
+    }
+
+    #[test]
+    fn extracts_a_direct_raydium_swap() {
+        // 150 USDC for 1 SOL
+        let transaction = TransactionBuilder::new()
+            .outer(RAYDIUM_AMM_V4_PROGRAM_ID, &raydium_swap_accounts(USER_USDC, USER_WSOL), &swap_base_in(150_000_000, 990_000_000))
+            .transfer(USER_USDC, PC_VAULT, USER, 150_000_000, 2)
+            .transfer(COIN_VAULT, USER_WSOL, AMM_AUTHORITY, 1_000_000_000, 2)
+            .balance(USER_USDC, USDC, USER, 200_000_000, 50_000_000)
+            .balance(USER_WSOL, WSOL, USER, 0, 1_000_000_000)
+            .balance(PC_VAULT, USDC, AMM_AUTHORITY, 9_000_000_000, 9_150_000_000)
+            .balance(COIN_VAULT, WSOL, AMM_AUTHORITY, 60_000_000_000, 59_000_000_000)
+            .build(None);
+
+        let swaps = SwapExtractor::new().extract(&transaction).unwrap();
+        assert_eq!(swaps.len(), 1);
+        let swap = &swaps[0];
+        assert_eq!((swap.source.as_str(), swap.program_id.as_str(), swap.trader.as_str()), ("raydium", RAYDIUM_AMM_V4_PROGRAM_ID, USER));
+        assert_eq!((swap.input_mint.as_str(), swap.input_amount), (USDC, 150_000_000));
+        assert_eq!((swap.output_mint.as_str(), swap.output_amount), (WSOL, 1_000_000_000));
+        assert_eq!(swap.legs[0].pool.as_deref(), Some(AMM));
+        assert_eq!((swap.legs[0].outer_index, swap.legs[0].inner_index), (0, None));
+    }
+
+    #[test]
+    fn collapses_a_jupiter_route() {
+        // USDC -> BONK on Whirlpool, BONK -> SOL on Raydium
+        let jupiter = JUPITER_PROGRAM_IDS[3];
+        let transaction = TransactionBuilder::new()
+            .outer(jupiter, &[TOKEN_PROGRAM, USER], &[0xe5, 0x17, 0xcb, 0x97, 0x7a, 0xe3, 0xad, 0x2a])
+            .invoke(WHIRLPOOL_PROGRAM, &[TOKEN_PROGRAM, USER, WHIRLPOOL, USER_USDC, WHIRLPOOL_USDC_VAULT, USER_BONK, WHIRLPOOL_BONK_VAULT], &[0xf8, 0xc6], 2)
+            .transfer(USER_USDC, WHIRLPOOL_USDC_VAULT, USER, 150_000_000, 3)
+            .transfer(WHIRLPOOL_BONK_VAULT, USER_BONK, WHIRLPOOL, 5_000_000_000, 3)
+            .invoke(RAYDIUM_AMM_V4_PROGRAM_ID, &raydium_swap_accounts(USER_BONK, USER_WSOL), &swap_base_in(5_000_000_000, 0), 2)
+            .transfer(USER_BONK, PC_VAULT, USER, 5_000_000_000, 3)
+            .transfer(COIN_VAULT, USER_WSOL, AMM_AUTHORITY, 1_000_000_000, 3)
+            .balance(USER_USDC, USDC, USER, 150_000_000, 0)
+            .balance(USER_BONK, BONK, USER, 0, 0)
+            .balance(USER_WSOL, WSOL, USER, 0, 1_000_000_000)
+            .balance(WHIRLPOOL_USDC_VAULT, USDC, WHIRLPOOL, 0, 150_000_000)
+            .balance(WHIRLPOOL_BONK_VAULT, BONK, WHIRLPOOL, 9_000_000_000, 4_000_000_000)
+            .build(None);
+
+        let extractor = SwapExtractor::new();
+        let swaps = extractor.extract(&transaction).unwrap();
+        assert_eq!(swaps.len(), 1);
+        let route = &swaps[0];
+        // The route instruction is not decoded without the Jupiter IDL, the fee payer trades
+        assert_eq!((route.source.as_str(), route.program_id.as_str(), route.trader.as_str()), (JUPITER_SOURCE, jupiter, USER));
+        assert_eq!((route.input_mint.as_str(), route.input_amount), (USDC, 150_000_000));
+        assert_eq!((route.output_mint.as_str(), route.output_amount), (WSOL, 1_000_000_000));
+        let legs: Vec<(&str, Option<&str>)> = route.legs.iter().map(|leg| (leg.source.as_str(), leg.pool.as_deref())).collect();
+        assert_eq!(legs, vec![("whirlpool", Some(WHIRLPOOL)), ("raydium", Some(AMM))]);
+
+        // The transfers take the source of the AMM invoking them
+        let tags = extractor.tag(&transaction.instructions().unwrap());
+        let tags: Vec<Option<&str>> = tags.iter().map(Option::as_deref).collect();
+        assert_eq!(tags, vec![Some("jupiter"), Some("whirlpool"), Some("whirlpool"), Some("whirlpool"), Some("raydium"), Some("raydium"), Some("raydium")]);
+    }
+
+    #[test]
+    fn deposits_and_failed_transactions_are_not_swaps() {
+        // Both sides go to the pool
+        let deposit = TransactionBuilder::new()
+            .outer(RAYDIUM_AMM_V4_PROGRAM_ID, &raydium_swap_accounts(USER_USDC, USER_WSOL), &[3])
+            .transfer(USER_WSOL, COIN_VAULT, USER, 1_000_000_000, 2)
+            .transfer(USER_USDC, PC_VAULT, USER, 150_000_000, 2)
+            .balance(USER_USDC, USDC, USER, 150_000_000, 0)
+            .balance(USER_WSOL, WSOL, USER, 1_000_000_000, 0)
+            .balance(PC_VAULT, USDC, AMM_AUTHORITY, 0, 150_000_000)
+            .balance(COIN_VAULT, WSOL, AMM_AUTHORITY, 0, 1_000_000_000);
+        assert!(SwapExtractor::new().extract(&deposit.build(None)).unwrap().is_empty());
+
+        let failed = TransactionBuilder::new()
+            .outer(RAYDIUM_AMM_V4_PROGRAM_ID, &raydium_swap_accounts(USER_USDC, USER_WSOL), &swap_base_in(150_000_000, 990_000_000))
+            .build(Some(json!({"InstructionError": [0, {"Custom": 30}]})));
+        assert!(SwapExtractor::new().extract(&failed).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,451 @@
+// https://github.com/solana-labs/solana-program-library/tree/master/token/program-2022/src/extension
+use std::error::Error;
+
+use serde::{Deserialize, Serialize};
+
+use crate::decoder::account_decoder::{AccountDecoder, DecodedAccount};
+use crate::decoder::byte_reader::ByteReader;
+use crate::decoder::spl_token_accounts::{Mint, TokenAccount, TokenAccountState, MINT_LEN, TOKEN_ACCOUNT_LEN};
+use crate::models::solana::token_info::TokenRiskFlag;
+
+pub const TOKEN_2022_PROGRAM_ID: &str = "TokenzQdBNbLqP5VEhdkAS6EPFLC1PeB5QfXGCQt1J8";
+
+// Extension types, https://github.com/solana-labs/solana-program-library/blob/master/token/program-2022/src/extension/mod.rs
+pub const EXTENSION_TRANSFER_FEE_CONFIG: u16 = 1;
+pub const EXTENSION_MINT_CLOSE_AUTHORITY: u16 = 3;
+pub const EXTENSION_DEFAULT_ACCOUNT_STATE: u16 = 6;
+pub const EXTENSION_NON_TRANSFERABLE: u16 = 9;
+pub const EXTENSION_PERMANENT_DELEGATE: u16 = 12;
+pub const EXTENSION_TRANSFER_HOOK: u16 = 14;
+pub const EXTENSION_METADATA_POINTER: u16 = 18;
+pub const EXTENSION_TOKEN_METADATA: u16 = 19;
+
+// Account type byte following the base account, mints being padded up to the token account size
+const ACCOUNT_TYPE_MINT: u8 = 1;
+const ACCOUNT_TYPE_ACCOUNT: u8 = 2;
+// Multisigs never have extensions, an account of this size is always one
+const MULTISIG_LEN: usize = 355;
+
+// (extension type, raw value) of the TLV entries
+type RawExtensions = Vec<(u16, Vec<u8>)>;
+
+/// The TLV entries following the account type byte at `TOKEN_ACCOUNT_LEN`, checked against `expected`
+fn unpack_extensions(data: &[u8], expected: u8) -> Result<RawExtensions, Box<dyn Error>> {
+    let mut reader = ByteReader::new(data);
+    reader.skip(TOKEN_ACCOUNT_LEN)?;
+    let account_type = reader.u8()?;
+    if account_type != expected {
+        return Err(format!("expected account type {}, found {}", expected, account_type).into());
+    }
+
+    let mut extensions = Vec::new();
+    // Each entry: u16 type, u16 length, value. Type 0 is the unused tail of the account
+    while reader.remaining() >= 4 {
+        let extension_type = reader.u16()?;
+        let length = reader.u16()? as usize;
+        if extension_type == 0 {
+            break;
+        }
+        extensions.push((extension_type, reader.bytes(length)?.to_vec()));
+    }
+    Ok(extensions)
+}
+
+fn find_extension(extensions: &[(u16, Vec<u8>)], extension_type: u16) -> Option<&[u8]> {
+    extensions.iter()
+        .find(|(found, _)| *found == extension_type)
+        .map(|(_, value)| value.as_slice())
+}
+
+/// A Token-2022 mint: the SPL Token layout, then (when it has extensions) padding up to the token account size, the account type
+/// byte and the TLV encoded extensions.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Token2022Mint {
+    pub mint: Mint,
+    pub extensions: RawExtensions,
+}
+
+impl Token2022Mint {
+    pub fn unpack(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let mint = Mint::unpack(data)?;
+        if data.len() <= MINT_LEN {
+            return Ok(Self { mint, extensions: Vec::new() });
+        }
+        Ok(Self { mint, extensions: unpack_extensions(data, ACCOUNT_TYPE_MINT)? })
+    }
+
+    pub fn extension(&self, extension_type: u16) -> Option<&[u8]> {
+        find_extension(&self.extensions, extension_type)
+    }
+
+    /// The extensions this crate models, decoded. One that fails to decode is listed in `other`, the others are kept.
+    pub fn decode_extensions(&self) -> MintExtensions {
+        let mut decoded = MintExtensions::default();
+        for (extension_type, value) in &self.extensions {
+            let result = match *extension_type {
+                EXTENSION_TRANSFER_FEE_CONFIG => TransferFeeConfig::unpack(value).map(|extension| decoded.transfer_fee_config = Some(extension)),
+                EXTENSION_MINT_CLOSE_AUTHORITY => MintCloseAuthority::unpack(value).map(|extension| decoded.mint_close_authority = Some(extension)),
+                EXTENSION_DEFAULT_ACCOUNT_STATE => DefaultAccountState::unpack(value).map(|extension| decoded.default_account_state = Some(extension)),
+                EXTENSION_NON_TRANSFERABLE => {
+                    decoded.non_transferable = true;
+                    Ok(())
+                }
+                EXTENSION_PERMANENT_DELEGATE => PermanentDelegate::unpack(value).map(|extension| decoded.permanent_delegate = Some(extension)),
+                EXTENSION_TRANSFER_HOOK => TransferHook::unpack(value).map(|extension| decoded.transfer_hook = Some(extension)),
+                EXTENSION_METADATA_POINTER => MetadataPointer::unpack(value).map(|extension| decoded.metadata_pointer = Some(extension)),
+                // Decoded into the name, symbol and uri of the token, see `TokenRegistry`
+                EXTENSION_TOKEN_METADATA => Ok(()),
+                other => Err(format!("extension type {} is not modeled", other).into()),
+            };
+            if result.is_err() {
+                decoded.other.push(*extension_type);
+            }
+        }
+        decoded
+    }
+}
+
+/// A Token-2022 token account: the SPL Token layout, then (when it has extensions) the account type byte and the TLV extensions
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Token2022Account {
+    pub account: TokenAccount,
+    // ie. ImmutableOwner, TransferFeeAmount
+    pub extensions: RawExtensions,
+}
+
+impl Token2022Account {
+    pub fn unpack(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let account = TokenAccount::unpack(data)?;
+        if data.len() <= TOKEN_ACCOUNT_LEN {
+            return Ok(Self { account, extensions: Vec::new() });
+        }
+        Ok(Self { account, extensions: unpack_extensions(data, ACCOUNT_TYPE_ACCOUNT)? })
+    }
+}
+
+/// Mints and token accounts of the Token-2022 program. Mints are padded to the token account size once they have extensions, the
+/// account type byte tells them apart. Multisigs are not decoded.
+pub struct Token2022AccountDecoder;
+
+impl AccountDecoder for Token2022AccountDecoder {
+    fn owner(&self) -> &str {
+        TOKEN_2022_PROGRAM_ID
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Option<DecodedAccount>, Box<dyn Error>> {
+        match data.len() {
+            MINT_LEN => Token2022Mint::unpack(data).map(|mint| Some(DecodedAccount::Token2022Mint(mint))),
+            TOKEN_ACCOUNT_LEN => Token2022Account::unpack(data).map(|account| Some(DecodedAccount::Token2022Account(account))),
+            MULTISIG_LEN => Ok(None),
+            len if len > TOKEN_ACCOUNT_LEN => match data[TOKEN_ACCOUNT_LEN] {
+                ACCOUNT_TYPE_MINT => Token2022Mint::unpack(data).map(|mint| Some(DecodedAccount::Token2022Mint(mint))),
+                ACCOUNT_TYPE_ACCOUNT => Token2022Account::unpack(data).map(|account| Some(DecodedAccount::Token2022Account(account))),
+                _ => Ok(None),
+            },
+            _ => Ok(None),
+        }
+    }
+}
+
+/// The mint extensions that change what holding the token means, decoded. Types this crate doesn't model, or that failed to decode,
+/// are listed in `other`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct MintExtensions {
+    pub transfer_fee_config: Option<TransferFeeConfig>,
+    pub transfer_hook: Option<TransferHook>,
+    pub permanent_delegate: Option<PermanentDelegate>,
+    pub non_transferable: bool,
+    pub mint_close_authority: Option<MintCloseAuthority>,
+    pub metadata_pointer: Option<MetadataPointer>,
+    pub default_account_state: Option<DefaultAccountState>,
+    pub other: Vec<u16>,
+}
+
+impl MintExtensions {
+    /// What the mint's authorities can do to the holders, see `TokenRiskFlag`. A permanent delegate or transfer hook that could
+    /// not be decoded is flagged all the same, with an unknown delegate or program.
+    pub fn risk_flags(&self) -> Vec<TokenRiskFlag> {
+        let mut flags = Vec::new();
+        if let Some(delegate) = self.permanent_delegate.as_ref().and_then(|extension| extension.delegate.clone()) {
+            flags.push(TokenRiskFlag::PermanentDelegate { delegate: Some(delegate) });
+        } else if self.other.contains(&EXTENSION_PERMANENT_DELEGATE) {
+            flags.push(TokenRiskFlag::PermanentDelegate { delegate: None });
+        }
+        if let Some(program_id) = self.transfer_hook.as_ref().and_then(|extension| extension.program_id.clone()) {
+            flags.push(TokenRiskFlag::TransferHook { program_id: Some(program_id) });
+        } else if self.other.contains(&EXTENSION_TRANSFER_HOOK) {
+            flags.push(TokenRiskFlag::TransferHook { program_id: None });
+        }
+        if let Some(config) = &self.transfer_fee_config {
+            let basis_points = config.max_basis_points();
+            if basis_points > 0 {
+                flags.push(TokenRiskFlag::TransferFee { basis_points, maximum_fee: config.newer_transfer_fee.maximum_fee });
+            }
+            if let Some(authority) = config.transfer_fee_config_authority.clone() {
+                flags.push(TokenRiskFlag::MutableTransferFee { authority });
+            }
+        }
+        if self.non_transferable {
+            flags.push(TokenRiskFlag::NonTransferable);
+        }
+        if self.default_account_state.as_ref().is_some_and(|extension| extension.state == TokenAccountState::Frozen) {
+            flags.push(TokenRiskFlag::DefaultFrozen);
+        }
+        if let Some(authority) = self.mint_close_authority.as_ref().and_then(|extension| extension.close_authority.clone()) {
+            flags.push(TokenRiskFlag::MintCloseAuthority { authority });
+        }
+        if let Some(authority) = self.metadata_pointer.as_ref().and_then(|extension| extension.authority.clone()) {
+            flags.push(TokenRiskFlag::MutableMetadataPointer { authority });
+        }
+        flags
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TransferFee {
+    // Epoch the fee applies from
+    pub epoch: u64,
+    // Raw amount, cap of the fee of a single transfer
+    pub maximum_fee: u64,
+    pub transfer_fee_basis_points: u16,
+}
+
+impl TransferFee {
+    fn unpack(reader: &mut ByteReader) -> Result<Self, Box<dyn Error>> {
+        Ok(TransferFee {
+            epoch: reader.u64()?,
+            maximum_fee: reader.u64()?,
+            transfer_fee_basis_points: reader.u16()?,
+        })
+    }
+}
+
+/// Fee withheld on every transfer. The config authority can schedule a new fee, applied two epochs later.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TransferFeeConfig {
+    pub transfer_fee_config_authority: Option<String>,
+    pub withdraw_withheld_authority: Option<String>,
+    // Fees withheld in the mint, harvested from the token accounts
+    pub withheld_amount: u64,
+    pub older_transfer_fee: TransferFee,
+    pub newer_transfer_fee: TransferFee,
+}
+
+impl TransferFeeConfig {
+    pub fn unpack(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let mut reader = ByteReader::new(data);
+        Ok(TransferFeeConfig {
+            transfer_fee_config_authority: reader.optional_nonzero_pubkey()?,
+            withdraw_withheld_authority: reader.optional_nonzero_pubkey()?,
+            withheld_amount: reader.u64()?,
+            older_transfer_fee: TransferFee::unpack(&mut reader)?,
+            newer_transfer_fee: TransferFee::unpack(&mut reader)?,
+        })
+    }
+
+    /// The highest of the current and the scheduled fee, the epoch the newer one starts at isn't always reached yet
+    pub fn max_basis_points(&self) -> u16 {
+        self.older_transfer_fee.transfer_fee_basis_points.max(self.newer_transfer_fee.transfer_fee_basis_points)
+    }
+}
+
+/// Program invoked by every transfer (through `Execute`), which can fail it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TransferHook {
+    // Can replace the program
+    pub authority: Option<String>,
+    pub program_id: Option<String>,
+}
+
+impl TransferHook {
+    pub fn unpack(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let mut reader = ByteReader::new(data);
+        Ok(TransferHook {
+            authority: reader.optional_nonzero_pubkey()?,
+            program_id: reader.optional_nonzero_pubkey()?,
+        })
+    }
+}
+
+/// Delegate of every token account of the mint, for any amount
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PermanentDelegate {
+    pub delegate: Option<String>,
+}
+
+impl PermanentDelegate {
+    pub fn unpack(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        Ok(PermanentDelegate { delegate: ByteReader::new(data).optional_nonzero_pubkey()? })
+    }
+}
+
+/// Can close the mint once its supply is 0
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MintCloseAuthority {
+    pub close_authority: Option<String>,
+}
+
+impl MintCloseAuthority {
+    pub fn unpack(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        Ok(MintCloseAuthority { close_authority: ByteReader::new(data).optional_nonzero_pubkey()? })
+    }
+}
+
+/// State of the token accounts created for the mint, frozen ones wait for the freeze authority to thaw them
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DefaultAccountState {
+    pub state: TokenAccountState,
+}
+
+impl DefaultAccountState {
+    pub fn unpack(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let state = match ByteReader::new(data).u8()? {
+            0 => TokenAccountState::Uninitialized,
+            1 => TokenAccountState::Initialized,
+            2 => TokenAccountState::Frozen,
+            state => return Err(format!("invalid default account state {}", state).into()),
+        };
+        Ok(DefaultAccountState { state })
+    }
+}
+
+/// Where the metadata of the mint lives, usually the mint itself (with a `TokenMetadata` extension)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MetadataPointer {
+    pub authority: Option<String>,
+    pub metadata_address: Option<String>,
+}
+
+impl MetadataPointer {
+    pub fn unpack(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let mut reader = ByteReader::new(data);
+        Ok(MetadataPointer {
+            authority: reader.optional_nonzero_pubkey()?,
+            metadata_address: reader.optional_nonzero_pubkey()?,
+        })
+    }
+}
+
+// https://github.com/solana-labs/solana-program-library/blob/master/token-metadata/interface/src/state.rs
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TokenMetadata {
+    // None makes the metadata immutable
+    pub update_authority: Option<String>,
+    pub mint: String,
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+    pub additional_metadata: Vec<(String, String)>,
+}
+
+impl TokenMetadata {
+    pub fn unpack(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let mut reader = ByteReader::new(data);
+        let update_authority = reader.optional_nonzero_pubkey()?;
+        let mint = reader.pubkey()?;
+        let name = reader.string()?;
+        let symbol = reader.string()?;
+        let uri = reader.string()?;
+        let pairs = reader.u32()?;
+        let mut additional_metadata = Vec::new();
+        for _ in 0..pairs {
+            additional_metadata.push((reader.string()?, reader.string()?));
+        }
+        Ok(TokenMetadata { update_authority, mint, name, symbol, uri, additional_metadata })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::*;
+
+    // ConfidentialTransferMint, not modeled
+    const EXTENSION_CONFIDENTIAL_TRANSFER_MINT: u16 = 4;
+
+    fn pubkey(byte: u8) -> Pubkey {
+        Pubkey::new_from_array([byte; 32])
+    }
+
+    fn tlv(extension_type: u16, value: &[u8]) -> Vec<u8> {
+        [&extension_type.to_le_bytes()[..], &(value.len() as u16).to_le_bytes(), value].concat()
+    }
+
+    /// A 6 decimals mint with both authorities set, padded to the token account size, then the mint account type and `extensions`
+    fn mint_data(extensions: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(pubkey(1).as_ref());
+        data.extend_from_slice(&5_000_000_000_000u64.to_le_bytes());
+        data.extend_from_slice(&[6, 1]);
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(pubkey(2).as_ref());
+        data.resize(TOKEN_ACCOUNT_LEN, 0);
+        data.push(ACCOUNT_TYPE_MINT);
+        extensions.iter().for_each(|extension| data.extend_from_slice(extension));
+        data
+    }
+
+    fn transfer_fee(epoch: u64, maximum_fee: u64, basis_points: u16) -> Vec<u8> {
+        [&epoch.to_le_bytes()[..], &maximum_fee.to_le_bytes(), &basis_points.to_le_bytes()].concat()
+    }
+
+    fn transfer_fee_config() -> Vec<u8> {
+        let value = [
+            pubkey(3).as_ref(),
+            &[0; 32],
+            &1_250u64.to_le_bytes(),
+            &transfer_fee(580, u64::MAX, 0),
+            &transfer_fee(612, 5_000_000_000, 100),
+        ].concat();
+        tlv(EXTENSION_TRANSFER_FEE_CONFIG, &value)
+    }
+
+    #[test]
+    fn decodes_transfer_fee_config() {
+        let data = mint_data(&[transfer_fee_config(), tlv(EXTENSION_CONFIDENTIAL_TRANSFER_MINT, &[0; 65])]);
+        let Some(DecodedAccount::Token2022Mint(mint)) = Token2022AccountDecoder.decode(&data).unwrap() else {
+            panic!("expected a Token-2022 mint");
+        };
+        assert_eq!(mint.mint.decimals, 6);
+        assert_eq!(mint.mint.supply, 5_000_000_000_000);
+        assert_eq!(mint.mint.freeze_authority, Some(pubkey(2).to_string()));
+
+        let extensions = mint.decode_extensions();
+        let config = extensions.transfer_fee_config.as_ref().unwrap();
+        assert_eq!(config.transfer_fee_config_authority, Some(pubkey(3).to_string()));
+        assert_eq!(config.withdraw_withheld_authority, None);
+        assert_eq!(config.withheld_amount, 1_250);
+        assert_eq!(config.older_transfer_fee, TransferFee { epoch: 580, maximum_fee: u64::MAX, transfer_fee_basis_points: 0 });
+        assert_eq!(config.max_basis_points(), 100);
+        assert_eq!(extensions.other, vec![EXTENSION_CONFIDENTIAL_TRANSFER_MINT]);
+        assert_eq!(extensions.risk_flags(), vec![
+            TokenRiskFlag::TransferFee { basis_points: 100, maximum_fee: 5_000_000_000 },
+            TokenRiskFlag::MutableTransferFee { authority: pubkey(3).to_string() },
+        ]);
+    }
+
+    #[test]
+    fn keeps_the_other_extensions_when_one_fails_to_decode() {
+        // A permanent delegate cut short
+        let data = mint_data(&[tlv(EXTENSION_PERMANENT_DELEGATE, &[9; 16]), transfer_fee_config()]);
+        let extensions = Token2022Mint::unpack(&data).unwrap().decode_extensions();
+        assert!(extensions.permanent_delegate.is_none());
+        assert!(extensions.transfer_fee_config.is_some());
+        assert_eq!(extensions.other, vec![EXTENSION_PERMANENT_DELEGATE]);
+        // Still flagged, the delegate is unknown
+        assert_eq!(extensions.risk_flags()[0], TokenRiskFlag::PermanentDelegate { delegate: None });
+    }
+
+    #[test]
+    fn mints_without_extensions_keep_the_spl_token_size() {
+        let mut data = mint_data(&[]);
+        data.truncate(MINT_LEN);
+        let Some(DecodedAccount::Token2022Mint(mint)) = Token2022AccountDecoder.decode(&data).unwrap() else {
+            panic!("expected a Token-2022 mint");
+        };
+        assert!(mint.extensions.is_empty());
+        assert_eq!(mint.decode_extensions(), MintExtensions::default());
+    }
+}
@@ -0,0 +1,492 @@
+// https://github.com/anza-xyz/agave/blob/master/sdk/program/src/instruction.rs (InstructionError)
+// https://github.com/anza-xyz/agave/blob/master/sdk/src/transaction/error.rs (TransactionError)
+use serde_json::Value;
+
+use crate::decoder::anchor_idl::AnchorIdl;
+use crate::decoder::raydium_amm_v4_state::RAYDIUM_AMM_V4_PROGRAM_ID;
+use crate::decoder::spl_token_accounts::SPL_TOKEN_PROGRAM_ID;
+use crate::decoder::token_2022::TOKEN_2022_PROGRAM_ID;
+use crate::models::solana::failed_transaction::{ErrorSource, FailedTransaction};
+use crate::models::solana::program_logs::{InvocationStatus, ProgramInvocation, ProgramLogs};
+
+pub const SYSTEM_PROGRAM_ID: &str = "11111111111111111111111111111111";
+
+const INSTRUCTION_ERROR: &str = "InstructionError";
+const CUSTOM: &str = "Custom";
+// `AnchorError occurred. Error Code: <name>. Error Number: <code>. Error Message: <message>.`
+const ANCHOR_ERROR_CODE: &str = "Error Code: ";
+const ANCHOR_ERROR_NUMBER: &str = ". Error Number: ";
+const ANCHOR_ERROR_MESSAGE: &str = ". Error Message: ";
+
+// (code, name, message)
+type ErrorTable = [(u32, &'static str, &'static str)];
+
+// https://github.com/solana-labs/solana-program-library/blob/master/token/program/src/error.rs
+const TOKEN_ERRORS: &ErrorTable = &[
+    (0, "NotRentExempt", "Lamport balance below rent-exempt threshold"),
+    (1, "InsufficientFunds", "Insufficient funds"),
+    (2, "InvalidMint", "Invalid Mint"),
+    (3, "MintMismatch", "Account not associated with this Mint"),
+    (4, "OwnerMismatch", "Owner does not match"),
+    (5, "FixedSupply", "Fixed supply"),
+    (6, "AlreadyInUse", "Already in use"),
+    (7, "InvalidNumberOfProvidedSigners", "Invalid number of provided signers"),
+    (8, "InvalidNumberOfRequiredSigners", "Invalid number of required signers"),
+    (9, "UninitializedState", "State is uninitialized"),
+    (10, "NativeNotSupported", "Instruction does not support native tokens"),
+    (11, "NonNativeHasBalance", "Non-native account can only be closed if its balance is zero"),
+    (12, "InvalidInstruction", "Invalid instruction"),
+    (13, "InvalidState", "State is invalid for requested operation"),
+    (14, "Overflow", "Operation overflowed"),
+    (15, "AuthorityTypeNotSupported", "Account does not support specified authority type"),
+    (16, "MintCannotFreeze", "This token mint cannot freeze accounts"),
+    (17, "AccountFrozen", "Account is frozen"),
+    (18, "MintDecimalsMismatch", "The provided decimals value different from the Mint decimals"),
+    (19, "NonNativeNotSupported", "Instruction does not support non-native tokens"),
+];
+
+// https://github.com/solana-labs/solana-program-library/blob/master/token/program-2022/src/error.rs, after the SPL Token ones
+const TOKEN_2022_ERRORS: &ErrorTable = &[
+    (20, "ExtensionTypeMismatch", "Extension type does not match already existing extensions"),
+    (21, "ExtensionBaseMismatch", "Extension does not match the base type provided"),
+    (22, "ExtensionAlreadyInitialized", "Extension already initialized on this account"),
+    (23, "ConfidentialTransferAccountHasBalance", "An account can only be closed if its confidential balance is zero"),
+    (24, "ConfidentialTransferAccountNotApproved", "Account not approved for confidential transfers"),
+    (25, "ConfidentialTransferDepositsAndTransfersDisabled", "Account not accepting deposits or transfers"),
+    (26, "ConfidentialTransferElGamalPubkeyMismatch", "ElGamal public key mismatch"),
+    (27, "ConfidentialTransferBalanceMismatch", "Balance mismatch"),
+    (28, "MintHasSupply", "Mint has non-zero supply. Burn all tokens before closing the mint"),
+    (29, "NoAuthorityExists", "No authority exists to perform the desired operation"),
+    (30, "TransferFeeExceedsMaximum", "Transfer fee exceeds maximum of 10,000 basis points"),
+    (31, "MintRequiredForTransfer", "Mint required for this account to transfer tokens, use `transfer_checked`"),
+    (32, "FeeMismatch", "Calculated fee does not match expected fee"),
+    (33, "FeeParametersMismatch", "Fee parameters associated with confidential transfer zero-knowledge proofs do not match fee parameters in mint"),
+    (34, "ImmutableOwner", "The owner authority cannot be changed"),
+    (35, "AccountHasWithheldTransferFees", "An account can only be closed if its withheld fee balance is zero"),
+    (36, "NoMemo", "No memo in previous instruction; required for recipient to receive a transfer"),
+    (37, "NonTransferable", "Transfer is disabled for this mint"),
+    (38, "NonTransferableNeedsImmutableOwnership", "Non-transferable tokens can't be minted to an account without immutable ownership"),
+    (39, "MaximumPendingBalanceCreditCounterExceeded", "The total number of pending balance credits exceeds the maximum"),
+    (40, "MaximumDepositAmountExceeded", "Deposit amount exceeds maximum limit"),
+    (41, "CpiGuardSettingsLocked", "CPI Guard cannot be enabled or disabled in CPI"),
+    (42, "CpiGuardTransferBlocked", "CPI Guard is enabled, and a program attempted to transfer user funds without using a delegate"),
+    (43, "CpiGuardBurnBlocked", "CPI Guard is enabled, and a program attempted to burn user funds without using a delegate"),
+    (44, "CpiGuardCloseAccountBlocked", "CPI Guard is enabled, and a program attempted to close an account without returning lamports to owner"),
+    (45, "CpiGuardApproveBlocked", "CPI Guard is enabled, and a program attempted to approve a delegate"),
+    (46, "CpiGuardSetAuthorityBlocked", "CPI Guard is enabled, and a program attempted to add or replace an authority"),
+    (47, "CpiGuardOwnerChangeBlocked", "Account ownership cannot be changed while CPI Guard is enabled"),
+    (48, "ExtensionNotFound", "Extension not found in account data"),
+];
+
+// https://github.com/raydium-io/raydium-amm/blob/master/program/src/error.rs
+const RAYDIUM_AMM_V4_ERRORS: &ErrorTable = &[
+    (0, "AlreadyInUse", "AlreadyInUse"),
+    (1, "InvalidProgramAddress", "InvalidProgramAddress"),
+    (2, "ExpectedMint", "ExpectedMint"),
+    (3, "ExpectedAccount", "ExpectedAccount"),
+    (4, "InvalidCoinVault", "InvalidCoinVault"),
+    (5, "InvalidPCVault", "InvalidPCVault"),
+    (6, "InvalidTokenLP", "InvalidTokenLP"),
+    (7, "InvalidDestTokenCoin", "InvalidDestTokenCoin"),
+    (8, "InvalidDestTokenPC", "InvalidDestTokenPC"),
+    (9, "InvalidPoolMint", "InvalidPoolMint"),
+    (10, "InvalidOpenOrders", "InvalidOpenOrders"),
+    (11, "InvalidSerumMarket", "InvalidSerumMarket"),
+    (12, "InvalidSerumProgram", "InvalidSerumProgram"),
+    (13, "InvalidTargetOrders", "InvalidTargetOrders"),
+    (14, "InvalidWithdrawQueue", "InvalidWithdrawQueue"),
+    (15, "InvalidTempLp", "InvalidTempLp"),
+    (16, "InvalidCoinMint", "InvalidCoinMint"),
+    (17, "InvalidPCMint", "InvalidPCMint"),
+    (18, "InvalidOwner", "InvalidOwner"),
+    (19, "InvalidSupply", "InvalidSupply"),
+    (20, "InvalidDelegate", "InvalidDelegate"),
+    (21, "InvalidSignAccount", "Invalid Sign Account"),
+    (22, "InvalidStatus", "InvalidStatus"),
+    (23, "InvalidInstruction", "Invalid instruction"),
+    (24, "WrongAccountsNumber", "Wrong accounts number"),
+    (25, "WithdrawTransferBusy", "Withdraw_transfer is busy"),
+    (26, "WithdrawQueueFull", "WithdrawQueue is full"),
+    (27, "WithdrawQueueEmpty", "WithdrawQueue is empty"),
+    (28, "InvalidParamsSet", "Params Set is invalid"),
+    (29, "InvalidInput", "InvalidInput"),
+    (30, "ExceededSlippage", "instruction exceeds desired slippage limit"),
+    (31, "CalculationExRateFailure", "CalculationExRateFailure"),
+    (32, "CheckedSubOverflow", "Checked_Sub Overflow"),
+    (33, "CheckedAddOverflow", "Checked_Add Overflow"),
+    (34, "CheckedMulOverflow", "Checked_Mul Overflow"),
+    (35, "CheckedDivOverflow", "Checked_Div Overflow"),
+    (36, "CheckedEmptyFunds", "Empty Funds"),
+    (37, "CalcPnlError", "Calc pnl error"),
+    (38, "InvalidSplTokenProgram", "InvalidSplTokenProgram"),
+    (39, "TakePnlError", "Take Pnl error"),
+    (40, "InsufficientFunds", "Insufficient funds"),
+    (41, "ConversionFailure", "Conversion to u64 failed with an overflow or underflow"),
+    (42, "InvalidUserToken", "user token input does not match amm"),
+    (43, "InvalidSrmMint", "InvalidSrmMint"),
+    (44, "InvalidSrmToken", "InvalidSrmToken"),
+    (45, "TooManyOpenOrders", "TooManyOpenOrders"),
+    (46, "OrderAtSlotIsPlaced", "OrderAtSlotIsPlaced"),
+    (47, "InvalidSysProgramAddress", "InvalidSysProgramAddress"),
+    (48, "InvalidFee", "The provided fee does not match the program owner's constraints"),
+];
+
+// https://github.com/anza-xyz/agave/blob/master/sdk/program/src/system_instruction.rs
+const SYSTEM_ERRORS: &ErrorTable = &[
+    (0, "AccountAlreadyInUse", "an account with the same address already exists"),
+    (1, "ResultWithNegativeLamports", "account does not have enough SOL to perform the operation"),
+    (2, "InvalidProgramId", "cannot assign account to this program id"),
+    (3, "InvalidAccountDataLength", "cannot allocate account data of this length"),
+    (4, "MaxSeedLengthExceeded", "length of requested seed is too long"),
+    (5, "AddressWithSeedMismatch", "provided address does not match addressed derived from seed"),
+    (6, "NonceNoRecentBlockhashes", "advancing stored nonce requires a populated RecentBlockhashes sysvar"),
+    (7, "NonceBlockhashNotExpired", "stored nonce is still in recent_blockhashes"),
+    (8, "NonceUnexpectedBlockhashValue", "specified nonce does not match stored nonce"),
+];
+
+// https://github.com/coral-xyz/anchor/blob/master/lang/src/error.rs, only meaningful for Anchor programs
+const ANCHOR_ERRORS: &ErrorTable = &[
+    (100, "InstructionMissing", "8 byte instruction identifier not provided"),
+    (101, "InstructionFallbackNotFound", "Fallback functions are not supported"),
+    (102, "InstructionDidNotDeserialize", "The program could not deserialize the given instruction"),
+    (103, "InstructionDidNotSerialize", "The program could not serialize the given instruction"),
+    (1000, "IdlInstructionStub", "The program was compiled without idl instructions"),
+    (1001, "IdlInstructionInvalidProgram", "Invalid program given to the IDL instruction"),
+    (1002, "IdlAccountNotEmpty", "IDL account must be empty in order to resize, try closing first"),
+    (1500, "EventInstructionStub", "The program was compiled without `event-cpi` feature"),
+    (2000, "ConstraintMut", "A mut constraint was violated"),
+    (2001, "ConstraintHasOne", "A has one constraint was violated"),
+    (2002, "ConstraintSigner", "A signer constraint was violated"),
+    (2003, "ConstraintRaw", "A raw constraint was violated"),
+    (2004, "ConstraintOwner", "An owner constraint was violated"),
+    (2005, "ConstraintRentExempt", "A rent exemption constraint was violated"),
+    (2006, "ConstraintSeeds", "A seeds constraint was violated"),
+    (2007, "ConstraintExecutable", "An executable constraint was violated"),
+    (2008, "ConstraintState", "Deprecated Error, feel free to replace with something else"),
+    (2009, "ConstraintAssociated", "An associated constraint was violated"),
+    (2010, "ConstraintAssociatedInit", "An associated init constraint was violated"),
+    (2011, "ConstraintClose", "A close constraint was violated"),
+    (2012, "ConstraintAddress", "An address constraint was violated"),
+    (2013, "ConstraintZero", "Expected zero account discriminant"),
+    (2014, "ConstraintTokenMint", "A token mint constraint was violated"),
+    (2015, "ConstraintTokenOwner", "A token owner constraint was violated"),
+    (2016, "ConstraintMintMintAuthority", "A mint mint authority constraint was violated"),
+    (2017, "ConstraintMintFreezeAuthority", "A mint freeze authority constraint was violated"),
+    (2018, "ConstraintMintDecimals", "A mint decimals constraint was violated"),
+    (2019, "ConstraintSpace", "A space constraint was violated"),
+    (2020, "ConstraintAccountIsNone", "A required account for the constraint is None"),
+    (2500, "RequireViolated", "A require expression was violated"),
+    (2501, "RequireEqViolated", "A require_eq expression was violated"),
+    (2502, "RequireKeysEqViolated", "A require_keys_eq expression was violated"),
+    (2503, "RequireNeqViolated", "A require_neq expression was violated"),
+    (2504, "RequireKeysNeqViolated", "A require_keys_neq expression was violated"),
+    (2505, "RequireGtViolated", "A require_gt expression was violated"),
+    (2506, "RequireGteViolated", "A require_gte expression was violated"),
+    (3000, "AccountDiscriminatorAlreadySet", "The account discriminator was already set on this account"),
+    (3001, "AccountDiscriminatorNotFound", "No 8 byte discriminator was found on the account"),
+    (3002, "AccountDiscriminatorMismatch", "8 byte discriminator did not match what was expected"),
+    (3003, "AccountDidNotDeserialize", "Failed to deserialize the account"),
+    (3004, "AccountDidNotSerialize", "Failed to serialize the account"),
+    (3005, "AccountNotEnoughKeys", "Not enough account keys given to the instruction"),
+    (3006, "AccountNotMutable", "The given account is not mutable"),
+    (3007, "AccountOwnedByWrongProgram", "The given account is owned by a different program than expected"),
+    (3008, "InvalidProgramId", "Program ID was not as expected"),
+    (3009, "InvalidProgramExecutable", "Program account is not executable"),
+    (3010, "AccountNotSigner", "The given account did not sign"),
+    (3011, "AccountNotSystemOwned", "The given account is not owned by the system program"),
+    (3012, "AccountNotInitialized", "The program expected this account to be already initialized"),
+    (3013, "AccountNotProgramData", "The given account is not a program data account"),
+    (3014, "AccountNotAssociatedTokenAccount", "The given account is not the associated token account"),
+    (3015, "AccountSysvarMismatch", "The given public key does not match the required sysvar"),
+    (3016, "AccountReallocExceedsLimit", "The account reallocation exceeds the MAX_PERMITTED_DATA_INCREASE limit"),
+    (3017, "AccountDuplicateReallocs", "The account was duplicated for more than one reallocation"),
+    (4100, "DeclaredProgramIdMismatch", "The declared program id does not match the actual program id"),
+    (5000, "Deprecated", "The API being used is deprecated and should no longer be used"),
+];
+
+fn lookup(table: &ErrorTable, code: u32) -> Option<(String, String)> {
+    table.iter()
+        .find(|(known, _, _)| *known == code)
+        .map(|(_, name, message)| (name.to_string(), message.to_string()))
+}
+
+/// The known error table of a native or well known program
+fn program_error(program_id: &str, code: u32) -> Option<(String, String)> {
+    match program_id {
+        SPL_TOKEN_PROGRAM_ID => lookup(TOKEN_ERRORS, code),
+        TOKEN_2022_PROGRAM_ID => lookup(TOKEN_ERRORS, code).or_else(|| lookup(TOKEN_2022_ERRORS, code)),
+        RAYDIUM_AMM_V4_PROGRAM_ID => lookup(RAYDIUM_AMM_V4_ERRORS, code),
+        SYSTEM_PROGRAM_ID => lookup(SYSTEM_ERRORS, code),
+        _ => None,
+    }
+}
+
+/// Whether custom errors of the program are named without an IDL
+pub fn has_error_table(program_id: &str) -> bool {
+    matches!(program_id, SPL_TOKEN_PROGRAM_ID | TOKEN_2022_PROGRAM_ID | RAYDIUM_AMM_V4_PROGRAM_ID | SYSTEM_PROGRAM_ID)
+}
+
+/// What the runtime means by the `InstructionError` variants and transaction errors worth explaining
+fn runtime_message(kind: &str) -> Option<&'static str> {
+    let message = match kind {
+        "InsufficientFunds" => "insufficient funds for instruction",
+        "InvalidArgument" => "invalid program argument",
+        "InvalidInstructionData" => "invalid instruction data",
+        "InvalidAccountData" => "invalid account data for instruction",
+        "AccountDataTooSmall" => "account data too small for instruction",
+        "IncorrectProgramId" => "incorrect program id for instruction",
+        "MissingRequiredSignature" => "missing required signature for instruction",
+        "AccountAlreadyInitialized" => "instruction requires an uninitialized account",
+        "UninitializedAccount" => "instruction requires an initialized account",
+        "NotEnoughAccountKeys" => "insufficient account keys for instruction",
+        "AccountBorrowFailed" => "instruction tries to borrow reference for an account which is already borrowed",
+        "ComputationalBudgetExceeded" => "computational budget exceeded",
+        "ProgramFailedToComplete" => "program failed to complete",
+        "PrivilegeEscalation" => "cross-program invocation with unauthorized signer or writable account",
+        "CallDepth" => "cross-program invocation call depth too deep",
+        "IllegalOwner" => "provided owner is not allowed",
+        "ArithmeticOverflow" => "arithmetic overflowed",
+        "InvalidSeeds" => "provided seeds do not result in a valid address",
+        "BorshIoError" => "failed to serialize or deserialize account data",
+        "AccountInUse" => "account in use",
+        "AccountLoadedTwice" => "account loaded twice",
+        "AccountNotFound" => "attempt to debit an account but found no record of a prior credit",
+        "ProgramAccountNotFound" => "attempt to load a program that does not exist",
+        "InsufficientFundsForFee" => "insufficient funds for fee",
+        "InsufficientFundsForRent" => "transaction results in an account with insufficient funds for rent",
+        "InvalidAccountForFee" => "this account may not be used to pay transaction fees",
+        "AlreadyProcessed" => "this transaction has already been processed",
+        "BlockhashNotFound" => "blockhash not found",
+        "DuplicateInstruction" => "transaction contains a duplicate instruction that is not allowed",
+        "InvalidWritableAccount" => "transaction loads a writable account that cannot be written",
+        "WouldExceedMaxBlockCostLimit" => "transaction would exceed max block cost limit",
+        "WouldExceedMaxAccountCostLimit" => "transaction would exceed max account limit within the block",
+        "WouldExceedAccountDataBlockLimit" => "transaction would exceed account data limit within the block",
+        "TooManyAccountLocks" => "transaction locked too many accounts",
+        "AddressLookupTableNotFound" => "transaction loads an address table account that doesn't exist",
+        "InvalidAddressLookupTableIndex" => "transaction address table lookup uses an invalid index",
+        "ProgramExecutionTemporarilyRestricted" => "execution of the program referenced is temporarily restricted",
+        _ => return None,
+    };
+    Some(message)
+}
+
+/// The failed invocation of the outer instruction: the deepest one, its error is the one the others propagated
+fn failed_invocation(logs: &ProgramLogs, instruction_index: usize) -> Option<&ProgramInvocation> {
+    logs.flatten().into_iter()
+        .filter(|invocation| invocation.outer_index == instruction_index && invocation.status == InvocationStatus::Failed)
+        .max_by_key(|invocation| invocation.depth)
+}
+
+/// (name, message) of the `AnchorError occurred` log of the invocation with this code
+fn anchor_log_error(invocation: &ProgramInvocation, code: u32) -> Option<(String, String)> {
+    invocation.logs.iter().find_map(|log| {
+        let (_, rest) = log.split_once(ANCHOR_ERROR_CODE)?;
+        let (name, rest) = rest.split_once(ANCHOR_ERROR_NUMBER)?;
+        let (number, message) = rest.split_once(ANCHOR_ERROR_MESSAGE)?;
+        if number.trim().parse::<u32>().ok()? != code {
+            return None;
+        }
+        Some((name.to_string(), message.trim_end_matches('.').to_string()))
+    })
+}
+
+/// The program whose custom error code has to be named, when the transaction failed with one
+pub fn custom_error_program(err: &serde_json::Value, logs: &ProgramLogs) -> Option<(String, u32)> {
+    let (index, instruction_error) = instruction_error(err)?;
+    let code = instruction_error.get(CUSTOM)?.as_u64()? as u32;
+    let program_id = failing_program(logs, index)?;
+    Some((program_id, code))
+}
+
+fn instruction_error(err: &Value) -> Option<(usize, &Value)> {
+    let error = err.get(INSTRUCTION_ERROR)?.as_array()?;
+    let index = error.first()?.as_u64()? as usize;
+    Some((index, error.get(1)?))
+}
+
+/// The program that failed in the outer instruction: the failed CPI when the logs have one, else the instruction's own program
+fn failing_program(logs: &ProgramLogs, instruction_index: usize) -> Option<String> {
+    failed_invocation(logs, instruction_index)
+        .or_else(|| logs.invocations.iter().find(|invocation| invocation.outer_index == instruction_index))
+        .map(|invocation| invocation.program_id.clone())
+}
+
+/// Decodes the `err` of a failed transaction into a named cause.
+///
+/// * `{"InstructionError": [index, {"Custom": code}]}` is named with the known tables (SPL Token, Token-2022, Raydium AMM v4,
+///   System program), then the `errors` of `idl` and the Anchor framework errors when the program has an IDL, then the
+///   `AnchorError occurred` log of the failing program
+/// * `{"InstructionError": [index, "Variant"]}` and the transaction level errors (`"BlockhashNotFound"`,
+///   `{"InsufficientFundsForRent": {...}}`) are named after the variant
+///
+/// `idl` is the IDL of the failing program, see `custom_error_program`.
+pub fn decode_error(signature: &str, slot: u64, err: &Value, logs: &ProgramLogs, idl: Option<&AnchorIdl>) -> FailedTransaction {
+    let mut failed = FailedTransaction {
+        signature: signature.to_string(),
+        slot,
+        instruction_index: None,
+        program_id: None,
+        kind: String::new(),
+        code: None,
+        name: String::new(),
+        message: None,
+        source: ErrorSource::Runtime,
+        err: err.clone(),
+    };
+
+    // The error itself: a variant without fields is a plain string, the others an object with a single key
+    let (kind, details) = match instruction_error(err) {
+        Some((index, instruction_error)) => {
+            failed.instruction_index = Some(index);
+            failed.program_id = failing_program(logs, index);
+            variant(instruction_error)
+        }
+        None => variant(err),
+    };
+    failed.kind = kind.clone();
+    failed.name = kind.clone();
+    failed.message = runtime_message(&kind).map(String::from);
+
+    if kind != CUSTOM {
+        // ie. {"InsufficientFundsForRent": {"account_index": 2}}, {"BorshIoError": "Unknown"}
+        if let Some(details) = details.filter(|details| !details.is_null()) {
+            let details = details.as_str().map(String::from).unwrap_or_else(|| details.to_string());
+            failed.message = Some(match failed.message {
+                Some(message) => format!("{} ({})", message, details),
+                None => details,
+            });
+        }
+        return failed;
+    }
+
+    let Some(code) = details.and_then(|details| details.as_u64()).map(|code| code as u32) else {
+        failed.source = ErrorSource::Unknown;
+        return failed;
+    };
+    failed.code = Some(code);
+    failed.source = ErrorSource::Unknown;
+    let Some(program_id) = failed.program_id.clone() else {
+        return failed;
+    };
+
+    let named = if let Some(named) = program_error(&program_id, code) {
+        Some((named, ErrorSource::Program))
+    } else if let Some(error) = idl.and_then(|idl| idl.error(code)) {
+        Some(((error.name.clone(), error.msg.clone().unwrap_or_default()), ErrorSource::Idl))
+    } else if let Some(named) = idl.and_then(|_| lookup(ANCHOR_ERRORS, code)) {
+        Some((named, ErrorSource::Anchor))
+    } else {
+        failed.instruction_index
+            .and_then(|index| failed_invocation(logs, index))
+            .and_then(|invocation| anchor_log_error(invocation, code))
+            .map(|named| (named, ErrorSource::Logs))
+    };
+    if let Some(((name, message), source)) = named {
+        failed.name = name;
+        failed.message = Some(message).filter(|message| !message.is_empty());
+        failed.source = source;
+    }
+    failed
+}
+
+/// (variant name, its fields)
+fn variant(error: &Value) -> (String, Option<&Value>) {
+    match error {
+        Value::String(name) => (name.clone(), None),
+        Value::Object(fields) => match fields.iter().next() {
+            Some((name, value)) => (name.clone(), Some(value)),
+            None => ("Unknown".to_string(), None),
+        },
+        other => (other.to_string(), None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::decoder::anchor_idl::IdlErrorCode;
+    use crate::decoder::log_parser::parse_logs;
+
+    const ROUTER: &str = "JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4";
+    const ANCHOR_PROGRAM: &str = "whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc";
+
+    /// A router at outer index 1 calling `program`, which fails with `failure`
+    fn logs(program: &str, failure: &str, program_logs: &[&str]) -> ProgramLogs {
+        let mut lines = vec![
+            "Program ComputeBudget111111111111111111111111111111 invoke [1]".to_string(),
+            "Program ComputeBudget111111111111111111111111111111 success".to_string(),
+            format!("Program {} invoke [1]", ROUTER),
+            format!("Program {} invoke [2]", program),
+        ];
+        lines.extend(program_logs.iter().map(|log| format!("Program log: {}", log)));
+        lines.push(format!("Program {} failed: {}", program, failure));
+        lines.push(format!("Program {} failed: {}", ROUTER, failure));
+        parse_logs(&lines)
+    }
+
+    fn idl(errors: Vec<IdlErrorCode>) -> AnchorIdl {
+        AnchorIdl { name: "whirlpool".to_string(), address: None, instructions: vec![], types: vec![], events: vec![], errors }
+    }
+
+    #[test]
+    fn names_custom_errors_of_the_failing_cpi_from_the_known_tables() {
+        let err = json!({"InstructionError": [1, {"Custom": 1}]});
+        let token_logs = logs(SPL_TOKEN_PROGRAM_ID, "custom program error: 0x1", &[]);
+        assert_eq!(custom_error_program(&err, &token_logs), Some((SPL_TOKEN_PROGRAM_ID.to_string(), 1)));
+
+        let failed = decode_error("sig", 7, &err, &token_logs, None);
+        assert_eq!((failed.instruction_index, failed.program_id.as_deref()), (Some(1), Some(SPL_TOKEN_PROGRAM_ID)));
+        assert_eq!((failed.kind.as_str(), failed.code, failed.name.as_str()), ("Custom", Some(1), "InsufficientFunds"));
+        assert_eq!(failed.source, ErrorSource::Program);
+
+        // Token-2022 extends the SPL Token table
+        let token_2022_logs = logs(TOKEN_2022_PROGRAM_ID, "custom program error: 0x25", &[]);
+        let failed = decode_error("sig", 7, &json!({"InstructionError": [1, {"Custom": 37}]}), &token_2022_logs, None);
+        assert_eq!(failed.name, "NonTransferable");
+    }
+
+    #[test]
+    fn names_custom_errors_from_the_idl_then_anchor_then_the_logs() {
+        let err = json!({"InstructionError": [1, {"Custom": 6004}]});
+        let logs = logs(ANCHOR_PROGRAM, "custom program error: 0x1774", &[
+            "AnchorError occurred. Error Code: AmountOutBelowMinimum. Error Number: 6004. Error Message: Amount out below minimum threshold.",
+        ]);
+
+        let with_idl = idl(vec![IdlErrorCode { code: 6004, name: "InvalidTickArraySequence".to_string(), msg: Some("Invalid tick array sequence".to_string()) }]);
+        let failed = decode_error("sig", 7, &err, &logs, Some(&with_idl));
+        assert_eq!((failed.name.as_str(), failed.source), ("InvalidTickArraySequence", ErrorSource::Idl));
+
+        let failed = decode_error("sig", 7, &err, &logs, None);
+        assert_eq!((failed.name.as_str(), failed.source), ("AmountOutBelowMinimum", ErrorSource::Logs));
+        assert_eq!(failed.message.as_deref(), Some("Amount out below minimum threshold"));
+
+        let constraint = json!({"InstructionError": [1, {"Custom": 2006}]});
+        let failed = decode_error("sig", 7, &constraint, &logs, Some(&idl(vec![])));
+        assert_eq!((failed.name.as_str(), failed.source), ("ConstraintSeeds", ErrorSource::Anchor));
+
+        let unknown = json!({"InstructionError": [1, {"Custom": 42}]});
+        let failed = decode_error("sig", 7, &unknown, &logs, None);
+        assert_eq!((failed.name.as_str(), failed.code, failed.source), ("Custom", Some(42), ErrorSource::Unknown));
+    }
+
+    #[test]
+    fn names_runtime_errors_after_their_variant() {
+        let logs = logs(ANCHOR_PROGRAM, "exceeded CUs meter at BPF instruction", &[]);
+        let failed = decode_error("sig", 7, &json!({"InstructionError": [1, "ComputationalBudgetExceeded"]}), &logs, None);
+        assert_eq!((failed.name.as_str(), failed.source), ("ComputationalBudgetExceeded", ErrorSource::Runtime));
+        assert_eq!(failed.program_id.as_deref(), Some(ANCHOR_PROGRAM));
+        assert_eq!(failed.message.as_deref(), Some("computational budget exceeded"));
+
+        let empty = ProgramLogs::default();
+        let failed = decode_error("sig", 7, &json!("BlockhashNotFound"), &empty, None);
+        assert_eq!((failed.instruction_index, failed.name.as_str()), (None, "BlockhashNotFound"));
+
+        let failed = decode_error("sig", 7, &json!({"InsufficientFundsForRent": {"account_index": 2}}), &empty, None);
+        assert_eq!(failed.name, "InsufficientFundsForRent");
+        assert_eq!(failed.message.as_deref(), Some(r#"transaction results in an account with insufficient funds for rent ({"account_index":2})"#));
+        assert!(custom_error_program(&json!("BlockhashNotFound"), &empty).is_none());
+    }
+}
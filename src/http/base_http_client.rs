@@ -1,92 +1,159 @@
-use reqwest::{Client as ReqwestClient, Response};
+use reqwest::{Client as ReqwestClient, RequestBuilder, Response, StatusCode};
 use serde::de::DeserializeOwned;
+use serde::Serialize;
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use crate::http::circuit_breaker::{CircuitBreaker, CircuitState};
+use crate::http::client_policy::ClientPolicy;
 use crate::http::http_client_error::HttpClientError;
+use crate::http::rate_limiter::TokenBucket;
 
+/**
+Every request goes through the `ClientPolicy` of the client: it waits for a token of the provider rate limit, fails fast while
+the circuit breaker is open and is retried with backoff on 429, 5xx, timeouts and connection errors (honoring Retry-After).
+ */
+#[derive(Clone)]
 pub struct BaseHttpClient {
     client: ReqwestClient,
     default_headers: HashMap<String, String>,
+    policy: ClientPolicy,
+    rate_limiter: Option<Arc<TokenBucket>>,
+    circuit_breaker: Arc<CircuitBreaker>,
 }
 
 impl BaseHttpClient {
     pub fn new() -> Self {
+        let policy = ClientPolicy::new("http");
         Self {
             client: ReqwestClient::new(),
             default_headers: HashMap::new(),
+            rate_limiter: None,
+            circuit_breaker: Arc::new(CircuitBreaker::new(&policy.provider, policy.failure_threshold, policy.open_for)),
+            policy,
         }
     }
 
+    /// Replaces the rate limit, retry and circuit breaker settings, starting from a full bucket and a closed circuit.
+    pub fn with_policy(mut self, policy: ClientPolicy) -> Self {
+        self.rate_limiter = policy.requests_per_second
+            .map(|requests_per_second| Arc::new(TokenBucket::new(requests_per_second, policy.burst)));
+        self.circuit_breaker = Arc::new(CircuitBreaker::new(&policy.provider, policy.failure_threshold, policy.open_for));
+        self.policy = policy;
+        self
+    }
+
     pub fn add_default_header(mut self, key: &str, value: &str) -> Self {
         self.default_headers.insert(key.to_string(), value.to_string());
         self
     }
 
-    async fn send_request<T: DeserializeOwned>(&self, method: reqwest::Method, url: &str, headers: Option<HashMap<&str, &str>>, body: Option<String>) -> Result<T, Box<dyn std::error::Error>> {
-        let mut request = self.client.request(method, url);
+    pub fn circuit_state(&self) -> CircuitState {
+        self.circuit_breaker.state()
+    }
 
-        // Apply default headers
-        for (key, value) in &self.default_headers {
-            request = request.header(key, value);
-        }
+    /// Sends the request built by `build` (called again for every attempt) and returns the response once its status is a success.
+    async fn execute<F: Fn() -> RequestBuilder>(&self, build: F) -> Result<Response, HttpClientError> {
+        let provider = &self.policy.provider;
+        let mut attempt = 0;
+        loop {
+            let Some(permit) = self.circuit_breaker.allow() else {
+                return Err(HttpClientError::CircuitOpen(provider.clone()));
+            };
+            if let Some(ref rate_limiter) = self.rate_limiter {
+                rate_limiter.acquire().await;
+            }
 
-        // Apply additional headers
-        if let Some(hdrs) = headers {
-            for (key, value) in hdrs {
+            let mut request = build().timeout(self.policy.timeout);
+            // Apply default headers
+            for (key, value) in &self.default_headers {
                 request = request.header(key, value);
             }
-        }
-
-        // Set JSON body if present
-        if let Some(b) = body {
-            request = request.body(b);
-        }
 
-        let response: Response = request.send().await?;
-        let status = response.status();
-        let result = response.json::<T>().await?;
+            let retry_after = match request.send().await {
+                Ok(response) if response.status() == StatusCode::TOO_MANY_REQUESTS => {
+                    // Throttled but up, as far as the breaker is concerned that is a success
+                    permit.record_success();
+                    let retry_after = retry_after(&response);
+                    if attempt >= self.policy.retry.max_retries {
+                        return Err(HttpClientError::TooManyRequests(provider.clone()));
+                    }
+                    retry_after
+                }
+                Ok(response) if response.status().is_server_error() => {
+                    permit.record_failure();
+                    if attempt >= self.policy.retry.max_retries {
+                        return Self::check_status(response).await;
+                    }
+                    retry_after(&response)
+                }
+                Ok(response) => {
+                    permit.record_success();
+                    return Self::check_status(response).await;
+                }
+                Err(e) if e.is_timeout() || e.is_connect() => {
+                    permit.record_failure();
+                    if attempt >= self.policy.retry.max_retries {
+                        return Err(HttpClientError::from(e));
+                    }
+                    None
+                }
+                // Not the provider's fault: a closed circuit ignores it, the permit of a trial records a failure when dropped
+                Err(e) => return Err(HttpClientError::from(e)),
+            };
 
-        if status.is_success() {
-            Ok(result)
-        } else {
-            Err(format!("Request failed with status: {}", status).into())
+            let delay = self.policy.retry.delay(attempt, retry_after);
+            if retry_after.is_some() {
+                // The whole provider asked us to slow down, not only this request
+                if let Some(ref rate_limiter) = self.rate_limiter {
+                    rate_limiter.pause(delay);
+                }
+            }
+            attempt += 1;
+            println!("[[HTTP]] {} request failed, retry {}/{} in {:?}", provider, attempt, self.policy.retry.max_retries, delay);
+            tokio::time::sleep(delay).await;
         }
     }
 
-    pub async fn get<T: DeserializeOwned>(&self, url: &str, headers: Option<HashMap<&str, &str>>) -> Result<T, Box<dyn std::error::Error>> {
-        self.send_request(reqwest::Method::GET, url, headers, None).await
-    }
+    pub async fn get_with_query<T: DeserializeOwned>(&self, url: &str, query: &[(&str, &str)]) -> Result<T, HttpClientError> {
+        // Apply query parameters
+        let response = self.execute(|| self.client.get(url).query(query)).await?;
 
-    pub async fn post<T: DeserializeOwned>(&self, url: &str, headers: Option<HashMap<&str, &str>>, body: String) -> Result<T, Box<dyn std::error::Error>> {
-        self.send_request(reqwest::Method::POST, url, headers, Some(body)).await
+        // Deserialize the response body into the expected type
+        response.json::<T>().await.map_err(HttpClientError::from)
     }
 
-    pub async fn get_with_query<T: DeserializeOwned>(&self, url: &str, query: &[(&str, &str)]) -> Result<T, HttpClientError> {
-        let request = self.client.get(url);
+    /// POSTs `body` as JSON. The status is checked before the body is parsed, so an error page never shows up as a parse error.
+    pub async fn post_json<B: Serialize + ?Sized, T: DeserializeOwned>(&self, url: &str, body: &B) -> Result<T, HttpClientError> {
+        let response = self.execute(|| self.client.post(url).json(body)).await?;
 
-        // Apply default headers
-        let mut request = request;
-        for (key, value) in &self.default_headers {
-            request = request.header(key, value);
-        }
+        let text = response.text().await.map_err(HttpClientError::from)?;
+        serde_json::from_str::<T>(&text).map_err(HttpClientError::from)
+    }
 
-        // Apply query parameters
-        let request = request.query(query);
-
-        // Send the request
-        let response = request.send().await.map_err(HttpClientError::from)?;
-
-        // Check for HTTP success status
-        if !response.status().is_success() {
-            let error = match response.status() {
-                reqwest::StatusCode::UNAUTHORIZED => HttpClientError::Unauthorized,
-                reqwest::StatusCode::BAD_REQUEST => HttpClientError::BadRequest("Bad request".into()),
-                reqwest::StatusCode::NOT_FOUND => HttpClientError::NotFound,
-                _ => HttpClientError::Other(response.error_for_status().unwrap_err()), // Convert to reqwest::Error for detailed error
-            };
-            return Err(error);
+    async fn check_status(response: Response) -> Result<Response, HttpClientError> {
+        if response.status().is_success() {
+            return Ok(response);
         }
 
-        // Deserialize the response body into the expected type
-        response.json::<T>().await.map_err(HttpClientError::from)
+        let error = match response.status() {
+            StatusCode::UNAUTHORIZED => HttpClientError::Unauthorized,
+            StatusCode::BAD_REQUEST => {
+                let message = response.text().await.unwrap_or_else(|_| "Bad request".into());
+                HttpClientError::BadRequest(message)
+            }
+            StatusCode::NOT_FOUND => HttpClientError::NotFound,
+            _ => HttpClientError::Other(response.error_for_status().unwrap_err()), // Convert to reqwest::Error for detailed error
+        };
+        Err(error)
     }
 }
+
+/// Retry-After in seconds, the HTTP date form is not used by our providers
+fn retry_after(response: &Response) -> Option<Duration> {
+    response.headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
+}
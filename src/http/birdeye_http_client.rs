@@ -0,0 +1,42 @@
+use std::env;
+
+use crate::http::base_http_client::BaseHttpClient;
+use crate::http::client_policy::ClientPolicy;
+use crate::http::http_client_error::HttpClientError;
+use crate::models::birdeye::multi_price_response::{MultiPriceRequest, MultiPriceResponse};
+
+
+#[derive(Clone)]
+pub struct BirdeyeHttpClient {
+    base_client: BaseHttpClient,
+    base_url: String,
+}
+
+impl BirdeyeHttpClient {
+    pub fn new(api_key: &str) -> Self {
+        let base_client = BaseHttpClient::new()
+            .with_policy(ClientPolicy::birdeye())
+            .add_default_header("X-API-KEY", api_key)
+            .add_default_header("accept", "application/json");
+
+        Self {
+            base_client,
+            base_url: "https://public-api.birdeye.so".to_string(),
+        }
+    }
+
+    /// Uses BIRDEYE_API_KEY, requests are rejected as unauthorized when it is not set.
+    pub fn from_env() -> Self {
+        let api_key = env::var("BIRDEYE_API_KEY").unwrap_or_else(|_| {
+            eprintln!("[[BIRDEYE]] BIRDEYE_API_KEY not set");
+            String::new()
+        });
+        Self::new(&api_key)
+    }
+
+    /// Prices of up to 100 comma separated addresses, liquidity always included
+    pub async fn get_multi_price(&self, request: &MultiPriceRequest) -> Result<MultiPriceResponse, HttpClientError> {
+        let url = format!("{}/defi/multi_price?include_liquidity=true", &self.base_url);
+        self.base_client.post_json::<MultiPriceRequest, MultiPriceResponse>(&url, request).await
+    }
+}
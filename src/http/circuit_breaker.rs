@@ -0,0 +1,189 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    // Requests go through
+    Closed,
+    // The provider looks down, requests fail right away
+    Open,
+    // `open_for` is over, a single trial request decides whether to close or open again
+    HalfOpen,
+}
+
+struct BreakerState {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    trial_in_flight: bool,
+}
+
+/**
+Fails fast while a provider is down instead of letting every caller wait for its own timeouts and retries.
+
+Opens after `failure_threshold` consecutive failures (5xx, timeouts, connection errors) and stays open for `open_for`. Then one
+request is let through: a success closes the circuit, a failure opens it again. Requests report their outcome through the
+`CircuitPermit` `allow` hands out, a trial dropped without an outcome (an error unrelated to the provider, a cancelled future)
+counts as a failure so the next trial isn't blocked forever.
+ */
+pub struct CircuitBreaker {
+    provider: String,
+    failure_threshold: u32,
+    open_for: Duration,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    pub fn new(provider: &str, failure_threshold: u32, open_for: Duration) -> Self {
+        Self {
+            provider: provider.to_string(),
+            failure_threshold: failure_threshold.max(1),
+            open_for,
+            state: Mutex::new(BreakerState { consecutive_failures: 0, opened_at: None, trial_in_flight: false }),
+        }
+    }
+
+    /// None while the circuit is open. The permit records the outcome of the request it was given for.
+    pub fn allow(&self) -> Option<CircuitPermit<'_>> {
+        let mut state = self.state.lock().unwrap();
+        match state.opened_at {
+            None => Some(CircuitPermit { breaker: self, trial: false, settled: false }),
+            Some(opened_at) if opened_at.elapsed() >= self.open_for && !state.trial_in_flight => {
+                state.trial_in_flight = true;
+                Some(CircuitPermit { breaker: self, trial: true, settled: false })
+            }
+            Some(_) => None,
+        }
+    }
+
+    fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.opened_at.is_some() {
+            println!("[[CIRCUIT BREAKER]] {} is back, closing the circuit", self.provider);
+        }
+        state.consecutive_failures = 0;
+        state.opened_at = None;
+        state.trial_in_flight = false;
+    }
+
+    fn record_failure(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.consecutive_failures += 1;
+        if state.trial_in_flight {
+            state.trial_in_flight = false;
+            state.opened_at = Some(Instant::now());
+            eprintln!("[[CIRCUIT BREAKER]] {} trial request failed, open for another {:?}", self.provider, self.open_for);
+        } else if state.opened_at.is_none() && state.consecutive_failures >= self.failure_threshold {
+            state.opened_at = Some(Instant::now());
+            eprintln!("[[CIRCUIT BREAKER]] {} failed {} times in a row, open for {:?}", self.provider, state.consecutive_failures, self.open_for);
+        }
+    }
+
+    pub fn state(&self) -> CircuitState {
+        let state = self.state.lock().unwrap();
+        match state.opened_at {
+            None => CircuitState::Closed,
+            Some(_) if state.trial_in_flight => CircuitState::HalfOpen,
+            Some(opened_at) if opened_at.elapsed() >= self.open_for => CircuitState::HalfOpen,
+            Some(_) => CircuitState::Open,
+        }
+    }
+}
+
+/// Permission to send one request. Dropping the permit of the half-open trial without recording an outcome records a failure.
+pub struct CircuitPermit<'a> {
+    breaker: &'a CircuitBreaker,
+    trial: bool,
+    settled: bool,
+}
+
+impl CircuitPermit<'_> {
+    pub fn record_success(mut self) {
+        self.settled = true;
+        self.breaker.record_success();
+    }
+
+    pub fn record_failure(mut self) {
+        self.settled = true;
+        self.breaker.record_failure();
+    }
+}
+
+impl Drop for CircuitPermit<'_> {
+    fn drop(&mut self) {
+        if self.trial && !self.settled {
+            self.breaker.record_failure();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPEN_FOR: Duration = Duration::from_millis(30);
+
+    fn open_breaker() -> CircuitBreaker {
+        let breaker = CircuitBreaker::new("test", 2, OPEN_FOR);
+        breaker.allow().unwrap().record_failure();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        breaker.allow().unwrap().record_failure();
+        assert_eq!(breaker.state(), CircuitState::Open);
+        breaker
+    }
+
+    #[test]
+    fn opens_after_consecutive_failures_only() {
+        let breaker = CircuitBreaker::new("test", 2, OPEN_FOR);
+        breaker.allow().unwrap().record_failure();
+        breaker.allow().unwrap().record_success();
+        breaker.allow().unwrap().record_failure();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        let breaker = open_breaker();
+        assert!(breaker.allow().is_none());
+    }
+
+    #[test]
+    fn a_single_trial_once_open_for_is_over() {
+        let breaker = open_breaker();
+        std::thread::sleep(OPEN_FOR + Duration::from_millis(10));
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+
+        let trial = breaker.allow().unwrap();
+        assert!(breaker.allow().is_none());
+        trial.record_success();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(breaker.allow().is_some());
+    }
+
+    #[test]
+    fn a_failed_trial_opens_again() {
+        let breaker = open_breaker();
+        std::thread::sleep(OPEN_FOR + Duration::from_millis(10));
+        breaker.allow().unwrap().record_failure();
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(breaker.allow().is_none());
+    }
+
+    #[test]
+    fn a_dropped_trial_counts_as_a_failure() {
+        let breaker = open_breaker();
+        std::thread::sleep(OPEN_FOR + Duration::from_millis(10));
+        drop(breaker.allow().unwrap());
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        // The slot was released: the next trial goes through
+        std::thread::sleep(OPEN_FOR + Duration::from_millis(10));
+        assert!(breaker.allow().is_some());
+    }
+
+    #[test]
+    fn dropping_a_closed_circuit_permit_records_nothing() {
+        let breaker = CircuitBreaker::new("test", 1, OPEN_FOR);
+        drop(breaker.allow().unwrap());
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+}
@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+use rand::Rng;
+
+/// Exponential backoff with jitter, used for 429s, 5xx, timeouts and connection errors.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(max_retries: u32, base_delay: Duration, max_delay: Duration) -> Self {
+        Self { max_retries, base_delay, max_delay }
+    }
+
+    /// Delay before retry number `attempt` (0 based). A Retry-After sent by the provider always wins, even over `max_delay`
+    pub fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(retry_after) = retry_after {
+            return retry_after;
+        }
+        let exponential = self.base_delay.saturating_mul(2u32.saturating_pow(attempt)).min(self.max_delay);
+        // Up to 25% of jitter so clients that failed together do not retry together
+        let jitter = exponential.mul_f64(rand::thread_rng().gen_range(0.0..0.25));
+        (exponential + jitter).min(self.max_delay)
+    }
+}
+
+/**
+How a `BaseHttpClient` treats its provider: rate limit, retries, circuit breaker and request timeout.
+
+Every client gets a preset matching its provider plan (see `birdeye`, `solana_rpc`) that can be replaced with `with_policy`.
+The limits are shared by every clone of the client, not by clients created separately.
+ */
+#[derive(Debug, Clone)]
+pub struct ClientPolicy {
+    // Used in logs and errors
+    pub provider: String,
+    // None means no client side limit
+    pub requests_per_second: Option<f64>,
+    pub burst: u32,
+    pub retry: RetryPolicy,
+    pub failure_threshold: u32,
+    pub open_for: Duration,
+    pub timeout: Duration,
+}
+
+impl ClientPolicy {
+    pub fn new(provider: &str) -> Self {
+        Self {
+            provider: provider.to_string(),
+            requests_per_second: None,
+            burst: 1,
+            retry: RetryPolicy::new(3, Duration::from_millis(250), Duration::from_secs(10)),
+            failure_threshold: 5,
+            open_for: Duration::from_secs(30),
+            timeout: Duration::from_secs(30),
+        }
+    }
+
+    pub fn with_rate_limit(mut self, requests_per_second: f64, burst: u32) -> Self {
+        self.requests_per_second = Some(requests_per_second);
+        self.burst = burst;
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    // https://docs.birdeye.so/docs/rate-limiting, standard package
+    pub fn birdeye() -> Self {
+        Self::new("birdeye").with_rate_limit(1.0, 1)
+    }
+
+    // QuickNode build plan
+    pub fn solana_rpc() -> Self {
+        Self::new("solana-rpc")
+            .with_rate_limit(50.0, 50)
+            .with_timeout(Duration::from_secs(20))
+    }
+
+    // https://solana.com/docs/core/clusters#mainnet-beta-rate-limits, 100 requests per 10 seconds per IP
+    pub fn public_solana_rpc() -> Self {
+        Self::new("mainnet-beta")
+            .with_rate_limit(10.0, 10)
+            .with_timeout(Duration::from_secs(20))
+    }
+}
@@ -1,11 +1,25 @@
 use std::fmt;
 
+use crate::models::solana::solana_rpc::JsonRpcError;
+
 #[derive(Debug)]
 pub enum HttpClientError {
     Unauthorized,
     BadRequest(String),
     NotFound,
     Other(reqwest::Error),
+    // The body was received but is not what we expected
+    Deserialize(serde_json::Error),
+    // A JSON-RPC error object, the HTTP request itself succeeded
+    Rpc(JsonRpcError),
+    // The batch POST carrying this request failed as a whole
+    Batch(String),
+    // Still throttled (429) after every retry, carries the provider name
+    TooManyRequests(String),
+    // The provider is considered down, the request was not sent
+    CircuitOpen(String),
+    // No endpoint of the RPC pool serves this method, carries the method
+    NoEndpoint(String),
 }
 
 impl fmt::Display for HttpClientError {
@@ -15,12 +29,24 @@ impl fmt::Display for HttpClientError {
             HttpClientError::BadRequest(ref message) => write!(f, "Bad Request: {}", message),
             HttpClientError::NotFound => write!(f, "Not Found: The requested resource could not be found."),
             HttpClientError::Other(ref e) => write!(f, "Other Error: {}", e),
+            HttpClientError::Deserialize(ref e) => write!(f, "Unexpected response body: {}", e),
+            HttpClientError::Rpc(ref e) => write!(f, "RPC Error {}: {}", e.code, e.message),
+            HttpClientError::Batch(ref message) => write!(f, "Batch Error: {}", message),
+            HttpClientError::TooManyRequests(ref provider) => write!(f, "Too Many Requests: {} is still rate limiting us after retrying.", provider),
+            HttpClientError::CircuitOpen(ref provider) => write!(f, "Circuit Open: {} is failing, request not sent.", provider),
+            HttpClientError::NoEndpoint(ref method) => write!(f, "No Endpoint: no RPC endpoint serves {}.", method),
         }
     }
 }
 
 impl std::error::Error for HttpClientError {}
 
+impl From<serde_json::Error> for HttpClientError {
+    fn from(error: serde_json::Error) -> Self {
+        HttpClientError::Deserialize(error)
+    }
+}
+
 impl From<reqwest::Error> for HttpClientError {
     fn from(error: reqwest::Error) -> Self {
         // Map specific reqwest errors to your custom errors
@@ -29,6 +55,7 @@ impl From<reqwest::Error> for HttpClientError {
                 Some(reqwest::StatusCode::UNAUTHORIZED) => HttpClientError::Unauthorized,
                 Some(reqwest::StatusCode::BAD_REQUEST) => HttpClientError::BadRequest("Bad request".into()),
                 Some(reqwest::StatusCode::NOT_FOUND) => HttpClientError::NotFound,
+                Some(reqwest::StatusCode::TOO_MANY_REQUESTS) => HttpClientError::TooManyRequests(error.url().map(|url| url.to_string()).unwrap_or_default()),
                 _ => HttpClientError::Other(error),
             }
         } else {
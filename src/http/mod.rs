@@ -1,3 +1,10 @@
 pub mod base_http_client;
 pub mod http_client_error;
 pub mod moralis_http_client;
+pub mod solana_rpc_client;
+pub mod rpc_pool;
+pub mod rpc_cache;
+pub mod client_policy;
+pub mod rate_limiter;
+pub mod circuit_breaker;
+pub mod birdeye_http_client;
@@ -0,0 +1,114 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+struct BucketState {
+    tokens: f64,
+    last_refill: Instant,
+    // Set when the provider told us to back off (429 + Retry-After), nobody gets a token before
+    paused_until: Option<Instant>,
+}
+
+/**
+Token bucket shared by every request sent to one provider: `burst` requests can go out at once, then `requests_per_second`.
+Callers wait (asynchronously) for a token instead of being rejected.
+ */
+pub struct TokenBucket {
+    capacity: f64,
+    requests_per_second: f64,
+    state: Mutex<BucketState>,
+}
+
+impl TokenBucket {
+    pub fn new(requests_per_second: f64, burst: u32) -> Self {
+        let capacity = burst.max(1) as f64;
+        Self {
+            capacity,
+            requests_per_second: requests_per_second.max(f64::MIN_POSITIVE),
+            state: Mutex::new(BucketState { tokens: capacity, last_refill: Instant::now(), paused_until: None }),
+        }
+    }
+
+    pub async fn acquire(&self) {
+        loop {
+            let wait = self.try_acquire();
+            match wait {
+                None => return,
+                Some(wait) => tokio::time::sleep(wait).await,
+            }
+        }
+    }
+
+    /// Takes a token, or returns how long to wait before trying again
+    fn try_acquire(&self) -> Option<Duration> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        if let Some(paused_until) = state.paused_until {
+            if paused_until > now {
+                return Some(paused_until - now);
+            }
+            state.paused_until = None;
+            state.last_refill = now;
+        }
+
+        let refilled = now.duration_since(state.last_refill).as_secs_f64() * self.requests_per_second;
+        state.tokens = (state.tokens + refilled).min(self.capacity);
+        state.last_refill = now;
+
+        if state.tokens >= 1.0 {
+            state.tokens -= 1.0;
+            None
+        } else {
+            Some(Duration::from_secs_f64((1.0 - state.tokens) / self.requests_per_second))
+        }
+    }
+
+    /// Holds every caller for `duration` and empties the bucket, so requests resume one by one once it is over.
+    pub fn pause(&self, duration: Duration) {
+        let mut state = self.state.lock().unwrap();
+        let until = Instant::now() + duration;
+        if state.paused_until.is_none_or(|paused_until| paused_until < until) {
+            state.paused_until = Some(until);
+        }
+        state.tokens = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn burst_then_refill_rate() {
+        let bucket = TokenBucket::new(20.0, 2);
+        assert_eq!(bucket.try_acquire(), None);
+        assert_eq!(bucket.try_acquire(), None);
+        // One token every 50ms once the burst is spent
+        let wait = bucket.try_acquire().unwrap();
+        assert!(wait > Duration::from_millis(40) && wait <= Duration::from_millis(50), "{:?}", wait);
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(bucket.try_acquire(), None);
+    }
+
+    #[test]
+    fn pause_holds_every_caller_and_empties_the_bucket() {
+        let bucket = TokenBucket::new(1000.0, 10);
+        bucket.pause(Duration::from_millis(100));
+        // A shorter pause doesn't cut the current one short
+        bucket.pause(Duration::from_millis(10));
+        assert!(bucket.try_acquire().unwrap() > Duration::from_millis(80));
+
+        std::thread::sleep(Duration::from_millis(110));
+        // Resumes with an empty bucket: a single request per refill
+        assert!(bucket.try_acquire().is_some());
+    }
+
+    #[tokio::test]
+    async fn acquire_waits_for_a_token() {
+        let bucket = TokenBucket::new(20.0, 1);
+        let started = Instant::now();
+        bucket.acquire().await;
+        bucket.acquire().await;
+        assert!(started.elapsed() >= Duration::from_millis(45), "{:?}", started.elapsed());
+    }
+}
@@ -0,0 +1,382 @@
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Entries kept in memory, least recently used first out
+const DEFAULT_CAPACITY: usize = 10_000;
+
+/// Cache class of getTransaction at finalized, the only commitment whose result can't change anymore
+pub const FINALIZED_TRANSACTION: &str = "getTransaction:finalized";
+/// Cache class of getAccountInfo on Metaplex metadata accounts, name and symbol are updated once in a blue moon
+pub const METADATA_ACCOUNT: &str = "getAccountInfo:metadata";
+/// Cache class of getAccountInfo on Anchor IDL accounts, only rewritten when the program is upgraded
+pub const IDL_ACCOUNT: &str = "getAccountInfo:idl";
+/// Cache class of getAccountInfo on address lookup tables, only ever extended: the addresses already read don't change
+pub const LOOKUP_TABLE_ACCOUNT: &str = "getAccountInfo:lookupTable";
+
+/// How long a result stays cached and whether it also goes to the disk tier
+#[derive(Debug, Clone)]
+pub struct CacheRule {
+    // None caches forever
+    pub ttl: Option<Duration>,
+    pub persist: bool,
+}
+
+impl CacheRule {
+    pub fn forever() -> Self {
+        Self { ttl: None, persist: false }
+    }
+
+    pub fn ttl(ttl: Duration) -> Self {
+        Self { ttl: Some(ttl), persist: false }
+    }
+
+    /// Also written to the disk tier (when there is one), so it survives restarts
+    pub fn persisted(mut self) -> Self {
+        self.persist = true;
+        self
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ClassStats {
+    pub hits: u64,
+    // Included in hits
+    pub disk_hits: u64,
+    pub misses: u64,
+    pub stores: u64,
+}
+
+/// Served by /api/metrics/cache
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub capacity: usize,
+    pub evictions: u64,
+    pub classes: HashMap<String, ClassStats>,
+}
+
+struct Entry {
+    value: Value,
+    expires_at: Option<Instant>,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct Lru {
+    entries: HashMap<String, Entry>,
+    // last_used -> key, the first one is the next to evict
+    recency: BTreeMap<u64, String>,
+    clock: u64,
+    evictions: u64,
+}
+
+impl Lru {
+    fn get(&mut self, key: &str) -> Option<Value> {
+        let expired = self.entries.get(key)?.expires_at.is_some_and(|expires_at| expires_at <= Instant::now());
+        if expired {
+            self.remove(key);
+            return None;
+        }
+
+        self.clock += 1;
+        let entry = self.entries.get_mut(key)?;
+        self.recency.remove(&entry.last_used);
+        entry.last_used = self.clock;
+        self.recency.insert(self.clock, key.to_string());
+        Some(entry.value.clone())
+    }
+
+    fn insert(&mut self, key: String, value: Value, expires_at: Option<Instant>, capacity: usize) {
+        self.remove(&key);
+        self.clock += 1;
+        self.recency.insert(self.clock, key.clone());
+        self.entries.insert(key, Entry { value, expires_at, last_used: self.clock });
+
+        while self.entries.len() > capacity {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+            self.evictions += 1;
+        }
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.recency.remove(&entry.last_used);
+        }
+    }
+}
+
+/// One file of the disk tier
+#[derive(Serialize, Deserialize)]
+struct DiskEntry {
+    key: String,
+    value: Value,
+    // Unix millis, None never expires
+    expires_at: Option<u64>,
+}
+
+/**
+Cache of RPC results, keyed by cache class and params. A class is the method name (`getTokenSupply`) or a narrower case of it
+(`getTransaction:finalized`, `getAccountInfo:metadata`) when the same method returns data that changes at a different pace.
+
+Only classes with a `CacheRule` are cached, null results (unknown signature, missing account) never are. Entries live in a
+size-bounded in-memory LRU, and persisted classes also go to a directory on disk (RPC_CACHE_DIR) so they survive restarts.
+ */
+pub struct RpcCache {
+    capacity: usize,
+    rules: HashMap<String, CacheRule>,
+    memory: Mutex<Lru>,
+    disk_dir: Option<PathBuf>,
+    stats: Mutex<HashMap<String, ClassStats>>,
+}
+
+impl RpcCache {
+    /// Memory only, with the default rules: finalized transactions forever, token supply for minutes, account info for seconds,
+    /// metadata and IDL accounts for an hour, lookup tables for 10 minutes.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            rules: HashMap::new(),
+            memory: Mutex::new(Lru::default()),
+            disk_dir: None,
+            stats: Mutex::new(HashMap::new()),
+        }
+            .with_rule(FINALIZED_TRANSACTION, CacheRule::forever().persisted())
+            .with_rule(METADATA_ACCOUNT, CacheRule::ttl(Duration::from_secs(60 * 60)).persisted())
+            .with_rule(IDL_ACCOUNT, CacheRule::ttl(Duration::from_secs(60 * 60)).persisted())
+            .with_rule(LOOKUP_TABLE_ACCOUNT, CacheRule::ttl(Duration::from_secs(10 * 60)).persisted())
+            .with_rule("getTokenSupply", CacheRule::ttl(Duration::from_secs(5 * 60)))
+            .with_rule("getAccountInfo", CacheRule::ttl(Duration::from_secs(5)))
+    }
+
+    /// RPC_CACHE_CAPACITY entries in memory (10000 by default) and the disk tier in RPC_CACHE_DIR when it is set
+    pub fn from_env() -> Self {
+        let capacity = env::var("RPC_CACHE_CAPACITY").ok()
+            .and_then(|capacity| capacity.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_CAPACITY);
+        let cache = Self::new(capacity);
+        match env::var("RPC_CACHE_DIR") {
+            Ok(dir) => cache.with_disk_dir(&dir),
+            Err(_) => cache,
+        }
+    }
+
+    /// Adds or replaces the rule of a class
+    pub fn with_rule(mut self, class: &str, rule: CacheRule) -> Self {
+        self.rules.insert(class.to_string(), rule);
+        self
+    }
+
+    pub fn with_disk_dir(mut self, dir: &str) -> Self {
+        match std::fs::create_dir_all(dir) {
+            Ok(_) => {
+                println!("[[RPC CACHE]] Persisting to {}", dir);
+                self.disk_dir = Some(PathBuf::from(dir));
+            }
+            Err(e) => eprintln!("[[RPC CACHE]] Can't use {} as the disk tier, memory only: {}", dir, e),
+        }
+        self
+    }
+
+    pub async fn get(&self, class: &str, params: &Value) -> Option<Value> {
+        let rule = self.rules.get(class)?;
+        let key = cache_key(class, params);
+
+        let cached = self.memory.lock().unwrap().get(&key);
+        if let Some(value) = cached {
+            self.count(class, |stats| stats.hits += 1);
+            return Some(value);
+        }
+
+        if rule.persist {
+            if let Some((value, expires_at)) = self.read_disk(&key).await {
+                // Back in memory for the next lookups
+                self.memory.lock().unwrap().insert(key, value.clone(), expires_at, self.capacity);
+                self.count(class, |stats| {
+                    stats.hits += 1;
+                    stats.disk_hits += 1;
+                });
+                return Some(value);
+            }
+        }
+
+        self.count(class, |stats| stats.misses += 1);
+        None
+    }
+
+    pub async fn put(&self, class: &str, params: &Value, value: &Value) {
+        let Some(rule) = self.rules.get(class) else {
+            return;
+        };
+        // Unknown signature, or missing account inside its RpcWithContext: it may exist by the next lookup
+        if value.is_null() || value.get("value").is_some_and(Value::is_null) {
+            return;
+        }
+
+        let key = cache_key(class, params);
+        let expires_at = rule.ttl.map(|ttl| Instant::now() + ttl);
+        self.memory.lock().unwrap().insert(key.clone(), value.clone(), expires_at, self.capacity);
+        self.count(class, |stats| stats.stores += 1);
+
+        if rule.persist {
+            let expires_at = rule.ttl.map(|ttl| unix_millis(SystemTime::now() + ttl));
+            self.write_disk(DiskEntry { key, value: value.clone(), expires_at }).await;
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let memory = self.memory.lock().unwrap();
+        CacheStats {
+            entries: memory.entries.len(),
+            capacity: self.capacity,
+            evictions: memory.evictions,
+            classes: self.stats.lock().unwrap().clone(),
+        }
+    }
+
+    fn count<F: FnOnce(&mut ClassStats)>(&self, class: &str, update: F) {
+        update(self.stats.lock().unwrap().entry(class.to_string()).or_default());
+    }
+
+    async fn read_disk(&self, key: &str) -> Option<(Value, Option<Instant>)> {
+        let path = self.disk_dir.as_ref()?.join(file_name(key));
+        let content = tokio::fs::read(&path).await.ok()?;
+        let entry = match serde_json::from_slice::<DiskEntry>(&content) {
+            Ok(entry) => entry,
+            Err(e) => {
+                eprintln!("[[RPC CACHE]] Ignoring unreadable {:?}: {}", path, e);
+                return None;
+            }
+        };
+        // Hash collision, as unlikely as it gets
+        if entry.key != key {
+            return None;
+        }
+
+        match entry.expires_at {
+            None => Some((entry.value, None)),
+            Some(expires_at) => {
+                let now = unix_millis(SystemTime::now());
+                if expires_at <= now {
+                    let _ = tokio::fs::remove_file(&path).await;
+                    return None;
+                }
+                Some((entry.value, Some(Instant::now() + Duration::from_millis(expires_at - now))))
+            }
+        }
+    }
+
+    async fn write_disk(&self, entry: DiskEntry) {
+        let Some(ref dir) = self.disk_dir else {
+            return;
+        };
+        let path = dir.join(file_name(&entry.key));
+        match serde_json::to_vec(&entry) {
+            Ok(content) => {
+                if let Err(e) = tokio::fs::write(&path, content).await {
+                    eprintln!("[[RPC CACHE]] Failed to write {:?}: {}", path, e);
+                }
+            }
+            Err(e) => eprintln!("[[RPC CACHE]] Failed to serialize {}: {}", entry.key, e),
+        }
+    }
+}
+
+fn cache_key(class: &str, params: &Value) -> String {
+    format!("{}:{}", class, params)
+}
+
+/// Keys carry signatures and json, their hash makes a safe file name
+fn file_name(key: &str) -> String {
+    format!("{}.json", solana_sdk::hash::hash(key.as_bytes()))
+}
+
+fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_millis() as u64).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn supply(amount: u64) -> Value {
+        json!({"context": {"slot": 1}, "value": {"amount": amount.to_string(), "decimals": 6}})
+    }
+
+    #[tokio::test]
+    async fn evicts_the_least_recently_used() {
+        let cache = RpcCache::new(2).with_rule("getTokenSupply", CacheRule::forever());
+        cache.put("getTokenSupply", &json!(["a"]), &supply(1)).await;
+        cache.put("getTokenSupply", &json!(["b"]), &supply(2)).await;
+        // "a" is now the most recently used
+        assert_eq!(cache.get("getTokenSupply", &json!(["a"])).await, Some(supply(1)));
+        cache.put("getTokenSupply", &json!(["c"]), &supply(3)).await;
+
+        assert!(cache.get("getTokenSupply", &json!(["b"])).await.is_none());
+        assert_eq!(cache.get("getTokenSupply", &json!(["a"])).await, Some(supply(1)));
+        assert_eq!(cache.get("getTokenSupply", &json!(["c"])).await, Some(supply(3)));
+
+        let stats = cache.stats();
+        assert_eq!((stats.entries, stats.capacity, stats.evictions), (2, 2, 1));
+        let class = &stats.classes["getTokenSupply"];
+        assert_eq!((class.hits, class.misses, class.stores), (3, 1, 3));
+    }
+
+    #[tokio::test]
+    async fn expires_after_the_ttl() {
+        let cache = RpcCache::new(10).with_rule("getAccountInfo", CacheRule::ttl(Duration::from_millis(50)));
+        cache.put("getAccountInfo", &json!(["a"]), &supply(1)).await;
+        assert!(cache.get("getAccountInfo", &json!(["a"])).await.is_some());
+
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        assert!(cache.get("getAccountInfo", &json!(["a"])).await.is_none());
+        assert_eq!(cache.stats().entries, 0);
+    }
+
+    #[tokio::test]
+    async fn only_caches_known_classes_and_existing_results() {
+        let cache = RpcCache::new(10);
+        cache.put("getSlot", &json!([]), &json!(280_000_000)).await;
+        cache.put(FINALIZED_TRANSACTION, &json!(["unknown signature"]), &Value::Null).await;
+        cache.put("getAccountInfo", &json!(["closed"]), &json!({"context": {"slot": 1}, "value": null})).await;
+
+        assert!(cache.get("getSlot", &json!([])).await.is_none());
+        assert!(cache.get(FINALIZED_TRANSACTION, &json!(["unknown signature"])).await.is_none());
+        assert!(cache.get("getAccountInfo", &json!(["closed"])).await.is_none());
+        assert_eq!(cache.stats().entries, 0);
+        // Same params, another class
+        cache.put("getTokenSupply", &json!(["a"]), &supply(1)).await;
+        assert!(cache.get("getAccountInfo", &json!(["a"])).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn persisted_classes_survive_a_restart() {
+        let dir = env::temp_dir().join(format!("rpc-cache-{}", std::process::id()));
+        let dir = dir.to_str().unwrap();
+        let transaction = json!({"slot": 280_000_000, "meta": {"err": null}});
+
+        let cache = RpcCache::new(10).with_disk_dir(dir);
+        cache.put(FINALIZED_TRANSACTION, &json!(["sig"]), &transaction).await;
+        cache.put("getTokenSupply", &json!(["a"]), &supply(1)).await;
+
+        let restarted = RpcCache::new(10).with_disk_dir(dir);
+        assert_eq!(restarted.get(FINALIZED_TRANSACTION, &json!(["sig"])).await, Some(transaction));
+        // Memory only
+        assert!(restarted.get("getTokenSupply", &json!(["a"])).await.is_none());
+        assert_eq!(restarted.stats().classes[FINALIZED_TRANSACTION].disk_hits, 1);
+        // Back in memory
+        assert_eq!(restarted.stats().entries, 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,390 @@
+use std::collections::{HashSet, VecDeque};
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::http::base_http_client::BaseHttpClient;
+use crate::http::circuit_breaker::CircuitState;
+use crate::http::client_policy::{ClientPolicy, RetryPolicy};
+use crate::http::http_client_error::HttpClientError;
+use crate::models::solana::solana_rpc::JsonRpcError;
+
+pub const PUBLIC_MAINNET_RPC_URL: &str = "https://api.mainnet-beta.solana.com";
+/// Outcomes remembered per endpoint for its latency and error rate
+const HEALTH_WINDOW: usize = 100;
+/// JSON-RPC errors that mean "this node can't serve it right now", another node may (behind, missing block, internal error...)
+const FAIL_OVER_RPC_CODES: [i64; 6] = [-32005, -32004, -32014, -32016, -32603, -32007];
+/// The public RPC rejects or heavily throttles everything else (getProgramAccounts, getTokenLargestAccounts...)
+const PUBLIC_RPC_METHODS: [&str; 6] = [
+    "getTransaction",
+    "getAccountInfo",
+    "getSignatureStatuses",
+    "getSignaturesForAddress",
+    "getTokenSupply",
+    "getSlot",
+];
+
+struct Sample {
+    latency_ms: u64,
+    ok: bool,
+}
+
+#[derive(Default)]
+struct EndpointHealth {
+    samples: VecDeque<Sample>,
+    requests: u64,
+    errors: u64,
+}
+
+impl EndpointHealth {
+    fn record(&mut self, latency: Duration, ok: bool) {
+        self.requests += 1;
+        if !ok {
+            self.errors += 1;
+        }
+        self.samples.push_back(Sample { latency_ms: latency.as_millis() as u64, ok });
+        if self.samples.len() > HEALTH_WINDOW {
+            self.samples.pop_front();
+        }
+    }
+
+    fn error_rate(&self) -> f64 {
+        if self.samples.is_empty() {
+            return 0.0;
+        }
+        self.samples.iter().filter(|sample| !sample.ok).count() as f64 / self.samples.len() as f64
+    }
+
+    fn average_latency_ms(&self) -> f64 {
+        if self.samples.is_empty() {
+            return 0.0;
+        }
+        self.samples.iter().map(|sample| sample.latency_ms).sum::<u64>() as f64 / self.samples.len() as f64
+    }
+}
+
+/// Rolling health of one endpoint, as served by /api/metrics/rpc
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EndpointHealthSnapshot {
+    pub label: String,
+    pub weight: f64,
+    pub requests: u64,
+    pub errors: u64,
+    // Over the last HEALTH_WINDOW requests
+    pub error_rate: f64,
+    pub average_latency_ms: f64,
+    pub circuit: CircuitState,
+    // Lower is better
+    pub score: f64,
+}
+
+/// One HTTP RPC provider of the pool. The url is never logged, it usually carries the api key.
+pub struct RpcEndpoint {
+    label: String,
+    url: String,
+    weight: f64,
+    // None allows every method
+    allowed_methods: Option<HashSet<String>>,
+    client: BaseHttpClient,
+    health: Mutex<EndpointHealth>,
+}
+
+impl RpcEndpoint {
+    pub fn new(label: &str, url: &str, policy: ClientPolicy) -> Self {
+        let client = BaseHttpClient::new()
+            .with_policy(policy)
+            .add_default_header("accept", "application/json");
+
+        Self {
+            label: label.to_string(),
+            url: url.to_string(),
+            weight: 1.0,
+            allowed_methods: None,
+            client,
+            health: Mutex::new(EndpointHealth::default()),
+        }
+    }
+
+    /// How much traffic this endpoint should take compared to the others, ie. 3.0 for a paid provider next to a 1.0 public one
+    pub fn with_weight(mut self, weight: f64) -> Self {
+        self.weight = weight.max(0.01);
+        self
+    }
+
+    pub fn with_allowed_methods(mut self, methods: &[&str]) -> Self {
+        self.allowed_methods = Some(methods.iter().map(|method| method.to_string()).collect());
+        self
+    }
+
+    pub fn allows(&self, method: &str) -> bool {
+        self.allowed_methods.as_ref().is_none_or(|allowed| allowed.contains(method))
+    }
+
+    /// Average latency inflated by the error rate, divided by the weight. Endpoints with an open circuit go last
+    fn score(&self) -> f64 {
+        let health = self.health.lock().unwrap();
+        let score = (1.0 + health.average_latency_ms()) * (1.0 + 10.0 * health.error_rate()) / self.weight;
+        match self.client.circuit_state() {
+            CircuitState::Open => f64::MAX,
+            _ => score,
+        }
+    }
+
+    fn snapshot(&self) -> EndpointHealthSnapshot {
+        let score = self.score();
+        let health = self.health.lock().unwrap();
+        EndpointHealthSnapshot {
+            label: self.label.clone(),
+            weight: self.weight,
+            requests: health.requests,
+            errors: health.errors,
+            error_rate: health.error_rate(),
+            average_latency_ms: health.average_latency_ms(),
+            circuit: self.client.circuit_state(),
+            score,
+        }
+    }
+}
+
+/**
+Several HTTP RPC endpoints used as one: every call goes to the healthiest endpoint that allows its method and fails over to the
+next one when it errors, so a provider outage costs one failed attempt instead of a failed request.
+
+Health is the rolling latency and error rate of each endpoint over its last `HEALTH_WINDOW` requests, weighted by the endpoint
+weight. Each endpoint keeps its own rate limit and circuit breaker (see `ClientPolicy`). Cheap to clone.
+ */
+#[derive(Clone)]
+pub struct RpcPool {
+    endpoints: Arc<Vec<RpcEndpoint>>,
+}
+
+impl RpcPool {
+    pub fn new(endpoints: Vec<RpcEndpoint>) -> Self {
+        Self { endpoints: Arc::new(endpoints) }
+    }
+
+    /// * PRIVATE_SOLANA_QUICKNODE_HTTP, weight 3, every method
+    /// * SECONDARY_SOLANA_HTTP (optional second provider), weight 2, every method
+    /// * the public mainnet RPC, weight 1, light methods only
+    pub fn from_env() -> Self {
+        // Failing over is faster than retrying the same provider, one retry is enough
+        let failover_retry = RetryPolicy::new(1, Duration::from_millis(250), Duration::from_secs(2));
+        let mut endpoints = Vec::new();
+        if let Ok(url) = env::var("PRIVATE_SOLANA_QUICKNODE_HTTP") {
+            let policy = ClientPolicy::solana_rpc().with_retry(failover_retry.clone());
+            endpoints.push(RpcEndpoint::new("quicknode", &url, ClientPolicy { provider: "quicknode".to_string(), ..policy }).with_weight(3.0));
+        }
+        if let Ok(url) = env::var("SECONDARY_SOLANA_HTTP") {
+            let policy = ClientPolicy::solana_rpc().with_retry(failover_retry.clone());
+            endpoints.push(RpcEndpoint::new("secondary", &url, ClientPolicy { provider: "secondary".to_string(), ..policy }).with_weight(2.0));
+        }
+        endpoints.push(
+            RpcEndpoint::new("mainnet-beta", PUBLIC_MAINNET_RPC_URL, ClientPolicy::public_solana_rpc().with_retry(failover_retry))
+                .with_weight(1.0)
+                .with_allowed_methods(&PUBLIC_RPC_METHODS)
+        );
+
+        let labels: Vec<&str> = endpoints.iter().map(|endpoint| endpoint.label.as_str()).collect();
+        println!("[[RPC POOL]] Endpoints: {:?}", labels);
+        Self::new(endpoints)
+    }
+
+    /// Endpoints allowing `method`, healthiest first
+    fn ranked(&self, method: &str) -> Vec<&RpcEndpoint> {
+        let mut eligible: Vec<(f64, &RpcEndpoint)> = self.endpoints.iter()
+            .filter(|endpoint| endpoint.allows(method))
+            .map(|endpoint| (endpoint.score(), endpoint))
+            .collect();
+        eligible.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+        eligible.into_iter().map(|(_, endpoint)| endpoint).collect()
+    }
+
+    /// POSTs a JSON-RPC request (or batch) for `method` and returns the raw body of the first endpoint that served it.
+    pub async fn post<B: Serialize + ?Sized>(&self, method: &str, body: &B) -> Result<Value, HttpClientError> {
+        let ranked = self.ranked(method);
+        if ranked.is_empty() {
+            return Err(HttpClientError::NoEndpoint(method.to_string()));
+        }
+
+        let mut last_error = None;
+        for endpoint in ranked {
+            let started = Instant::now();
+            let outcome = endpoint.client.post_json::<B, Value>(&endpoint.url, body).await
+                .and_then(|response| match rpc_error_to_fail_over(&response) {
+                    Some(error) => Err(error),
+                    None => Ok(response),
+                });
+
+            match outcome {
+                Ok(response) => {
+                    endpoint.health.lock().unwrap().record(started.elapsed(), true);
+                    return Ok(response);
+                }
+                Err(e) if fails_over(&e) => {
+                    endpoint.health.lock().unwrap().record(started.elapsed(), false);
+                    eprintln!("[[RPC POOL]] {} failed on {}, failing over: {}", method, endpoint.label, e);
+                    last_error = Some(e);
+                }
+                Err(e) => {
+                    // The request itself is wrong, any other endpoint would say the same
+                    endpoint.health.lock().unwrap().record(started.elapsed(), true);
+                    return Err(e);
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| HttpClientError::NoEndpoint(method.to_string())))
+    }
+
+    pub fn health(&self) -> Vec<EndpointHealthSnapshot> {
+        self.endpoints.iter().map(RpcEndpoint::snapshot).collect()
+    }
+}
+
+fn fails_over(error: &HttpClientError) -> bool {
+    match error {
+        HttpClientError::BadRequest(_) | HttpClientError::Batch(_) | HttpClientError::NoEndpoint(_) => false,
+        HttpClientError::Rpc(error) => FAIL_OVER_RPC_CODES.contains(&error.code),
+        _ => true,
+    }
+}
+
+/// A single JSON-RPC response carrying one of `FAIL_OVER_RPC_CODES`. Errors inside a batch are per request and never fail over
+fn rpc_error_to_fail_over(response: &Value) -> Option<HttpClientError> {
+    let error = response.get("error")?;
+    let error = serde_json::from_value::<JsonRpcError>(error.clone()).ok()?;
+    if FAIL_OVER_RPC_CODES.contains(&error.code) {
+        Some(HttpClientError::Rpc(error))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use serde_json::json;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    fn policy(provider: &str) -> ClientPolicy {
+        ClientPolicy {
+            failure_threshold: 1,
+            ..ClientPolicy::new(provider)
+                .with_retry(RetryPolicy::new(0, Duration::ZERO, Duration::ZERO))
+                .with_timeout(Duration::from_secs(5))
+        }
+    }
+
+    /// Answers every request with `status` and `body`, returns the url and the number of requests served
+    async fn serve(status: u16, body: &'static str) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let served = Arc::new(AtomicUsize::new(0));
+        let counter = served.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = [0u8; 4096];
+                let _ = socket.read(&mut request).await;
+                counter.fetch_add(1, Ordering::SeqCst);
+                let response = format!(
+                    "HTTP/1.1 {} Test\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    status, body.len(), body
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        (url, served)
+    }
+
+    fn labels(ranked: Vec<&RpcEndpoint>) -> Vec<&str> {
+        ranked.into_iter().map(|endpoint| endpoint.label.as_str()).collect()
+    }
+
+    #[test]
+    fn ranks_by_latency_error_rate_and_weight() {
+        let pool = RpcPool::new(vec![
+            RpcEndpoint::new("slow", "http://127.0.0.1:1", policy("slow")),
+            RpcEndpoint::new("erroring", "http://127.0.0.1:1", policy("erroring")),
+            RpcEndpoint::new("public", "http://127.0.0.1:1", policy("public")).with_allowed_methods(&["getSlot"]),
+        ]);
+        let [slow, erroring, public] = &pool.endpoints[..] else {
+            unreachable!()
+        };
+        for _ in 0..10 {
+            slow.health.lock().unwrap().record(Duration::from_millis(300), true);
+            erroring.health.lock().unwrap().record(Duration::from_millis(50), true);
+            public.health.lock().unwrap().record(Duration::from_millis(100), true);
+        }
+        assert_eq!(labels(pool.ranked("getSlot")), vec!["erroring", "public", "slow"]);
+
+        // 50% errors: 51ms * 6
+        for _ in 0..10 {
+            erroring.health.lock().unwrap().record(Duration::from_millis(50), false);
+        }
+        assert_eq!(labels(pool.ranked("getSlot")), vec!["public", "slow", "erroring"]);
+        assert_eq!(labels(pool.ranked("getProgramAccounts")), vec!["slow", "erroring"]);
+    }
+
+    #[test]
+    fn weight_divides_the_score() {
+        let pool = RpcPool::new(vec![
+            RpcEndpoint::new("public", "http://127.0.0.1:1", policy("public")),
+            RpcEndpoint::new("paid", "http://127.0.0.1:1", policy("paid")).with_weight(3.0),
+        ]);
+        pool.endpoints[0].health.lock().unwrap().record(Duration::from_millis(100), true);
+        pool.endpoints[1].health.lock().unwrap().record(Duration::from_millis(200), true);
+        assert_eq!(labels(pool.ranked("getSlot")), vec!["paid", "public"]);
+    }
+
+    #[tokio::test]
+    async fn fails_over_on_server_errors_and_node_errors() {
+        let (down, _) = serve(503, "unavailable").await;
+        let (behind, behind_served) = serve(200, r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32005,"message":"Node is behind"}}"#).await;
+        let (healthy, healthy_served) = serve(200, r#"{"jsonrpc":"2.0","id":1,"result":250000000}"#).await;
+        let pool = RpcPool::new(vec![
+            RpcEndpoint::new("down", &down, policy("down")).with_weight(3.0),
+            RpcEndpoint::new("behind", &behind, policy("behind")).with_weight(2.0),
+            RpcEndpoint::new("healthy", &healthy, policy("healthy")),
+        ]);
+
+        let response = pool.post("getSlot", &json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"})).await.unwrap();
+        assert_eq!(response["result"], 250000000);
+        assert_eq!((behind_served.load(Ordering::SeqCst), healthy_served.load(Ordering::SeqCst)), (1, 1));
+
+        let health = pool.health();
+        assert_eq!(health.iter().map(|endpoint| endpoint.errors).collect::<Vec<u64>>(), vec![1, 1, 0]);
+        // One failure opens the circuit of the test policy: the endpoint goes last
+        assert_eq!(health[0].circuit, CircuitState::Open);
+        assert_eq!(labels(pool.ranked("getSlot"))[2], "down");
+    }
+
+    #[tokio::test]
+    async fn request_errors_do_not_fail_over() {
+        let (invalid, _) = serve(200, r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32602,"message":"Invalid params"}}"#).await;
+        let (other, other_served) = serve(200, r#"{"jsonrpc":"2.0","id":1,"result":null}"#).await;
+        let pool = RpcPool::new(vec![
+            RpcEndpoint::new("first", &invalid, policy("first")).with_weight(3.0),
+            RpcEndpoint::new("second", &other, policy("second")),
+        ]);
+
+        // Returned as is, the caller reads the error of the response
+        let response = pool.post("getAccountInfo", &json!({"jsonrpc": "2.0", "id": 1, "method": "getAccountInfo"})).await.unwrap();
+        assert_eq!(response["error"]["code"], -32602);
+        assert_eq!(other_served.load(Ordering::SeqCst), 0);
+        assert_eq!(pool.health()[0].errors, 0);
+    }
+
+    #[tokio::test]
+    async fn no_endpoint_for_the_method() {
+        let pool = RpcPool::new(vec![RpcEndpoint::new("public", "http://127.0.0.1:1", policy("public")).with_allowed_methods(&["getSlot"])]);
+        let error = pool.post("getProgramAccounts", &json!({})).await.unwrap_err();
+        assert!(matches!(error, HttpClientError::NoEndpoint(method) if method == "getProgramAccounts"));
+    }
+}
@@ -0,0 +1,283 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+use crate::http::http_client_error::HttpClientError;
+use crate::http::rpc_cache::{CacheStats, RpcCache};
+use crate::http::rpc_pool::{EndpointHealthSnapshot, RpcPool};
+use crate::models::solana::solana_rpc::{
+    JsonRpcRequest, JsonRpcResponse, RpcAccount, RpcKeyedAccount, RpcWithContext, SignatureInfo, TokenLargestAccount, UiTokenAmount,
+};
+use crate::models::solana::solana_signature_statuses::SignatureStatus;
+
+/// Requests per batch POST, most providers reject or throttle larger batches
+const DEFAULT_MAX_BATCH_SIZE: usize = 100;
+
+/**
+Typed client for the Solana HTTP JSON-RPC API, https://solana.com/docs/rpc/http
+
+Every method builds its request body, posts it through the `RpcPool` (healthiest endpoint first, failing over) and unwraps the
+JSON-RPC envelope: a JSON-RPC error object comes back as `HttpClientError::Rpc` and an unexpected body as
+`HttpClientError::Deserialize`. Cheap to clone.
+
+With an `RpcCache`, results of the cached classes (finalized transactions, supplies, account info...) are served from it first.
+
+Bulk lookups go through `batch_as`: one POST carries up to `max_batch_size` requests and every request gets its own result, so a
+signature the node does not know or a rejected request never fails the rest of the batch.
+ */
+#[derive(Clone)]
+pub struct SolanaRpcClient {
+    pool: RpcPool,
+    cache: Option<Arc<RpcCache>>,
+    next_id: Arc<AtomicU64>,
+    max_batch_size: usize,
+}
+
+impl SolanaRpcClient {
+    pub fn with_pool(pool: RpcPool) -> Self {
+        Self {
+            pool,
+            cache: None,
+            next_id: Arc::new(AtomicU64::new(1)),
+            max_batch_size: DEFAULT_MAX_BATCH_SIZE,
+        }
+    }
+
+    pub fn with_cache(mut self, cache: RpcCache) -> Self {
+        self.cache = Some(Arc::new(cache));
+        self
+    }
+
+    /// Every provider configured in the environment (see `RpcPool::from_env`), cached as `RpcCache::from_env` says
+    pub fn from_env() -> Self {
+        Self::with_pool(RpcPool::from_env()).with_cache(RpcCache::from_env())
+    }
+
+    pub fn health(&self) -> Vec<EndpointHealthSnapshot> {
+        self.pool.health()
+    }
+
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.cache.as_ref().map(|cache| cache.stats())
+    }
+
+    fn request(&self, method: &str, params: Value) -> JsonRpcRequest {
+        JsonRpcRequest::new(self.next_id.fetch_add(1, Ordering::SeqCst), method, params)
+    }
+
+    /// Sends any method and deserializes its `result`. A null result is only accepted when `T` is an Option.
+    pub async fn call<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<T, HttpClientError> {
+        self.call_as(method, method, params).await
+    }
+
+    /// `call` cached under `cache_class` instead of the method name, see `RpcCache`
+    pub async fn call_as<T: DeserializeOwned>(&self, cache_class: &str, method: &str, params: Value) -> Result<T, HttpClientError> {
+        if let Some(ref cache) = self.cache {
+            if let Some(result) = cache.get(cache_class, &params).await {
+                return serde_json::from_value::<T>(result).map_err(HttpClientError::from);
+            }
+        }
+
+        let response = self.pool.post(method, &self.request(method, params.clone())).await?;
+        let result: Value = unwrap_response(serde_json::from_value::<JsonRpcResponse<Value>>(response)?)?;
+        if let Some(ref cache) = self.cache {
+            cache.put(cache_class, &params, &result).await;
+        }
+        serde_json::from_value::<T>(result).map_err(HttpClientError::from)
+    }
+
+    /// Sends `method` once per params, in as few POSTs as `max_batch_size` allows. Results are in the same order as `params`.
+    /// A POST that fails as a whole (ie. the provider rejects batches) fails every request it carried, not the other POSTs.
+    /// Cached under `cache_class`, only the params missing from the cache are sent.
+    pub async fn batch_as<T: DeserializeOwned>(&self, cache_class: &str, method: &str, params: Vec<Value>) -> Vec<Result<T, HttpClientError>> {
+        self.batch_with(cache_class, method, params, true).await
+    }
+
+    /// `batch_as`, sending every params when `read_cache` is false. The results still replace the cached ones.
+    async fn batch_with<T: DeserializeOwned>(&self, cache_class: &str, method: &str, params: Vec<Value>, read_cache: bool) -> Vec<Result<T, HttpClientError>> {
+        let mut results: Vec<Option<Result<Value, HttpClientError>>> = Vec::with_capacity(params.len());
+        let mut missing = Vec::new();
+        for (index, params) in params.iter().enumerate() {
+            let cached = match self.cache {
+                Some(ref cache) if read_cache => cache.get(cache_class, params).await,
+                _ => None,
+            };
+            if cached.is_none() {
+                missing.push(index);
+            }
+            results.push(cached.map(Ok));
+        }
+
+        let missing_params: Vec<Value> = missing.iter().map(|index| params[*index].clone()).collect();
+        let fetched = self.send_batch(method, missing_params).await;
+        for (index, result) in missing.into_iter().zip(fetched) {
+            if let (Some(cache), Ok(result)) = (&self.cache, &result) {
+                cache.put(cache_class, &params[index], result).await;
+            }
+            results[index] = Some(result);
+        }
+
+        results.into_iter()
+            .map(|result| result
+                .unwrap_or_else(|| Err(HttpClientError::Batch(format!("no result for {} request", method))))
+                .and_then(|result| serde_json::from_value::<T>(result).map_err(HttpClientError::from)))
+            .collect()
+    }
+
+    async fn send_batch(&self, method: &str, params: Vec<Value>) -> Vec<Result<Value, HttpClientError>> {
+        let mut results = Vec::with_capacity(params.len());
+        let requests: Vec<JsonRpcRequest> = params.into_iter().map(|params| self.request(method, params)).collect();
+
+        for chunk in requests.chunks(self.max_batch_size) {
+            match self.pool.post(method, chunk).await {
+                Ok(Value::Array(responses)) => {
+                    // Responses can come back in any order, match them by id
+                    let mut by_id: HashMap<u64, JsonRpcResponse<Value>> = HashMap::new();
+                    for response in responses {
+                        match serde_json::from_value::<JsonRpcResponse<Value>>(response) {
+                            Ok(response) => {
+                                if let Some(id) = response.id {
+                                    by_id.insert(id, response);
+                                }
+                            }
+                            Err(e) => eprintln!("[[SOLANA RPC]] Skipping malformed {} batch response: {}", method, e),
+                        }
+                    }
+
+                    for request in chunk {
+                        results.push(match by_id.remove(&request.id) {
+                            Some(response) => unwrap_response::<Value>(response),
+                            None => Err(HttpClientError::Batch(format!("no response for {} request {}", method, request.id))),
+                        });
+                    }
+                }
+                // A single error object instead of an array: the batch itself was rejected
+                Ok(other) => {
+                    let message = match serde_json::from_value::<JsonRpcResponse<Value>>(other) {
+                        Ok(JsonRpcResponse { error: Some(error), .. }) => format!("batch rejected, {}: {}", error.code, error.message),
+                        _ => "batch rejected, unexpected response".to_string(),
+                    };
+                    results.extend(chunk.iter().map(|_| Err(HttpClientError::Batch(message.clone()))));
+                }
+                Err(e) => {
+                    let message = e.to_string();
+                    results.extend(chunk.iter().map(|_| Err(HttpClientError::Batch(message.clone()))));
+                }
+            }
+        }
+
+        results
+    }
+
+    /// None when the node does not know the signature (yet). `T` is the transaction model of the caller, matching `encoding`:
+    /// "json" for `EncodedTransactionWithMeta` and the instruction decoders, "jsonParsed" for the node's own parsing.
+    pub async fn get_transaction<T: DeserializeOwned>(&self, signature: &str, commitment: &str, encoding: &str) -> Result<Option<T>, HttpClientError> {
+        self.call_as(&transaction_class(commitment), "getTransaction", transaction_params(signature, commitment, encoding)).await
+    }
+
+    /// Batched `get_transaction`, same order as `signatures`
+    pub async fn get_transactions<T: DeserializeOwned>(&self, signatures: &[String], commitment: &str, encoding: &str) -> Vec<Result<Option<T>, HttpClientError>> {
+        let params = signatures.iter().map(|signature| transaction_params(signature, commitment, encoding)).collect();
+        self.batch_as(&transaction_class(commitment), "getTransaction", params).await
+    }
+
+    pub async fn get_token_supply(&self, mint_address: &str) -> Result<UiTokenAmount, HttpClientError> {
+        let supply: RpcWithContext<UiTokenAmount> = self.call("getTokenSupply", json!([mint_address])).await?;
+        Ok(supply.value)
+    }
+
+    /// The 20 largest token accounts of a mint
+    pub async fn get_token_largest_accounts(&self, mint_address: &str) -> Result<Vec<TokenLargestAccount>, HttpClientError> {
+        let accounts: RpcWithContext<Vec<TokenLargestAccount>> = self.call("getTokenLargestAccounts", json!([mint_address])).await?;
+        Ok(accounts.value)
+    }
+
+    /// `D` is the account data for the requested encoding, see `RpcAccount`. Filters are sent as is (ie. {"dataSize": 165})
+    pub async fn get_program_accounts<D: DeserializeOwned>(&self, program_id: &str, encoding: &str, filters: Vec<Value>) -> Result<Vec<RpcKeyedAccount<D>>, HttpClientError> {
+        self.call("getProgramAccounts", json!([
+            program_id,
+            {
+                "encoding": encoding,
+                "filters": filters
+            }
+        ])).await
+    }
+
+    /// Newest first, at most `limit` (1000 max) signatures older than `before` when set
+    pub async fn get_signatures_for_address(&self, address: &str, limit: usize, before: Option<&str>) -> Result<Vec<SignatureInfo>, HttpClientError> {
+        let mut config = json!({ "limit": limit });
+        if let Some(before) = before {
+            config["before"] = json!(before);
+        }
+        self.call("getSignaturesForAddress", json!([address, config])).await
+    }
+
+    /// Same order as `addresses`, None for the accounts that do not exist
+    pub async fn get_account_infos<D: DeserializeOwned>(&self, addresses: &[String], encoding: &str) -> Vec<Result<Option<RpcAccount<D>>, HttpClientError>> {
+        self.get_account_infos_as("getAccountInfo", addresses, encoding).await
+    }
+
+    /// `get_account_infos` cached under `cache_class`, for accounts that change slower than the usual account (ie. `METADATA_ACCOUNT`)
+    pub async fn get_account_infos_as<D: DeserializeOwned>(&self, cache_class: &str, addresses: &[String], encoding: &str) -> Vec<Result<Option<RpcAccount<D>>, HttpClientError>> {
+        self.account_infos(cache_class, addresses, encoding, true).await
+    }
+
+    /// `get_account_infos_as` ignoring what is cached, for accounts known to have changed since. Their cached entries are replaced.
+    pub async fn refresh_account_infos_as<D: DeserializeOwned>(&self, cache_class: &str, addresses: &[String], encoding: &str) -> Vec<Result<Option<RpcAccount<D>>, HttpClientError>> {
+        self.account_infos(cache_class, addresses, encoding, false).await
+    }
+
+    async fn account_infos<D: DeserializeOwned>(&self, cache_class: &str, addresses: &[String], encoding: &str, read_cache: bool) -> Vec<Result<Option<RpcAccount<D>>, HttpClientError>> {
+        let params = addresses.iter().map(|address| account_info_params(address, encoding)).collect();
+        self.batch_with::<RpcWithContext<Option<RpcAccount<D>>>>(cache_class, "getAccountInfo", params, read_cache).await
+            .into_iter()
+            .map(|account| account.map(|account| account.value))
+            .collect()
+    }
+
+    /// Same order as `signatures` (256 max), None for signatures the node does not know
+    pub async fn get_signature_statuses(&self, signatures: &[String], search_transaction_history: bool) -> Result<Vec<Option<SignatureStatus>>, HttpClientError> {
+        let statuses: RpcWithContext<Vec<Option<SignatureStatus>>> = self.call("getSignatureStatuses", json!([
+            signatures,
+            {
+                "searchTransactionHistory": search_transaction_history
+            }
+        ])).await?;
+        Ok(statuses.value)
+    }
+}
+
+fn unwrap_response<T: DeserializeOwned>(response: JsonRpcResponse<Value>) -> Result<T, HttpClientError> {
+    if let Some(error) = response.error {
+        return Err(HttpClientError::Rpc(error));
+    }
+    serde_json::from_value::<T>(response.result.unwrap_or(Value::Null)).map_err(HttpClientError::from)
+}
+
+/// Only `FINALIZED_TRANSACTION` is cached by default, a confirmed transaction can still be dropped
+fn transaction_class(commitment: &str) -> String {
+    format!("getTransaction:{}", commitment)
+}
+
+fn transaction_params(signature: &str, commitment: &str, encoding: &str) -> Value {
+    json!([
+        signature,
+        {
+            "encoding": encoding,
+            "commitment": commitment,
+            "maxSupportedTransactionVersion": 0
+        }
+    ])
+}
+
+fn account_info_params(address: &str, encoding: &str) -> Value {
+    json!([
+        address,
+        {
+            "encoding": encoding
+        }
+    ])
+}
@@ -15,17 +15,17 @@ extern crate timely;
 //deserializer for data received from solana_client
 use borsh::BorshDeserialize;
 use std::{env, thread};
-use std::collections::HashMap;
+use std::collections::HashSet;
 use std::error::Error;
+use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
 use std::task::Context;
 use std::time::Duration;
 
-use crossbeam_channel::{bounded, Sender};
+use crossbeam_channel::Sender;
 use dotenv::dotenv;
 use futures_util::{sink::SinkExt, stream::StreamExt};
-use reqwest::Client as Client;
 use serde::{Serialize, Deserialize};
 use serde_json::{json, Value};
 use timely::dataflow::InputHandle;
@@ -46,15 +46,30 @@ use crate::subscriber::websocket_subscriber::{WebSocketSubscriber, AuthMethod, S
 // use crate::util::event_filters::{
 //     EventFilters, FilterCriteria, FilterValue, ParameterizedFilter,
 // };
-use crate::subscriber::consume_stream::{consume_stream};
+use crate::subscriber::subscription_registry::SubscriptionEvent;
+use crate::subscriber::fan_in_subscriber::FanInSubscriber;
+use crate::subscriber::subscription_manifest::{ManifestWatcher, SubscriptionManifest};
+use crate::subscriber::capture::{FrameRecorder, ReplaySource, ReplaySpeed, RpcCapture};
+use crate::subscriber::event_bus::{EventBus, OverflowPolicy};
 use crate::trackers::raydium::new_token_tracker;
 use crate::trackers::raydium::new_token_tracker::NewTokenTracker;
+use crate::decoder::raydium_amm_v4_state::RAYDIUM_AMM_V4_PROGRAM_ID;
+use crate::trackers::solana::commitment_tracker::{CommitmentLevel, CommitmentTracker, LifecycleStage};
+use crate::trackers::solana::token_registry::TokenRegistry;
+use crate::trackers::solana::idl_registry::IdlRegistry;
+use crate::trackers::solana::lookup_table_registry::LookupTableRegistry;
+use crate::trackers::solana::fee_market::FeeMarket;
+use crate::trackers::solana::failure_tracker::FailureTracker;
+use crate::http::solana_rpc_client::SolanaRpcClient;
+use crate::models::solana::solana_encoded_transaction::EncodedTransactionWithMeta;
+use crate::models::solana::net_balance_change::NetBalanceChange;
+use crate::decoder::instruction_decoder::{DecodedInstruction, INSTRUCTION_DECODERS};
+use crate::decoder::spl_token_instructions::{ui_amount, TokenInstruction};
+use crate::decoder::swap_extractor::SWAP_EXTRACTOR;
+use crate::decoder::log_parser::parse_logs;
 
 use actix::prelude::*;
-use mpl_token_metadata::accounts::Metadata;
-use mpl_token_metadata::ID;
 
-use solana_client::rpc_client::RpcClient;
 use solana_sdk::pubkey::Pubkey;
 use tokio::time::interval;
 use crate::models::solana::solana_account_notification::SolanaAccountNotification;
@@ -72,6 +87,8 @@ mod subscriber;
 mod decoder;
 mod scraper;
 
+/// How long confirmed whale signatures are collected before being fetched in one batch
+const TRANSACTION_BATCH_WINDOW: Duration = Duration::from_millis(250);
 
 /** Welcome to the Solana Sniper */
 #[tokio::main]
@@ -93,222 +110,216 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         // ws_server.run().await
     });
 
+    // ------------ CAPTURE / REPLAY ------------
+    // * RECORD_CAPTURE=<file> appends every raw frame and every getTransaction/getAccountInfo response to the file
+    // * REPLAY_CAPTURE=<file> plays a capture back instead of connecting, no network needed. REPLAY_SPEED=original|max|<factor>
+    let replay_source = match env::var("REPLAY_CAPTURE") {
+        Ok(path) => {
+            let speed = env::var("REPLAY_SPEED").ok()
+                .and_then(|speed| ReplaySpeed::parse(&speed))
+                .unwrap_or(ReplaySpeed::Original);
+            Some(ReplaySource::load(PathBuf::from(path), speed)?)
+        }
+        Err(_) => None,
+    };
+    let recorder = match env::var("RECORD_CAPTURE") {
+        Ok(path) if replay_source.is_none() => Some(FrameRecorder::create(PathBuf::from(path))?),
+        _ => None,
+    };
+    let rpc_capture = match replay_source {
+        Some(ref source) => RpcCapture::Replay(source.rpc_responses()),
+        None => RpcCapture::Live(recorder.clone()),
+    };
+
     // ------------ WEBSOCKET CONNECTION ------------
     let solana_public_ws_url    = String::from("wss://api.mainnet-beta.solana.com");
     let solana_private_ws_url   = env::var("PRIVATE_SOLANA_QUICKNODE_WS").expect("PRIVATE_SOLANA_QUICKNODE_WS must be set");
-    let solana_private_http_url = env::var("PRIVATE_SOLANA_QUICKNODE_HTTP").expect("PRIVATE_SOLANA_QUICKNODE_HTTP must be set");
-
-    let (mut solana_ws_stream, _) = connect_async(solana_public_ws_url.clone()).await?;
-    println!("Connected to Solana WebSocket");
 
     //https://solana.com/docs/rpc/websocket/accountsubscribe
     // * api key is provided in the path
-    let solana_subscriber = WebSocketSubscriber::<SolanaSubscriptionBuilder>::new(
+    // * each subscriber owns its connection: it reconnects and replays every subscription added below
+    // * the fan in keeps the same subscriptions open on both RPCs and forwards whichever notification arrives first
+    let mut solana_private_subscriber = WebSocketSubscriber::<SolanaSubscriptionBuilder>::new(
         solana_private_ws_url.to_string(),
-        // solana_public_ws_url.to_string(), //TODO here to switch between private and public RPC URLS
         None,
         AuthMethod::None,
         SolanaSubscriptionBuilder,
-    );
-
-    // ------------ PROGRAM SUBSCRIPTION ------------
-    let subscription_program_ids = vec![
-        "EKpQGSJtjMFqKZ9KQanSqYXRcF8fBopzLHYxdM65zcjm", // WIF
-        "DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263", // BONK
-        "HJ39rRZ6ys22KdB3USxDgNsL7RKiQmsC3yL8AS3Suuku", // UPDOG
-    ];
-
-    let mut sub_program_params: Vec<(&str, Vec<String>)> = Vec::new();
-    for id in subscription_program_ids {
-        let param = (
-            "programSubscribe",
-            vec![
-                id.to_string(),
-                json!({
-                    "encoding": "jsonParsed",
-                    "commitment": "finalized"
-                })
-                    .to_string(),
-            ],
-        );
-        sub_program_params.push(param);
+    ).with_label("quicknode");
+    let mut solana_public_subscriber = WebSocketSubscriber::<SolanaSubscriptionBuilder>::new(
+        solana_public_ws_url.to_string(),
+        None,
+        AuthMethod::None,
+        SolanaSubscriptionBuilder,
+    ).with_label("mainnet-beta");
+    if let Some(ref recorder) = recorder {
+        solana_private_subscriber = solana_private_subscriber.with_recorder(recorder.clone());
+        solana_public_subscriber = solana_public_subscriber.with_recorder(recorder.clone());
     }
-
-    // println!("Subscribing to PROGRAM NOTIFICATIONS on  {} with provided messages :: {:?}", solana_private_ws_url.clone(), sub_accounts_message.clone());
-    // solana_ws_stream.send(Message::Text(sub_accounts_message)).await?;
-
-    // ------------ ACCOUNT SUBSCRIPTION ------------
-    let account_program_ids: Vec<String> = vec![
-        ("FJRZ5sTp27n6GhUVqgVkY4JGUJPjhRPnWtH4du5UhKbw".to_string()), //whale de miglio
-        ("5Q544fKrFoe6tsEbD7S8EmxGTJYAKtTVhAW5Q5pge4j1".to_string()), //raydium
-        ("11111111111111111111111111111111".to_string()),             //system program
-        ("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA".to_string()),  //token program
-    ];
-
-    let sub_accounts_message =
-        json!({
-              "jsonrpc": "2.0",
-              "id": 1,
-              "method": "accountSubscribe",
-              "params": [
-                "FJRZ5sTp27n6GhUVqgVkY4JGUJPjhRPnWtH4du5UhKbw",
-                {
-                  "encoding": "jsonParsed",
-                  "commitment": "finalized"
-                }
-              ]
-            }).to_string();
-
-    // println!("Subscribing to ACCOUNT NOTIFICATOINS on  {} with provided messages :: {:?}", solana_private_ws_url.clone(), sub_accounts_message.clone());
-    // solana_ws_stream.send(Message::Text(sub_accounts_message)).await?;
-
-    // ------------ LOG SUBSCRIPTION ------------
-    let raydium_public_key = "srmqPvymJeFKQ4zGQed1GFppgkRHL9kaELCbyksJtPX";
-    let openbook_public_key = "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8";
-
-    // --- WHALE KEYS --- https://birdeye.so/leaderboard/7D?chain=solana
-    let miglio_whale = "FJRZ5sTp27n6GhUVqgVkY4JGUJPjhRPnWtH4du5UhKbw";
-    let a_whale = "MfDuWeqSHEqTFVYZ7LoexgAK9dxk7cy4DFJWjWMGVWa";
-    let a_bad_whale = "bobCPc5nqVoX7r8gKzCMPLrKjFidjnSCrAdcYGCH2Ye";
-    let a_magaiba_top_trader = "71WDyyCsZwyEYDV91Qrb212rdg6woCHYQhFnmZUBxiJ6";
-    let a_solana_top_trader = "MfDuWeqSHEqTFVYZ7LoexgAK9dxk7cy4DFJWjWMGVWa";
-    let a_solana_top_trader_2 = "DzYV9AFEbe9eGc8GRaNvsGjnt7coYiLDY7omCS1jykJU";
-    let a_solana_top_trader_3 = "JDTCk7yjN8X3X93chPtPyfgqU4MzazCzGmbyftGzp2JX";
-
-    //TODO THIS IS USED BELOW TO BUILD TRANSACTION SUMMARIES
-    let currently_tracked_whale = a_solana_top_trader_3.clone();
-
-    let log_program_ids = vec![
-        currently_tracked_whale.clone(),
-    ];
-
-    let sub_logs_messages =
-        json!({
-                        "jsonrpc": "2.0",
-                        "id": 1,
-                        "method": "logsSubscribe",
-                        "params": [
-                            {
-                                "mentions": log_program_ids
-                            },
-                            {
-                                "encoding": "jsonParsed",
-                                "commitment": "finalized"
-                            }
-                        ]
-                    }).to_string();
-
-    println!("Subscribing to LOG NOTIFICATIONS on {} with provided messages :: {:?}", solana_private_ws_url.clone(), sub_logs_messages.clone());
-    solana_ws_stream.send(Message::Text(sub_logs_messages)).await?;
-
-    //TODO - we should use this pattern when we implement multiple whale tracking
-    let sub_log_params = vec![
-        ("logsSubscribe", vec!["5Q544fKrFoe6tsEbD7S8EmxGTJYAKtTVhAW5Q5pge4j1".to_string(), "finalized".to_string()]),
-        // Add more subscriptions .... the solana_subscriber knows how to handle them
-    ];
-    // solana_subscriber.subscribe(&mut solana_ws_stream, &sub_log_params).await?;
+    let solana_subscriber = FanInSubscriber::new(vec![solana_private_subscriber, solana_public_subscriber]);
+    let solana_fan_in_stats = solana_subscriber.stats();
+
+    // ------------ SUBSCRIPTIONS ------------
+    // Declared in the manifest (whales, programs, accounts...) and hot reloaded on change, no need to recompile to switch whales
+    let manifest_path = PathBuf::from(env::var("SUBSCRIPTION_MANIFEST").unwrap_or_else(|_| "config/subscriptions.json".to_string()));
+    // handles survive reconnects, every event is tagged with the handle of the subscription that produced it
+    let manifest_subscriptions = match replay_source {
+        // replayed events carry the handles of the recording, map them back to the manifest specs they were opened for
+        Some(ref source) => {
+            let manifest = SubscriptionManifest::load(&manifest_path)?;
+            Arc::new(Mutex::new(manifest.specs_for(&source.subscriptions())))
+        }
+        None => {
+            let mut manifest_watcher = ManifestWatcher::new(manifest_path, solana_subscriber.control());
+            manifest_watcher.reload()?;
+            let manifest_subscriptions = manifest_watcher.active();
+            tokio::spawn(manifest_watcher.watch());
+            manifest_subscriptions
+        }
+    };
 
     // ------------ CHANNEL CREATION ------------
-    let (solana_event_sender, mut solana_event_receiver) =
-        bounded::<SolanaEventTypes>(5000);
-
-    let solana_ws_message_processing_task = tokio::spawn(async move {
-        consume_stream::<SolanaEventTypes>(&mut solana_ws_stream, solana_event_sender).await;
-    });
-
-    let solana_private_http_url = env::var("PRIVATE_SOLANA_QUICKNODE_HTTP")
-        .expect("PRIVATE_SOLANA_QUICKNODE_HTTP must be set");
-    let client = Client::new();
+    // * whale logs wait for room, we never want to lose a whale transaction
+    // * account/program/slot updates only keep the latest state per key when the processing falls behind
+    let solana_event_bus = EventBus::new(5000)
+        .with_policy("LogNotification", OverflowPolicy::Block)
+        .with_policy("SignatureNotification", OverflowPolicy::Block)
+        .with_policy("StreamGap", OverflowPolicy::Block)
+        .with_policy("AccountNotification", OverflowPolicy::CoalesceByKey)
+        .with_policy("ProgramNotification", OverflowPolicy::CoalesceByKey)
+        .with_policy("SlotNotification", OverflowPolicy::CoalesceByKey)
+        .with_policy("RootNotification", OverflowPolicy::CoalesceByKey)
+        .with_policy("BlockNotification", OverflowPolicy::DropOldest);
+    let solana_event_metrics = solana_event_bus.metrics();
+    let http_event_metrics = solana_event_metrics.clone();
+    let (solana_event_sender, mut solana_event_receiver) = solana_event_bus.split::<SolanaEventTypes>();
+
+    let solana_ws_message_processing_task = match replay_source {
+        Some(source) => tokio::spawn(async move {
+            source.run::<SolanaEventTypes, SolanaSubscriptionBuilder>(solana_event_sender).await;
+        }),
+        None => tokio::spawn(async move {
+            solana_subscriber.run::<SolanaEventTypes>(solana_event_sender).await;
+        }),
+    };
+
+    // Shared by the event processing and the http server
+    let solana_rpc = SolanaRpcClient::from_env();
+
+    // ------------ COMMITMENT LIFECYCLE ------------
+    // whales are subscribed at processed to hear about them ~13s earlier, the tracker follows each of their signatures up to finalized
+    let (commitment_tracker, mut whale_lifecycle_receiver) =
+        CommitmentTracker::<String>::new(solana_rpc.clone(), rpc_capture.clone());
+    let commitment_tracker_handle = commitment_tracker.handle();
+    let commitment_tracker_task = tokio::spawn(commitment_tracker.run());
+    let http_solana_rpc = solana_rpc.clone();
+
+    // v0 transactions the node did not resolve the lookup tables of, for every decoder
+    let lookup_tables = LookupTableRegistry::new(solana_rpc.clone(), rpc_capture.clone());
+    let http_lookup_tables = lookup_tables.clone();
+
+    // ------------ RAYDIUM LAUNCHES ------------
+    // logs of the Raydium AMM v4 subscription go to the new token tracker, which fetches and decodes the pool creations
+    let (raydium_log_sender, raydium_log_receiver) = tokio::sync::mpsc::unbounded_channel();
+    let new_token_tracker = NewTokenTracker::new(solana_rpc.clone(), rpc_capture.clone(), lookup_tables.clone());
+    let new_token_tracker_task = tokio::spawn(new_token_tracker.run(raydium_log_receiver));
+
+    // Mint -> name, symbol, decimals... for every summary and endpoint, persisted so each mint is only fetched once
+    let token_registry = TokenRegistry::from_env(solana_rpc.clone(), rpc_capture.clone());
+    let http_token_registry = token_registry.clone();
+    // Local Anchor IDLs register their instruction decoders right away, the rest are fetched on-chain by /decode
+    let idl_registry = IdlRegistry::from_env(solana_rpc.clone(), rpc_capture.clone());
+    let log_idl_registry = idl_registry.clone();
+    // Failed transactions of every log subscription with their decoded cause, for /failures
+    let failure_tracker = FailureTracker::new(idl_registry.clone());
+    let http_failure_tracker = failure_tracker.clone();
+    // Priority fees of every transaction observed over the last FEE_MARKET_SLOTS slots, per program and per writable account
+    let fee_market = FeeMarket::from_env();
+    let block_fee_market = fee_market.clone();
+    let leaderboard_fee_market = fee_market.clone();
+    let http_fee_market = fee_market.clone();
+    let leaderboard_solana_rpc = solana_rpc.clone();
 
     let mut interval = interval(Duration::from_secs(30)); //TODO implement heartbeat to check bot healthz
+    let leaderboard_task = tokio::spawn(async move {
+        loop {
+            interval.tick().await;
+            for (endpoint, stats) in solana_fan_in_stats.leaderboard() {
+                println!("[[LEADERBOARD]] {} first: {} duplicates: {} avg lag: {:.1}ms",
+                         endpoint, stats.delivered_first, stats.duplicates, stats.average_lag_ms());
+            }
+            for (event_type, metrics) in solana_event_metrics.snapshot() {
+                println!("[[EVENT BUS]] {} enqueued: {} dropped: {} coalesced: {} queued: {} lag: {}ms (max {}ms)",
+                         event_type, metrics.enqueued, metrics.dropped, metrics.coalesced, metrics.queued,
+                         metrics.last_lag_ms, metrics.max_lag_ms);
+            }
+            if let Some(cache) = leaderboard_solana_rpc.cache_stats() {
+                for (class, stats) in cache.classes {
+                    println!("[[RPC CACHE]] {} hits: {} (disk {}) misses: {} stores: {}",
+                             class, stats.hits, stats.disk_hits, stats.misses, stats.stores);
+                }
+                println!("[[RPC CACHE]] {}/{} entries, {} evictions", cache.entries, cache.capacity, cache.evictions);
+            }
+            for endpoint in leaderboard_solana_rpc.health() {
+                println!("[[RPC POOL]] {} requests: {} errors: {} error rate: {:.2} avg latency: {:.1}ms circuit: {:?}",
+                         endpoint.label, endpoint.requests, endpoint.errors, endpoint.error_rate,
+                         endpoint.average_latency_ms, endpoint.circuit);
+            }
+            if let Some(estimate) = leaderboard_fee_market.estimate() {
+                println!("[[FEE MARKET]] {} transactions over slots {}-{}, micro-lamports/CU p50: {} p75: {} p90: {} p99: {}",
+                         estimate.samples, estimate.first_slot, estimate.last_slot, estimate.p50, estimate.p75, estimate.p90, estimate.p99);
+            }
+        }
+    });
     // ------------ DESERIALIZED SOLANA EVENT PROCESSING ------------
     let solana_task = tokio::spawn(async move {
-        while let Ok(event) = solana_event_receiver.recv() {
+        while let Some(SubscriptionEvent { subscription, endpoint, event }) = solana_event_receiver.recv().await {
+            let spec = subscription.and_then(|handle| manifest_subscriptions.lock().unwrap().get(&handle).cloned());
             match event {
                 SolanaEventTypes::LogNotification(ref log) => {
                     // println!("[[SOLANA TASK]] Processing log with signature {:?}", event);
+                    // Failed attempts are events too: a failing swap or snipe says as much as a landed one
+                    if let Some(failed) = failure_tracker.record(log.params.result.context.slot, &log.params.result.value) {
+                        println!("[[FAILED]] {}", failed);
+                    }
+                    let from_raydium = spec.as_ref().and_then(|spec| spec.target.as_deref()) == Some(RAYDIUM_AMM_V4_PROGRAM_ID);
+                    if from_raydium {
+                        let _ = raydium_log_sender.send(log.clone());
+                        continue;
+                    }
+                    // Only wallets flagged with track_transactions in the manifest get their transactions summarized
+                    let tracked_whale = match spec {
+                        Some(ref spec) if spec.track_transactions => spec.target.clone(),
+                        _ => None,
+                    };
+                    let Some(tracked_whale) = tracked_whale else {
+                        continue;
+                    };
+
+                    // The CPI tree from the logs alone, the transaction itself is only fetched once confirmed. Decoding the events
+                    // may fetch IDLs on-chain, off the event loop
+                    let log_idl_registry = log_idl_registry.clone();
+                    let value = log.params.result.value.clone();
+                    let whale = tracked_whale.clone();
+                    tokio::spawn(async move {
+                        let mut program_logs = parse_logs(&value.logs);
+                        log_idl_registry.decode_events(&mut program_logs).await;
+                        println!("[[LOGS]] {} {} ({} CU)\n{}", whale, value.signature, program_logs.compute_consumed(), program_logs);
+                        if let Some(failed) = program_logs.failed_invocation() {
+                            println!("[[LOGS]] {} failed in {} (instruction {}): {}",
+                                     value.signature, failed.program_id, failed.outer_index, failed.error.as_deref().unwrap_or("unknown error"));
+                        }
+                    });
+
                     if log.params.result.value.err.is_none() {
                         let signature = log.params.result.value.signature.clone();
                         // println!("[[SOLANA TASK]] SUCCESSFUL TRANSACTION Signature: {}", signature);
 
-                        // ------------ GET TRANSACTION WITH RECEIVED SIGNATURE ------------
-                        let transaction_response = client
-                            .post(&solana_private_http_url)
-                            .json(&json!({
-                                "jsonrpc": "2.0",
-                                "id": 1,
-                                "method": "getTransaction",
-                                "params": [
-                                    signature,
-                                    {
-                                        "encoding": "jsonParsed",
-                                        "maxSupportedTransactionVersion": 0
-                                    }
-                                ]
-                            }))
-                            .send()
-                            .await;
-
-                        // ------------ PROCESS TRANSACTION INSTRUCTION AND PRE/POST TOKEN BALANCES ------------
-                        // For now, we are only supporting transactions of type TRANSFER CHECKED. We can easily implement a parser for any type following this exmaple.
-                        if let Ok(response) = transaction_response {
-                            if response.status().is_success() {
-                                match response.text().await {
-                                    Ok(text) => {
-                                        let value: serde_json::Value = serde_json::from_str(&text).expect("Failed to deserialize into value !!!!");
-                                        // println!("[[TRANSACTION DATA]] {:#?}", value);
-                                        match serde_json::from_value::<RpcResponse>(value) {
-                                            Ok(tx) => {
-                                                if let Some(result) = tx.result {
-                                                    let mut transfer_checked_instructions: Vec<TransferCheckedInfo> = Vec::new();
-                                                    let pre: Vec<TokenBalance> = result.clone().meta.pre_token_balances;
-                                                    let post: Vec<TokenBalance> = result.clone().meta.post_token_balances;
-
-                                                    let inner_instructions = result.clone().meta.inner_instructions;
-                                                    for inner_instruction in inner_instructions {
-                                                        for instruction in inner_instruction.instructions {
-                                                            if let Ok(transfer_checked) = serde_json::from_value::<TransferChecked>(instruction) {
-                                                                if transfer_checked.parsed.as_ref().map_or(false, |p| p.instruction_type == "transferChecked") {
-                                                                    // Found a TransferChecked instruction
-                                                                    let transfer_info = transfer_checked.parsed.unwrap().info;
-                                                                    // println!("[[TRANSFER CHECKED INFO]] {:?}", transfer_info);
-                                                                    transfer_checked_instructions.push(transfer_info);
-                                                                }
-                                                            }
-                                                        }
-                                                    }
-
-                                                     match prepare_transaction_summary(
-                                                         signature.clone(),
-                                                         currently_tracked_whale.clone().to_string(),
-                                                         pre,
-                                                         post,
-                                                         transfer_checked_instructions).await {
-
-                                                         Ok(_) => {
-                                                             println!("Successfully processed transaction {:?}", signature.clone());
-                                                             continue
-                                                         }
-                                                         Err(e) => {
-                                                             println!("Failed to process transaction {:?} : {:?}", signature.clone(), e);
-                                                             continue
-                                                         }
-                                                     }
-                                                } else {
-                                                    continue;
-                                                }
-                                            }
-                                            Err(e) => eprintln!("Error deserializing transaction {:?}", e),
-                                        }
-                                    },
-                                    Err(e) => eprintln!("Failed to read response text: {:?}", e),
-                                }
-                            } else {
-                                eprintln!("Getting transaction returned failure: {:?}", response);
-                            }
-                        } else {
-                            eprintln!("Could not get transaction for signature: {:?}", signature);
-                        }
+                        let seen_at = spec.as_ref()
+                            .and_then(|spec| spec.commitment.as_deref())
+                            .and_then(CommitmentLevel::parse)
+                            .unwrap_or(CommitmentLevel::Finalized);
+                        // The summary is built once the tracker sees it confirmed, see the lifecycle task
+                        commitment_tracker_handle.track(&signature, seen_at, Some(log.params.result.context.slot), tracked_whale);
                     }
                 }
 
@@ -316,6 +327,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     let signature = notification;
                     println!("[[SOLANA TASK]] GOT ACCOUNT NOTIFICATION {:?}", signature)
                 }
+                SolanaEventTypes::BlockNotification(notification) => {
+                    let value = notification.params.result.value;
+                    if let Some(block) = value.block {
+                        let observed = block.encoded_transactions(value.slot).iter()
+                            .filter_map(|transaction| block_fee_market.observe(transaction))
+                            .count();
+                        println!("[[FEE MARKET]] Block {}: {} of {} transactions observed", value.slot, observed, block.transactions.len());
+                    }
+                }
+                SolanaEventTypes::StreamGap(gap) => {
+                    //TODO backfill the tracked whale with getSignaturesForAddress over the gap
+                    println!("[[SOLANA TASK]] Notifications may have been missed: {:?}", gap)
+                }
                 _ => {
                     println!("Stand by")
                 }
@@ -323,12 +347,71 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     });
 
-    let _ = server::http_server::run_server().await;
+    // ------------ WHALE TRANSACTION LIFECYCLE ------------
+    // Summaries are built at confirmed (getTransaction does not serve processed transactions) and retracted if the transaction is dropped
+    // Confirmed signatures are collected for TRANSACTION_BATCH_WINDOW and fetched with a single batched getTransaction
+    let whale_lifecycle_task = tokio::spawn(async move {
+        let mut summarized: HashSet<String> = HashSet::new();
+        // (signature, tracked whale) waiting for the next batch
+        let mut pending: Vec<(String, String)> = Vec::new();
+        let mut batch_window = tokio::time::interval(TRANSACTION_BATCH_WINDOW);
+        loop {
+            tokio::select! {
+                lifecycle = whale_lifecycle_receiver.recv() => {
+                    let Some(lifecycle) = lifecycle else {
+                        break;
+                    };
+                    let signature = lifecycle.signature.clone();
+                    let tracked_whale = lifecycle.context.clone();
+                    match lifecycle.stage {
+                        LifecycleStage::SeenProcessed => {
+                            println!("[[LIFECYCLE]] {} sent {} (processed at slot {:?})", tracked_whale, signature, lifecycle.slot);
+                        }
+                        LifecycleStage::Confirmed | LifecycleStage::Finalized => {
+                            // Whales subscribed at finalized skip confirmed, their summary is built here
+                            if summarized.insert(signature.clone()) {
+                                pending.push((signature.clone(), tracked_whale));
+                            }
+                            if lifecycle.stage == LifecycleStage::Finalized {
+                                println!("[[LIFECYCLE]] {} finalized at slot {:?}, its summary stands", signature, lifecycle.slot);
+                                summarized.remove(&signature);
+                            }
+                        }
+                        LifecycleStage::Dropped => {
+                            let waiting = pending.len();
+                            pending.retain(|(pending_signature, _)| *pending_signature != signature);
+                            if pending.len() < waiting {
+                                summarized.remove(&signature);
+                                println!("[[LIFECYCLE]] {} was dropped before its summary was built", signature);
+                            } else if summarized.remove(&signature) {
+                                println!("[[LIFECYCLE]] {} was dropped or forked out, RETRACT its summary", signature);
+                            } else {
+                                println!("[[LIFECYCLE]] {} was dropped before being confirmed", signature);
+                            }
+                        }
+                    }
+                }
+                _ = batch_window.tick() => {
+                    if !pending.is_empty() {
+                        summarize_whale_transactions(&solana_rpc, std::mem::take(&mut pending), &rpc_capture, &token_registry, &lookup_tables, &fee_market).await;
+                    }
+                }
+            }
+        }
+        if !pending.is_empty() {
+            summarize_whale_transactions(&solana_rpc, pending, &rpc_capture, &token_registry, &lookup_tables, &fee_market).await;
+        }
+    });
+
+    let _ = server::http_server::run_server(http_event_metrics, http_solana_rpc, http_token_registry, idl_registry, http_lookup_tables, http_fee_market, http_failure_tracker).await;
 
     match tokio::try_join!(
         ws_server_task,
         solana_ws_message_processing_task,
-        solana_task
+        solana_task,
+        commitment_tracker_task,
+        new_token_tracker_task,
+        whale_lifecycle_task
     ) {
         Ok(_) => println!("All tasks completed successfully"),
         Err(e) => eprintln!("A task exited with an error: {:?}", e),
@@ -337,82 +420,143 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-// https://developers.metaplex.com/token-metadata
+/// Fetches confirmed whale transactions, as (signature, tracked whale), in one batch and summarizes each of them.
+async fn summarize_whale_transactions(rpc: &SolanaRpcClient, confirmed: Vec<(String, String)>, rpc_capture: &RpcCapture, token_registry: &TokenRegistry,
+                                      lookup_tables: &LookupTableRegistry, fee_market: &FeeMarket) {
+    // ------------ GET TRANSACTIONS WITH RECEIVED SIGNATURES ------------
+    let signatures: Vec<String> = confirmed.iter().map(|(signature, _)| signature.clone()).collect();
+    let transactions = rpc_capture.get_transactions(rpc, &signatures).await;
+
+    for ((signature, tracked_whale), transaction) in confirmed.into_iter().zip(transactions) {
+        summarize_whale_transaction(signature, tracked_whale, transaction, token_registry, lookup_tables, fee_market).await;
+    }
+}
+
+/// Prints the summary of the TransferChecked instructions of a whale transaction.
+async fn summarize_whale_transaction(signature: String, tracked_whale: String, transaction: Result<Option<Value>, String>, token_registry: &TokenRegistry,
+                                     lookup_tables: &LookupTableRegistry, fee_market: &FeeMarket) {
+    // ------------ PROCESS TRANSACTION INSTRUCTION AND PRE/POST TOKEN BALANCES ------------
+    // For now, we are only summarizing TRANSFER CHECKED instructions, any instruction of INSTRUCTION_DECODERS can be summarized the same way.
+    match transaction {
+        Ok(value) => {
+            // println!("[[TRANSACTION DATA]] {:#?}", value);
+            match value.map(serde_json::from_value::<EncodedTransactionWithMeta>).transpose() {
+                Ok(Some(mut transaction)) => {
+                    if let Err(e) = lookup_tables.resolve(&mut transaction).await {
+                        eprintln!("Could not resolve the lookup tables of transaction {:?}: {}", signature, e);
+                        return;
+                    }
+                    if let Some(budget) = fee_market.observe(&transaction) {
+                        println!("[[COMPUTE]] {}", budget);
+                    }
+                    let instructions = match transaction.instructions() {
+                        Ok(instructions) => instructions,
+                        Err(e) => {
+                            eprintln!("Could not resolve the instructions of transaction {:?}: {}", signature, e);
+                            return;
+                        }
+                    };
+
+                    // Outer and inner instructions alike, a transfer through an aggregator is an inner one
+                    let mut transfer_checked_instructions: Vec<TokenInstruction> = Vec::new();
+                    {
+                        let decoders = INSTRUCTION_DECODERS.read().unwrap();
+                        for instruction in &instructions {
+                            match decoders.decode(instruction) {
+                                Ok(Some(DecodedInstruction::SplToken(transfer @ TokenInstruction::TransferChecked { .. }))) => {
+                                    // println!("[[TRANSFER CHECKED]] {:?}", transfer);
+                                    transfer_checked_instructions.push(transfer);
+                                }
+                                Ok(_) => {}
+                                Err(e) => eprintln!("Could not decode instruction {} of {:?}: {}", instruction.outer_index, signature, e),
+                            }
+                        }
+                    }
+
+                    // Whichever DEX or aggregator the whale went through
+                    match SWAP_EXTRACTOR.extract(&transaction) {
+                        Ok(swaps) => swaps.iter().for_each(|swap| println!("[[SWAP]] {}", swap)),
+                        Err(e) => eprintln!("Could not extract the swaps of {:?}: {}", signature, e),
+                    }
+
+                    // SOL (native and wrapped) and tokens, fees and rent of the whale apart
+                    let net_change = transaction.net_balance_change(&tracked_whale);
+                    println!("[[NET CHANGE]] {}", net_change);
+
+                    match prepare_transaction_summary(
+                        signature.clone(),
+                        tracked_whale.clone(),
+                        net_change,
+                        transfer_checked_instructions,
+                        token_registry).await {
+
+                        Ok(_) => {
+                            println!("Successfully processed transaction {:?}", signature.clone());
+                        }
+                        Err(e) => {
+                            println!("Failed to process transaction {:?} : {:?}", signature.clone(), e);
+                        }
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => eprintln!("Error deserializing transaction {:?}", e),
+            }
+        }
+        Err(e) => eprintln!("Could not get transaction for signature {:?}: {}", signature, e),
+    }
+}
+
+/// Names the tokens of the transfers through the `TokenRegistry` and prints their summary.
 async fn prepare_transaction_summary(
     signature: String,
     tracked_whale: String,
-    pre_token_balances: Vec<TokenBalance>,
-    post_token_balances: Vec<TokenBalance>,
-    transfer_checked_info: Vec<TransferCheckedInfo>
+    net_change: NetBalanceChange,
+    transfer_checked_instructions: Vec<TokenInstruction>,
+    token_registry: &TokenRegistry,
 ) -> Result<Vec<TxCheckedSummary>, Box<dyn Error>> {
     let mut summaries = Vec::new();
-    let solana_private_http_url = env::var("PRIVATE_SOLANA_QUICKNODE_HTTP")
-        .expect("PRIVATE_SOLANA_QUICKNODE_HTTP must be set");
-    let client = RpcClient::new(solana_private_http_url);
-
-    //track known addresses
-    let mut known_addresses = HashMap::new();
-    known_addresses.insert("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v", ("USDC", "USD Coin"));
-    known_addresses.insert("Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB", ("USDT", "USD Token"));
-    known_addresses.insert("So11111111111111111111111111111111111111112", ("SOL", "Wrapped SOL"));
-
-    // Determine buy or sell
-    let mut activity_detail = String::new();
-
-    let pre_balance = pre_token_balances.iter()
-        .find(|balance| balance.owner == tracked_whale);
-    let post_balance = post_token_balances.iter()
-        .find(|balance| balance.owner == tracked_whale);
-
-    if let (Some(pre), Some(post)) = (pre_balance, post_balance) {
-        let pre_amount = pre.ui_token_amount.ui_amount.unwrap_or(0.0);
-        let post_amount = post.ui_token_amount.ui_amount.unwrap_or(0.0);
-
-        if post_amount > pre_amount {
-            activity_detail = format!("{} bought {:.2} tokens", tracked_whale, post_amount - pre_amount);
-        } else if pre_amount > post_amount {
-            activity_detail = format!("{} sold {:.2} tokens", tracked_whale, pre_amount - post_amount);
+
+    // Determine buy or sell, from the net change of every token of the whale and the SOL it traded for them
+    let changed_mints: Vec<String> = net_change.tokens.iter().map(|token| token.mint.clone()).collect();
+    token_registry.resolve_many(&changed_mints).await;
+    let mut activity_detail = net_change.describe(|mint| token_registry.get(mint)
+        .map(|token| token.display_symbol())
+        .unwrap_or_else(|| mint.to_string()));
+    if activity_detail.is_empty() && net_change.traded_lamports() != 0 {
+        activity_detail = format!("{} moved {:+.4} SOL", tracked_whale, net_change.traded_sol());
+    }
+    // Token-2022 extensions the mint authorities could rug the holders with
+    for token in changed_mints.iter().filter_map(|mint| token_registry.get(mint)) {
+        if !token.risk_flags.is_empty() {
+            let flags: Vec<String> = token.risk_flags.iter().map(|flag| flag.to_string()).collect();
+            activity_detail.push_str(&format!(" [!] {}: {}", token.display_symbol(), flags.join(", ")));
         }
     }
 
     // println!("{:?}", activity_detail);
 
-    for transfer_info in transfer_checked_info {
-
-        if known_addresses.contains_key(&*transfer_info.mint) {
-            // If the mint is known, skip fetching metadata and printing summary
-            //todo suele ser ruido / internal transfers
-            continue;
-        }
-
-        let metadata_program_id = &ID;
-        let token_mint_address = Pubkey::from_str(transfer_info.mint.as_str()).unwrap();
-        let (metadata_account_address, _) = Pubkey::find_program_address(
-            &[
-                b"metadata",
-                metadata_program_id.as_ref(),
-                token_mint_address.as_ref(),
-            ],
-            &metadata_program_id,
-        );
-
-        // Attempt to fetch and deserialize the account data for the metadata account
-        let account_data_result = client.get_account_data(&metadata_account_address);
-        let (token_name, token_symbol) = match account_data_result {
-            Ok(account_data) => match Metadata::from_bytes(&account_data) {
-                Ok(metadata) => {
-                    // println!("[[METADATA]] {:?}", metadata);
-                    (metadata.name, metadata.symbol)
-                },
-                Err(e) => {
-                    eprintln!("Error while parsing metadata: {:?}", e);
-                    // Default to "Unknown" if there's an error parsing metadata
-                    ("Unknown".to_string(), "Unknown".to_string())
-                }
-            },
+    // Only the TransferChecked instructions are summarized, as (source, destination, mint, ui amount)
+    let transfers: Vec<(String, String, String, f64)> = transfer_checked_instructions.into_iter()
+        .filter_map(|instruction| match instruction {
+            TokenInstruction::TransferChecked { source, mint, destination, amount, decimals, .. } =>
+                Some((source, destination, mint, ui_amount(amount, decimals))),
+            _ => None,
+        })
+        // SOL and stables are the payment side of the transfer, skip their summary
+        //todo suele ser ruido / internal transfers
+        .filter(|(_, _, mint, _)| !token_registry.is_quote(mint))
+        .collect();
+
+    // Every mint transferred is resolved at once, only the ones the registry doesn't know yet are fetched
+    let mints: Vec<String> = transfers.iter().map(|(_, _, mint, _)| mint.clone()).collect();
+    let tokens = token_registry.resolve_many(&mints).await;
+
+    for ((source, destination, mint, token_amount), token) in transfers.into_iter().zip(tokens) {
+        let (token_name, token_symbol) = match token {
+            Ok(token) => (token.display_name(), token.display_symbol()),
             Err(e) => {
-                eprintln!("Failed to fetch account data: {:?}", e);
-                // Same
+                eprintln!("Failed to resolve token {}: {}", mint, e);
+                // Default to "Unknown" if the mint can't be resolved
                 ("Unknown".to_string(), "Unknown".to_string())
             }
         };
@@ -420,12 +564,12 @@ async fn prepare_transaction_summary(
         let summary = TxCheckedSummary {
             signature: signature.clone(),
             transaction_type: "Confirmed Transfer".to_string(),
-            source: transfer_info.source.clone(),
-            destination: transfer_info.destination.clone(),
-            mint: transfer_info.mint.clone(),
+            source,
+            destination,
+            mint,
             token_name,
             token_symbol,
-            token_amount: Some(transfer_info.token_amount.ui_amount.unwrap_or(0.0)),
+            token_amount: Some(token_amount),
             detail: activity_detail.clone()
         };
 
@@ -437,140 +581,3 @@ async fn prepare_transaction_summary(
 
     Ok(summaries)
 }
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
-pub struct RpcResponse {
-    id: u64,
-    jsonrpc: String,
-    result: Option<ResultField>,
-    block_time: Option<u64>,
-}
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct ResultField {
-    meta: Meta,
-    transaction: Transaction,
-    slot: u64,
-}
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
-pub struct Meta {
-    err: Option<Value>,
-    fee: u64,
-    inner_instructions: Vec<InnerInstruction>,
-    post_balances: Vec<u64>,
-    post_token_balances: Vec<TokenBalance>,
-    pre_balances: Vec<u64>,
-    pre_token_balances: Vec<TokenBalance>,
-    rewards: Vec<Value>,
-    status: Status,
-}
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
-pub struct TokenBalance {
-    account_index: u64,
-    mint: String,
-    owner: String,
-    program_id: String,
-    ui_token_amount: UiTokenAmount,
-}
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
-pub struct UiTokenAmount {
-    amount: String,
-    decimals: u8,
-    ui_amount: Option<f64>,
-    ui_amount_string: String,
-}
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
-pub struct InnerInstruction {
-    index: u64,
-    instructions: Vec<Value>,
-}
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
-pub struct TransferChecked {
-    pub program: String,
-    pub program_id: String,
-    pub parsed: Option<TransferCheckedInstruction>,
-}
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
-pub struct TransferCheckedInstruction {
-    #[serde(rename = "type")]
-    pub instruction_type: String,
-    pub info: TransferCheckedInfo,
-}
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
-pub struct TransferCheckedInfo {
-    pub authority: String,
-    pub destination: String,
-    pub mint: String,
-    pub source: String,
-    pub token_amount: TokenAmount,
-}
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
-pub struct TokenAmount {
-    pub amount: String,
-    pub decimals: u8,
-    #[serde(rename = "uiAmount")]
-    pub ui_amount: Option<f64>,
-    #[serde(rename = "uiAmountString")]
-    pub ui_amount_string: String,
-}
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct Status {
-    ok: Option<Value>,
-}
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
-pub struct TransactionMessage {
-    account_keys: Vec<AccountKey>,
-    instructions: Vec<Value>,
-    recent_blockhash: String,
-}
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
-pub struct AccountKey {
-    pubkey: String,
-    signer: bool,
-    writable: bool,
-}
-
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct Transaction {
-    message: TransactionMessage,
-    signatures: Vec<String>,
-}
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
-pub struct Header {
-    num_readonly_signed_accounts: u8,
-    num_readonly_unsigned_accounts: u8,
-    num_required_signatures: u8,
-}
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
-pub struct InstructionData {
-    accounts: Vec<u8>,
-    data: String,
-    program_id_index: Option<u8>,
-}
\ No newline at end of file
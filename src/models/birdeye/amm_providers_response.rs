@@ -0,0 +1,19 @@
+// Birdeye's list of pool providers, bundled as public/birdeye/amm_providers.json
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AmmProvidersResponse {
+    pub data: Vec<AmmProvider>,
+    pub success: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AmmProvider {
+    pub source: String, // raydium, orca, whirlpool...
+    pub program_ids: Vec<String>,
+    pub website: Option<String>,
+    pub icon: Option<String>,
+    pub token_address: Option<String>, // governance token of the AMM
+    pub symbol: Option<String>,
+    pub summary: Option<bool>,
+}
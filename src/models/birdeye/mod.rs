@@ -0,0 +1,2 @@
+pub mod multi_price_response;
+pub mod amm_providers_response;
@@ -0,0 +1,26 @@
+// https://docs.birdeye.so/reference/post_defi-multi-price
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MultiPriceRequest {
+    pub list_address: String, // comma separated addresses
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MultiPriceResponse {
+    pub data: HashMap<String, TokenData>, //signature - data
+    pub success: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TokenData {
+    pub value: f64,
+    #[serde(rename = "updateUnixTime")]
+    pub update_unix_time: f64,
+    #[serde(rename = "updateHumanTime")]
+    pub update_human_time: String,
+    #[serde(rename = "priceChange24h")]
+    pub price_change_24_h: f64,
+    pub liquidity: f64,
+}
@@ -1,4 +1,4 @@
 pub mod solana;
 pub mod moralis;
-
-
+pub mod birdeye;
+pub mod raydium;
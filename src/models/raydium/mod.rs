@@ -0,0 +1 @@
+pub mod raydium_amm_event;
@@ -0,0 +1,109 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// What a Raydium AMM v4 instruction did to a pool. Base is the coin side of the pool, quote the pc side, amounts are raw.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type")]
+pub enum RaydiumAmmEvent {
+    PoolCreated(PoolCreated),
+    Swap(Swap),
+    LiquidityChange(LiquidityChange),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PoolCreated {
+    pub signature: String,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub pool: String,
+    pub base_mint: String,
+    pub quote_mint: String,
+    pub lp_mint: String,
+    pub base_vault: String,
+    pub quote_vault: String,
+    pub market: String,
+    pub creator: String,
+    // Unix timestamp (seconds) from which swaps are allowed, launches usually set it a few minutes ahead
+    pub open_time: u64,
+    // Initial liquidity
+    pub base_amount: u64,
+    pub quote_amount: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SwapSide {
+    // Base out of the pool
+    Buy,
+    // Base into the pool
+    Sell,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Swap {
+    pub signature: String,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub pool: String,
+    pub trader: String,
+    pub base_mint: String,
+    pub quote_mint: String,
+    // From the transfers the instruction invoked, what actually moved. None when they can't be told apart from the rest of
+    // the transaction (inner instruction without a stack height)
+    pub side: Option<SwapSide>,
+    pub base_amount: Option<u64>,
+    pub quote_amount: Option<u64>,
+    // From the instruction data: amount in for swapBaseIn, amount out for swapBaseOut
+    pub amount_specified: u64,
+    // From the instruction data: minimum out for swapBaseIn, maximum in for swapBaseOut
+    pub other_amount_threshold: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LiquidityChangeKind {
+    Deposit,
+    Withdraw,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LiquidityChange {
+    pub signature: String,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub pool: String,
+    pub owner: String,
+    pub kind: LiquidityChangeKind,
+    pub base_mint: String,
+    pub quote_mint: String,
+    pub lp_mint: String,
+    // From the transfers, mints and burns the instruction invoked, None when they can't be told apart (see `Swap`)
+    pub base_amount: Option<u64>,
+    pub quote_amount: Option<u64>,
+    // LP tokens minted or burnt
+    pub lp_amount: Option<u64>,
+}
+
+impl fmt::Display for RaydiumAmmEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RaydiumAmmEvent::PoolCreated(event) => write!(
+                f,
+                "NEW POOL {} base {} quote {} lp {} market {} by {}, opens at {}, initial liquidity {} base / {} quote ({})",
+                event.pool, event.base_mint, event.quote_mint, event.lp_mint, event.market, event.creator, event.open_time,
+                event.base_amount, event.quote_amount, event.signature
+            ),
+            RaydiumAmmEvent::Swap(event) => write!(
+                f,
+                "SWAP {} {:?} {:?} base {} for {:?} quote {} by {} ({})",
+                event.pool, event.side, event.base_amount, event.base_mint, event.quote_amount, event.quote_mint, event.trader, event.signature
+            ),
+            RaydiumAmmEvent::LiquidityChange(event) => write!(
+                f,
+                "LIQUIDITY {} {:?} {:?} base / {:?} quote, lp {:?} by {} ({})",
+                event.pool, event.kind, event.base_amount, event.quote_amount, event.lp_amount, event.owner, event.signature
+            ),
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::solana::solana_encoded_transaction::{EncodedMessage, LoadedAddresses};
+
+/// Where a v0 message gets an account from
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum AccountKeySource {
+    // Listed in the message
+    Static,
+    // At `index` of the lookup table `table`
+    LookupTable { table: String, index: u8 },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AccountKey {
+    pub pubkey: String,
+    pub writable: bool,
+    pub signer: bool,
+    pub source: AccountKeySource,
+}
+
+/**
+Every account of a message in the order instruction indexes point into: the static keys, then the writable and the readonly
+accounts loaded from the address lookup tables (each group in the order of `address_table_lookups`). Legacy messages only have
+static keys. Writable and signer flags follow the message header, accounts loaded from lookup tables never sign.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct AccountKeys {
+    pub keys: Vec<AccountKey>,
+}
+
+impl AccountKeys {
+    /// `loaded` are the addresses resolved from the lookup tables, None for a legacy message (or tables not resolved yet)
+    pub fn from_message(message: &EncodedMessage, loaded: Option<&LoadedAddresses>) -> Self {
+        let header = &message.header;
+        let static_len = message.account_keys.len();
+        let signers = header.num_required_signatures as usize;
+        let writable_signers = signers.saturating_sub(header.num_readonly_signed_accounts as usize);
+        let writable_unsigned_end = static_len.saturating_sub(header.num_readonly_unsigned_accounts as usize);
+
+        let mut keys: Vec<AccountKey> = message.account_keys.iter().enumerate()
+            .map(|(index, pubkey)| AccountKey {
+                pubkey: pubkey.clone(),
+                signer: index < signers,
+                writable: if index < signers { index < writable_signers } else { index < writable_unsigned_end },
+                source: AccountKeySource::Static,
+            })
+            .collect();
+
+        let Some(loaded) = loaded else {
+            return Self { keys };
+        };
+        let lookups = message.address_table_lookups.as_deref().unwrap_or_default();
+        let writable_sources: Vec<(&str, u8)> = lookups.iter()
+            .flat_map(|lookup| lookup.writable_indexes.iter().map(move |index| (lookup.account_key.as_str(), *index)))
+            .collect();
+        let readonly_sources: Vec<(&str, u8)> = lookups.iter()
+            .flat_map(|lookup| lookup.readonly_indexes.iter().map(move |index| (lookup.account_key.as_str(), *index)))
+            .collect();
+        for (addresses, sources, writable) in [(&loaded.writable, writable_sources, true), (&loaded.readonly, readonly_sources, false)] {
+            for (position, pubkey) in addresses.iter().enumerate() {
+                // Both come from the same message, the node loads one address per lookup index
+                let (table, index) = sources.get(position).copied().unwrap_or_default();
+                keys.push(AccountKey {
+                    pubkey: pubkey.clone(),
+                    writable,
+                    signer: false,
+                    source: AccountKeySource::LookupTable { table: table.to_string(), index },
+                });
+            }
+        }
+        Self { keys }
+    }
+
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.keys.get(index).map(|key| key.pubkey.as_str())
+    }
+
+    /// First signer, pays the fees
+    pub fn fee_payer(&self) -> Option<&str> {
+        self.get(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use serde_json::json;
+    use solana_sdk::bs58;
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::*;
+    use crate::decoder::address_lookup_table::{AddressLookupTable, LOOKUP_TABLE_META_SIZE};
+    use crate::models::solana::solana_encoded_transaction::EncodedTransactionWithMeta;
+
+    fn pubkeys(count: usize) -> Vec<String> {
+        (0..count).map(|_| Pubkey::new_unique().to_string()).collect()
+    }
+
+    /// A v0 transfer: payer, co-signer and program static, source and destination from `table`, a readonly account from `other`
+    fn transaction(keys: &[String], table: &str, other: &str, loaded: Option<serde_json::Value>) -> EncodedTransactionWithMeta {
+        serde_json::from_value(json!({
+            "slot": 1,
+            "blockTime": null,
+            "version": 0,
+            "meta": {
+                "err": null,
+                "fee": 5000,
+                "preBalances": [],
+                "postBalances": [],
+                "loadedAddresses": loaded,
+            },
+            "transaction": {
+                "signatures": ["sig"],
+                "message": {
+                    // 2 signers of which 1 readonly, 1 readonly unsigned
+                    "header": {"numRequiredSignatures": 2, "numReadonlySignedAccounts": 1, "numReadonlyUnsignedAccounts": 1},
+                    "accountKeys": keys,
+                    "recentBlockhash": "11111111111111111111111111111111",
+                    "instructions": [{"programIdIndex": 2, "accounts": [0, 3, 4, 5], "data": bs58::encode([2u8]).into_string()}],
+                    "addressTableLookups": [
+                        {"accountKey": table, "writableIndexes": [3, 1], "readonlyIndexes": []},
+                        {"accountKey": other, "writableIndexes": [], "readonlyIndexes": [0]},
+                    ],
+                },
+            },
+        })).unwrap()
+    }
+
+    fn packed_table(addresses: &[String]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend(1u32.to_le_bytes());
+        data.extend(u64::MAX.to_le_bytes());
+        data.extend(42u64.to_le_bytes());
+        data.push(0);
+        data.push(0);
+        data.resize(LOOKUP_TABLE_META_SIZE, 0);
+        for address in addresses {
+            data.extend(address.parse::<Pubkey>().unwrap().to_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn legacy_flags_follow_the_header() {
+        let keys = pubkeys(3);
+        let message = transaction(&keys, "t", "o", None).transaction.message;
+        let account_keys = AccountKeys::from_message(&message, None);
+
+        let flags: Vec<(bool, bool)> = account_keys.keys.iter().map(|key| (key.signer, key.writable)).collect();
+        assert_eq!(flags, vec![(true, true), (true, false), (false, false)]);
+        assert_eq!(account_keys.fee_payer(), Some(keys[0].as_str()));
+        assert_eq!(account_keys.keys.len(), 3);
+    }
+
+    #[test]
+    fn loaded_addresses_follow_the_static_keys() {
+        let keys = pubkeys(3);
+        let loaded = pubkeys(3);
+        let transaction = transaction(&keys, "table", "other", Some(json!({"writable": &loaded[..2], "readonly": &loaded[2..]})));
+        let account_keys = transaction.account_keys();
+
+        assert_eq!(account_keys.keys.len(), 6);
+        assert_eq!(account_keys.keys[3], AccountKey {
+            pubkey: loaded[0].clone(),
+            writable: true,
+            signer: false,
+            source: AccountKeySource::LookupTable { table: "table".to_string(), index: 3 },
+        });
+        assert_eq!(account_keys.keys[4].source, AccountKeySource::LookupTable { table: "table".to_string(), index: 1 });
+        assert!(!account_keys.keys[5].writable);
+        assert_eq!(account_keys.keys[5].source, AccountKeySource::LookupTable { table: "other".to_string(), index: 0 });
+    }
+
+    #[test]
+    fn resolves_instructions_through_lookup_tables() {
+        let keys = pubkeys(3);
+        let table_addresses = pubkeys(4);
+        let other_addresses = pubkeys(1);
+        let mut transaction = transaction(&keys, "table", "other", None);
+        assert_eq!(transaction.unresolved_lookup_tables(), vec!["table", "other"]);
+
+        let table = AddressLookupTable::unpack(&packed_table(&table_addresses)).unwrap();
+        assert_eq!((table.deactivation_slot, table.last_extended_slot, table.authority.clone()), (u64::MAX, 42, None));
+        let tables: HashMap<String, Arc<AddressLookupTable>> = HashMap::from([
+            ("table".to_string(), Arc::new(table)),
+            ("other".to_string(), Arc::new(AddressLookupTable::unpack(&packed_table(&other_addresses)).unwrap())),
+        ]);
+        transaction.load_addresses(&tables).unwrap();
+        assert!(transaction.unresolved_lookup_tables().is_empty());
+
+        let instructions = transaction.instructions().unwrap();
+        assert_eq!(instructions[0].program_id, keys[2]);
+        assert_eq!(instructions[0].accounts, vec![keys[0].clone(), table_addresses[3].clone(), table_addresses[1].clone(), other_addresses[0].clone()]);
+    }
+
+    #[test]
+    fn index_past_the_table_fails() {
+        let keys = pubkeys(3);
+        let mut transaction = transaction(&keys, "table", "other", None);
+        // Read before it was extended to 4 addresses
+        let tables: HashMap<String, Arc<AddressLookupTable>> = HashMap::from([
+            ("table".to_string(), Arc::new(AddressLookupTable::unpack(&packed_table(&pubkeys(2))).unwrap())),
+            ("other".to_string(), Arc::new(AddressLookupTable::unpack(&packed_table(&pubkeys(1))).unwrap())),
+        ]);
+        assert!(transaction.load_addresses(&tables).is_err());
+        // Without the loaded addresses the instruction points past the account keys
+        assert!(transaction.instructions().is_err());
+    }
+}
@@ -0,0 +1,36 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// What a transaction paid for its compute, see `decoder::compute_budget_instructions::compute_budget`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ComputeBudget {
+    pub signature: String,
+    pub slot: u64,
+    // The limit the runtime applied: the requested one, or 200k per instruction, 1.4M at most
+    pub unit_limit: u32,
+    // None when the transaction relies on the default limit
+    pub requested_unit_limit: Option<u32>,
+    // Micro-lamports per compute unit, 0 without SetComputeUnitPrice
+    pub unit_price: u64,
+    // Lamports: unit price times the unit limit (not the units consumed)
+    pub priority_fee: u64,
+    // Lamports: 5000 per signature
+    pub base_fee: u64,
+    // Lamports the transaction was charged, from the meta
+    pub fee: Option<u64>,
+    pub units_consumed: Option<u64>,
+}
+
+impl fmt::Display for ComputeBudget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Compute - {} - limit {} CU{}, consumed {} CU, price {} micro-lamports/CU, priority fee {} lamports, fee {} lamports",
+               self.signature,
+               self.unit_limit,
+               if self.requested_unit_limit.is_some() { "" } else { " (default)" },
+               self.units_consumed.map_or("?".to_string(), |units| units.to_string()),
+               self.unit_price,
+               self.priority_fee,
+               self.fee.unwrap_or(self.base_fee + self.priority_fee))
+    }
+}
@@ -0,0 +1,71 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorSource {
+    // The runtime rejected the transaction or the instruction (InsufficientFundsForFee, ComputationalBudgetExceeded...)
+    Runtime,
+    // Known error table of the program (SPL Token, Token-2022, Raydium AMM v4, System program)
+    Program,
+    // `errors` section of the program's Anchor IDL
+    Idl,
+    // Error of the Anchor framework itself (constraints, account deserialization...)
+    Anchor,
+    // `AnchorError occurred` log of a program without IDL
+    Logs,
+    // A custom code nothing knows about
+    Unknown,
+}
+
+/**
+A transaction that landed and failed, from the `err` of its log notification (or its meta) decoded into a named cause, see
+`decoder::transaction_error::decode_error`. `program_id` is the program that returned the error: for a CPI, the callee that
+failed, not the outer instruction's program.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FailedTransaction {
+    pub signature: String,
+    pub slot: u64,
+    // The outer instruction that failed, None for transaction level errors
+    pub instruction_index: Option<usize>,
+    pub program_id: Option<String>,
+    // The error variant: "InstructionError" variant (ie. "Custom", "InsufficientFunds") or the transaction level error
+    pub kind: String,
+    // Custom program error code
+    pub code: Option<u32>,
+    // ie. "ExceededSlippage", the kind when the error isn't a custom one
+    pub name: String,
+    pub message: Option<String>,
+    pub source: ErrorSource,
+    // The `err` as received
+    pub err: serde_json::Value,
+}
+
+impl FailedTransaction {
+    /// What the failures are grouped by
+    pub fn reason(&self) -> String {
+        match self.code {
+            Some(code) => format!("{} ({})", self.name, code),
+            None => self.name.clone(),
+        }
+    }
+}
+
+impl fmt::Display for FailedTransaction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Failed - {} - ", self.signature)?;
+        if let Some(index) = self.instruction_index {
+            write!(f, "instruction {} ", index)?;
+        }
+        if let Some(program_id) = &self.program_id {
+            write!(f, "in {} ", program_id)?;
+        }
+        write!(f, "failed with {}", self.reason())?;
+        if let Some(message) = &self.message {
+            write!(f, ": {}", message)?;
+        }
+        Ok(())
+    }
+}
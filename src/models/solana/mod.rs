@@ -3,5 +3,17 @@ pub mod solana_account_notification;
 pub mod solana_program_notification;
 pub mod solana_event_types;
 pub mod solana_block_notification;
+pub mod solana_slot_notification;
+pub mod solana_signature_notification;
+pub mod solana_signature_statuses;
+pub mod solana_rpc;
 pub mod alchemy;
-pub mod solana_transaction;
\ No newline at end of file
+pub mod solana_transaction;
+pub mod token_info;
+pub mod solana_encoded_transaction;
+pub mod normalized_swap;
+pub mod program_logs;
+pub mod account_keys;
+pub mod net_balance_change;
+pub mod compute_budget;
+pub mod failed_transaction;
@@ -0,0 +1,189 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use solana_sdk::native_token::LAMPORTS_PER_SOL;
+
+/// Net change of the balance of a mint over every token account of the owner, raw amount
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TokenDelta {
+    pub mint: String,
+    pub decimals: u8,
+    pub delta: i128,
+}
+
+impl TokenDelta {
+    pub fn ui_delta(&self) -> f64 {
+        self.delta as f64 / 10f64.powi(self.decimals as i32)
+    }
+}
+
+/**
+What a transaction did to the SOL and tokens of a wallet. Lamports are split between what the wallet paid to get the transaction
+through (fee, rent of the token accounts it created, refunded when it closes them) and what it traded: native SOL and wrapped SOL
+alike, wrapping and unwrapping SOL nets out. Wrapped SOL is never part of `tokens`.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NetBalanceChange {
+    pub owner: String,
+    pub signature: String,
+    // Change of the wallet's own lamports, as explorers show it
+    pub lamports: i128,
+    // Paid by the wallet when it is the fee payer, 0 otherwise
+    pub fee: u64,
+    // Lamports moved into the token accounts of the wallet by this transaction: the rent of the accounts it created (positive),
+    // minus the rent refunded by the accounts it closed
+    pub rent: i128,
+    // Change of the wSOL balance of the wallet's token accounts
+    pub wrapped_sol: i128,
+    pub tokens: Vec<TokenDelta>,
+}
+
+impl NetBalanceChange {
+    /// SOL bought (positive) or spent (negative) by the wallet, in lamports: fees and rent excluded, wSOL included
+    pub fn traded_lamports(&self) -> i128 {
+        self.lamports + self.fee as i128 + self.rent + self.wrapped_sol
+    }
+
+    pub fn traded_sol(&self) -> f64 {
+        self.traded_lamports() as f64 / LAMPORTS_PER_SOL as f64
+    }
+
+    /// Bought/sold details of every token, priced in SOL when SOL is the only other side of the trade
+    pub fn describe(&self, symbol_of: impl Fn(&str) -> String) -> String {
+        let traded_sol = self.traded_sol();
+        let priced = self.tokens.len() == 1 && self.traded_lamports() != 0;
+        let details: Vec<String> = self.tokens.iter()
+            .map(|token| {
+                let verb = if token.delta > 0 { "bought" } else { "sold" };
+                let mut detail = format!("{} {} {:.2} {}", self.owner, verb, token.ui_delta().abs(), symbol_of(&token.mint));
+                if priced {
+                    detail.push_str(&format!(" for {:.4} SOL", traded_sol.abs()));
+                }
+                detail
+            })
+            .collect();
+        details.join(", ")
+    }
+}
+
+impl fmt::Display for NetBalanceChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Net change - {} - {}: {:+.6} SOL traded (wallet {:+.6} SOL, fee {:.6} SOL, rent {:+.6} SOL, wSOL {:+.6} SOL)",
+               self.signature,
+               self.owner,
+               self.traded_sol(),
+               self.lamports as f64 / LAMPORTS_PER_SOL as f64,
+               self.fee as f64 / LAMPORTS_PER_SOL as f64,
+               self.rent as f64 / LAMPORTS_PER_SOL as f64,
+               self.wrapped_sol as f64 / LAMPORTS_PER_SOL as f64)?;
+        for token in &self.tokens {
+            write!(f, ", {:+} {}", token.ui_delta(), token.mint)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::decoder::spl_token_accounts::NATIVE_MINT;
+    use crate::models::solana::solana_encoded_transaction::EncodedTransactionWithMeta;
+
+    const WALLET: &str = "WaLLet1111111111111111111111111111111111111";
+    const PAYER: &str = "Payer11111111111111111111111111111111111111";
+    const MINT: &str = "Mint111111111111111111111111111111111111111";
+    const RENT: u64 = 2_039_280;
+    const FEE: u64 = 5_000;
+
+    fn token_balance(account_index: u8, mint: &str, amount: u64) -> Value {
+        json!({
+            "accountIndex": account_index,
+            "mint": mint,
+            "owner": WALLET,
+            "programId": "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+            "uiTokenAmount": {"amount": amount.to_string(), "decimals": 6, "uiAmount": null, "uiAmountString": ""},
+        })
+    }
+
+    fn transaction(keys: &[&str], pre_balances: &[u64], post_balances: &[u64], pre_tokens: Value, post_tokens: Value) -> EncodedTransactionWithMeta {
+        serde_json::from_value(json!({
+            "slot": 1,
+            "blockTime": null,
+            "version": "legacy",
+            "meta": {
+                "err": null,
+                "fee": FEE,
+                "preBalances": pre_balances,
+                "postBalances": post_balances,
+                "preTokenBalances": pre_tokens,
+                "postTokenBalances": post_tokens,
+            },
+            "transaction": {
+                "signatures": ["sig"],
+                "message": {
+                    "header": {"numRequiredSignatures": 1, "numReadonlySignedAccounts": 0, "numReadonlyUnsignedAccounts": 0},
+                    "accountKeys": keys,
+                    "recentBlockhash": "11111111111111111111111111111111",
+                    "instructions": [],
+                },
+            },
+        })).unwrap()
+    }
+
+    #[test]
+    fn buy_with_native_sol_excludes_fee_and_rent() {
+        // The wallet pays the fee, wraps 1 SOL in a temporary wSOL account (created and closed, not in the balances) and receives
+        // the tokens in an account it creates
+        let transaction = transaction(
+            &[WALLET, "TokenAccount1111111111111111111111111111111"],
+            &[10 * LAMPORTS_PER_SOL, 0],
+            &[9 * LAMPORTS_PER_SOL - FEE - RENT, RENT],
+            json!([]),
+            json!([token_balance(1, MINT, 5_000_000)]),
+        );
+        let change = transaction.net_balance_change(WALLET);
+
+        assert_eq!(change.lamports, -((LAMPORTS_PER_SOL + FEE + RENT) as i128));
+        assert_eq!((change.fee, change.rent, change.wrapped_sol), (FEE, RENT as i128, 0));
+        assert_eq!(change.traded_lamports(), -(LAMPORTS_PER_SOL as i128));
+        assert_eq!(change.tokens, vec![TokenDelta { mint: MINT.to_string(), decimals: 6, delta: 5_000_000 }]);
+        assert_eq!(change.describe(|mint| mint[..4].to_string()), format!("{} bought 5.00 Mint for 1.0000 SOL", WALLET));
+    }
+
+    #[test]
+    fn sell_into_wrapped_sol_counts_the_refunded_rent() {
+        // Someone else pays the fee, the wallet sells every token into its wSOL account and closes the token account
+        let transaction = transaction(
+            &[PAYER, WALLET, "WrappedSoL111111111111111111111111111111111", "TokenAccount1111111111111111111111111111111"],
+            &[LAMPORTS_PER_SOL, LAMPORTS_PER_SOL, LAMPORTS_PER_SOL / 2 + RENT, RENT],
+            &[LAMPORTS_PER_SOL - FEE, LAMPORTS_PER_SOL + RENT, 3 * LAMPORTS_PER_SOL / 2 + RENT, 0],
+            json!([token_balance(2, NATIVE_MINT, LAMPORTS_PER_SOL / 2), token_balance(3, MINT, 5_000_000)]),
+            json!([token_balance(2, NATIVE_MINT, 3 * LAMPORTS_PER_SOL / 2)]),
+        );
+        let change = transaction.net_balance_change(WALLET);
+
+        assert_eq!((change.lamports, change.fee, change.rent), (RENT as i128, 0, -(RENT as i128)));
+        assert_eq!(change.wrapped_sol, LAMPORTS_PER_SOL as i128);
+        assert_eq!(change.traded_lamports(), LAMPORTS_PER_SOL as i128);
+        // wSOL is never a token
+        assert_eq!(change.tokens, vec![TokenDelta { mint: MINT.to_string(), decimals: 6, delta: -5_000_000 }]);
+    }
+
+    #[test]
+    fn wrapping_sol_nets_out() {
+        let transaction = transaction(
+            &[WALLET, "WrappedSoL111111111111111111111111111111111"],
+            &[10 * LAMPORTS_PER_SOL, RENT],
+            &[8 * LAMPORTS_PER_SOL - FEE, 2 * LAMPORTS_PER_SOL + RENT],
+            json!([token_balance(1, NATIVE_MINT, 0)]),
+            json!([token_balance(1, NATIVE_MINT, 2 * LAMPORTS_PER_SOL)]),
+        );
+        let change = transaction.net_balance_change(WALLET);
+
+        assert_eq!((change.fee, change.rent), (FEE, 0));
+        assert_eq!(change.traded_lamports(), 0);
+        assert!(change.tokens.is_empty());
+    }
+}
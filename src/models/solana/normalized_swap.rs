@@ -0,0 +1,55 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// One pool traded against: what went in and what came out, raw amounts.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SwapLeg {
+    // AMM source of amm_providers.json (raydium, whirlpool...), only listed programs make legs
+    pub source: String,
+    pub program_id: String,
+    // The pool for the AMMs decoded by this crate, the owner of the vault paying out otherwise (the pool itself or its authority)
+    pub pool: Option<String>,
+    pub input_mint: String,
+    pub input_amount: u64,
+    pub output_mint: String,
+    pub output_amount: u64,
+    // Position of the AMM instruction in the transaction
+    pub outer_index: usize,
+    pub inner_index: Option<usize>,
+}
+
+/**
+A trade, whichever DEX it went through. A direct swap on an AMM has a single leg, a Jupiter route is collapsed into the net trade
+(first input -> last output) with every pool it went through in `legs`.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NormalizedSwap {
+    pub signature: String,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub trader: String,
+    // AMM source for a direct swap, the router (ie. "jupiter") for a route
+    pub source: String,
+    pub program_id: String,
+    pub input_mint: String,
+    pub input_amount: u64,
+    pub output_mint: String,
+    pub output_amount: u64,
+    pub legs: Vec<SwapLeg>,
+}
+
+impl fmt::Display for NormalizedSwap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let route: Vec<&str> = self.legs.iter().map(|leg| leg.source.as_str()).collect();
+        write!(f, "Swap - {} - {} swapped {} {} for {} {} on {} (via {})",
+               self.signature,
+               self.trader,
+               self.input_amount,
+               self.input_mint,
+               self.output_amount,
+               self.output_mint,
+               self.source,
+               route.join(" -> "))
+    }
+}
@@ -0,0 +1,134 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::decoder::anchor_idl::AnchorEvent;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum InvocationStatus {
+    Success,
+    Failed,
+    // The logs end (truncated, or the transaction aborted) before the program returns
+    Incomplete,
+}
+
+/// A `Program data:` log: base64 chunks, decoded when the program has an Anchor IDL declaring the event.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ProgramDataLog {
+    pub data: Vec<String>,
+    pub event: Option<AnchorEvent>,
+}
+
+/// A program invocation: an outer instruction (depth 1) or a CPI, with the invocations it made in order.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ProgramInvocation {
+    pub program_id: String,
+    // 1 for an outer instruction, as logged by `invoke [n]`
+    pub depth: usize,
+    // The outer instruction this invocation belongs to
+    pub outer_index: usize,
+    pub status: InvocationStatus,
+    // What follows `failed:`, ie. "custom program error: 0x1771"
+    pub error: Option<String>,
+    // Units used by this invocation, its CPIs included, and its budget. None for the native programs that don't log it.
+    pub compute_consumed: Option<u64>,
+    pub compute_limit: Option<u64>,
+    // `Program log:` messages
+    pub logs: Vec<String>,
+    pub data: Vec<ProgramDataLog>,
+    // `Program return:` base64 data
+    pub return_data: Option<String>,
+    pub invocations: Vec<ProgramInvocation>,
+}
+
+/**
+The CPI tree of a transaction rebuilt from its runtime logs (see `decoder::log_parser::parse_logs`), one root per outer
+instruction. Enough to classify a transaction from a `logsSubscribe` notification without fetching it.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct ProgramLogs {
+    pub invocations: Vec<ProgramInvocation>,
+    // The runtime stops logging past 10KB, the tree is partial
+    pub truncated: bool,
+    // Lines out of the grammar, outside of any invocation
+    pub unparsed: Vec<String>,
+}
+
+impl ProgramInvocation {
+    pub fn new(program_id: &str, depth: usize, outer_index: usize) -> Self {
+        Self {
+            program_id: program_id.to_string(),
+            depth,
+            outer_index,
+            status: InvocationStatus::Incomplete,
+            error: None,
+            compute_consumed: None,
+            compute_limit: None,
+            logs: Vec::new(),
+            data: Vec::new(),
+            return_data: None,
+            invocations: Vec::new(),
+        }
+    }
+
+    /// This invocation and every invocation under it, depth first
+    pub fn flatten(&self) -> Vec<&ProgramInvocation> {
+        let mut flattened = vec![self];
+        for invocation in &self.invocations {
+            flattened.extend(invocation.flatten());
+        }
+        flattened
+    }
+}
+
+impl ProgramLogs {
+    /// Every invocation, depth first in execution order
+    pub fn flatten(&self) -> Vec<&ProgramInvocation> {
+        self.invocations.iter().flat_map(|invocation| invocation.flatten()).collect()
+    }
+
+    /// The invocation the transaction failed in: the deepest failed one, a failing CPI fails every invocation above it.
+    pub fn failed_invocation(&self) -> Option<&ProgramInvocation> {
+        self.flatten().into_iter()
+            .filter(|invocation| invocation.status == InvocationStatus::Failed)
+            .max_by_key(|invocation| invocation.depth)
+    }
+
+    /// Whether a successful invocation of `program_id` logged a message containing `needle`
+    pub fn logged(&self, program_id: &str, needle: &str) -> bool {
+        self.flatten().iter().any(|invocation| invocation.program_id == program_id
+            && invocation.status == InvocationStatus::Success
+            && invocation.logs.iter().any(|log| log.contains(needle)))
+    }
+
+    /// Units used by the outer instructions, the compute units of the transaction
+    pub fn compute_consumed(&self) -> u64 {
+        self.invocations.iter().filter_map(|invocation| invocation.compute_consumed).sum()
+    }
+}
+
+impl fmt::Display for ProgramLogs {
+    /// One line per invocation, indented by depth
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for invocation in self.flatten() {
+            write!(f, "{}[{}] {} {:?}", "  ".repeat(invocation.depth.saturating_sub(1)), invocation.outer_index, invocation.program_id, invocation.status)?;
+            if let (Some(consumed), Some(limit)) = (invocation.compute_consumed, invocation.compute_limit) {
+                write!(f, " {}/{} CU", consumed, limit)?;
+            }
+            if let Some(error) = &invocation.error {
+                write!(f, " - {}", error)?;
+            }
+            for data in &invocation.data {
+                if let Some(event) = &data.event {
+                    write!(f, " - {}.{} {}", event.program, event.name, event.data)?;
+                }
+            }
+            writeln!(f)?;
+        }
+        if self.truncated {
+            writeln!(f, "(log truncated)")?;
+        }
+        Ok(())
+    }
+}
@@ -147,6 +147,12 @@ impl Default for FeeCalculator {
 
 use std::fmt;
 
+impl SolanaAccountNotification {
+    pub fn slot(&self) -> u64 {
+        self.params.result.context.slot
+    }
+}
+
 impl fmt::Display for SolanaAccountNotification {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         // Simplified example: Customize according to what you want to display
@@ -1,98 +1,74 @@
+// https://solana.com/docs/rpc/websocket/blocksubscribe
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-#[derive(Serialize, Deserialize, Debug)]
-struct BlockNotificationResponse {
-    jsonrpc: String,
-    method: String,
-    params: BlockNotificationParams,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-struct BlockNotificationParams {
-    result: BlockNotificationResult,
-    subscription: u64,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-struct BlockNotificationResult {
-    context: Context,
-    value: BlockValue,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-struct Context {
-    slot: u64,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-struct BlockValue {
-    slot: u64,
-    block: Block,
-}
+use crate::models::solana::solana_encoded_transaction::{EncodedTransaction, EncodedTransactionWithMeta, TransactionMeta};
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Block {
-    previous_blockhash: String,
-    blockhash: String,
-    parent_slot: u64,
-    transactions: Vec<Transaction>,
-    block_time: Option<u64>,
-    block_height: Option<u64>,
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SolanaBlockNotification {
+    pub jsonrpc: String,
+    pub method: String,
+    pub params: BlockNotificationParams,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Transaction {
-    transaction: Vec<String>, // This could be further detailed based on the actual structure
-    meta: TransactionMeta,
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BlockNotificationParams {
+    pub result: BlockNotificationResult,
+    pub subscription: u64,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct TransactionMeta {
-    err: Option<Value>,
-    status: TransactionStatus,
-    fee: u64,
-    pre_balances: Vec<u64>,
-    post_balances: Vec<u64>,
-    inner_instructions: Vec<InnerInstruction>,
-    // Add more fields as necessary
-    log_messages: Option<Vec<String>>,
-    pre_token_balances: Option<Vec<TokenBalance>>,
-    post_token_balances: Option<Vec<TokenBalance>>,
-    rewards: Option<Vec<Value>>,
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BlockNotificationResult {
+    pub context: Context,
+    pub value: BlockValue,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct TransactionStatus {
-    Ok: Option<Value>,
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Context {
+    pub slot: u64,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct InnerInstruction {
-    index: u8,
-    instructions: Vec<InstructionDetail>,
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BlockValue {
+    pub slot: u64,
+    pub err: Option<Value>, // ie. "BlockStoreError", block is null when set
+    pub block: Option<Block>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct InstructionDetail {
-    program_id_index: u8,
-    accounts: Vec<u8>,
-    data: String,
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Block {
+    pub previous_blockhash: String,
+    pub blockhash: String,
+    pub parent_slot: u64,
+    #[serde(default)]
+    pub transactions: Vec<Transaction>,
+    #[serde(default)]
+    pub signatures: Vec<String>, // filled instead of transactions when subscribing with transactionDetails: "signatures"
+    pub block_time: Option<u64>,
+    pub block_height: Option<u64>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct TokenBalance {
-    account_index: u8,
-    mint: String,
-    ui_token_amount: UiTokenAmount,
-    owner: String,
-    program_id: String,
+/// A transaction of the block, "json" encoded like `getTransaction`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Transaction {
+    pub transaction: EncodedTransaction,
+    pub meta: Option<TransactionMeta>,
+    // "legacy" or 0
+    pub version: Option<Value>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct UiTokenAmount {
-    ui_amount: Option<f64>,
-    decimals: u8,
-    amount: String,
-    ui_amount_string: String,
+impl Block {
+    /// The transactions of the block as `getTransaction` would return them, for the decoders
+    pub fn encoded_transactions(&self, slot: u64) -> Vec<EncodedTransactionWithMeta> {
+        self.transactions.iter()
+            .map(|transaction| EncodedTransactionWithMeta {
+                slot,
+                block_time: self.block_time.map(|block_time| block_time as i64),
+                meta: transaction.meta.clone(),
+                transaction: transaction.transaction.clone(),
+                version: transaction.version.clone(),
+            })
+            .collect()
+    }
 }
@@ -0,0 +1,317 @@
+// https://solana.com/docs/rpc/http/gettransaction, requested with "encoding": "json"
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_sdk::bs58;
+
+use crate::decoder::address_lookup_table::AddressLookupTable;
+use crate::decoder::spl_token_accounts::NATIVE_MINT;
+use crate::models::solana::account_keys::AccountKeys;
+use crate::models::solana::net_balance_change::{NetBalanceChange, TokenDelta};
+use crate::models::solana::solana_rpc::UiTokenAmount;
+
+/// A transaction as the node stores it: instructions point at accounts by index and carry their data base58 encoded, nothing is
+/// parsed. Decode its instructions with `instructions` + `INSTRUCTION_DECODERS`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct EncodedTransactionWithMeta {
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub meta: Option<TransactionMeta>,
+    pub transaction: EncodedTransaction,
+    // "legacy" or 0
+    pub version: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EncodedTransaction {
+    pub signatures: Vec<String>,
+    pub message: EncodedMessage,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct EncodedMessage {
+    pub header: MessageHeader,
+    // Static keys only, v0 messages load the rest from address lookup tables
+    pub account_keys: Vec<String>,
+    pub recent_blockhash: String,
+    pub instructions: Vec<CompiledInstruction>,
+    #[serde(default)]
+    pub address_table_lookups: Option<Vec<AddressTableLookup>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageHeader {
+    pub num_required_signatures: u8,
+    pub num_readonly_signed_accounts: u8,
+    pub num_readonly_unsigned_accounts: u8,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CompiledInstruction {
+    pub program_id_index: u8,
+    pub accounts: Vec<u8>,
+    pub data: String, // base58
+    #[serde(default)]
+    pub stack_height: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AddressTableLookup {
+    pub account_key: String,
+    pub writable_indexes: Vec<u8>,
+    pub readonly_indexes: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionMeta {
+    pub err: Option<Value>,
+    pub fee: u64,
+    pub pre_balances: Vec<u64>,
+    pub post_balances: Vec<u64>,
+    #[serde(default)]
+    pub inner_instructions: Option<Vec<InnerInstructions>>,
+    #[serde(default)]
+    pub log_messages: Option<Vec<String>>,
+    #[serde(default)]
+    pub pre_token_balances: Option<Vec<TransactionTokenBalance>>,
+    #[serde(default)]
+    pub post_token_balances: Option<Vec<TransactionTokenBalance>>,
+    // Accounts of the address lookup tables of a v0 message, in this order after the static keys
+    #[serde(default)]
+    pub loaded_addresses: Option<LoadedAddresses>,
+    #[serde(default)]
+    pub compute_units_consumed: Option<u64>,
+}
+
+/// Instructions invoked (CPI) by the outer instruction at `index`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InnerInstructions {
+    pub index: u8,
+    pub instructions: Vec<CompiledInstruction>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct LoadedAddresses {
+    pub writable: Vec<String>,
+    pub readonly: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionTokenBalance {
+    pub account_index: u8,
+    pub mint: String,
+    pub owner: Option<String>,
+    pub program_id: Option<String>,
+    pub ui_token_amount: UiTokenAmount,
+}
+
+/// An instruction with its program and accounts resolved to pubkeys and its data decoded, ready for an `InstructionDecoder`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ResolvedInstruction {
+    pub program_id: String,
+    pub accounts: Vec<String>,
+    pub data: Vec<u8>,
+    // Index of the outer instruction, the one invoking this instruction when it is an inner one
+    pub outer_index: usize,
+    // Position among the inner instructions of `outer_index`, None for the outer instruction itself
+    pub inner_index: Option<usize>,
+    pub stack_height: Option<u32>,
+}
+
+/// Balance of a token account before and after the transaction, raw amounts. Accounts created by the transaction have no pre
+/// balance, accounts closed by it no post balance: both count as 0.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TokenBalanceChange {
+    pub account: String,
+    pub mint: String,
+    pub owner: Option<String>,
+    pub decimals: u8,
+    pub pre: u64,
+    pub post: u64,
+}
+
+impl TokenBalanceChange {
+    pub fn delta(&self) -> i128 {
+        self.post as i128 - self.pre as i128
+    }
+}
+
+/// Lamports of an account before and after the transaction, the fee included for the fee payer
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LamportChange {
+    pub account: String,
+    pub pre: u64,
+    pub post: u64,
+}
+
+impl LamportChange {
+    pub fn delta(&self) -> i128 {
+        self.post as i128 - self.pre as i128
+    }
+}
+
+impl EncodedTransactionWithMeta {
+    pub fn signature(&self) -> Option<&str> {
+        self.transaction.signatures.first().map(String::as_str)
+    }
+
+    /// Static keys followed by the writable then readonly accounts loaded from lookup tables, what instruction indexes point into.
+    pub fn account_keys(&self) -> AccountKeys {
+        AccountKeys::from_message(&self.transaction.message, self.meta.as_ref().and_then(|meta| meta.loaded_addresses.as_ref()))
+    }
+
+    /// Lookup tables of a v0 message, whose addresses the node did not send (`meta.loadedAddresses`)
+    pub fn unresolved_lookup_tables(&self) -> Vec<String> {
+        let loaded = self.meta.as_ref().is_some_and(|meta| meta.loaded_addresses.is_some());
+        if loaded {
+            return Vec::new();
+        }
+        let mut tables: Vec<String> = self.transaction.message.address_table_lookups.iter()
+            .flatten()
+            .map(|lookup| lookup.account_key.clone())
+            .collect();
+        tables.dedup();
+        tables
+    }
+
+    /// Loads the accounts of the lookup tables of the message, as the node does in `meta.loadedAddresses`. `tables` by address.
+    pub fn load_addresses(&mut self, tables: &HashMap<String, Arc<AddressLookupTable>>) -> Result<(), Box<dyn Error>> {
+        let mut loaded = LoadedAddresses::default();
+        for lookup in self.transaction.message.address_table_lookups.iter().flatten() {
+            let table = tables.get(&lookup.account_key).ok_or_else(|| format!("lookup table {} not loaded", lookup.account_key))?;
+            loaded.writable.extend(table.lookup(&lookup.writable_indexes)?);
+        }
+        for lookup in self.transaction.message.address_table_lookups.iter().flatten() {
+            let table = tables.get(&lookup.account_key).ok_or_else(|| format!("lookup table {} not loaded", lookup.account_key))?;
+            loaded.readonly.extend(table.lookup(&lookup.readonly_indexes)?);
+        }
+        let meta = self.meta.as_mut().ok_or("transaction without meta to load the addresses into")?;
+        meta.loaded_addresses = Some(loaded);
+        Ok(())
+    }
+
+    /// Token account -> its balance change, for every token account of the pre/post token balances
+    pub fn token_balance_changes(&self) -> HashMap<String, TokenBalanceChange> {
+        let keys = self.account_keys();
+        let mut changes: HashMap<String, TokenBalanceChange> = HashMap::new();
+        let Some(meta) = self.meta.as_ref() else {
+            return changes;
+        };
+
+        let balances = [(meta.pre_token_balances.as_deref(), true), (meta.post_token_balances.as_deref(), false)];
+        for (balances, is_pre) in balances {
+            for balance in balances.unwrap_or_default() {
+                let Some(account) = keys.get(balance.account_index as usize).map(String::from) else {
+                    continue;
+                };
+                let amount = balance.ui_token_amount.amount.parse::<u64>().unwrap_or(0);
+                let change = changes.entry(account.clone()).or_insert_with(|| TokenBalanceChange {
+                    account: account.clone(),
+                    mint: balance.mint.clone(),
+                    owner: balance.owner.clone(),
+                    decimals: balance.ui_token_amount.decimals,
+                    pre: 0,
+                    post: 0,
+                });
+                if is_pre {
+                    change.pre = amount;
+                } else {
+                    change.post = amount;
+                }
+            }
+        }
+        changes
+    }
+
+    /// Account -> its lamport change, for every account of the message
+    pub fn lamport_changes(&self) -> HashMap<String, LamportChange> {
+        let Some(meta) = self.meta.as_ref() else {
+            return HashMap::new();
+        };
+        self.account_keys().keys.into_iter()
+            .zip(meta.pre_balances.iter().zip(meta.post_balances.iter()))
+            .map(|(key, (pre, post))| (key.pubkey.clone(), LamportChange { account: key.pubkey, pre: *pre, post: *post }))
+            .collect()
+    }
+
+    /// What the transaction did to the SOL and tokens of `owner`, see `NetBalanceChange`
+    pub fn net_balance_change(&self, owner: &str) -> NetBalanceChange {
+        let lamports = self.lamport_changes();
+        let fee_payer = self.account_keys().fee_payer() == Some(owner);
+        let fee = if fee_payer { self.meta.as_ref().map_or(0, |meta| meta.fee) } else { 0 };
+
+        let mut rent: i128 = 0;
+        let mut wrapped_sol: i128 = 0;
+        let mut tokens: HashMap<String, TokenDelta> = HashMap::new();
+        for change in self.token_balance_changes().into_values().filter(|change| change.owner.as_deref() == Some(owner)) {
+            let account_lamports = lamports.get(&change.account).map_or(0, LamportChange::delta);
+            if change.mint == NATIVE_MINT {
+                // Lamports of a wSOL account are its rent plus its balance
+                wrapped_sol += change.delta();
+                rent += account_lamports - change.delta();
+            } else {
+                rent += account_lamports;
+                let token = tokens.entry(change.mint.clone()).or_insert_with(|| TokenDelta { mint: change.mint.clone(), decimals: change.decimals, delta: 0 });
+                token.delta += change.delta();
+            }
+        }
+
+        let mut tokens: Vec<TokenDelta> = tokens.into_values().filter(|token| token.delta != 0).collect();
+        tokens.sort_by(|a, b| a.mint.cmp(&b.mint));
+        NetBalanceChange {
+            owner: owner.to_string(),
+            signature: self.signature().unwrap_or_default().to_string(),
+            lamports: lamports.get(owner).map_or(0, LamportChange::delta),
+            fee,
+            rent,
+            wrapped_sol,
+            tokens,
+        }
+    }
+
+    /// Every instruction in execution order: each outer instruction followed by the inner instructions it invoked.
+    pub fn instructions(&self) -> Result<Vec<ResolvedInstruction>, Box<dyn Error>> {
+        let keys = self.account_keys();
+        let inner = self.meta.as_ref()
+            .and_then(|meta| meta.inner_instructions.as_ref())
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+
+        let mut instructions = Vec::new();
+        for (outer_index, instruction) in self.transaction.message.instructions.iter().enumerate() {
+            instructions.push(resolve(&keys, instruction, outer_index, None)?);
+            for inner_instructions in inner.iter().filter(|inner| inner.index as usize == outer_index) {
+                for (inner_index, instruction) in inner_instructions.instructions.iter().enumerate() {
+                    instructions.push(resolve(&keys, instruction, outer_index, Some(inner_index))?);
+                }
+            }
+        }
+        Ok(instructions)
+    }
+}
+
+fn resolve(keys: &AccountKeys, instruction: &CompiledInstruction, outer_index: usize, inner_index: Option<usize>) -> Result<ResolvedInstruction, Box<dyn Error>> {
+    let key = |index: u8| -> Result<String, Box<dyn Error>> {
+        keys.get(index as usize)
+            .map(String::from)
+            .ok_or_else(|| format!("account index {} out of the {} account keys", index, keys.keys.len()).into())
+    };
+    Ok(ResolvedInstruction {
+        program_id: key(instruction.program_id_index)?,
+        accounts: instruction.accounts.iter().map(|index| key(*index)).collect::<Result<Vec<String>, _>>()?,
+        data: bs58::decode(&instruction.data).into_vec()?,
+        outer_index,
+        inner_index,
+        stack_height: instruction.stack_height,
+    })
+}
@@ -1,6 +1,10 @@
 use crate::models::solana::solana_logs_notification::SolanaLogsNotification;
 use crate::models::solana::solana_program_notification::SolanaProgramNotification;
 use crate::models::solana::solana_account_notification::SolanaAccountNotification;
+use crate::models::solana::solana_block_notification::SolanaBlockNotification;
+use crate::models::solana::solana_signature_notification::SolanaSignatureNotification;
+use crate::models::solana::solana_slot_notification::{SolanaRootNotification, SolanaSlotNotification};
+use crate::subscriber::reconnect::StreamGap;
 
 use serde::{Serialize, Deserialize};
 
@@ -8,5 +12,10 @@ use serde::{Serialize, Deserialize};
 pub enum SolanaEventTypes {
     LogNotification(SolanaLogsNotification),
     AccountNotification(SolanaAccountNotification),
-    ProgramNotification(SolanaProgramNotification)
+    ProgramNotification(SolanaProgramNotification),
+    SlotNotification(SolanaSlotNotification),
+    RootNotification(SolanaRootNotification),
+    BlockNotification(SolanaBlockNotification),
+    SignatureNotification(SolanaSignatureNotification),
+    StreamGap(StreamGap)
 }
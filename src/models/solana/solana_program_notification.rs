@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value; // For handling flexible data structures
 
+use crate::decoder::account_decoder::{DecodedAccount, ACCOUNT_DECODERS};
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SolanaProgramNotification {
     pub jsonrpc: String,
@@ -34,7 +36,8 @@ pub struct ProgramNotificationValue {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(untagged)] // Allows for different types of `data` field representations
 pub enum ProgramAccountData {
-    Base58(Vec<String>), // For base58 encoding: ["data", "base58"]
+    Encoded(Vec<String>), // ["data", "base58" | "base64" | "base64+zstd"]
+    Binary(String), // Legacy "binary" encoding, base58 without the encoding tag
     ParsedJson {
         program: String,
         parsed: Value, // Flexible to accommodate any structure
@@ -49,6 +52,20 @@ pub struct ProgramAccount {
     pub owner: String,
     pub rentEpoch: u64,
     pub space: u64,
+    // Filled after deserialization by the decoder registered for `owner`, see `SolanaProgramNotification::decode_account`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decoded: Option<DecodedAccount>,
+}
+
+impl SolanaProgramNotification {
+    /// Decodes the raw account data with the decoder registered for its owner, if any.
+    pub fn decode_account(&mut self) {
+        let account = &mut self.params.result.value.account;
+        match ACCOUNT_DECODERS.read().unwrap().decode(&account.owner, &account.data) {
+            Ok(decoded) => account.decoded = decoded,
+            Err(e) => eprintln!("[[ACCOUNT DECODER]] Failed to decode {} owned by {}: {}", self.params.result.value.pubkey, account.owner, e),
+        }
+    }
 }
 
 impl Default for SolanaProgramNotification {
@@ -99,12 +116,13 @@ impl Default for ProgramNotificationValue {
 impl Default for ProgramAccount {
     fn default() -> Self {
         ProgramAccount {
-            data: ProgramAccountData::Base58(vec![]), // Default to one of the possible types
+            data: ProgramAccountData::Encoded(vec![]), // Default to one of the possible types
             executable: false,
             lamports: 0,
             owner: "".to_string(),
             rentEpoch: 0,
             space: 0,
+            decoded: None,
         }
     }
 }
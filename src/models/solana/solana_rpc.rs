@@ -0,0 +1,94 @@
+// https://solana.com/docs/rpc/http
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JsonRpcRequest {
+    pub jsonrpc: String,
+    pub id: u64,
+    pub method: String,
+    pub params: Value,
+}
+
+impl JsonRpcRequest {
+    pub fn new(id: u64, method: &str, params: Value) -> Self {
+        Self { jsonrpc: "2.0".to_string(), id, method: method.to_string(), params }
+    }
+}
+
+/// Either `result` or `error` is set. `result` can legitimately be null (ie. getTransaction for an unknown signature)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JsonRpcResponse<T> {
+    pub jsonrpc: String,
+    pub id: Option<u64>,
+    pub result: Option<T>,
+    pub error: Option<JsonRpcError>,
+}
+
+/// https://www.jsonrpc.org/specification#error_object, Solana puts the preflight simulation or the min context slot in `data`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JsonRpcError {
+    pub code: i64,
+    pub message: String,
+    pub data: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RpcContext {
+    pub slot: u64,
+}
+
+/// Results of the methods that answer with the slot they were evaluated at
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RpcWithContext<T> {
+    pub context: RpcContext,
+    pub value: T,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct UiTokenAmount {
+    pub amount: String,
+    pub decimals: u8,
+    pub ui_amount: Option<f64>, // null when it does not fit in a f64
+    pub ui_amount_string: String,
+}
+
+// https://solana.com/docs/rpc/http/gettokenlargestaccounts
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TokenLargestAccount {
+    pub address: String,
+    #[serde(flatten)]
+    pub amount: UiTokenAmount,
+}
+
+// https://solana.com/docs/rpc/http/getsignaturesforaddress
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SignatureInfo {
+    pub signature: String,
+    pub slot: u64,
+    pub err: Option<Value>,
+    pub memo: Option<String>,
+    pub block_time: Option<i64>,
+    pub confirmation_status: Option<String>,
+}
+
+/// `data` depends on the requested encoding: [data, "base64"] for binary encodings, the parsed json for jsonParsed.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcAccount<D> {
+    pub lamports: u64,
+    pub owner: String,
+    pub data: D,
+    pub executable: bool,
+    pub rent_epoch: u64,
+    pub space: Option<u64>,
+}
+
+// https://solana.com/docs/rpc/http/getprogramaccounts
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RpcKeyedAccount<D> {
+    pub pubkey: String,
+    pub account: RpcAccount<D>,
+}
@@ -0,0 +1,52 @@
+// https://solana.com/docs/rpc/websocket/signaturesubscribe
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SolanaSignatureNotification {
+    pub jsonrpc: String,
+    pub method: String,
+    pub params: SignatureNotificationParams,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SignatureNotificationParams {
+    pub result: SignatureNotificationResult,
+    pub subscription: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SignatureNotificationResult {
+    pub context: SignatureNotificationContext,
+    pub value: SignatureNotificationValue,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SignatureNotificationContext {
+    pub slot: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum SignatureNotificationValue {
+    // Sent once the transaction reaches the subscribed commitment, the server drops the subscription right after
+    Processed { err: Option<Value> },
+    // "receivedSignature", only sent when subscribing with enableReceivedNotification
+    Received(String),
+}
+
+impl SolanaSignatureNotification {
+    /// The final notification for this subscription (anything but the "receivedSignature" ack)
+    pub fn is_final(&self) -> bool {
+        matches!(self.params.result.value, SignatureNotificationValue::Processed { .. })
+    }
+}
+
+impl Default for SignatureNotificationResult {
+    fn default() -> Self {
+        SignatureNotificationResult {
+            context: SignatureNotificationContext::default(),
+            value: SignatureNotificationValue::Processed { err: None },
+        }
+    }
+}
@@ -0,0 +1,12 @@
+// https://solana.com/docs/rpc/http/getsignaturestatuses
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SignatureStatus {
+    pub slot: u64,
+    pub confirmations: Option<u64>, // null once finalized
+    pub err: Option<Value>,
+    pub confirmation_status: Option<String>, // "processed" | "confirmed" | "finalized"
+}
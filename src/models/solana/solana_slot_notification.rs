@@ -0,0 +1,37 @@
+// https://solana.com/docs/rpc/websocket/slotsubscribe
+// https://solana.com/docs/rpc/websocket/rootsubscribe
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SolanaSlotNotification {
+    pub jsonrpc: String,
+    pub method: String,
+    pub params: SlotNotificationParams,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SlotNotificationParams {
+    pub result: SlotInfo,
+    pub subscription: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SlotInfo {
+    pub parent: u64,
+    pub root: u64,
+    pub slot: u64,
+}
+
+/// rootNotification only carries the new root slot
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SolanaRootNotification {
+    pub jsonrpc: String,
+    pub method: String,
+    pub params: RootNotificationParams,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct RootNotificationParams {
+    pub result: u64,
+    pub subscription: u64,
+}
@@ -0,0 +1,97 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::decoder::token_2022::MintExtensions;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenProgram {
+    SplToken,
+    Token2022,
+}
+
+/// Where the name, symbol and uri of a token come from
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataSource {
+    // Bundled config/known_tokens.json
+    KnownList,
+    // Metaplex metadata PDA
+    Metaplex,
+    // Token-2022 TokenMetadata extension of the mint
+    Token2022Extension,
+    // The mint has no metadata at all
+    Missing,
+}
+
+/// What a Token-2022 extension lets the mint's authorities do to the holders, see `MintExtensions::risk_flags`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "flag", rename_all = "snake_case")]
+pub enum TokenRiskFlag {
+    // Can transfer or burn the tokens of any holder. None when the extension is there but could not be decoded
+    PermanentDelegate { delegate: Option<String> },
+    // Every transfer invokes the program, which can refuse it (ie. sells). None when the extension could not be decoded
+    TransferHook { program_id: Option<String> },
+    // Withheld on every transfer, the highest of the current and the scheduled fee
+    TransferFee { basis_points: u16, maximum_fee: u64 },
+    // Can raise the transfer fee
+    MutableTransferFee { authority: String },
+    // Can't be transferred at all, so can't be sold
+    NonTransferable,
+    // New token accounts are frozen until the freeze authority thaws them
+    DefaultFrozen,
+    // Can close the mint once the supply is burnt
+    MintCloseAuthority { authority: String },
+    // Can point the metadata to another account
+    MutableMetadataPointer { authority: String },
+}
+
+impl fmt::Display for TokenRiskFlag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TokenRiskFlag::PermanentDelegate { delegate } =>
+                write!(f, "permanent delegate {} can move any holder's tokens", delegate.as_deref().unwrap_or("(unknown)")),
+            TokenRiskFlag::TransferHook { program_id } =>
+                write!(f, "transfers go through hook program {}", program_id.as_deref().unwrap_or("(unknown)")),
+            TokenRiskFlag::TransferFee { basis_points, maximum_fee } =>
+                write!(f, "{:.2}% transfer fee (max {} raw)", *basis_points as f64 / 100.0, maximum_fee),
+            TokenRiskFlag::MutableTransferFee { authority } => write!(f, "transfer fee can be changed by {}", authority),
+            TokenRiskFlag::NonTransferable => write!(f, "non-transferable"),
+            TokenRiskFlag::DefaultFrozen => write!(f, "new token accounts are frozen"),
+            TokenRiskFlag::MintCloseAuthority { authority } => write!(f, "mint can be closed by {}", authority),
+            TokenRiskFlag::MutableMetadataPointer { authority } => write!(f, "metadata pointer can be changed by {}", authority),
+        }
+    }
+}
+
+/// Everything the `TokenRegistry` knows about a mint
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TokenInfo {
+    pub mint: String,
+    pub name: Option<String>,
+    pub symbol: Option<String>,
+    pub decimals: u8,
+    pub uri: Option<String>,
+    pub update_authority: Option<String>,
+    pub is_mutable: Option<bool>,
+    // Raw amount when the mint was resolved, None for tokens of the known list
+    pub supply: Option<u64>,
+    pub token_program: TokenProgram,
+    pub metadata_source: MetadataSource,
+    // Token-2022 mints resolved on-chain only
+    #[serde(default)]
+    pub extensions: Option<MintExtensions>,
+    #[serde(default)]
+    pub risk_flags: Vec<TokenRiskFlag>,
+}
+
+impl TokenInfo {
+    pub fn display_name(&self) -> String {
+        self.name.clone().unwrap_or_else(|| "Unknown".to_string())
+    }
+
+    pub fn display_symbol(&self) -> String {
+        self.symbol.clone().unwrap_or_else(|| "Unknown".to_string())
+    }
+}
@@ -0,0 +1,96 @@
+use actix_web::{web, HttpResponse, Responder};
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+
+use crate::decoder::account_decoder::decode_payload;
+use crate::decoder::instruction_decoder::DecodedInstruction;
+use crate::decoder::swap_extractor::SWAP_EXTRACTOR;
+use crate::http::solana_rpc_client::SolanaRpcClient;
+use crate::models::solana::solana_encoded_transaction::{EncodedTransactionWithMeta, ResolvedInstruction};
+use crate::trackers::solana::idl_registry::IdlRegistry;
+use crate::trackers::solana::lookup_table_registry::LookupTableRegistry;
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/decode")
+        .route(web::post().to(decode))
+    );
+}
+
+/// Either the `signature` of a transaction, or a single instruction: `program_id`, `data` and `accounts`
+#[derive(Serialize, Deserialize, Debug)]
+struct DecodeRequest {
+    signature: Option<String>,
+    program_id: Option<String>,
+    data: Option<String>,
+    // "base58" (default) or "base64"
+    encoding: Option<String>,
+    #[serde(default)]
+    accounts: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct DecodeResult {
+    program_id: String,
+    outer_index: usize,
+    inner_index: Option<usize>,
+    // AMM source of the instruction or of the AMM instruction invoking it (raydium, jupiter...), see `SwapExtractor::tag`
+    source: Option<String>,
+    // None when no decoder or IDL knows the instruction
+    instruction: Option<DecodedInstruction>,
+    error: Option<String>,
+}
+
+/// Decodes the instructions (outer and inner) of a transaction, or a single instruction, with the registered decoders and the
+/// Anchor IDL of the program, fetched on-chain when it isn't loaded.
+pub async fn decode(request: web::Json<DecodeRequest>, rpc: web::Data<SolanaRpcClient>, idl_registry: web::Data<IdlRegistry>,
+                    lookup_tables: web::Data<LookupTableRegistry>) -> impl Responder {
+    let instructions = match resolve_instructions(&request, &rpc, &lookup_tables).await {
+        Ok(instructions) => instructions,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+
+    let decoded = idl_registry.decode_instructions(&instructions).await;
+    let sources = SWAP_EXTRACTOR.tag(&instructions);
+    let results: Vec<DecodeResult> = instructions.into_iter().zip(decoded).zip(sources)
+        .map(|((instruction, decoded), source)| {
+            let (instruction_decoded, error) = match decoded {
+                Ok(decoded) => (decoded, None),
+                Err(e) => (None, Some(e)),
+            };
+            DecodeResult {
+                program_id: instruction.program_id,
+                outer_index: instruction.outer_index,
+                inner_index: instruction.inner_index,
+                source,
+                instruction: instruction_decoded,
+                error,
+            }
+        })
+        .collect();
+    HttpResponse::Ok().json(results)
+}
+
+async fn resolve_instructions(request: &DecodeRequest, rpc: &SolanaRpcClient, lookup_tables: &LookupTableRegistry) -> Result<Vec<ResolvedInstruction>, String> {
+    if let Some(signature) = &request.signature {
+        let transaction = rpc.get_transaction::<Value>(signature, "confirmed", "json").await
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("transaction {} not found", signature))?;
+        let mut transaction: EncodedTransactionWithMeta = serde_json::from_value(transaction).map_err(|e| e.to_string())?;
+        lookup_tables.resolve(&mut transaction).await?;
+        return transaction.instructions().map_err(|e| e.to_string());
+    }
+
+    let (Some(program_id), Some(data)) = (&request.program_id, &request.data) else {
+        return Err("expected a signature, or a program_id and data".to_string());
+    };
+    let encoding = request.encoding.as_deref().unwrap_or("base58");
+    let data = decode_payload(data, encoding).map_err(|e| e.to_string())?;
+    Ok(vec![ResolvedInstruction {
+        program_id: program_id.clone(),
+        accounts: request.accounts.clone(),
+        data,
+        outer_index: 0,
+        inner_index: None,
+        stack_height: None,
+    }])
+}
@@ -0,0 +1,41 @@
+use actix_web::{web, HttpResponse, Responder};
+use serde::{Serialize, Deserialize};
+
+use crate::models::solana::failed_transaction::FailedTransaction;
+use crate::trackers::solana::failure_tracker::{FailureCount, FailureTracker};
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/failures")
+        .route(web::get().to(failure_counts))
+        .route(web::post().to(recent_failures))
+    );
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct FailuresRequest {
+    // Failures of every program when None
+    program_id: Option<String>,
+    // 100 by default
+    limit: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct FailuresResponse {
+    failures: Vec<FailedTransaction>,
+    counts: Vec<FailureCount>,
+}
+
+/// How many transactions failed per program and reason since startup
+pub async fn failure_counts(failure_tracker: web::Data<FailureTracker>) -> impl Responder {
+    HttpResponse::Ok().json(failure_tracker.counts())
+}
+
+/// The latest failed transactions, newest first, with the counts of the program (or of every program)
+pub async fn recent_failures(request: web::Json<FailuresRequest>, failure_tracker: web::Data<FailureTracker>) -> impl Responder {
+    let program_id = request.program_id.as_deref();
+    let failures = failure_tracker.recent(program_id, request.limit.unwrap_or(100));
+    let counts = failure_tracker.counts().into_iter()
+        .filter(|count| program_id.is_none_or(|program_id| count.program_id.as_deref() == Some(program_id)))
+        .collect();
+    HttpResponse::Ok().json(FailuresResponse { failures, counts })
+}
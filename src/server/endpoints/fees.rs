@@ -0,0 +1,71 @@
+use actix_web::{web, HttpResponse, Responder};
+use serde::{Serialize, Deserialize};
+
+use crate::trackers::solana::fee_market::{FeeEstimate, FeeMarket};
+
+// Accounts listed by /fees/hot
+const HOT_ACCOUNTS: usize = 20;
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/fees")
+        .route(web::post().to(estimate_fees))
+    );
+    cfg.service(web::resource("/fees/hot")
+        .route(web::get().to(hot_accounts))
+    );
+}
+
+/// The programs a transaction to send invokes and the accounts it write-locks (ie. the Raydium AMM v4 program and the pool)
+#[derive(Serialize, Deserialize, Debug)]
+struct FeeRequest {
+    #[serde(default)]
+    program_ids: Vec<String>,
+    #[serde(default)]
+    accounts: Vec<String>,
+    // 75 by default
+    percentile: Option<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct FeeEstimateResult {
+    key: String,
+    // None when no transaction of the window touched it
+    estimate: Option<FeeEstimate>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct FeeResponse {
+    global: Option<FeeEstimate>,
+    programs: Vec<FeeEstimateResult>,
+    accounts: Vec<FeeEstimateResult>,
+    percentile: u8,
+    // Micro-lamports per CU: the percentile of the most contested program or account, of every transaction when none has samples
+    recommended_unit_price: Option<u64>,
+}
+
+/// Unit price percentiles of the recent transactions invoking each program and write-locking each account, and the unit price
+/// to set for a transaction touching all of them.
+pub async fn estimate_fees(request: web::Json<FeeRequest>, fee_market: web::Data<FeeMarket>) -> impl Responder {
+    let percentile = request.percentile.unwrap_or(75).min(100);
+    let programs: Vec<FeeEstimateResult> = request.program_ids.iter()
+        .map(|program_id| FeeEstimateResult { key: program_id.clone(), estimate: fee_market.estimate_program(program_id) })
+        .collect();
+    let accounts: Vec<FeeEstimateResult> = request.accounts.iter()
+        .map(|account| FeeEstimateResult { key: account.clone(), estimate: fee_market.estimate_account(account) })
+        .collect();
+    let global = fee_market.estimate();
+
+    let recommended_unit_price = programs.iter().chain(accounts.iter())
+        .filter_map(|result| result.estimate.as_ref().map(|estimate| estimate.at(percentile)))
+        .max()
+        .or_else(|| global.as_ref().map(|estimate| estimate.at(percentile)));
+    HttpResponse::Ok().json(FeeResponse { global, programs, accounts, percentile, recommended_unit_price })
+}
+
+/// The most write-locked accounts of the window with their estimate, the pools everybody is fighting over
+pub async fn hot_accounts(fee_market: web::Data<FeeMarket>) -> impl Responder {
+    let hottest: Vec<FeeEstimateResult> = fee_market.hottest_accounts(HOT_ACCOUNTS).into_iter()
+        .map(|(account, estimate)| FeeEstimateResult { key: account, estimate: Some(estimate) })
+        .collect();
+    HttpResponse::Ok().json(hottest)
+}
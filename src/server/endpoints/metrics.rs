@@ -0,0 +1,31 @@
+use actix_web::{web, HttpResponse, Responder};
+
+use crate::http::solana_rpc_client::SolanaRpcClient;
+use crate::subscriber::event_bus::BusMetrics;
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/metrics/events")
+        .route(web::get().to(event_metrics))
+    );
+    cfg.service(web::resource("/metrics/rpc")
+        .route(web::get().to(rpc_metrics))
+    );
+    cfg.service(web::resource("/metrics/cache")
+        .route(web::get().to(cache_metrics))
+    );
+}
+
+/// Enqueued/dropped/coalesced counters and lag of the solana event bus, per event type.
+pub async fn event_metrics(metrics: web::Data<BusMetrics>) -> impl Responder {
+    HttpResponse::Ok().json(metrics.snapshot())
+}
+
+/// Rolling latency, error rate, circuit state and routing score of every RPC endpoint of the pool.
+pub async fn rpc_metrics(rpc: web::Data<SolanaRpcClient>) -> impl Responder {
+    HttpResponse::Ok().json(rpc.health())
+}
+
+/// Entries, evictions and hits/misses per cache class of the RPC response cache. Null when the client has no cache.
+pub async fn cache_metrics(rpc: web::Data<SolanaRpcClient>) -> impl Responder {
+    HttpResponse::Ok().json(rpc.cache_stats())
+}
@@ -5,3 +5,8 @@ pub mod signatures_for_address;
 pub mod transactions;
 pub mod accounts;
 pub mod birdeye;
+pub mod metrics;
+pub mod tokens;
+pub mod decode;
+pub mod fees;
+pub mod failures;
@@ -0,0 +1,35 @@
+use actix_web::{web, HttpResponse, Responder};
+use serde::{Serialize, Deserialize};
+
+use crate::models::solana::token_info::TokenInfo;
+use crate::trackers::solana::token_registry::TokenRegistry;
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/tokens")
+        .route(web::post().to(find_tokens))
+    );
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct FindTokensRequest {
+    token_mint_addresses: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct TokenResult {
+    mint_address: String,
+    token: Option<TokenInfo>,
+    error: Option<String>,
+}
+
+/// Name, symbol, decimals, uri, update authority, mutability, supply and token program of each mint, from the `TokenRegistry`.
+pub async fn find_tokens(request: web::Json<FindTokensRequest>, token_registry: web::Data<TokenRegistry>) -> impl Responder {
+    let tokens = token_registry.resolve_many(&request.token_mint_addresses).await;
+    let results: Vec<TokenResult> = request.token_mint_addresses.iter().zip(tokens)
+        .map(|(mint_address, token)| match token {
+            Ok(token) => TokenResult { mint_address: mint_address.clone(), token: Some(token), error: None },
+            Err(e) => TokenResult { mint_address: mint_address.clone(), token: None, error: Some(e) },
+        })
+        .collect();
+    HttpResponse::Ok().json(results)
+}
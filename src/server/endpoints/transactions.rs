@@ -5,7 +5,6 @@ use std::str::FromStr;
 use actix_web::{web, HttpResponse, Responder};
 use diesel::serialize::IsNull::No;
 use log::Level::Debug;
-use reqwest::{Client, header};
 use serde::{Serialize, Deserialize};
 use serde_json::{json, Value};
 use solana_sdk::bs58;
@@ -13,7 +12,11 @@ use rust_decimal::{prelude::FromPrimitive, Decimal};
 use rust_decimal::prelude::{One, Zero};
 use crate::server::endpoints::holders;
 
-use crate::server::endpoints::whales::get_token_supply;
+use crate::decoder::swap_extractor::SWAP_EXTRACTOR;
+use crate::http::solana_rpc_client::SolanaRpcClient;
+use crate::models::solana::account_keys::AccountKey;
+use crate::models::solana::solana_encoded_transaction::{EncodedTransactionWithMeta, ResolvedInstruction};
+use crate::trackers::solana::lookup_table_registry::LookupTableRegistry;
 
 pub fn init_routes(cfg: &mut web::ServiceConfig){
     cfg.service(web::resource("/transactions")
@@ -26,8 +29,8 @@ struct FindTransactionsRequest {
     transaction_signatures: Vec<String>
 }
 
-async fn find_transactions(request: web::Json<FindTransactionsRequest>) -> impl Responder {
-    let transaction_data = process_transaction_signatures(request.transaction_signatures.clone()).await;
+async fn find_transactions(request: web::Json<FindTransactionsRequest>, rpc: web::Data<SolanaRpcClient>, lookup_tables: web::Data<LookupTableRegistry>) -> impl Responder {
+    let transaction_data = process_transaction_signatures(&rpc, &lookup_tables, request.transaction_signatures.clone()).await;
     match transaction_data {
         Ok(data) => HttpResponse::Ok().json(data),
         Err(_) => HttpResponse::InternalServerError().finish(),
@@ -37,47 +40,41 @@ async fn find_transactions(request: web::Json<FindTransactionsRequest>) -> impl
 /**
 Returns transaction details for a confirmed transaction. Params:
  - Transaction signature vector, as base-58 encoded strings
- */
-async fn process_transaction_signatures(signatures: Vec<String>) -> Result<Vec<TransactionResponse>, Box<dyn Error>> {
-
-    let mut request_headers = header::HeaderMap::new();
-    request_headers.insert(header::CONTENT_TYPE, "application/json".parse().unwrap());
-    let rpc_url = "https://api.mainnet-beta.solana.com";//env::var("PRIVATE_SOLANA_QUICKNODE").expect("PRIVATE_SOLANA_QUICKNODE must be set");
 
+Legacy and v0 transactions alike: accounts loaded from address lookup tables are part of `account_keys` and the instructions
+point at the right accounts.
+ */
+async fn process_transaction_signatures(rpc: &SolanaRpcClient, lookup_tables: &LookupTableRegistry, signatures: Vec<String>) -> Result<Vec<TransactionResponse>, Box<dyn Error>> {
     println!("Finding transactions for signatures {:#?}", signatures);
     let mut transactions: Vec<TransactionResponse>= Vec::new();
-    let client = Client::new();
 
-    for signature in signatures {
-        let params = json!([
-            signature,
-            {
-                "encoding": "jsonParsed"
+    // One batched request instead of one request per signature
+    let results = rpc.get_transactions::<EncodedTransactionWithMeta>(&signatures, "confirmed", "json").await;
+    for (signature, result) in signatures.iter().zip(results) {
+        match result {
+            Ok(Some(mut transaction)) => {
+                if let Err(e) = lookup_tables.resolve(&mut transaction).await {
+                    eprintln!("Failed to resolve the lookup tables of transaction {}: {}", signature, e);
+                    continue;
+                }
+                match transaction.instructions() {
+                    Ok(instructions) => transactions.push(TransactionResponse {
+                        instructions: SWAP_EXTRACTOR.tag(&instructions).into_iter().zip(instructions)
+                            .map(|(source, instruction)| TaggedInstruction { instruction, source })
+                            .collect(),
+                        signature: signature.clone(),
+                        slot: transaction.slot,
+                        block_time: transaction.block_time,
+                        version: transaction.version.clone(),
+                        err: transaction.meta.as_ref().and_then(|meta| meta.err.clone()),
+                        fee: transaction.meta.as_ref().map(|meta| meta.fee),
+                        account_keys: transaction.account_keys().keys,
+                    }),
+                    Err(e) => eprintln!("Failed to resolve the instructions of transaction {}: {}", signature, e),
+                }
             }
-        ]);
-
-        let rpc_request = json!({
-            "jsonrpc": "2.0",
-            "id": 1,
-            "method": "getTransaction",
-            "params": params
-        });
-
-       
-        let response = client
-            .post(rpc_url.clone())
-            .headers(request_headers.clone())
-            .json(&rpc_request)
-            .send()
-            .await?;
-
-        if response.status().is_success() {
-            let response_text = response.text().await?;
-            // TODO most useful print ever
-            let value: serde_json::Value = serde_json::from_str(&response_text)?;
-            println!("{:#?}", value);
-
-            //TODO FINISH THIS
+            Ok(None) => eprintln!("Transaction {} not found", signature),
+            Err(e) => eprintln!("Failed to fetch transaction {}: {}", signature, e),
         }
     }
 
@@ -86,56 +83,23 @@ async fn process_transaction_signatures(signatures: Vec<String>) -> Result<Vec<T
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 struct TransactionResponse {
-    jsonrpc: String,
-    result: TransactionResult,
-    block_time: Option<u64>,
-    id: u8,
-}
-
-#[derive(Serialize, Deserialize, Clone, Debug)]
-struct TransactionResult {
-    meta: TransactionMeta,
+    signature: String,
     slot: u64,
-    transaction: TransactionDetails,
-}
-
-#[derive(Serialize, Deserialize, Clone, Debug)]
-struct TransactionMeta {
-    err: Option<serde_json::Value>,
-    fee: u64,
-    inner_instructions: Vec<serde_json::Value>,
-    post_balances: Vec<u64>,
-    post_token_balances: Vec<serde_json::Value>,
-    pre_balances: Vec<u64>,
-    pre_token_balances: Vec<serde_json::Value>,
-    rewards: Vec<serde_json::Value>,
-    status: serde_json::Value,
-}
-
-#[derive(Serialize, Deserialize, Clone, Debug)]
-struct TransactionDetails {
-    message: TransactionMessage,
-    signatures: Vec<String>,
-}
-
-#[derive(Serialize, Deserialize, Clone, Debug)]
-struct TransactionMessage {
-    account_keys: Vec<String>,
-    header: TransactionHeader,
-    instructions: Vec<TransactionInstruction>,
-    recent_blockhash: String,
-}
-
-#[derive(Serialize, Deserialize, Clone, Debug)]
-struct TransactionHeader {
-    num_readonly_signed_accounts: u8,
-    num_readonly_unsigned_accounts: u8,
-    num_required_signatures: u8,
+    block_time: Option<i64>,
+    // "legacy" or 0
+    version: Option<Value>,
+    err: Option<Value>,
+    fee: Option<u64>,
+    // Static keys then the accounts loaded from lookup tables, with their writable/signer flags
+    account_keys: Vec<AccountKey>,
+    // Outer and inner instructions, accounts resolved and data decoded
+    instructions: Vec<TaggedInstruction>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
-struct TransactionInstruction {
-    accounts: Vec<u8>,
-    data: String,
-    program_id_index: u8,
+struct TaggedInstruction {
+    #[serde(flatten)]
+    instruction: ResolvedInstruction,
+    // AMM source of the instruction or of the AMM instruction invoking it (raydium, jupiter...), see `SwapExtractor::tag`
+    source: Option<String>,
 }
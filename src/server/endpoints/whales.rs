@@ -1,6 +1,5 @@
 use actix_web::web::block;
 use actix_web::{web, HttpResponse, Responder};
-use reqwest::{Client, header};
 use serde::{Serialize, Deserialize};
 use serde_json::{json, Value};
 use solana_sdk::bs58;
@@ -20,6 +19,9 @@ use std::fs::create_dir_all;
 use std::path::{Path, PathBuf};
 use std::error::Error;
 
+use crate::http::solana_rpc_client::SolanaRpcClient;
+use crate::trackers::solana::token_registry::TokenRegistry;
+
 
 //https://solana.com/es/docs/rpc/http/gettokenlargestaccounts
 type TokenSupplyMap = HashMap<(String, u8), TokenSupply>;
@@ -47,24 +49,6 @@ struct FindWhalesRequest {
 }
 
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct TokenSupplyRpcResponse {
-    pub jsonrpc: String,
-    pub result: TokenSupplyRpcResult,
-    pub id: u64,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-pub struct TokenSupplyRpcResult {
-    pub context: RpcContext,
-    pub value: TokenSupply,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-pub struct RpcContext {
-    pub slot: u64,
-}
-
 #[derive(Serialize, Deserialize, Debug)]
 pub struct TokenSupply {
     pub amount: String,
@@ -75,35 +59,11 @@ pub struct TokenSupply {
     pub ui_amount_string: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct WhaleAccountsRpcResponse {
-    pub jsonrpc: String,
-    pub result: WhaleAccountRpcResult,
-    pub id: u64,
-}
-
-
-#[derive(Serialize, Deserialize, Debug)]
-pub struct WhaleAccountRpcResult {
-    pub context: RpcContext,
-    pub value: Vec<WhaleAccount>,
-}
-
-
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct WhaleAccount {
-    address: String,
-    amount: String,
-    decimals: u8,
-    #[serde(rename = "uiAmount")]
-    ui_amount: f64, 
-    #[serde(rename = "uiAmountString")]
-    ui_amount_string: String,
-}
-
 #[derive(Serialize, Deserialize, Debug, Clone)]
 struct WhaleDetail {
     pub address: String,
+    pub mint: String,
+    pub token_symbol: String,
     pub amount: String,
     pub decimals: u8,
     pub ui_amount_string: String,
@@ -118,67 +78,51 @@ pub fn init_routes(cfg: &mut web::ServiceConfig) {
     );
 }
 
-async fn find_whales(request: web::Json<FindWhalesRequest>) -> impl Responder {
-    let whale_data = get_largest_accounts_for_mints(request.token_mint_addresses.clone()).await;
+async fn find_whales(request: web::Json<FindWhalesRequest>, rpc: web::Data<SolanaRpcClient>, token_registry: web::Data<TokenRegistry>) -> impl Responder {
+    let whale_data = get_largest_accounts_for_mints(&rpc, &token_registry, request.token_mint_addresses.clone()).await;
     match whale_data {
         Ok(data) => HttpResponse::Ok().json(data),
         Err(_) => HttpResponse::InternalServerError().finish(),
     }
 }
 
-async fn get_largest_accounts_for_mints(mint_addresses: Vec<String>) -> Result<Vec<WhaleDetail>, Box<dyn Error>> {
+async fn get_largest_accounts_for_mints(rpc: &SolanaRpcClient, token_registry: &TokenRegistry, mint_addresses: Vec<String>) -> Result<Vec<WhaleDetail>, Box<dyn Error>> {
     println!("Finding whales for {:#?}", mint_addresses);
-    let solana = env::var("PRIVATE_SOLANA_QUICKNODE").expect("PRIVATE_SOLANA_QUICKNODE must be set");
-    let client = Client::new();
     let mut all_whales: Vec<WhaleDetail> = Vec::new();
 
-    for mint_address in &mint_addresses {
+    let tokens = token_registry.resolve_many(&mint_addresses).await;
+    for (mint_address, token) in mint_addresses.iter().zip(tokens) {
+        let token_symbol = token.map(|token| token.display_symbol()).unwrap_or_else(|_| "Unknown".to_string());
         // Fetch the total supply for the mint address
-        if let Ok(supply) = get_token_supply(&client, &mint_address).await {
-            let total_supply: Decimal = Decimal::from_f64(supply.ui_amount).unwrap_or_else(|| Decimal::new(0, 0));
+        if let Ok(supply) = rpc.get_token_supply(mint_address).await {
+            let total_supply: Decimal = supply.ui_amount_string.parse::<Decimal>().unwrap_or_else(|_| Decimal::new(0, 0));
 
             println!("Total supply for {:#?} is {:#?}", mint_address.clone(), total_supply.clone());
 
-            let rpc_request = json!({
-                "jsonrpc": "2.0",
-                "id": 1,
-                "method": "getTokenLargestAccounts",
-                "params": [mint_address]
-            });
-
-            println!("Requesting whales  for {:#?} ::: {:#?}", mint_address, rpc_request);
-
-            let response = client
-                .post(solana.clone())
-                .header("Content-Type", "application/json")
-                .json(&rpc_request)
-                .send()
-                .await?;
+            match rpc.get_token_largest_accounts(mint_address).await {
+                Ok(largest_accounts) => {
+                    println!("Largest account holders for {:#?} are: {:#?}", mint_address, largest_accounts);
 
-            if response.status().is_success() {
-                let response_text = response.text().await?;
-                let value: serde_json::Value = serde_json::from_str(&response_text)?;
-                println!("Largest account holders for {:#?} are: {:#?}", mint_address, value);
-                let largest_accounts: WhaleAccountsRpcResponse = serde_json::from_str(&response_text)?;
+                    for account in largest_accounts {
+                        let ui_amount: Decimal = account.amount.ui_amount_string.parse::<Decimal>()?;
 
-                for account in largest_accounts.result.value {
-                    let ui_amount: Decimal = account.ui_amount_string.parse::<Decimal>()?;
+                        let owned_percentage = (ui_amount / total_supply) * Decimal::from(100);
 
-                    let owned_percentage = (ui_amount / total_supply) * Decimal::from(100);
+                        println!("WHALE {:#?} own {:#?} % of {:#?}", account.address.clone(), owned_percentage, mint_address );
 
-                    println!("WHALE {:#?} own {:#?} % of {:#?}", account.address.clone(), owned_percentage, mint_address );
 
-
-                    all_whales.push(WhaleDetail {
-                        address: account.address,
-                        amount: account.amount,
-                        decimals: account.decimals,
-                        ui_amount_string: account.ui_amount_string,
-                        owned_percentage, // Include the ownership percentage
-                    });
+                        all_whales.push(WhaleDetail {
+                            address: account.address,
+                            mint: mint_address.clone(),
+                            token_symbol: token_symbol.clone(),
+                            amount: account.amount.amount,
+                            decimals: account.amount.decimals,
+                            ui_amount_string: account.amount.ui_amount_string,
+                            owned_percentage, // Include the ownership percentage
+                        });
+                    }
                 }
-            } else {
-                println!("Error fetching data for mint address: {}", mint_address);
+                Err(e) => println!("Error fetching data for mint address {}: {}", mint_address, e),
             }
         }
     }
@@ -196,40 +140,6 @@ async fn get_largest_accounts_for_mints(mint_addresses: Vec<String>) -> Result<V
     Ok(all_whales)
 }
 
-
-pub async fn get_token_supply(client: &Client, mint_address: &str) -> Result<TokenSupply, Box<dyn Error>> {
-    println!("Finding token supply for {:#?}", mint_address);
-
-    let rpc_request = json!({
-        "jsonrpc": "2.0",
-        "id": 1,
-        "method": "getTokenSupply",
-        "params": [mint_address]
-    });
-
-    let response = client
-        .post(env::var("PRIVATE_SOLANA_QUICKNODE").expect("PRIVATE_SOLANA_QUICKNODE must be set"))
-        .header("Content-Type", "application/json")
-        .json(&rpc_request)
-        .send()
-        .await?;
-
-    match response.status() {
-        reqwest::StatusCode::OK => {
-            let response_text = response.text().await?;
-            println!("Token supply for {:#?}: {:#?}", mint_address, response_text);
-            let supply_response: TokenSupplyRpcResponse = serde_json::from_str(&response_text)?;
-            println!("Deserialized token response ::: {:#?}", supply_response);
-            Ok(supply_response.result.value)
-        },
-        _ => {
-            let error_message = format!("Error fetching token supply for mint address: {}", mint_address);
-            eprintln!("{}", error_message);
-            Err(Box::new(std::io::Error::new(std::io::ErrorKind::Other, error_message)))
-        },
-    }
-}
-
 pub async fn write_whales_to_csv(program_address: &str, whales: &[WhaleDetail]) -> Result<(), Box<dyn Error>> {
     let date = Local::now().format("%Y-%m-%d").to_string();
     let dir_path = PathBuf::from(format!("data/{}/{}/whales", program_address, date));
@@ -7,13 +7,38 @@ use crate::server::endpoints::signatures_for_address;
 use crate::server::endpoints::holders;
 use crate::server::endpoints::whales;
 use crate::server::endpoints::new_spls;
+use crate::server::endpoints::metrics;
+use crate::server::endpoints::tokens;
+use crate::server::endpoints::decode;
+use crate::server::endpoints::fees;
+use crate::server::endpoints::failures;
+use crate::subscriber::event_bus::BusMetrics;
+use crate::http::solana_rpc_client::SolanaRpcClient;
+use crate::http::birdeye_http_client::BirdeyeHttpClient;
+use crate::trackers::solana::token_registry::TokenRegistry;
+use crate::trackers::solana::idl_registry::IdlRegistry;
+use crate::trackers::solana::lookup_table_registry::LookupTableRegistry;
+use crate::trackers::solana::fee_market::FeeMarket;
+use crate::trackers::solana::failure_tracker::FailureTracker;
 
 use crate::server::endpoints::birdeye::token_prices;
 
 
-pub async fn run_server() -> std::io::Result<()> {
-    HttpServer::new(|| {
+pub async fn run_server(event_metrics: BusMetrics, solana_rpc: SolanaRpcClient, token_registry: TokenRegistry, idl_registry: IdlRegistry,
+                        lookup_tables: LookupTableRegistry, fee_market: FeeMarket,
+                        failure_tracker: FailureTracker) -> std::io::Result<()> {
+    // Created once so every worker shares the same rate limit and circuit breaker
+    let birdeye = BirdeyeHttpClient::from_env();
+    HttpServer::new(move || {
         App::new()
+            .app_data(web::Data::new(event_metrics.clone()))
+            .app_data(web::Data::new(solana_rpc.clone()))
+            .app_data(web::Data::new(birdeye.clone()))
+            .app_data(web::Data::new(token_registry.clone()))
+            .app_data(web::Data::new(idl_registry.clone()))
+            .app_data(web::Data::new(lookup_tables.clone()))
+            .app_data(web::Data::new(fee_market.clone()))
+            .app_data(web::Data::new(failure_tracker.clone()))
             .service(web::scope("/api")
                          .configure(signatures_for_address::init_routes)
                          .configure(holders::init_routes)
@@ -21,6 +46,11 @@ pub async fn run_server() -> std::io::Result<()> {
                          .configure(transactions::init_routes)
                          .configure(accounts::init_routes)
                          .configure(token_prices::init_routes)
+                         .configure(metrics::init_routes)
+                         .configure(tokens::init_routes)
+                         .configure(decode::init_routes)
+                         .configure(fees::init_routes)
+                         .configure(failures::init_routes)
             )
     })
         .bind("127.0.0.1:8080")?
@@ -0,0 +1,338 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::http::solana_rpc_client::SolanaRpcClient;
+use crate::models::solana::solana_rpc::{JsonRpcResponse, RpcAccount};
+use crate::subscriber::consume_stream::{process_text_message, StreamContext};
+use crate::subscriber::event_bus::{EventBus, EventSender};
+use crate::subscriber::fan_in_subscriber::Deduplicator;
+use crate::subscriber::subscription_registry::{SubscriptionEntry, SubscriptionHandle, SubscriptionRegistry};
+use crate::subscriber::websocket_event_types::WebsocketEventTypes;
+use crate::subscriber::websocket_subscriber::SubscriptionBuilder;
+
+/// One line of a capture file (JSONL). Timestamps are millis since epoch at the time we received the data.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CaptureRecord {
+    // Raw text frame exactly as received from the websocket
+    Frame { received_at: i64, endpoint: String, text: String },
+    // A subscription confirmed by the server, needed to map the server ids found in frames back to handles
+    Subscribed { received_at: i64, endpoint: String, handle: SubscriptionHandle, server_id: u64, method: String, args: Vec<String> },
+    // Body of an HTTP RPC call made while processing events (ie. getTransaction keyed by signature)
+    Rpc { received_at: i64, method: String, key: String, body: String },
+}
+
+/// Appends raw frames and RPC responses to a capture file. Cheap to clone, every subscriber can share the same file.
+#[derive(Clone)]
+pub struct FrameRecorder {
+    path: PathBuf,
+    writer: Arc<Mutex<BufWriter<File>>>,
+}
+
+impl FrameRecorder {
+    pub fn create(path: PathBuf) -> Result<Self, Box<dyn Error>> {
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        println!("[[CAPTURE]] Recording to {:?}", path);
+        Ok(Self { path, writer: Arc::new(Mutex::new(BufWriter::new(file))) })
+    }
+
+    pub fn record_frame(&self, endpoint: &str, text: &str) {
+        self.write(&CaptureRecord::Frame {
+            received_at: Utc::now().timestamp_millis(),
+            endpoint: endpoint.to_string(),
+            text: text.to_string(),
+        });
+    }
+
+    pub fn record_subscribed(&self, endpoint: &str, entry: &SubscriptionEntry, server_id: u64) {
+        self.write(&CaptureRecord::Subscribed {
+            received_at: Utc::now().timestamp_millis(),
+            endpoint: endpoint.to_string(),
+            handle: entry.handle,
+            server_id,
+            method: entry.method.clone(),
+            args: entry.args.clone(),
+        });
+    }
+
+    pub fn record_rpc(&self, method: &str, key: &str, body: &str) {
+        self.write(&CaptureRecord::Rpc {
+            received_at: Utc::now().timestamp_millis(),
+            method: method.to_string(),
+            key: key.to_string(),
+            body: body.to_string(),
+        });
+    }
+
+    // Recording must never take the stream down, failures are only logged
+    fn write(&self, record: &CaptureRecord) {
+        let line = match serde_json::to_string(record) {
+            Ok(line) => line,
+            Err(e) => {
+                eprintln!("[[CAPTURE]] Failed to serialize record: {:?}", e);
+                return;
+            }
+        };
+
+        let mut writer = self.writer.lock().unwrap();
+        // Flushed on every record so a crash loses at most the frame being written
+        if let Err(e) = writeln!(writer, "{}", line).and_then(|_| writer.flush()) {
+            eprintln!("[[CAPTURE]] Failed to write to {:?}: {:?}", self.path, e);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ReplaySpeed {
+    Original,
+    // ie. 10.0 replays ten times faster than recorded
+    Accelerated(f64),
+    AsFastAsPossible,
+}
+
+impl ReplaySpeed {
+    /// Parses "original", "max" or a speed factor (ie. "10")
+    pub fn parse(speed: &str) -> Option<Self> {
+        match speed.trim().to_lowercase().as_str() {
+            "original" | "1" => Some(ReplaySpeed::Original),
+            "max" | "fast" => Some(ReplaySpeed::AsFastAsPossible),
+            factor => factor.parse::<f64>().ok()
+                .filter(|factor| *factor > 0.0)
+                .map(ReplaySpeed::Accelerated),
+        }
+    }
+
+    fn scale(&self, elapsed_ms: i64) -> Option<Duration> {
+        let elapsed_ms = elapsed_ms.max(0) as f64;
+        match self {
+            ReplaySpeed::Original => Some(Duration::from_millis(elapsed_ms as u64)),
+            ReplaySpeed::Accelerated(factor) => Some(Duration::from_millis((elapsed_ms / factor) as u64)),
+            ReplaySpeed::AsFastAsPossible => None,
+        }
+    }
+}
+
+/// RPC responses found in a capture, keyed by (method, key).
+#[derive(Clone, Default)]
+pub struct RecordedResponses {
+    responses: Arc<HashMap<(String, String), String>>,
+}
+
+impl RecordedResponses {
+    pub fn get(&self, method: &str, key: &str) -> Option<String> {
+        self.responses.get(&(method.to_string(), key.to_string())).cloned()
+    }
+}
+
+/// Where the HTTP side of event processing gets its data from: the network, optionally recording what it got,
+/// or the responses stored in the capture being replayed.
+#[derive(Clone)]
+pub enum RpcCapture {
+    Live(Option<FrameRecorder>),
+    Replay(RecordedResponses),
+}
+
+impl RpcCapture {
+    pub fn is_replay(&self) -> bool {
+        matches!(self, RpcCapture::Replay(_))
+    }
+
+    /// The recorded response when replaying, None when live
+    pub fn recorded(&self, method: &str, key: &str) -> Option<String> {
+        match self {
+            RpcCapture::Replay(responses) => responses.get(method, key),
+            RpcCapture::Live(_) => None,
+        }
+    }
+
+    pub fn record(&self, method: &str, key: &str, body: &str) {
+        if let RpcCapture::Live(Some(recorder)) = self {
+            recorder.record_rpc(method, key, body);
+        }
+    }
+
+    /// Batched getTransaction at confirmed, "json" encoded for the instruction decoders, processed transactions are not served. None for signatures the node does not know.
+    /// Served from the capture when replaying, recorded (as the whole JSON-RPC response) when a capture is being written.
+    pub async fn get_transactions(&self, rpc: &SolanaRpcClient, signatures: &[String]) -> Vec<Result<Option<Value>, String>> {
+        if self.is_replay() {
+            return signatures.iter()
+                .map(|signature| {
+                    let recorded = self.recorded("getTransaction", signature)
+                        .ok_or_else(|| "not found in the replayed capture".to_string())?;
+                    let response = serde_json::from_str::<JsonRpcResponse<Value>>(&recorded).map_err(|e| e.to_string())?;
+                    Ok(response.result)
+                })
+                .collect();
+        }
+
+        let transactions = rpc.get_transactions::<Value>(signatures, "confirmed", "json").await;
+        signatures.iter().zip(transactions)
+            .map(|(signature, transaction)| {
+                let result = transaction.map_err(|e| e.to_string())?;
+                let recorded = JsonRpcResponse { jsonrpc: "2.0".to_string(), id: Some(1), result, error: None };
+                if let Ok(body) = serde_json::to_string(&recorded) {
+                    self.record("getTransaction", signature, &body);
+                }
+                Ok(recorded.result)
+            })
+            .collect()
+    }
+
+    /// Batched getAccountInfo (base64) cached under `cache_class`, same order as `addresses`. None for accounts that do not exist.
+    /// Served from the capture when replaying, recorded when a capture is being written.
+    pub async fn get_account_infos(&self, rpc: &SolanaRpcClient, cache_class: &str, addresses: &[String]) -> Vec<Result<Option<RpcAccount<Vec<String>>>, String>> {
+        self.account_infos(rpc, cache_class, addresses, false).await
+    }
+
+    /// `get_account_infos` around the RPC cache, see `SolanaRpcClient::refresh_account_infos_as`
+    pub async fn refresh_account_infos(&self, rpc: &SolanaRpcClient, cache_class: &str, addresses: &[String]) -> Vec<Result<Option<RpcAccount<Vec<String>>>, String>> {
+        self.account_infos(rpc, cache_class, addresses, true).await
+    }
+
+    async fn account_infos(&self, rpc: &SolanaRpcClient, cache_class: &str, addresses: &[String], refresh: bool) -> Vec<Result<Option<RpcAccount<Vec<String>>>, String>> {
+        if self.is_replay() {
+            return addresses.iter()
+                .map(|address| {
+                    let recorded = self.recorded("getAccountInfo", address)
+                        .ok_or_else(|| "not found in the replayed capture".to_string())?;
+                    serde_json::from_str::<Option<RpcAccount<Vec<String>>>>(&recorded).map_err(|e| e.to_string())
+                })
+                .collect();
+        }
+
+        let accounts = if refresh {
+            rpc.refresh_account_infos_as::<Vec<String>>(cache_class, addresses, "base64").await
+        } else {
+            rpc.get_account_infos_as::<Vec<String>>(cache_class, addresses, "base64").await
+        };
+        addresses.iter().zip(accounts)
+            .map(|(address, account)| {
+                let account = account.map_err(|e| e.to_string())?;
+                if let Ok(body) = serde_json::to_string(&account) {
+                    self.record("getAccountInfo", address, &body);
+                }
+                Ok(account)
+            })
+            .collect()
+    }
+}
+
+/**
+Feeds a capture file back through `process_text_message`, so the whole notification -> getTransaction -> summary path can be
+reproduced without touching the network.
+
+Subscription confirmations are restored into one registry per recorded endpoint before the frames that depend on them, so events
+are tagged with the same handles they had live. Frames recorded from several endpoints (fan in) go through the same deduplication.
+ */
+pub struct ReplaySource {
+    path: PathBuf,
+    records: Vec<CaptureRecord>,
+    speed: ReplaySpeed,
+}
+
+impl ReplaySource {
+    pub fn load(path: PathBuf, speed: ReplaySpeed) -> Result<Self, Box<dyn Error>> {
+        let reader = BufReader::new(File::open(&path)?);
+        let mut records = Vec::new();
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<CaptureRecord>(&line) {
+                Ok(record) => records.push(record),
+                // A crash while recording can leave a truncated last line
+                Err(e) => eprintln!("[[REPLAY]] Skipping line {} of {:?}: {}", number + 1, path, e),
+            }
+        }
+        println!("[[REPLAY]] Loaded {} records from {:?}", records.len(), path);
+        Ok(Self { path, records, speed })
+    }
+
+    pub fn rpc_responses(&self) -> RecordedResponses {
+        let responses = self.records.iter()
+            .filter_map(|record| match record {
+                CaptureRecord::Rpc { method, key, body, .. } => Some(((method.clone(), key.clone()), body.clone())),
+                _ => None,
+            })
+            .collect();
+        RecordedResponses { responses: Arc::new(responses) }
+    }
+
+    /// Every subscription that was confirmed during the capture, as (handle, method, args)
+    pub fn subscriptions(&self) -> Vec<(SubscriptionHandle, String, Vec<String>)> {
+        let mut subscriptions: Vec<(SubscriptionHandle, String, Vec<String>)> = Vec::new();
+        for record in &self.records {
+            if let CaptureRecord::Subscribed { handle, method, args, .. } = record {
+                if !subscriptions.iter().any(|(known, _, _)| known == handle) {
+                    subscriptions.push((*handle, method.clone(), args.clone()));
+                }
+            }
+        }
+        subscriptions
+    }
+
+    /// Replays every frame in order, honoring the recorded timing according to `speed`.
+    pub async fn run<T: WebsocketEventTypes + Send + 'static, B: SubscriptionBuilder>(self, tx: EventSender<T>) {
+        let mut registries: HashMap<String, Arc<Mutex<SubscriptionRegistry>>> = HashMap::new();
+        // Holds the events of a single frame, drained right after the frame is processed
+        let (merged_sender, merged_receiver) = EventBus::new(usize::MAX).split::<T>();
+        let mut deduplicator = Deduplicator::new(tx);
+        let mut previous_at: Option<i64> = None;
+        let mut frames = 0;
+
+        for record in self.records {
+            match record {
+                CaptureRecord::Subscribed { endpoint, handle, server_id, method, args, .. } => {
+                    let registry = registries.entry(endpoint).or_insert_with(|| Arc::new(Mutex::new(SubscriptionRegistry::new())));
+                    let mut registry = registry.lock().unwrap();
+                    // A resubscription after a reconnect replaces the previous server id
+                    registry.remove(handle);
+                    registry.register_as(handle, &method, args);
+                    if let Some((request_id, _)) = registry.begin_subscribe(handle) {
+                        registry.confirm(request_id, server_id);
+                    }
+                }
+                CaptureRecord::Frame { received_at, endpoint, text } => {
+                    if let Some(delay) = previous_at.and_then(|previous| self.speed.scale(received_at - previous)) {
+                        tokio::time::sleep(delay).await;
+                    }
+                    previous_at = Some(received_at);
+
+                    let registry = registries.entry(endpoint.clone())
+                        .or_insert_with(|| Arc::new(Mutex::new(SubscriptionRegistry::new())))
+                        .clone();
+                    let ctx = StreamContext { registry: &registry, endpoint: &endpoint, recorder: None };
+                    // Replies (ie. unsubscribing orphans) have nowhere to go, there is no connection
+                    let processed = process_text_message::<T, B>(text, &merged_sender, &ctx).await.map_err(|e| e.to_string());
+                    if let Err(e) = processed {
+                        eprintln!("[[REPLAY]] Failed to process frame from {}: {}", endpoint, e);
+                    }
+
+                    while let Some(event) = merged_receiver.try_recv() {
+                        if !deduplicator.forward(event).await {
+                            println!("[[REPLAY]] Receiver dropped, stopping replay of {:?}", self.path);
+                            return;
+                        }
+                    }
+                    frames += 1;
+                }
+                CaptureRecord::Rpc { .. } => {}
+            }
+        }
+
+        println!("[[REPLAY]] Finished replaying {} frames from {:?}", frames, self.path);
+    }
+}
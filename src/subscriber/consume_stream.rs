@@ -1,76 +1,210 @@
 use async_trait::async_trait;
-use crossbeam_channel::Sender;
-use futures_util::StreamExt;
+use futures_util::{SinkExt, StreamExt};
 use serde_json::Value;
 use std::error::Error as StdError;
+use std::sync::{Arc, Mutex};
 use tokio::net::TcpStream;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::time::{timeout, Instant};
 use tokio_tungstenite::{connect_async, tungstenite::protocol::Message, WebSocketStream, MaybeTlsStream};
 use tungstenite::Error;
 
+use crate::subscriber::capture::FrameRecorder;
+use crate::subscriber::event_bus::EventSender;
+use crate::subscriber::reconnect::{ReconnectPolicy, StreamEnd};
+use crate::subscriber::subscription_registry::{unsubscribe_method, Confirmation, SubscriberCommand, SubscriptionEvent, SubscriptionRegistry};
 use crate::subscriber::websocket_event_types::WebsocketEventTypes;
+use crate::subscriber::websocket_subscriber::SubscriptionBuilder;
 
 
-pub async fn consume_stream<T: WebsocketEventTypes + Send + 'static>(
+/// Everything the frame handlers need besides the frame itself.
+pub struct StreamContext<'a> {
+    pub registry: &'a Arc<Mutex<SubscriptionRegistry>>,
+    // Label of the endpoint, tagged on every event
+    pub endpoint: &'a str,
+    // When set, every raw frame is appended to the capture file before being processed
+    pub recorder: Option<&'a FrameRecorder>,
+}
+
+/// Reads the stream until the connection is no longer usable and reports why.
+/// Reconnecting is the caller's job (see `WebSocketSubscriber::run`).
+///
+/// Besides notifications it also reads the JSON-RPC responses to our own requests to keep the registry's
+/// server ids up to date, and sends the (un)subscribe commands issued through `SubscriptionControl`.
+pub async fn consume_stream<T: WebsocketEventTypes + Send + 'static, B: SubscriptionBuilder>(
     ws_stream: &mut WebSocketStream<MaybeTlsStream<TcpStream>>,
-    tx: Sender<T>,
-) {
-    while let Some(message) = ws_stream.next().await {
-        // println!("Got message message: {:?}", message);
-        match message {
+    tx: EventSender<T>,
+    policy: &ReconnectPolicy,
+    commands: &mut UnboundedReceiver<SubscriberCommand>,
+    ctx: &StreamContext<'_>,
+) -> StreamEnd {
+    let mut awaiting_pong = false;
+    let mut last_notification = Instant::now();
+
+    loop {
+        let wait = if awaiting_pong { policy.ping_timeout } else { policy.ping_interval };
+        let received = tokio::select! {
+            Some(command) = commands.recv() => {
+                if let Some(message) = command_message::<B>(command, ctx.registry) {
+                    if let Err(e) = ws_stream.send(message).await {
+                        return StreamEnd::Errored(e.to_string());
+                    }
+                }
+                continue;
+            }
+            received = timeout(wait, ws_stream.next()) => received,
+        };
+
+        let message = match received {
+            Ok(Some(message)) => message,
+            Ok(None) => return StreamEnd::Closed,
+            Err(_) if awaiting_pong => return StreamEnd::PingTimeout,
+            Err(_) => {
+                // Nothing received for a whole ping interval, probe the connection
+                if let Err(e) = ws_stream.send(Message::Ping(Vec::new())).await {
+                    return StreamEnd::Errored(e.to_string());
+                }
+                awaiting_pong = true;
+                continue;
+            }
+        };
+
+        // Any frame proves the connection is alive
+        awaiting_pong = false;
 
+        match message {
             Ok(Message::Text(text)) => {
+                last_notification = Instant::now();
+                if let Some(recorder) = ctx.recorder {
+                    recorder.record_frame(ctx.endpoint, &text);
+                }
                 // if text.contains("initialize2") {
                 // println!("[[CONSUM STREAM]] GOT MESSAGE: {}", text);
-                if let Err(e) = process_text_message(text, &tx).await {
-                    // eprintln!("Failed to process text message: {:?}", e);
-                }
+                // A message that fails to process has nothing to reply
+                let replies = process_text_message::<T, B>(text, &tx, ctx).await.unwrap_or_default();
                 // }
-
-
+                for reply in replies {
+                    if let Err(e) = ws_stream.send(reply).await {
+                        return StreamEnd::Errored(e.to_string());
+                    }
+                }
+            }
+            Ok(Message::Close(frame)) => {
+                println!("[[CONSUME STREAM]] Server closed the connection: {:?}", frame);
+                return StreamEnd::Closed;
+            }
+            Err(e) => {
+                eprintln!("Error receiving message: {:?}", e);
+                return StreamEnd::Errored(e.to_string());
             }
-            Err(e) => eprintln!("Error receiving message: {:?}", e),
             _ => {}
         }
+
+        if let Some(stall_timeout) = policy.stall_timeout {
+            if last_notification.elapsed() > stall_timeout {
+                return StreamEnd::Stalled;
+            }
+        }
+    }
+}
+
+fn command_message<B: SubscriptionBuilder>(
+    command: SubscriberCommand,
+    registry: &Arc<Mutex<SubscriptionRegistry>>,
+) -> Option<Message> {
+    let mut registry = registry.lock().unwrap();
+    match command {
+        // Skipped if the replay on (re)connect already sent it
+        SubscriberCommand::Subscribe(handle) => registry.begin_subscribe(handle)
+            .and_then(|(request_id, entry)| match B::build_subscription_message(request_id, &entry.method, &entry.args) {
+                Ok(message) => {
+                    println!("[[CONSUME STREAM]] Subscribing {} ({})", entry.handle, entry.method);
+                    Some(message)
+                }
+                Err(e) => {
+                    eprintln!("[[SUBSCRIBER]] Skipping subscription {} ({}): {}", entry.handle, entry.method, e);
+                    None
+                }
+            }),
+        // Server ids from a previous connection could point to someone else's subscription on this one
+        SubscriberCommand::Unsubscribe { method, server_id, epoch } if epoch == registry.epoch() => {
+            let request_id = registry.next_request_id();
+            println!("[[CONSUME STREAM]] Unsubscribing {} {}", method, server_id);
+            Some(B::build_unsubscribe_message(request_id, &method, server_id))
+        }
+        SubscriberCommand::Unsubscribe { .. } => None,
     }
 }
 
-async fn process_text_message<T: WebsocketEventTypes + Send + 'static>(
+/// Deserializes a raw frame, updates the registry with responses to our own requests and forwards the tagged events.
+/// Returns the messages that must be sent back (ie. unsubscribing orphaned subscriptions).
+pub async fn process_text_message<T: WebsocketEventTypes + Send + 'static, B: SubscriptionBuilder>(
     text: String,
-    tx: &Sender<T>,
-) -> Result<(), Box<dyn StdError>> {
+    tx: &EventSender<T>,
+    ctx: &StreamContext<'_>,
+) -> Result<Vec<Message>, Box<dyn StdError>> {
+    let mut replies = Vec::new();
+    let mut deserialized = Vec::new();
     let event_jsons: Result<Value, _> = serde_json::from_str(&text);
-    match event_jsons {
-        Ok(events) => {
-            process_json_events(events, tx)?;
-        }
+    let processed = match event_jsons {
+        Ok(events) => process_json_events::<T, B>(events, ctx, &mut deserialized, &mut replies),
         Err(e) => {
             eprintln!("Error parsing JSON: {:?}", e);
+            Ok(())
         }
+    };
+    // Box<dyn Error> is not Send, it can't be held across the sends below
+    let processed = processed.map_err(|e| e.to_string());
+
+    // Whatever was deserialized before a failure in the same batch still goes out
+    for event in deserialized {
+        tx.send(event).await.map_err(|e| e.to_string())?;
     }
-    Ok(())
+    processed.map(|_| replies).map_err(|e| e.into())
 }
 
-fn process_json_events<T: WebsocketEventTypes + Send + 'static>(
+fn process_json_events<T: WebsocketEventTypes + Send + 'static, B: SubscriptionBuilder>(
     events: Value,
-    tx: &Sender<T>,
+    ctx: &StreamContext<'_>,
+    deserialized: &mut Vec<SubscriptionEvent<T>>,
+    replies: &mut Vec<Message>,
 ) -> Result<(), Box<dyn StdError>> {
     if events.is_array() {
         for event in events.as_array().unwrap() {
-            process_single_event(event, tx)?;
+            process_single_event::<T, B>(event, ctx, deserialized, replies)?;
         }
     } else {
-        process_single_event(&events, tx)?;
+        process_single_event::<T, B>(&events, ctx, deserialized, replies)?;
     }
     Ok(())
 }
 
-fn process_single_event<T: WebsocketEventTypes + Send + 'static>(
+fn process_single_event<T: WebsocketEventTypes + Send + 'static, B: SubscriptionBuilder>(
     event: &Value,
-    sender: &Sender<T>,
+    ctx: &StreamContext<'_>,
+    deserialized: &mut Vec<SubscriptionEvent<T>>,
+    replies: &mut Vec<Message>,
 ) -> Result<(), Box<dyn StdError>> {
+    // Responses to our own requests have an id and no method
+    if event.get("method").is_none() {
+        if let Some(request_id) = event["id"].as_u64() {
+            process_rpc_response::<B>(request_id, event, ctx, replies);
+            return Ok(());
+        }
+    }
+
+    let subscription = event["params"]["subscription"].as_u64()
+        .and_then(|server_id| ctx.registry.lock().unwrap().handle_for(server_id));
+
     match T::deserialize_event(event) {
         Ok(event) => {
-            sender.send(event).map_err(|e| e.into())
+            if event.ends_subscription() {
+                if let Some(handle) = subscription {
+                    ctx.registry.lock().unwrap().remove(handle);
+                }
+            }
+            deserialized.push(SubscriptionEvent { subscription, endpoint: ctx.endpoint.to_string(), event });
+            Ok(())
         }
         Err(e) => {
             // eprintln!("consume_stream.process_single_event: Error deserializing message: {:?}", e);
@@ -79,3 +213,35 @@ fn process_single_event<T: WebsocketEventTypes + Send + 'static>(
     }
 }
 
+fn process_rpc_response<B: SubscriptionBuilder>(
+    request_id: u64,
+    response: &Value,
+    ctx: &StreamContext<'_>,
+    replies: &mut Vec<Message>,
+) {
+    let mut registry = ctx.registry.lock().unwrap();
+
+    if let Some(error) = response.get("error") {
+        let handle = registry.reject(request_id);
+        eprintln!("[[CONSUME STREAM]] Request {} ({:?}) was rejected: {}", request_id, handle, error);
+        return;
+    }
+
+    // Unsubscribe responses carry a bool, only subscribe responses carry the subscription number
+    if let Some(server_id) = response["result"].as_u64() {
+        match registry.confirm(request_id, server_id) {
+            Confirmation::Subscribed(handle) => {
+                println!("[[CONSUME STREAM]] {} confirmed as subscription {}", handle, server_id);
+                // Replays need the handle <-> server id mapping to tag events the same way
+                if let (Some(recorder), Some(entry)) = (ctx.recorder, registry.get(handle)) {
+                    recorder.record_subscribed(ctx.endpoint, entry, server_id);
+                }
+            }
+            Confirmation::Orphaned { method, server_id } => {
+                let unsubscribe_request_id = registry.next_request_id();
+                replies.push(B::build_unsubscribe_message(unsubscribe_request_id, &unsubscribe_method(&method), server_id));
+            }
+            Confirmation::Unknown => {}
+        }
+    }
+}
@@ -0,0 +1,409 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+
+use crate::subscriber::subscription_registry::SubscriptionEvent;
+use crate::subscriber::websocket_event_types::WebsocketEventTypes;
+
+/// What happens to an event published while the bus is full. Configured per event type (see `WebsocketEventTypes::event_type`).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    // Wait (asynchronously) for room, nothing is lost but a slow consumer slows the producer down
+    Block,
+    // Evict the oldest queued event of the same type to make room
+    DropOldest,
+    // Discard the event being published
+    DropNewest,
+    // Replace the queued event with the same `coalesce_key`, only the latest state matters (ie. account updates).
+    // Falls back to DropOldest when nothing can be coalesced
+    CoalesceByKey,
+}
+
+/// Counters for one event type.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct EventTypeMetrics {
+    pub enqueued: u64,
+    pub delivered: u64,
+    pub dropped: u64,
+    pub coalesced: u64,
+    // Currently waiting in the bus
+    pub queued: u64,
+    // Time between publishing and delivery to the consumer
+    pub last_lag_ms: u64,
+    pub max_lag_ms: u64,
+}
+
+/// Per event type counters of an `EventBus`, cheap to clone and safe to read from anywhere (ie. the http server).
+#[derive(Clone, Default)]
+pub struct BusMetrics {
+    event_types: Arc<Mutex<HashMap<String, EventTypeMetrics>>>,
+}
+
+impl BusMetrics {
+    fn update<F: FnOnce(&mut EventTypeMetrics)>(&self, event_type: &str, update: F) {
+        let mut event_types = self.event_types.lock().unwrap();
+        update(event_types.entry(event_type.to_string()).or_default());
+    }
+
+    pub fn snapshot(&self) -> HashMap<String, EventTypeMetrics> {
+        self.event_types.lock().unwrap().clone()
+    }
+}
+
+/// Returned by `EventSender::send` once the receiver is gone, carries the event back.
+#[derive(Debug)]
+pub struct BusClosed<T>(pub SubscriptionEvent<T>);
+
+impl<T> fmt::Display for BusClosed<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "event bus receiver dropped")
+    }
+}
+
+impl<T: fmt::Debug> std::error::Error for BusClosed<T> {}
+
+struct Queued<T> {
+    enqueued_at: Instant,
+    event_type: String,
+    coalesce_key: Option<String>,
+    event: SubscriptionEvent<T>,
+}
+
+struct Shared<T> {
+    queue: Mutex<VecDeque<Queued<T>>>,
+    capacity: usize,
+    policies: HashMap<String, OverflowPolicy>,
+    metrics: BusMetrics,
+    senders: AtomicUsize,
+    receiver_dropped: AtomicBool,
+    not_empty: Notify,
+    not_full: Notify,
+}
+
+/**
+Async, bounded channel between the subscribers and whoever processes their events.
+
+Unlike a blocking channel, a full bus never parks a runtime worker: `Block` publishers await for room and every other
+policy resolves the overflow right away. Every event type gets its own counters so dropped notifications are visible.
+ */
+pub struct EventBus {
+    capacity: usize,
+    policies: HashMap<String, OverflowPolicy>,
+    metrics: BusMetrics,
+}
+
+impl EventBus {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            policies: HashMap::new(),
+            metrics: BusMetrics::default(),
+        }
+    }
+
+    /// Event types without their own policy use Block.
+    pub fn with_policy(mut self, event_type: &str, policy: OverflowPolicy) -> Self {
+        self.policies.insert(event_type.to_string(), policy);
+        self
+    }
+
+    pub fn metrics(&self) -> BusMetrics {
+        self.metrics.clone()
+    }
+
+    pub fn split<T>(self) -> (EventSender<T>, EventReceiver<T>) {
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(VecDeque::new()),
+            capacity: self.capacity,
+            policies: self.policies,
+            metrics: self.metrics,
+            senders: AtomicUsize::new(1),
+            receiver_dropped: AtomicBool::new(false),
+            not_empty: Notify::new(),
+            not_full: Notify::new(),
+        });
+        (EventSender { shared: shared.clone() }, EventReceiver { shared })
+    }
+}
+
+pub struct EventSender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Clone for EventSender<T> {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, Ordering::SeqCst);
+        Self { shared: self.shared.clone() }
+    }
+}
+
+impl<T> Drop for EventSender<T> {
+    fn drop(&mut self) {
+        // The last sender wakes the receiver up so it can see the bus is closed
+        if self.shared.senders.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.shared.not_empty.notify_one();
+        }
+    }
+}
+
+impl<T: WebsocketEventTypes> EventSender<T> {
+    /// Publishes the event according to the overflow policy of its type.
+    /// Only `Block` ever waits, dropping or coalescing is not an error.
+    pub async fn send(&self, event: SubscriptionEvent<T>) -> Result<(), BusClosed<T>> {
+        let event_type = event.event.event_type();
+        let policy = self.shared.policies.get(&event_type).copied().unwrap_or(OverflowPolicy::Block);
+        let coalesce_key = match policy {
+            OverflowPolicy::CoalesceByKey => event.event.coalesce_key()
+                .map(|key| format!("{:?}:{}", event.subscription, key)),
+            _ => None,
+        };
+        let mut queued = Queued { enqueued_at: Instant::now(), event_type, coalesce_key, event };
+
+        loop {
+            let notified = self.shared.not_full.notified();
+            tokio::pin!(notified);
+            // Registered before checking the queue so a pop in between can't be missed
+            notified.as_mut().enable();
+
+            match self.try_enqueue(queued, policy) {
+                Ok(()) => return Ok(()),
+                Err(Rejected::Full(rejected)) => queued = rejected,
+                Err(Rejected::Closed(rejected)) => return Err(BusClosed(rejected.event)),
+            }
+            notified.await;
+        }
+    }
+
+    fn try_enqueue(&self, queued: Queued<T>, policy: OverflowPolicy) -> Result<(), Rejected<T>> {
+        if self.shared.receiver_dropped.load(Ordering::SeqCst) {
+            return Err(Rejected::Closed(queued));
+        }
+
+        let metrics = &self.shared.metrics;
+        let mut queue = self.shared.queue.lock().unwrap();
+        if queue.len() >= self.shared.capacity {
+            match policy {
+                OverflowPolicy::Block => return Err(Rejected::Full(queued)),
+                OverflowPolicy::DropNewest => {
+                    metrics.update(&queued.event_type, |m| m.dropped += 1);
+                    return Ok(());
+                }
+                OverflowPolicy::CoalesceByKey | OverflowPolicy::DropOldest => {
+                    if policy == OverflowPolicy::CoalesceByKey && queued.coalesce_key.is_some() {
+                        let same_key = queue.iter_mut().find(|pending| pending.coalesce_key == queued.coalesce_key);
+                        if let Some(pending) = same_key {
+                            // Keeps its place in line and its original enqueue time, lag measures how stale the slot is
+                            pending.event = queued.event;
+                            metrics.update(&queued.event_type, |m| m.coalesced += 1);
+                            return Ok(());
+                        }
+                    }
+
+                    // Only events of the same type are evicted, other types keep the guarantees of their own policy
+                    match queue.iter().position(|pending| pending.event_type == queued.event_type) {
+                        Some(oldest) => {
+                            queue.remove(oldest);
+                            metrics.update(&queued.event_type, |m| {
+                                m.dropped += 1;
+                                m.queued = m.queued.saturating_sub(1);
+                            });
+                        }
+                        None => {
+                            metrics.update(&queued.event_type, |m| m.dropped += 1);
+                            return Ok(());
+                        }
+                    }
+                }
+            }
+        }
+
+        metrics.update(&queued.event_type, |m| {
+            m.enqueued += 1;
+            m.queued += 1;
+        });
+        queue.push_back(queued);
+        drop(queue);
+        self.shared.not_empty.notify_one();
+        Ok(())
+    }
+}
+
+enum Rejected<T> {
+    Full(Queued<T>),
+    Closed(Queued<T>),
+}
+
+pub struct EventReceiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Drop for EventReceiver<T> {
+    fn drop(&mut self) {
+        self.shared.receiver_dropped.store(true, Ordering::SeqCst);
+        self.shared.not_full.notify_waiters();
+    }
+}
+
+impl<T> EventReceiver<T> {
+    /// Waits for the next event, None once every sender is gone and the bus is drained.
+    pub async fn recv(&mut self) -> Option<SubscriptionEvent<T>> {
+        loop {
+            let notified = self.shared.not_empty.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            if let Some(event) = self.try_recv() {
+                return Some(event);
+            }
+            if self.shared.senders.load(Ordering::SeqCst) == 0 {
+                return None;
+            }
+            notified.await;
+        }
+    }
+
+    pub fn try_recv(&self) -> Option<SubscriptionEvent<T>> {
+        let queued = self.shared.queue.lock().unwrap().pop_front()?;
+        let lag_ms = queued.enqueued_at.elapsed().as_millis() as u64;
+        self.shared.metrics.update(&queued.event_type, |m| {
+            m.delivered += 1;
+            m.queued = m.queued.saturating_sub(1);
+            m.last_lag_ms = lag_ms;
+            m.max_lag_ms = m.max_lag_ms.max(lag_ms);
+        });
+        self.shared.not_full.notify_one();
+        Some(queued.event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+    use std::time::Duration;
+
+    use serde_json::Value;
+
+    use crate::subscriber::reconnect::StreamGap;
+    use crate::subscriber::subscription_registry::SubscriptionHandle;
+
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct TestEvent {
+        event_type: &'static str,
+        key: Option<&'static str>,
+        value: u64,
+    }
+
+    impl WebsocketEventTypes for TestEvent {
+        fn event_type(&self) -> String {
+            self.event_type.to_string()
+        }
+
+        fn deserialize_event(_value: &Value) -> Result<Self, Box<dyn Error>> {
+            Err("test events are built, not deserialized".into())
+        }
+
+        fn stream_gap(_gap: StreamGap) -> Self {
+            TestEvent { event_type: "StreamGap", key: None, value: 0 }
+        }
+
+        fn coalesce_key(&self) -> Option<String> {
+            self.key.map(String::from)
+        }
+    }
+
+    fn event(event_type: &'static str, key: Option<&'static str>, value: u64) -> SubscriptionEvent<TestEvent> {
+        SubscriptionEvent {
+            subscription: Some(SubscriptionHandle(1)),
+            endpoint: "wss://test".to_string(),
+            event: TestEvent { event_type, key, value },
+        }
+    }
+
+    fn drain(receiver: &EventReceiver<TestEvent>) -> Vec<(&'static str, u64)> {
+        std::iter::from_fn(|| receiver.try_recv()).map(|event| (event.event.event_type, event.event.value)).collect()
+    }
+
+    #[tokio::test]
+    async fn drop_newest_discards_the_event_published_while_full() {
+        let bus = EventBus::new(2).with_policy("slot", OverflowPolicy::DropNewest);
+        let metrics = bus.metrics();
+        let (sender, receiver) = bus.split();
+        for value in 1..=3 {
+            sender.send(event("slot", None, value)).await.unwrap();
+        }
+
+        assert_eq!(drain(&receiver), vec![("slot", 1), ("slot", 2)]);
+        let slot = &metrics.snapshot()["slot"];
+        assert_eq!((slot.enqueued, slot.delivered, slot.dropped, slot.queued), (2, 2, 1, 0));
+    }
+
+    #[tokio::test]
+    async fn drop_oldest_only_evicts_events_of_the_same_type() {
+        let bus = EventBus::new(2).with_policy("slot", OverflowPolicy::DropOldest);
+        let metrics = bus.metrics();
+        let (sender, receiver) = bus.split();
+        sender.send(event("log", None, 1)).await.unwrap();
+        sender.send(event("slot", None, 1)).await.unwrap();
+        sender.send(event("slot", None, 2)).await.unwrap();
+        assert_eq!(drain(&receiver), vec![("log", 1), ("slot", 2)]);
+
+        // Nothing of its type to evict: the event itself is dropped
+        sender.send(event("log", None, 2)).await.unwrap();
+        sender.send(event("log", None, 3)).await.unwrap();
+        sender.send(event("slot", None, 3)).await.unwrap();
+        assert_eq!(drain(&receiver), vec![("log", 2), ("log", 3)]);
+        assert_eq!(metrics.snapshot()["slot"].dropped, 2);
+    }
+
+    #[tokio::test]
+    async fn coalesce_replaces_the_queued_event_with_the_same_key() {
+        let bus = EventBus::new(2).with_policy("account", OverflowPolicy::CoalesceByKey);
+        let metrics = bus.metrics();
+        let (sender, receiver) = bus.split();
+        sender.send(event("account", Some("a"), 1)).await.unwrap();
+        sender.send(event("account", Some("b"), 1)).await.unwrap();
+        sender.send(event("account", Some("a"), 2)).await.unwrap();
+        assert_eq!(drain(&receiver), vec![("account", 2), ("account", 1)]);
+        assert_eq!(metrics.snapshot()["account"].coalesced, 1);
+
+        // No key to coalesce on: falls back to evicting the oldest
+        sender.send(event("account", Some("a"), 3)).await.unwrap();
+        sender.send(event("account", Some("b"), 3)).await.unwrap();
+        sender.send(event("account", None, 4)).await.unwrap();
+        assert_eq!(drain(&receiver), vec![("account", 3), ("account", 4)]);
+    }
+
+    #[tokio::test]
+    async fn block_waits_for_room() {
+        let (sender, mut receiver) = EventBus::new(1).split();
+        sender.send(event("log", None, 1)).await.unwrap();
+
+        let blocked = tokio::spawn(async move { sender.send(event("log", None, 2)).await.is_ok() });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!blocked.is_finished());
+
+        assert_eq!(receiver.recv().await.map(|event| event.event.value), Some(1));
+        assert!(blocked.await.unwrap());
+        assert_eq!(receiver.recv().await.map(|event| event.event.value), Some(2));
+        // Every sender is gone and the bus is drained
+        assert!(receiver.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn send_fails_once_the_receiver_is_dropped() {
+        let (sender, receiver) = EventBus::new(1).split::<TestEvent>();
+        drop(receiver);
+        let Err(BusClosed(returned)) = sender.send(event("log", None, 7)).await else {
+            panic!("expected the bus to be closed");
+        };
+        assert_eq!(returned.event.value, 7);
+    }
+}
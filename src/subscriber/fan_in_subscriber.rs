@@ -0,0 +1,322 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use crate::subscriber::event_bus::{EventBus, EventReceiver, EventSender};
+use crate::subscriber::subscription_registry::{SubscriptionControl, SubscriptionEvent, SubscriptionHandle, SubscriptionSink};
+use crate::subscriber::websocket_event_types::WebsocketEventTypes;
+use crate::subscriber::websocket_subscriber::{SubscriptionBuilder, WebSocketSubscriber};
+
+/// How many dedup keys we remember. Duplicates arriving after this many newer events are forwarded again.
+const DEFAULT_DEDUP_CAPACITY: usize = 50_000;
+/// Room between the endpoints and the deduplication stage, endpoints wait (asynchronously) when it is full.
+const MERGE_CAPACITY: usize = 10_000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct EndpointStats {
+    pub delivered_first: u64,
+    pub duplicates: u64,
+    // How far behind the first endpoint this one was, summed over its duplicates
+    pub total_lag_ms: u64,
+}
+
+impl EndpointStats {
+    pub fn average_lag_ms(&self) -> f64 {
+        if self.duplicates == 0 {
+            0.0
+        } else {
+            self.total_lag_ms as f64 / self.duplicates as f64
+        }
+    }
+}
+
+/// Per-endpoint delivery counters, shared with whoever wants to print the latency leaderboard.
+#[derive(Clone, Default)]
+pub struct FanInStats {
+    endpoints: Arc<Mutex<HashMap<String, EndpointStats>>>,
+}
+
+impl FanInStats {
+    fn record_first(&self, endpoint: &str) {
+        let mut endpoints = self.endpoints.lock().unwrap();
+        endpoints.entry(endpoint.to_string()).or_default().delivered_first += 1;
+    }
+
+    fn record_duplicate(&self, endpoint: &str, lag_ms: u64) {
+        let mut endpoints = self.endpoints.lock().unwrap();
+        let stats = endpoints.entry(endpoint.to_string()).or_default();
+        stats.duplicates += 1;
+        stats.total_lag_ms += lag_ms;
+    }
+
+    /// Endpoints sorted by how many events they delivered first
+    pub fn leaderboard(&self) -> Vec<(String, EndpointStats)> {
+        let endpoints = self.endpoints.lock().unwrap();
+        let mut leaderboard: Vec<(String, EndpointStats)> = endpoints.iter()
+            .map(|(endpoint, stats)| (endpoint.clone(), stats.clone()))
+            .collect();
+        leaderboard.sort_by_key(|(_, stats)| Reverse(stats.delivered_first));
+        leaderboard
+    }
+}
+
+/// Adds and drops a subscription on every endpoint of a `FanInSubscriber` under the same handle.
+#[derive(Clone)]
+pub struct FanInControl {
+    controls: Vec<SubscriptionControl>,
+    next_handle: Arc<AtomicU64>,
+}
+
+impl FanInControl {
+    pub fn subscribe(&self, method: &str, args: Vec<String>) -> SubscriptionHandle {
+        let handle = SubscriptionHandle(self.next_handle.fetch_add(1, Ordering::SeqCst));
+        for control in &self.controls {
+            control.subscribe_as(handle, method, args.clone());
+        }
+        handle
+    }
+
+    pub fn unsubscribe(&self, handle: SubscriptionHandle) -> bool {
+        let mut removed = false;
+        for control in &self.controls {
+            removed |= control.unsubscribe(handle);
+        }
+        removed
+    }
+}
+
+impl SubscriptionSink for FanInControl {
+    fn subscribe(&self, method: &str, args: Vec<String>) -> SubscriptionHandle {
+        FanInControl::subscribe(self, method, args)
+    }
+
+    fn unsubscribe(&self, handle: SubscriptionHandle) -> bool {
+        FanInControl::unsubscribe(self, handle)
+    }
+}
+
+/**
+Keeps the same subscriptions open on N endpoints at once and merges their notifications onto a single channel.
+
+Every endpoint runs its own `WebSocketSubscriber` (so each one reconnects on its own) and all of them share subscription handles.
+Notifications are deduplicated by (subscription, `dedup_key`), only the first copy is forwarded and it keeps the `endpoint`
+that delivered it. Later copies only feed the stats, which double as a per-provider latency leaderboard.
+ */
+pub struct FanInSubscriber<B: SubscriptionBuilder> {
+    endpoints: Vec<WebSocketSubscriber<B>>,
+    control: FanInControl,
+    stats: FanInStats,
+}
+
+impl<B: SubscriptionBuilder + Send + Sync + 'static> FanInSubscriber<B> {
+    pub fn new(endpoints: Vec<WebSocketSubscriber<B>>) -> Self {
+        let control = FanInControl {
+            controls: endpoints.iter().map(|endpoint| endpoint.control()).collect(),
+            next_handle: Arc::new(AtomicU64::new(1)),
+        };
+        Self { endpoints, control, stats: FanInStats::default() }
+    }
+
+    pub fn control(&self) -> FanInControl {
+        self.control.clone()
+    }
+
+    pub fn stats(&self) -> FanInStats {
+        self.stats.clone()
+    }
+
+    /// Runs every endpoint and the deduplication stage until all of them stop.
+    /// Stream gaps are forwarded as is: a gap on one endpoint is only a real gap if the others were down too.
+    pub async fn run<T: WebsocketEventTypes + Send + 'static>(self, tx: EventSender<T>) {
+        let (merged_sender, merged_receiver) = EventBus::new(MERGE_CAPACITY).split::<T>();
+
+        let mut tasks = Vec::new();
+        for mut endpoint in self.endpoints {
+            let sender = merged_sender.clone();
+            tasks.push(tokio::spawn(async move {
+                endpoint.run::<T>(sender).await;
+            }));
+        }
+        drop(merged_sender);
+
+        let stats = self.stats.clone();
+        tasks.push(tokio::spawn(async move {
+            deduplicate(merged_receiver, tx, stats, DEFAULT_DEDUP_CAPACITY).await;
+        }));
+
+        for task in tasks {
+            if let Err(e) = task.await {
+                eprintln!("[[FAN IN]] Endpoint task failed: {:?}", e);
+            }
+        }
+    }
+}
+
+/// Forwards the first copy of every (subscription, `dedup_key`) and drops later ones, remembering the last `capacity` keys.
+/// Events without a dedup key are always forwarded.
+pub struct Deduplicator<T> {
+    tx: EventSender<T>,
+    stats: FanInStats,
+    capacity: usize,
+    seen: HashMap<(Option<SubscriptionHandle>, String), Instant>,
+    order: VecDeque<(Option<SubscriptionHandle>, String)>,
+}
+
+impl<T: WebsocketEventTypes> Deduplicator<T> {
+    pub fn new(tx: EventSender<T>) -> Self {
+        Self { tx, stats: FanInStats::default(), capacity: DEFAULT_DEDUP_CAPACITY, seen: HashMap::new(), order: VecDeque::new() }
+    }
+
+    pub fn with_stats(mut self, stats: FanInStats) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Returns false once the receiver is gone.
+    pub async fn forward(&mut self, tagged: SubscriptionEvent<T>) -> bool {
+        if let Some(key) = tagged.event.dedup_key().map(|key| (tagged.subscription, key)) {
+            if let Some(first_seen) = self.seen.get(&key) {
+                self.stats.record_duplicate(&tagged.endpoint, first_seen.elapsed().as_millis() as u64);
+                return true;
+            }
+
+            self.stats.record_first(&tagged.endpoint);
+            self.seen.insert(key.clone(), Instant::now());
+            self.order.push_back(key);
+            if self.order.len() > self.capacity {
+                if let Some(oldest) = self.order.pop_front() {
+                    self.seen.remove(&oldest);
+                }
+            }
+        }
+
+        self.tx.send(tagged).await.is_ok()
+    }
+}
+
+async fn deduplicate<T: WebsocketEventTypes>(
+    mut merged: EventReceiver<T>,
+    tx: EventSender<T>,
+    stats: FanInStats,
+    capacity: usize,
+) {
+    let mut deduplicator = Deduplicator::new(tx).with_stats(stats).with_capacity(capacity);
+    while let Some(tagged) = merged.recv().await {
+        if !deduplicator.forward(tagged).await {
+            eprintln!("[[FAN IN]] Receiver dropped, stopping deduplication");
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use serde_json::Value;
+
+    use super::*;
+    use crate::subscriber::reconnect::StreamGap;
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum TestEvent {
+        Notification(String),
+        Heartbeat,
+        Gap,
+    }
+
+    impl WebsocketEventTypes for TestEvent {
+        fn event_type(&self) -> String {
+            format!("{:?}", self)
+        }
+
+        fn deserialize_event(_: &Value) -> Result<Self, Box<dyn Error>> {
+            Err("not deserialized in tests".into())
+        }
+
+        fn stream_gap(_: StreamGap) -> Self {
+            TestEvent::Gap
+        }
+
+        fn dedup_key(&self) -> Option<String> {
+            match self {
+                TestEvent::Notification(key) => Some(key.clone()),
+                _ => None,
+            }
+        }
+    }
+
+    fn tagged(subscription: u64, endpoint: &str, event: TestEvent) -> SubscriptionEvent<TestEvent> {
+        SubscriptionEvent { subscription: Some(SubscriptionHandle(subscription)), endpoint: endpoint.to_string(), event }
+    }
+
+    /// Runs `events` through a deduplicator and returns what it forwarded, as (subscription, endpoint, event)
+    async fn deduplicated(events: Vec<SubscriptionEvent<TestEvent>>, stats: FanInStats, capacity: usize) -> Vec<(u64, String, TestEvent)> {
+        let (tx, mut rx) = EventBus::new(100).split::<TestEvent>();
+        let mut deduplicator = Deduplicator::new(tx).with_stats(stats).with_capacity(capacity);
+        for event in events {
+            assert!(deduplicator.forward(event).await);
+        }
+        drop(deduplicator);
+
+        let mut forwarded = Vec::new();
+        while let Some(tagged) = rx.recv().await {
+            forwarded.push((tagged.subscription.unwrap().0, tagged.endpoint, tagged.event));
+        }
+        forwarded
+    }
+
+    #[tokio::test]
+    async fn forwards_the_first_copy_only() {
+        let stats = FanInStats::default();
+        let forwarded = deduplicated(vec![
+            tagged(1, "helius", TestEvent::Notification("sig-a".to_string())),
+            tagged(1, "triton", TestEvent::Notification("sig-a".to_string())),
+            tagged(1, "triton", TestEvent::Notification("sig-b".to_string())),
+            // Same key, another subscription
+            tagged(2, "helius", TestEvent::Notification("sig-a".to_string())),
+            tagged(1, "helius", TestEvent::Notification("sig-b".to_string())),
+            // Never deduplicated
+            tagged(1, "helius", TestEvent::Heartbeat),
+            tagged(1, "triton", TestEvent::Heartbeat),
+        ], stats.clone(), 100).await;
+
+        assert_eq!(forwarded, vec![
+            (1, "helius".to_string(), TestEvent::Notification("sig-a".to_string())),
+            (1, "triton".to_string(), TestEvent::Notification("sig-b".to_string())),
+            (2, "helius".to_string(), TestEvent::Notification("sig-a".to_string())),
+            (1, "helius".to_string(), TestEvent::Heartbeat),
+            (1, "triton".to_string(), TestEvent::Heartbeat),
+        ]);
+
+        let leaderboard = stats.leaderboard();
+        assert_eq!(leaderboard.len(), 2);
+        assert_eq!((leaderboard[0].0.as_str(), leaderboard[0].1.delivered_first, leaderboard[0].1.duplicates), ("helius", 2, 1));
+        assert_eq!((leaderboard[1].0.as_str(), leaderboard[1].1.delivered_first, leaderboard[1].1.duplicates), ("triton", 1, 1));
+    }
+
+    #[tokio::test]
+    async fn forgets_keys_past_its_capacity() {
+        let forwarded = deduplicated(vec![
+            tagged(1, "helius", TestEvent::Notification("sig-a".to_string())),
+            tagged(1, "helius", TestEvent::Notification("sig-b".to_string())),
+            tagged(1, "helius", TestEvent::Notification("sig-c".to_string())),
+            // sig-a was forgotten when sig-c came in
+            tagged(1, "triton", TestEvent::Notification("sig-a".to_string())),
+            tagged(1, "triton", TestEvent::Notification("sig-c".to_string())),
+        ], FanInStats::default(), 2).await;
+
+        let keys: Vec<(String, TestEvent)> = forwarded.into_iter().map(|(_, endpoint, event)| (endpoint, event)).collect();
+        assert_eq!(keys.len(), 4);
+        assert_eq!(keys[3], ("triton".to_string(), TestEvent::Notification("sig-a".to_string())));
+    }
+}
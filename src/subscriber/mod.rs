@@ -1,3 +1,9 @@
 pub mod websocket_subscriber;
 pub mod consume_stream;
-pub mod websocket_event_types;
\ No newline at end of file
+pub mod websocket_event_types;
+pub mod reconnect;
+pub mod subscription_registry;
+pub mod fan_in_subscriber;
+pub mod subscription_manifest;
+pub mod capture;
+pub mod event_bus;
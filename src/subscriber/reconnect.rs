@@ -0,0 +1,78 @@
+use std::fmt;
+use std::time::Duration;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// Controls how a `WebSocketSubscriber` keeps its connection alive and how it backs off between reconnects.
+///
+/// Backoff is exponential with full jitter: attempt `n` sleeps a random duration in `[0, min(max_backoff, initial_backoff * multiplier^n)]`,
+/// so a fleet of subscribers dropped by the same provider does not hammer it back in lockstep.
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub multiplier: f64,
+    /// If no frame arrives for this long we send a Ping to probe the connection.
+    pub ping_interval: Duration,
+    /// How long we wait for any frame (usually the Pong) after probing before declaring the connection dead.
+    pub ping_timeout: Duration,
+    /// Max time without a Text frame (an actual notification) before we assume the subscription silently died.
+    /// Leave as None for low-volume subscriptions (ie. a single whale wallet) where long silences are normal.
+    pub stall_timeout: Option<Duration>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            multiplier: 2.0,
+            ping_interval: Duration::from_secs(15),
+            ping_timeout: Duration::from_secs(10),
+            stall_timeout: None,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    pub fn backoff_for(&self, attempt: u32) -> Duration {
+        let exponential = self.initial_backoff.as_millis() as f64 * self.multiplier.powi(attempt as i32);
+        let capped = exponential.min(self.max_backoff.as_millis() as f64) as u64;
+        if capped == 0 {
+            return Duration::from_millis(0);
+        }
+        Duration::from_millis(rand::thread_rng().gen_range(0..=capped))
+    }
+}
+
+/// Why `consume_stream` gave up on a connection.
+#[derive(Debug, Clone)]
+pub enum StreamEnd {
+    Closed,
+    Errored(String),
+    PingTimeout,
+    Stalled,
+}
+
+impl fmt::Display for StreamEnd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StreamEnd::Closed => write!(f, "closed by server"),
+            StreamEnd::Errored(e) => write!(f, "errored: {}", e),
+            StreamEnd::PingTimeout => write!(f, "ping timed out"),
+            StreamEnd::Stalled => write!(f, "stalled, no notifications received"),
+        }
+    }
+}
+
+/// Marker event emitted after a reconnect. Anything that happened on chain between `disconnected_at` and `reconnected_at`
+/// may have been missed, so trackers relying on a continuous stream should re-sync (ie. backfill with getSignaturesForAddress).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StreamGap {
+    pub endpoint: String,
+    pub reason: String,
+    pub disconnected_at: i64, // millis
+    pub reconnected_at: i64, // millis
+    pub attempts: u32,
+}
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::subscriber::subscription_registry::{SubscriptionHandle, SubscriptionSink};
+use crate::subscriber::websocket_subscriber::{SolanaSubscriptionBuilder, SubscriptionBuilder};
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// One subscription declared in the manifest. See config/subscriptions.json
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SubscriptionSpec {
+    pub label: String,
+    pub method: String,
+    // Pubkey, program id, signature or "all" depending on the method. Not needed for slot/root subscriptions
+    pub target: Option<String>,
+    pub commitment: Option<String>,
+    pub encoding: Option<String>,
+    #[serde(default)]
+    pub filters: Vec<Value>,
+    // For logsSubscribe on a wallet: fetch every transaction and build summaries with `target` as the tracked wallet
+    #[serde(default)]
+    pub track_transactions: bool,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+impl SubscriptionSpec {
+    /// Positional args understood by `SolanaSubscriptionBuilder`: [target, commitment, encoding, filters...]
+    pub fn to_params(&self) -> (String, Vec<String>) {
+        let mut args = Vec::new();
+        if let Some(ref target) = self.target {
+            args.push(target.clone());
+            args.push(self.commitment.clone().unwrap_or_default());
+            args.push(self.encoding.clone().unwrap_or_default());
+            args.extend(self.filters.iter().map(|filter| filter.to_string()));
+        }
+        (self.method.clone(), args)
+    }
+
+    /// Whether the subscriber can send it: a known method, with the target it needs
+    pub fn validate(&self) -> Result<(), String> {
+        let (method, args) = self.to_params();
+        SolanaSubscriptionBuilder::build_subscription_message(0, &method, &args)
+            .map(|_| ())
+            .map_err(|e| format!("subscription \"{}\": {}", self.label, e))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SubscriptionManifest {
+    pub subscriptions: Vec<SubscriptionSpec>,
+}
+
+impl SubscriptionManifest {
+    /// Fails when any spec is invalid, see `SubscriptionSpec::validate`
+    pub fn load(path: &PathBuf) -> Result<Self, Box<dyn Error>> {
+        let contents = fs::read_to_string(path)?;
+        let manifest: SubscriptionManifest = serde_json::from_str(&contents)?;
+        for spec in &manifest.subscriptions {
+            spec.validate()?;
+        }
+        Ok(manifest)
+    }
+
+    /// Matches subscriptions opened elsewhere (ie. in a replayed capture) to the enabled specs declaring the same params.
+    pub fn specs_for(&self, subscriptions: &[(SubscriptionHandle, String, Vec<String>)]) -> HashMap<SubscriptionHandle, SubscriptionSpec> {
+        let mut specs = HashMap::new();
+        for (handle, method, args) in subscriptions {
+            let spec = self.subscriptions.iter()
+                .filter(|spec| spec.enabled)
+                .find(|spec| spec.to_params() == (method.clone(), args.clone()));
+            if let Some(spec) = spec {
+                specs.insert(*handle, spec.clone());
+            }
+        }
+        specs
+    }
+}
+
+/// Subscriptions currently opened from the manifest, keyed by the handle tagged on every event they produce.
+pub type ActiveSubscriptions = Arc<Mutex<HashMap<SubscriptionHandle, SubscriptionSpec>>>;
+
+/**
+Loads the manifest into a subscriber and keeps them in sync.
+
+The file is polled for changes, on every change the enabled specs are diffed against the active ones: removed specs are
+unsubscribed, new ones subscribed and specs that only changed their label or flags are updated in place without resubscribing.
+A manifest that fails to parse, or declares a subscription the subscriber can't send, is ignored and the current subscriptions
+are kept.
+ */
+pub struct ManifestWatcher<S: SubscriptionSink> {
+    path: PathBuf,
+    sink: S,
+    active: ActiveSubscriptions,
+    last_modified: Option<SystemTime>,
+    poll_interval: Duration,
+}
+
+impl<S: SubscriptionSink> ManifestWatcher<S> {
+    pub fn new(path: PathBuf, sink: S) -> Self {
+        Self {
+            path,
+            sink,
+            active: Arc::new(Mutex::new(HashMap::new())),
+            last_modified: None,
+            poll_interval: Duration::from_secs(2),
+        }
+    }
+
+    pub fn active(&self) -> ActiveSubscriptions {
+        self.active.clone()
+    }
+
+    pub fn reload(&mut self) -> Result<(), Box<dyn Error>> {
+        self.last_modified = fs::metadata(&self.path)?.modified().ok();
+        let manifest = SubscriptionManifest::load(&self.path)?;
+
+        let mut desired: HashMap<(String, Vec<String>), SubscriptionSpec> = HashMap::new();
+        for spec in manifest.subscriptions.into_iter().filter(|spec| spec.enabled) {
+            desired.insert(spec.to_params(), spec);
+        }
+
+        let mut active = self.active.lock().unwrap();
+
+        // Drop what is no longer declared, refresh labels/flags of what stays
+        let handles: Vec<SubscriptionHandle> = active.keys().copied().collect();
+        for handle in handles {
+            let params = active[&handle].to_params();
+            match desired.remove(&params) {
+                Some(spec) => {
+                    active.insert(handle, spec);
+                }
+                None => {
+                    let spec = active.remove(&handle).unwrap();
+                    println!("[[MANIFEST]] Unsubscribing {} ({})", spec.label, handle);
+                    self.sink.unsubscribe(handle);
+                }
+            }
+        }
+
+        // Whatever is left is new
+        for ((method, args), spec) in desired {
+            let handle = self.sink.subscribe(&method, args);
+            println!("[[MANIFEST]] Subscribed {} ({})", spec.label, handle);
+            active.insert(handle, spec);
+        }
+
+        Ok(())
+    }
+
+    fn changed(&self) -> bool {
+        let modified = fs::metadata(&self.path).ok().and_then(|metadata| metadata.modified().ok());
+        modified.is_some() && modified != self.last_modified
+    }
+
+    /// Polls the manifest forever, reloading it whenever it changes on disk.
+    pub async fn watch(mut self) {
+        let mut interval = tokio::time::interval(self.poll_interval);
+        loop {
+            interval.tick().await;
+            if !self.changed() {
+                continue;
+            }
+
+            println!("[[MANIFEST]] {:?} changed, reloading", self.path);
+            // Box<dyn Error> is not Send, stringify it before the next await
+            if let Err(e) = self.reload().map_err(|e| e.to_string()) {
+                eprintln!("[[MANIFEST]] Failed to reload {:?}, keeping current subscriptions: {}", self.path, e);
+            }
+        }
+    }
+}
@@ -0,0 +1,366 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::UnboundedSender;
+
+/// Logical id of a subscription. Unlike the server-assigned subscription number it survives reconnects.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SubscriptionHandle(pub u64);
+
+impl fmt::Display for SubscriptionHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "sub-{}", self.0)
+    }
+}
+
+/// A deserialized event tagged with the logical subscription that produced it and the endpoint that delivered it.
+/// `subscription` is None for events not tied to a subscription (ie. stream gaps) or for notifications we could not map.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SubscriptionEvent<T> {
+    pub subscription: Option<SubscriptionHandle>,
+    pub endpoint: String,
+    pub event: T,
+}
+
+#[derive(Debug, Clone)]
+pub struct SubscriptionEntry {
+    pub handle: SubscriptionHandle,
+    pub method: String,
+    pub args: Vec<String>,
+    // JSON-RPC id of the in-flight subscribe request on the current connection
+    pub request_id: Option<u64>,
+    // Subscription number assigned by the server on the current connection
+    pub server_id: Option<u64>,
+}
+
+/// What a JSON-RPC response meant for the registry.
+#[derive(Debug, Clone)]
+pub enum Confirmation {
+    Subscribed(SubscriptionHandle),
+    // The subscription was dropped while its subscribe request was in flight, the caller must unsubscribe it right away
+    Orphaned { method: String, server_id: u64 },
+    Unknown,
+}
+
+/// Source of truth for what a subscriber should be subscribed to, and the bridge between
+/// the ids we send, the ids the server hands back and the handles callers hold on to.
+pub struct SubscriptionRegistry {
+    next_handle: u64,
+    next_request_id: u64,
+    // Bumped on every reconnect, server ids from a previous connection are meaningless on the new one
+    epoch: u64,
+    entries: HashMap<SubscriptionHandle, SubscriptionEntry>,
+    pending: HashMap<u64, SubscriptionHandle>,
+    orphaned: HashMap<u64, String>,
+    by_server_id: HashMap<u64, SubscriptionHandle>,
+}
+
+impl SubscriptionRegistry {
+    pub fn new() -> Self {
+        Self {
+            next_handle: 1,
+            next_request_id: 1,
+            epoch: 0,
+            entries: HashMap::new(),
+            pending: HashMap::new(),
+            orphaned: HashMap::new(),
+            by_server_id: HashMap::new(),
+        }
+    }
+
+    pub fn register(&mut self, method: &str, args: Vec<String>) -> SubscriptionHandle {
+        let handle = SubscriptionHandle(self.next_handle);
+        self.next_handle += 1;
+        self.entries.insert(handle, SubscriptionEntry {
+            handle,
+            method: method.to_string(),
+            args,
+            request_id: None,
+            server_id: None,
+        });
+        handle
+    }
+
+    /// Registers under a handle chosen by the caller, used to share handles between the endpoints of a `FanInSubscriber`.
+    pub fn register_as(&mut self, handle: SubscriptionHandle, method: &str, args: Vec<String>) {
+        self.next_handle = self.next_handle.max(handle.0 + 1);
+        self.entries.insert(handle, SubscriptionEntry {
+            handle,
+            method: method.to_string(),
+            args,
+            request_id: None,
+            server_id: None,
+        });
+    }
+
+    pub fn next_request_id(&mut self) -> u64 {
+        let id = self.next_request_id;
+        self.next_request_id += 1;
+        id
+    }
+
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Allocates a request id for the subscribe message of `handle`.
+    /// Returns None if the handle is gone or was already sent on this connection.
+    pub fn begin_subscribe(&mut self, handle: SubscriptionHandle) -> Option<(u64, SubscriptionEntry)> {
+        let already_sent = match self.entries.get(&handle) {
+            Some(entry) => entry.request_id.is_some() || entry.server_id.is_some(),
+            None => return None,
+        };
+        if already_sent {
+            return None;
+        }
+
+        let request_id = self.next_request_id();
+        let entry = self.entries.get_mut(&handle)?;
+        entry.request_id = Some(request_id);
+        self.pending.insert(request_id, handle);
+        Some((request_id, entry.clone()))
+    }
+
+    pub fn confirm(&mut self, request_id: u64, server_id: u64) -> Confirmation {
+        if let Some(method) = self.orphaned.remove(&request_id) {
+            return Confirmation::Orphaned { method, server_id };
+        }
+
+        match self.pending.remove(&request_id) {
+            Some(handle) => match self.entries.get_mut(&handle) {
+                Some(entry) => {
+                    entry.request_id = None;
+                    entry.server_id = Some(server_id);
+                    self.by_server_id.insert(server_id, handle);
+                    Confirmation::Subscribed(handle)
+                }
+                None => Confirmation::Unknown,
+            },
+            None => Confirmation::Unknown,
+        }
+    }
+
+    /// The server rejected the subscribe request, the entry stays registered and is retried on the next reconnect.
+    pub fn reject(&mut self, request_id: u64) -> Option<SubscriptionHandle> {
+        self.orphaned.remove(&request_id);
+        let handle = self.pending.remove(&request_id)?;
+        if let Some(entry) = self.entries.get_mut(&handle) {
+            entry.request_id = None;
+        }
+        Some(handle)
+    }
+
+    pub fn handle_for(&self, server_id: u64) -> Option<SubscriptionHandle> {
+        self.by_server_id.get(&server_id).copied()
+    }
+
+    pub fn get(&self, handle: SubscriptionHandle) -> Option<&SubscriptionEntry> {
+        self.entries.get(&handle)
+    }
+
+    pub fn entries(&self) -> Vec<SubscriptionEntry> {
+        let mut entries: Vec<SubscriptionEntry> = self.entries.values().cloned().collect();
+        entries.sort_by_key(|entry| entry.handle);
+        entries
+    }
+
+    pub fn remove(&mut self, handle: SubscriptionHandle) -> Option<SubscriptionEntry> {
+        let entry = self.entries.remove(&handle)?;
+        if let Some(server_id) = entry.server_id {
+            self.by_server_id.remove(&server_id);
+        }
+        if let Some(request_id) = entry.request_id {
+            self.pending.remove(&request_id);
+            self.orphaned.insert(request_id, entry.method.clone());
+        }
+        Some(entry)
+    }
+
+    /// Forgets everything tied to the previous connection, entries are kept so they can be replayed.
+    pub fn reset_connection(&mut self) {
+        self.epoch += 1;
+        self.pending.clear();
+        self.orphaned.clear();
+        self.by_server_id.clear();
+        for entry in self.entries.values_mut() {
+            entry.request_id = None;
+            entry.server_id = None;
+        }
+    }
+}
+
+/// Maps a subscribe method to its unsubscribe counterpart (ie. logsSubscribe -> logsUnsubscribe).
+pub fn unsubscribe_method(method: &str) -> String {
+    method.replace("Subscribe", "Unsubscribe")
+}
+
+#[derive(Debug, Clone)]
+pub enum SubscriberCommand {
+    Subscribe(SubscriptionHandle),
+    Unsubscribe { method: String, server_id: u64, epoch: u64 },
+}
+
+/// Anything that can add and drop subscriptions at runtime (a single subscriber or a fan-in over several).
+pub trait SubscriptionSink {
+    fn subscribe(&self, method: &str, args: Vec<String>) -> SubscriptionHandle;
+
+    fn unsubscribe(&self, handle: SubscriptionHandle) -> bool;
+}
+
+impl SubscriptionSink for SubscriptionControl {
+    fn subscribe(&self, method: &str, args: Vec<String>) -> SubscriptionHandle {
+        SubscriptionControl::subscribe(self, method, args)
+    }
+
+    fn unsubscribe(&self, handle: SubscriptionHandle) -> bool {
+        SubscriptionControl::unsubscribe(self, handle)
+    }
+}
+
+/// Cloneable handle to add and drop subscriptions on a running `WebSocketSubscriber` from any task.
+#[derive(Clone)]
+pub struct SubscriptionControl {
+    registry: Arc<Mutex<SubscriptionRegistry>>,
+    commands: UnboundedSender<SubscriberCommand>,
+}
+
+impl SubscriptionControl {
+    pub fn new(registry: Arc<Mutex<SubscriptionRegistry>>, commands: UnboundedSender<SubscriberCommand>) -> Self {
+        Self { registry, commands }
+    }
+
+    /// Registers the subscription and, if the subscriber is connected, sends it right away.
+    /// Otherwise it goes out with the replay on the next (re)connect.
+    pub fn subscribe(&self, method: &str, args: Vec<String>) -> SubscriptionHandle {
+        let handle = self.registry.lock().unwrap().register(method, args);
+        let _ = self.commands.send(SubscriberCommand::Subscribe(handle));
+        handle
+    }
+
+    pub fn subscribe_as(&self, handle: SubscriptionHandle, method: &str, args: Vec<String>) {
+        self.registry.lock().unwrap().register_as(handle, method, args);
+        let _ = self.commands.send(SubscriberCommand::Subscribe(handle));
+    }
+
+    /// Drops the subscription from the registry and sends the matching *Unsubscribe message if the server confirmed it.
+    pub fn unsubscribe(&self, handle: SubscriptionHandle) -> bool {
+        let mut registry = self.registry.lock().unwrap();
+        let epoch = registry.epoch();
+        match registry.remove(handle) {
+            Some(entry) => {
+                if let Some(server_id) = entry.server_id {
+                    let _ = self.commands.send(SubscriberCommand::Unsubscribe {
+                        method: unsubscribe_method(&entry.method),
+                        server_id,
+                        epoch,
+                    });
+                }
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc::unbounded_channel;
+
+    use super::*;
+
+    fn logs_args() -> Vec<String> {
+        vec![r#"{"mentions":["whale"]}"#.to_string()]
+    }
+
+    #[test]
+    fn maps_server_ids_to_handles() {
+        let mut registry = SubscriptionRegistry::new();
+        let handle = registry.register("logsSubscribe", logs_args());
+        let (request_id, entry) = registry.begin_subscribe(handle).unwrap();
+        assert_eq!((entry.handle, entry.method.as_str(), entry.request_id), (handle, "logsSubscribe", Some(request_id)));
+        // Already in flight
+        assert!(registry.begin_subscribe(handle).is_none());
+
+        assert!(matches!(registry.confirm(request_id, 42), Confirmation::Subscribed(confirmed) if confirmed == handle));
+        assert_eq!(registry.handle_for(42), Some(handle));
+        assert_eq!(registry.get(handle).unwrap().server_id, Some(42));
+        assert!(matches!(registry.confirm(request_id, 42), Confirmation::Unknown));
+    }
+
+    #[test]
+    fn subscriptions_dropped_in_flight_are_orphaned() {
+        let mut registry = SubscriptionRegistry::new();
+        let handle = registry.register("logsSubscribe", logs_args());
+        let (request_id, _) = registry.begin_subscribe(handle).unwrap();
+        registry.remove(handle).unwrap();
+
+        // The server still subscribes, the caller has to unsubscribe it
+        match registry.confirm(request_id, 7) {
+            Confirmation::Orphaned { method, server_id } => assert_eq!((method.as_str(), server_id), ("logsSubscribe", 7)),
+            other => panic!("expected an orphan, got {:?}", other),
+        }
+        assert!(registry.handle_for(7).is_none());
+        assert!(registry.entries().is_empty());
+    }
+
+    #[test]
+    fn rejected_subscriptions_are_retried() {
+        let mut registry = SubscriptionRegistry::new();
+        let handle = registry.register("blockSubscribe", vec![]);
+        let (request_id, _) = registry.begin_subscribe(handle).unwrap();
+        assert_eq!(registry.reject(request_id), Some(handle));
+        assert!(registry.begin_subscribe(handle).is_some());
+    }
+
+    #[test]
+    fn reconnect_forgets_the_previous_connection() {
+        let mut registry = SubscriptionRegistry::new();
+        let confirmed = registry.register("logsSubscribe", logs_args());
+        let in_flight = registry.register("slotSubscribe", vec![]);
+        let (request_id, _) = registry.begin_subscribe(confirmed).unwrap();
+        registry.confirm(request_id, 42);
+        let (in_flight_request, _) = registry.begin_subscribe(in_flight).unwrap();
+
+        registry.reset_connection();
+        assert_eq!(registry.epoch(), 1);
+        assert!(registry.handle_for(42).is_none());
+        assert!(matches!(registry.confirm(in_flight_request, 43), Confirmation::Unknown));
+        // Both are replayed, in handle order
+        let entries = registry.entries();
+        assert_eq!(entries.iter().map(|entry| entry.handle).collect::<Vec<_>>(), vec![confirmed, in_flight]);
+        assert!(entries.iter().all(|entry| entry.request_id.is_none() && entry.server_id.is_none()));
+        assert!(registry.begin_subscribe(confirmed).is_some());
+    }
+
+    #[test]
+    fn control_unsubscribes_confirmed_subscriptions_only() {
+        let registry = Arc::new(Mutex::new(SubscriptionRegistry::new()));
+        let (commands, mut received) = unbounded_channel();
+        let control = SubscriptionControl::new(registry.clone(), commands);
+
+        let confirmed = control.subscribe("logsSubscribe", logs_args());
+        let pending = control.subscribe("slotSubscribe", vec![]);
+        assert!(matches!(received.try_recv(), Ok(SubscriberCommand::Subscribe(handle)) if handle == confirmed));
+        assert!(matches!(received.try_recv(), Ok(SubscriberCommand::Subscribe(handle)) if handle == pending));
+        {
+            let mut registry = registry.lock().unwrap();
+            let (request_id, _) = registry.begin_subscribe(confirmed).unwrap();
+            registry.confirm(request_id, 42);
+            registry.reset_connection();
+            let (request_id, _) = registry.begin_subscribe(confirmed).unwrap();
+            registry.confirm(request_id, 50);
+        }
+
+        assert!(control.unsubscribe(confirmed));
+        match received.try_recv() {
+            Ok(SubscriberCommand::Unsubscribe { method, server_id, epoch }) => assert_eq!((method.as_str(), server_id, epoch), ("logsUnsubscribe", 50, 1)),
+            other => panic!("expected an unsubscribe, got {:?}", other),
+        }
+        // Never confirmed, nothing to send
+        assert!(control.unsubscribe(pending));
+        assert!(received.try_recv().is_err());
+        assert!(!control.unsubscribe(pending));
+    }
+}
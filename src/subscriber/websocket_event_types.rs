@@ -4,6 +4,10 @@ use crate::models::solana::solana_event_types::SolanaEventTypes;
 use crate::models::solana::solana_logs_notification::SolanaLogsNotification;
 use crate::models::solana::solana_account_notification::SolanaAccountNotification;
 use crate::models::solana::solana_program_notification::SolanaProgramNotification;
+use crate::models::solana::solana_block_notification::SolanaBlockNotification;
+use crate::models::solana::solana_signature_notification::SolanaSignatureNotification;
+use crate::models::solana::solana_slot_notification::{SolanaRootNotification, SolanaSlotNotification};
+use crate::subscriber::reconnect::StreamGap;
 use crate::util::serde_helper::deserialize_into;
 
 ///Trait used for event deserialization
@@ -13,6 +17,26 @@ pub trait WebsocketEventTypes: Sized {
 
     // Method to deserialize a JSON value into a specific event type.
     fn deserialize_event(value: &Value) -> Result<Self, Box<dyn Error>>;
+
+    // Wraps the marker the subscriber emits after reconnecting, notifications in between may have been missed.
+    fn stream_gap(gap: StreamGap) -> Self;
+
+    // True if the server drops the subscription after this event (ie. one-shot subscriptions), so it must not be replayed.
+    fn ends_subscription(&self) -> bool {
+        false
+    }
+
+    // Identifies the same notification delivered by different endpoints, None means never deduplicate.
+    // Keys only need to be unique within a subscription, the fan-in pairs them with the subscription handle.
+    fn dedup_key(&self) -> Option<String> {
+        None
+    }
+
+    // Identifies events that supersede each other (ie. successive states of the same account), the event bus keeps only the
+    // latest one on overflow when the type is configured to coalesce. Also paired with the subscription handle, None means never coalesce.
+    fn coalesce_key(&self) -> Option<String> {
+        None
+    }
 }
 
 impl WebsocketEventTypes for SolanaEventTypes {
@@ -20,7 +44,12 @@ impl WebsocketEventTypes for SolanaEventTypes {
         match self {
             SolanaEventTypes::LogNotification(_) => "LogNotification".to_string(),
             SolanaEventTypes::ProgramNotification(_) => "ProgramNotification".to_string(),
-            SolanaEventTypes::AccountNotification(_) =>"AccountNotification".to_string()
+            SolanaEventTypes::AccountNotification(_) =>"AccountNotification".to_string(),
+            SolanaEventTypes::SlotNotification(_) => "SlotNotification".to_string(),
+            SolanaEventTypes::RootNotification(_) => "RootNotification".to_string(),
+            SolanaEventTypes::BlockNotification(_) => "BlockNotification".to_string(),
+            SolanaEventTypes::SignatureNotification(_) => "SignatureNotification".to_string(),
+            SolanaEventTypes::StreamGap(_) => "StreamGap".to_string()
         }
     }
 
@@ -42,9 +71,33 @@ impl WebsocketEventTypes for SolanaEventTypes {
             },
 
             "programNotification" => {
-                deserialize_into::<SolanaProgramNotification>(&value)
+                deserialize_into::<SolanaProgramNotification>(value)
+                    .map(|mut notification| {
+                        notification.decode_account();
+                        notification
+                    })
                     .map(SolanaEventTypes::ProgramNotification)
             },
+
+            "slotNotification" => {
+                deserialize_into::<SolanaSlotNotification>(value)
+                    .map(SolanaEventTypes::SlotNotification)
+            },
+
+            "rootNotification" => {
+                deserialize_into::<SolanaRootNotification>(value)
+                    .map(SolanaEventTypes::RootNotification)
+            },
+
+            "blockNotification" => {
+                deserialize_into::<SolanaBlockNotification>(value)
+                    .map(SolanaEventTypes::BlockNotification)
+            },
+
+            "signatureNotification" => {
+                deserialize_into::<SolanaSignatureNotification>(value)
+                    .map(SolanaEventTypes::SignatureNotification)
+            },
             _ => Err(format!("Unsupported event type: {}", method).into()),
         };
 
@@ -56,4 +109,47 @@ impl WebsocketEventTypes for SolanaEventTypes {
         result
     }
 
+    fn stream_gap(gap: StreamGap) -> Self {
+        SolanaEventTypes::StreamGap(gap)
+    }
+
+    fn ends_subscription(&self) -> bool {
+        match self {
+            SolanaEventTypes::SignatureNotification(notification) => notification.is_final(),
+            _ => false
+        }
+    }
+
+    // The subscription handle pins the commitment, so signature -> (signature, commitment) and pubkey -> (pubkey, slot)
+    fn dedup_key(&self) -> Option<String> {
+        match self {
+            SolanaEventTypes::LogNotification(log) => Some(log.params.result.value.signature.clone()),
+            SolanaEventTypes::AccountNotification(account) => Some(format!("slot:{}", account.slot())),
+            SolanaEventTypes::ProgramNotification(program) => Some(format!(
+                "{}:{}",
+                program.params.result.value.pubkey,
+                program.params.result.context.slot
+            )),
+            SolanaEventTypes::SlotNotification(slot) => Some(format!("slot:{}", slot.params.result.slot)),
+            SolanaEventTypes::RootNotification(root) => Some(format!("root:{}", root.params.result)),
+            SolanaEventTypes::BlockNotification(block) => Some(format!("block:{}", block.params.result.value.slot)),
+            // The handle already pins the signature, only the ack and the final notification differ
+            SolanaEventTypes::SignatureNotification(signature) => Some(
+                if signature.is_final() { "final".to_string() } else { "received".to_string() }
+            ),
+            SolanaEventTypes::StreamGap(_) => None,
+        }
+    }
+
+    fn coalesce_key(&self) -> Option<String> {
+        match self {
+            SolanaEventTypes::AccountNotification(_) => Some("account".to_string()),
+            SolanaEventTypes::ProgramNotification(program) => Some(program.params.result.value.pubkey.clone()),
+            SolanaEventTypes::SlotNotification(_) => Some("slot".to_string()),
+            SolanaEventTypes::RootNotification(_) => Some("root".to_string()),
+            // Every transaction, block and signature status matters on its own
+            _ => None,
+        }
+    }
+
 }
@@ -1,14 +1,27 @@
 use std::error::Error;
+use std::sync::{Arc, Mutex};
 
+use chrono::Utc;
 use futures_util::SinkExt;
 use futures_util::StreamExt;
 use serde_json::json;
 use tokio::net::TcpStream;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
 use tokio_tungstenite::{connect_async, MaybeTlsStream, tungstenite::protocol::Message, WebSocketStream};
 use url::Url;
 
+use crate::subscriber::capture::FrameRecorder;
+use crate::subscriber::consume_stream::{consume_stream, StreamContext};
+use crate::subscriber::event_bus::EventSender;
+use crate::subscriber::reconnect::{ReconnectPolicy, StreamGap};
+use crate::subscriber::subscription_registry::{SubscriberCommand, SubscriptionControl, SubscriptionEvent, SubscriptionHandle, SubscriptionRegistry};
+use crate::subscriber::websocket_event_types::WebsocketEventTypes;
+
 pub trait SubscriptionBuilder {
-    fn build_subscription_messages(params: &[(&str, Vec<String>)]) -> Vec<Message>;
+    // Err for methods the builder doesn't know and missing or malformed args, never sent
+    fn build_subscription_message(request_id: u64, method: &str, args: &[String]) -> Result<Message, String>;
+
+    fn build_unsubscribe_message(request_id: u64, method: &str, server_id: u64) -> Message;
 }
 
 pub enum AuthMethod {
@@ -19,14 +32,44 @@ pub enum AuthMethod {
 
 pub struct WebSocketSubscriber<B: SubscriptionBuilder> {
     ws_url: String,
+    // Safe to print, private urls carry the api key in the path
+    label: String,
     api_key: Option<String>,
     auth_method: AuthMethod,
     builder: B,
+    // Every subscription we were told about, replayed on each reconnect
+    registry: Arc<Mutex<SubscriptionRegistry>>,
+    control: SubscriptionControl,
+    commands: UnboundedReceiver<SubscriberCommand>,
+    reconnect_policy: ReconnectPolicy,
+    recorder: Option<FrameRecorder>,
 }
 
 impl<B: SubscriptionBuilder> WebSocketSubscriber<B> {
     pub fn new(ws_url: String, api_key: Option<String>, auth_method: AuthMethod, builder: B) -> Self {
-        Self { ws_url, api_key, auth_method, builder }
+        let registry = Arc::new(Mutex::new(SubscriptionRegistry::new()));
+        let (command_sender, commands) = unbounded_channel();
+        let control = SubscriptionControl::new(registry.clone(), command_sender);
+        let label = Url::parse(&ws_url).ok()
+            .and_then(|url| url.host_str().map(|host| host.to_string()))
+            .unwrap_or_else(|| ws_url.clone());
+        Self { ws_url, label, api_key, auth_method, builder, registry, control, commands, reconnect_policy: ReconnectPolicy::default(), recorder: None }
+    }
+
+    pub fn with_label(mut self, label: &str) -> Self {
+        self.label = label.to_string();
+        self
+    }
+
+    /// Appends every raw frame received by this subscriber to the capture, see `ReplaySource` to play it back.
+    pub fn with_recorder(mut self, recorder: FrameRecorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Use this to add/drop subscriptions once `run` owns the subscriber.
+    pub fn control(&self) -> SubscriptionControl {
+        self.control.clone()
     }
 
     pub async fn connect(&self) -> Result<WebSocketStream<MaybeTlsStream<TcpStream>>, Box<dyn Error>> {
@@ -69,8 +112,24 @@ impl<B: SubscriptionBuilder> WebSocketSubscriber<B> {
     }
 
 
-    pub async fn subscribe(&self, ws_stream: &mut WebSocketStream<MaybeTlsStream<TcpStream>>, params: &[(&str, Vec<String>)]) -> Result<(), Box<dyn Error>> {
-        let messages = B::build_subscription_messages(params);
+    /// Sends every registered subscription with a fresh request id, the server ids of the previous connection are forgotten.
+    pub async fn replay_subscriptions(&self, ws_stream: &mut WebSocketStream<MaybeTlsStream<TcpStream>>) -> Result<(), Box<dyn Error>> {
+        let messages: Vec<Message> = {
+            let mut registry = self.registry.lock().unwrap();
+            registry.reset_connection();
+            let handles: Vec<SubscriptionHandle> = registry.entries().iter().map(|entry| entry.handle).collect();
+            handles.into_iter()
+                .filter_map(|handle| registry.begin_subscribe(handle))
+                .filter_map(|(request_id, entry)| match B::build_subscription_message(request_id, &entry.method, &entry.args) {
+                    Ok(message) => Some(message),
+                    Err(e) => {
+                        eprintln!("[[SUBSCRIBER]] Skipping subscription {} ({}): {}", entry.handle, entry.method, e);
+                        None
+                    }
+                })
+                .collect()
+        };
+
         for message in messages {
             println!("Subscribing to {} with provided messages :: {:?}", self.ws_url, message);
             ws_stream.send(message).await?;
@@ -78,92 +137,226 @@ impl<B: SubscriptionBuilder> WebSocketSubscriber<B> {
 
         Ok(())
     }
-}
 
-pub struct SolanaSubscriptionBuilder;
+    /// Owns the connection lifecycle: connects, replays every registered subscription, consumes the stream and, when the
+    /// connection closes, errors, stops answering pings or stalls, reconnects with jittered exponential backoff.
+    /// After each reconnect a `StreamGap` marker is pushed downstream so trackers know notifications may have been missed.
+    pub async fn run<T: WebsocketEventTypes + Send + 'static>(&mut self, tx: EventSender<T>) {
+        let mut attempt: u32 = 0;
+        let mut disconnected: Option<(i64, String)> = None;
 
-impl SubscriptionBuilder for SolanaSubscriptionBuilder {
-    fn build_subscription_messages(params: &[(&str, Vec<String>)]) -> Vec<Message> {
-        params.iter().map(|&(method, ref args)| {
-            let message = match method {
-                "accountSubscribe" => {
-                    let pubkey = args.get(0).expect("Account pubkey is required");
-                    json!({
-                        "jsonrpc": "2.0",
-                        "id": 1,
-                        "method": method,
-                        "params": [
-                            pubkey,
-                            {
-                                "encoding": "jsonParsed",
-                                "commitment": "finalized"
-                            }
-                        ]
-                    })
+        loop {
+            // Errors are stringified right away, Box<dyn Error> is not Send and can't be held across the awaits below
+            let connected = self.connect().await.map_err(|e| e.to_string());
+            let connected = match connected {
+                Ok(mut ws_stream) => {
+                    let subscribed = self.replay_subscriptions(&mut ws_stream).await.map_err(|e| e.to_string());
+                    subscribed.map(|_| ws_stream)
                 }
-                "logsSubscribe" => {
-                    if args.is_empty() || args[0] == "all" {
-                        // Subscribe to all transactions except for simple vote transactions
-                        json!({
-                            "jsonrpc": "2.0",
-                            "id": 1,
-                            "method": "logsSubscribe",
-                            "params": ["all"]
-                        })
-                    } else if args[0] == "allWithVotes" {
-                        // Subscribe to all transactions, including simple vote transactions
-                        json!({
-                            "jsonrpc": "2.0",
-                            "id": 1,
-                            "method": "logsSubscribe",
-                            "params": ["allWithVotes"]
-                        })
-                    } else {
-                        println!("[[SUBSCRIBER]] SUBSCRIBING TO LOGS");
-                        json!({
-                            "jsonrpc": "2.0",
-                            "id": 1,
-                            "method": "logsSubscribe",
-                            "params": [
-                                {
-                                    "mentions": [args[0]]
-                                },
-                                {
-                                    "commitment": "finalized"
-                                }
-                            ]
-                        })
+                Err(e) => Err(e),
+            };
+
+            match connected {
+                Ok(mut ws_stream) => {
+                    if let Some((disconnected_at, reason)) = disconnected.take() {
+                        let gap = StreamGap {
+                            endpoint: self.label.clone(),
+                            reason,
+                            disconnected_at,
+                            reconnected_at: Utc::now().timestamp_millis(),
+                            attempts: attempt + 1,
+                        };
+                        println!("[[SUBSCRIBER]] Reconnected to {} after {:?}", self.label, gap);
+                        let gap_event = SubscriptionEvent { subscription: None, endpoint: self.label.clone(), event: T::stream_gap(gap) };
+                        if let Err(e) = tx.send(gap_event).await {
+                            eprintln!("[[SUBSCRIBER]] Failed to publish stream gap: {}", e);
+                        }
                     }
+                    attempt = 0;
+
+                    let ctx = StreamContext {
+                        registry: &self.registry,
+                        endpoint: &self.label,
+                        recorder: self.recorder.as_ref(),
+                    };
+                    let end = consume_stream::<T, B>(
+                        &mut ws_stream,
+                        tx.clone(),
+                        &self.reconnect_policy,
+                        &mut self.commands,
+                        &ctx,
+                    ).await;
+                    eprintln!("[[SUBSCRIBER]] Connection to {} ended: {}", self.label, end);
+                    disconnected = Some((Utc::now().timestamp_millis(), end.to_string()));
                 }
+                Err(e) => {
+                    eprintln!("[[SUBSCRIBER]] Failed to connect to {} (attempt {}): {}", self.label, attempt + 1, e);
+                    if disconnected.is_none() {
+                        disconnected = Some((Utc::now().timestamp_millis(), e));
+                    }
+                    attempt = attempt.saturating_add(1);
+                }
+            }
 
-                "programSubscribe" => {
-                    let program_id = args.get(0).expect("Program ID is required");
-                    let encoding = "jsonParsed";
-                    let commitment = "finalized";
+            let backoff = self.reconnect_policy.backoff_for(attempt);
+            println!("[[SUBSCRIBER]] Reconnecting to {} in {:?}", self.label, backoff);
+            tokio::time::sleep(backoff).await;
+        }
+    }
 
-                    let filters = args.iter().skip(3).map(|filter| {
-                        serde_json::from_str::<serde_json::Value>(filter)
-                            .expect("Filter must be a valid JSON")
-                    }).collect::<Vec<_>>();
+}
 
+pub struct SolanaSubscriptionBuilder;
+
+/// Args are positional: [target, commitment, encoding, filters...]. Empty or missing commitment/encoding fall back to the defaults.
+fn arg_or<'a>(args: &'a [String], index: usize, default: &'a str) -> &'a str {
+    args.get(index).map(|arg| arg.as_str()).filter(|arg| !arg.is_empty()).unwrap_or(default)
+}
+
+impl SubscriptionBuilder for SolanaSubscriptionBuilder {
+    fn build_subscription_message(request_id: u64, method: &str, args: &[String]) -> Result<Message, String> {
+        let message = match method {
+            "accountSubscribe" => {
+                let pubkey = args.get(0).ok_or("Account pubkey is required")?;
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": request_id,
+                    "method": method,
+                    "params": [
+                        pubkey,
+                        {
+                            "encoding": arg_or(args, 2, "jsonParsed"),
+                            "commitment": arg_or(args, 1, "finalized")
+                        }
+                    ]
+                })
+            }
+            "logsSubscribe" => {
+                if args.is_empty() || args[0] == "all" {
+                    // Subscribe to all transactions except for simple vote transactions
+                    json!({
+                        "jsonrpc": "2.0",
+                        "id": request_id,
+                        "method": "logsSubscribe",
+                        "params": ["all"]
+                    })
+                } else if args[0] == "allWithVotes" {
+                    // Subscribe to all transactions, including simple vote transactions
                     json!({
                         "jsonrpc": "2.0",
-                        "id": 1,
-                        "method": method,
+                        "id": request_id,
+                        "method": "logsSubscribe",
+                        "params": ["allWithVotes"]
+                    })
+                } else {
+                    println!("[[SUBSCRIBER]] SUBSCRIBING TO LOGS");
+                    json!({
+                        "jsonrpc": "2.0",
+                        "id": request_id,
+                        "method": "logsSubscribe",
                         "params": [
-                            program_id,
                             {
-                                "encoding": encoding,
-                                "commitment": "finalized",
-                                "filters": filters
+                                "mentions": [args[0]]
+                            },
+                            {
+                                "commitment": arg_or(args, 1, "finalized")
                             }
                         ]
                     })
                 }
-                _ => panic!("Unsupported subscription method: {}", method),
-            };
-            Message::Text(message.to_string())
-        }).collect()
+            }
+
+            "programSubscribe" => {
+                let program_id = args.get(0).ok_or("Program ID is required")?;
+                let encoding = arg_or(args, 2, "jsonParsed");
+                let commitment = arg_or(args, 1, "finalized");
+
+                let filters = args.iter().skip(3).map(|filter| {
+                    serde_json::from_str::<serde_json::Value>(filter)
+                        .map_err(|e| format!("Filter must be a valid JSON: {}", e))
+                }).collect::<Result<Vec<_>, _>>()?;
+
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": request_id,
+                    "method": method,
+                    "params": [
+                        program_id,
+                        {
+                            "encoding": encoding,
+                            "commitment": commitment,
+                            "filters": filters
+                        }
+                    ]
+                })
+            }
+            // Chain head, slotNotification on every processed slot and rootNotification on every new root
+            "slotSubscribe" | "rootSubscribe" => {
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": request_id,
+                    "method": method,
+                    "params": []
+                })
+            }
+
+            "blockSubscribe" => {
+                // "all" or a pubkey to only get blocks with transactions mentioning it
+                let filter = match args.first().map(|arg| arg.as_str()) {
+                    None | Some("all") => json!("all"),
+                    Some(pubkey) => json!({ "mentionsAccountOrProgram": pubkey }),
+                };
+                // blockSubscribe does not accept processed
+                let commitment = arg_or(args, 1, "confirmed");
+
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": request_id,
+                    "method": method,
+                    "params": [
+                        filter,
+                        {
+                            "commitment": commitment,
+                            // Same encoding as getTransaction, the decoders and the fee market read it as is
+                            "encoding": "json",
+                            "transactionDetails": "full",
+                            "showRewards": false,
+                            "maxSupportedTransactionVersion": 0
+                        }
+                    ]
+                })
+            }
+
+            "signatureSubscribe" => {
+                // One-shot: the server drops the subscription once the signature reaches the commitment
+                let signature = args.first().ok_or("Transaction signature is required")?;
+                let commitment = arg_or(args, 1, "finalized");
+
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": request_id,
+                    "method": method,
+                    "params": [
+                        signature,
+                        {
+                            "commitment": commitment,
+                            "enableReceivedNotification": false
+                        }
+                    ]
+                })
+            }
+            _ => return Err(format!("Unsupported subscription method: {}", method)),
+        };
+        Ok(Message::Text(message.to_string()))
     }
-}
 
+    fn build_unsubscribe_message(request_id: u64, method: &str, server_id: u64) -> Message {
+        // Every *Unsubscribe method takes the subscription number as its only param
+        Message::Text(json!({
+            "jsonrpc": "2.0",
+            "id": request_id,
+            "method": method,
+            "params": [server_id]
+        }).to_string())
+    }
+}
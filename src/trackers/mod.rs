@@ -1,2 +1,3 @@
 pub mod binance;
 pub mod raydium;
+pub mod solana;
@@ -1,7 +1,14 @@
-use std::hash::Hash;
-use crate::models::solana::solana_event_types::SolanaEventTypes;
-use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::collections::HashMap;
+use tokio::sync::mpsc::UnboundedReceiver;
+use crate::decoder::raydium_amm_v4_instructions::{amm_events, INITIALIZE2_LOG};
+use crate::decoder::raydium_amm_v4_state::RAYDIUM_AMM_V4_PROGRAM_ID;
+use crate::decoder::log_parser::parse_logs;
+use crate::http::solana_rpc_client::SolanaRpcClient;
+use crate::models::raydium::raydium_amm_event::{PoolCreated, RaydiumAmmEvent};
+use crate::models::solana::solana_encoded_transaction::EncodedTransactionWithMeta;
 use crate::models::solana::solana_logs_notification::SolanaLogsNotification;
+use crate::subscriber::capture::RpcCapture;
+use crate::trackers::solana::lookup_table_registry::LookupTableRegistry;
 
 
 /*struct TrackerState {
@@ -70,29 +77,97 @@ impl TrackerState {
 }
 */
 
+// Pools kept in `new_tokens`
+const NEW_TOKENS_CAPACITY: usize = 1000;
+
+/**
+Follows Raydium AMM v4 launches from the program logs: every successful transaction logging `initialize2` is fetched (through
+the `RpcCapture`) and its instructions decoded into `RaydiumAmmEvent`s. The swaps and liquidity changes bundled with the pool
+creation (ie. the creator's first buy) come along. Logs of plain swaps are ignored, there are far too many to fetch them all.
+
+The last 1000 pools are kept in `new_tokens`, the ones that opened to trading first are dropped.
+ */
 pub struct NewTokenTracker {
-    pub new_tokens: HashMap<String, String>, // token tx signature / data
+    pub new_tokens: HashMap<String, PoolCreated>, // pool address / creation
+    rpc: SolanaRpcClient,
+    rpc_capture: RpcCapture,
+    lookup_tables: LookupTableRegistry,
 }
 
 impl NewTokenTracker {
-    pub fn new() -> Self {
+    pub fn new(rpc: SolanaRpcClient, rpc_capture: RpcCapture, lookup_tables: LookupTableRegistry) -> Self {
         Self {
             new_tokens: HashMap::new(),
+            rpc,
+            rpc_capture,
+            lookup_tables,
         }
     }
 
-    pub(crate) fn apply(&mut self, event: &SolanaEventTypes) {
-        println!("APPLYING NEW TOKEN TRACKER EVENT {:?}", event);
-        match event {
-            SolanaEventTypes::LogNotification(log) => self.handle_new_token_signature(log),
-            _ => {}
+    /// Handles the logs of the Raydium AMM v4 subscription until the sender is dropped.
+    pub async fn run(mut self, mut logs: UnboundedReceiver<SolanaLogsNotification>) {
+        while let Some(log) = logs.recv().await {
+            self.handle_new_token_signature(&log).await;
         }
+        println!("[[NEW TOKEN TRACKER]] Log sender dropped, stopping");
     }
 
-    /// The PartialBookDepth events provide snapshots of the top levels of the order book (both bids and asks) for a symbol. When such an event is received, the tracker should update the order book state for that symbol with the new top levels of bids and asks provided.
-    pub(crate) fn handle_new_token_signature(&mut self, log: &SolanaLogsNotification) {
-        println!("[[NEW TOKEN TRACKER]] Processing signature: {:?}", log.params.result.value.signature)
+    /// Fetches and decodes the transaction when its logs show a pool creation, the pools created are kept in `new_tokens`.
+    pub(crate) async fn handle_new_token_signature(&mut self, log: &SolanaLogsNotification) -> Vec<RaydiumAmmEvent> {
+        let value = &log.params.result.value;
+        // Only initialize2 logged by the AMM itself, not a memo or another program quoting it
+        if value.err.is_some() || !parse_logs(&value.logs).logged(RAYDIUM_AMM_V4_PROGRAM_ID, INITIALIZE2_LOG) {
+            return Vec::new();
+        }
+        println!("[[NEW TOKEN TRACKER]] Pool creation in {}", value.signature);
+
+        let transaction = self.rpc_capture.get_transactions(&self.rpc, std::slice::from_ref(&value.signature)).await.remove(0);
+        let transaction = match transaction {
+            Ok(Some(transaction)) => transaction,
+            Ok(None) => {
+                eprintln!("[[NEW TOKEN TRACKER]] Transaction {} not found", value.signature);
+                return Vec::new();
+            }
+            Err(e) => {
+                eprintln!("[[NEW TOKEN TRACKER]] Could not get transaction {}: {}", value.signature, e);
+                return Vec::new();
+            }
+        };
+
+        let events = match serde_json::from_value::<EncodedTransactionWithMeta>(transaction) {
+            Ok(mut transaction) => match self.lookup_tables.resolve(&mut transaction).await {
+                Ok(_) => amm_events(&transaction).map_err(|e| e.to_string()),
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e.to_string()),
+        };
+        match events {
+            Ok(events) => {
+                for event in &events {
+                    println!("[[NEW TOKEN TRACKER]] {}", event);
+                    if let RaydiumAmmEvent::PoolCreated(pool) = event {
+                        self.new_tokens.insert(pool.pool.clone(), pool.clone());
+                        self.evict_new_tokens();
+                    }
+                }
+                events
+            }
+            Err(e) => {
+                eprintln!("[[NEW TOKEN TRACKER]] Could not decode transaction {}: {}", value.signature, e);
+                Vec::new()
+            }
+        }
+    }
 
+    /// Drops the pools that opened first until `new_tokens` fits its capacity. An `open_time` of 0 opens at creation.
+    fn evict_new_tokens(&mut self) {
+        while self.new_tokens.len() > NEW_TOKENS_CAPACITY {
+            let Some(oldest) = self.new_tokens.values()
+                .min_by_key(|pool| (pool.open_time.max(pool.block_time.unwrap_or_default().max(0) as u64), pool.slot))
+                .map(|pool| pool.pool.clone()) else {
+                break;
+            };
+            self.new_tokens.remove(&oldest);
+        }
     }
 }
-
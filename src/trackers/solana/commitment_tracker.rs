@@ -0,0 +1,356 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+use crate::http::solana_rpc_client::SolanaRpcClient;
+use crate::models::solana::solana_signature_statuses::SignatureStatus;
+use crate::subscriber::capture::RpcCapture;
+
+/// getSignatureStatuses accepts up to 256 signatures per call
+const MAX_SIGNATURES_PER_REQUEST: usize = 256;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum CommitmentLevel {
+    Processed,
+    Confirmed,
+    Finalized,
+}
+
+impl CommitmentLevel {
+    pub fn parse(commitment: &str) -> Option<Self> {
+        match commitment {
+            "processed" => Some(CommitmentLevel::Processed),
+            "confirmed" => Some(CommitmentLevel::Confirmed),
+            "finalized" => Some(CommitmentLevel::Finalized),
+            _ => None,
+        }
+    }
+
+    fn stage(&self) -> LifecycleStage {
+        match self {
+            CommitmentLevel::Processed => LifecycleStage::SeenProcessed,
+            CommitmentLevel::Confirmed => LifecycleStage::Confirmed,
+            CommitmentLevel::Finalized => LifecycleStage::Finalized,
+        }
+    }
+
+    fn next(&self) -> Option<Self> {
+        match self {
+            CommitmentLevel::Processed => Some(CommitmentLevel::Confirmed),
+            CommitmentLevel::Confirmed => Some(CommitmentLevel::Finalized),
+            CommitmentLevel::Finalized => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LifecycleStage {
+    SeenProcessed,
+    Confirmed,
+    Finalized,
+    // Did not make it to finalized in time: dropped (ie. expired blockhash) or forked out. Whatever was done early must be retracted
+    Dropped,
+}
+
+/// One step in the life of a tracked signature. Stages are emitted once each and in order, starting at the commitment the
+/// signature was first seen at (a signature seen at confirmed never emits SeenProcessed). Dropped and Finalized are final.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LifecycleEvent<C> {
+    pub signature: String,
+    pub stage: LifecycleStage,
+    pub slot: Option<u64>,
+    // Set when the transaction landed but failed, failed transactions still get finalized
+    pub err: Option<Value>,
+    pub first_seen_at: i64, // millis
+    pub observed_at: i64, // millis
+    // Whatever the caller attached when tracking (ie. the whale that sent it)
+    pub context: C,
+}
+
+struct Tracked<C> {
+    level: CommitmentLevel,
+    slot: Option<u64>,
+    err: Option<Value>,
+    first_seen: Instant,
+    first_seen_at: i64,
+    context: C,
+}
+
+impl<C: Clone> Tracked<C> {
+    fn event(&self, signature: &str, stage: LifecycleStage) -> LifecycleEvent<C> {
+        LifecycleEvent {
+            signature: signature.to_string(),
+            stage,
+            slot: self.slot,
+            err: self.err.clone(),
+            first_seen_at: self.first_seen_at,
+            observed_at: Utc::now().timestamp_millis(),
+            context: self.context.clone(),
+        }
+    }
+}
+
+type TrackedSignatures<C> = Arc<Mutex<HashMap<String, Tracked<C>>>>;
+
+/// Cloneable handle to start tracking signatures from any task (ie. the websocket event loop).
+#[derive(Clone)]
+pub struct CommitmentTrackerHandle<C> {
+    tracked: TrackedSignatures<C>,
+    events: UnboundedSender<LifecycleEvent<C>>,
+}
+
+impl<C: Clone> CommitmentTrackerHandle<C> {
+    /// Starts following `signature` from the commitment it was observed at and emits that stage right away.
+    /// Returns false if the signature is already tracked (ie. the same transaction mentioned by two subscriptions).
+    pub fn track(&self, signature: &str, seen_at: CommitmentLevel, slot: Option<u64>, context: C) -> bool {
+        let mut tracked = self.tracked.lock().unwrap();
+        if tracked.contains_key(signature) {
+            return false;
+        }
+
+        let entry = Tracked {
+            level: seen_at,
+            slot,
+            err: None,
+            first_seen: Instant::now(),
+            first_seen_at: Utc::now().timestamp_millis(),
+            context,
+        };
+        let _ = self.events.send(entry.event(signature, seen_at.stage()));
+        if seen_at != CommitmentLevel::Finalized {
+            tracked.insert(signature.to_string(), entry);
+        }
+        true
+    }
+}
+
+/**
+Follows signatures observed at processed/confirmed up to finalized, so consumers can act as soon as a transaction is seen and
+later confirm or retract what they did.
+
+Pending signatures are polled in batches with getSignatureStatuses. A signature that is not confirmed within `confirm_timeout`
+(a blockhash is only valid for ~150 slots) or not finalized within `finalize_timeout` is reported as Dropped.
+When replaying a capture the final status of every signature is read from it instead of polling.
+ */
+pub struct CommitmentTracker<C> {
+    rpc: SolanaRpcClient,
+    rpc_capture: RpcCapture,
+    handle: CommitmentTrackerHandle<C>,
+    poll_interval: Duration,
+    confirm_timeout: Duration,
+    finalize_timeout: Duration,
+}
+
+impl<C: Clone + Send + 'static> CommitmentTracker<C> {
+    pub fn new(rpc: SolanaRpcClient, rpc_capture: RpcCapture) -> (Self, UnboundedReceiver<LifecycleEvent<C>>) {
+        let (events, receiver) = unbounded_channel();
+        let handle = CommitmentTrackerHandle { tracked: Arc::new(Mutex::new(HashMap::new())), events };
+        let tracker = Self {
+            rpc,
+            rpc_capture,
+            handle,
+            poll_interval: Duration::from_millis(800),
+            confirm_timeout: Duration::from_secs(90),
+            finalize_timeout: Duration::from_secs(150),
+        };
+        (tracker, receiver)
+    }
+
+    pub fn handle(&self) -> CommitmentTrackerHandle<C> {
+        self.handle.clone()
+    }
+
+    /// Polls until the lifecycle receiver is dropped.
+    pub async fn run(self) {
+        let mut interval = tokio::time::interval(self.poll_interval);
+        loop {
+            interval.tick().await;
+            if self.handle.events.is_closed() {
+                println!("[[COMMITMENT TRACKER]] Lifecycle receiver dropped, stopping");
+                return;
+            }
+
+            let signatures: Vec<String> = self.handle.tracked.lock().unwrap().keys().cloned().collect();
+            for batch in signatures.chunks(MAX_SIGNATURES_PER_REQUEST) {
+                match self.fetch_statuses(batch).await {
+                    Ok(statuses) => {
+                        for (signature, status) in batch.iter().zip(statuses) {
+                            self.apply(signature, status);
+                        }
+                    }
+                    Err(e) => eprintln!("[[COMMITMENT TRACKER]] Failed to fetch {} signature statuses: {}", batch.len(), e),
+                }
+            }
+            self.expire(&signatures.into_iter().collect());
+        }
+    }
+
+    async fn fetch_statuses(&self, signatures: &[String]) -> Result<Vec<Option<SignatureStatus>>, String> {
+        if self.rpc_capture.is_replay() {
+            return Ok(signatures.iter()
+                .map(|signature| self.rpc_capture.recorded("getSignatureStatuses", signature)
+                    .and_then(|body| serde_json::from_str::<Option<SignatureStatus>>(&body).ok())
+                    .flatten())
+                .collect());
+        }
+
+        self.rpc.get_signature_statuses(signatures, false).await.map_err(|e| e.to_string())
+    }
+
+    fn apply(&self, signature: &str, status: Option<SignatureStatus>) {
+        // Unknown to the node: not landed yet, or forked out. The timeouts decide
+        let Some(status) = status else {
+            return;
+        };
+        let level = status.confirmation_status.as_deref()
+            .and_then(CommitmentLevel::parse)
+            .unwrap_or(CommitmentLevel::Processed);
+
+        let mut tracked = self.handle.tracked.lock().unwrap();
+        let Some(entry) = tracked.get_mut(signature) else {
+            return;
+        };
+        entry.slot = Some(status.slot);
+        entry.err = status.err.clone();
+
+        while entry.level < level {
+            let Some(next) = entry.level.next() else {
+                break;
+            };
+            entry.level = next;
+            let _ = self.handle.events.send(entry.event(signature, next.stage()));
+        }
+
+        if entry.level == CommitmentLevel::Finalized {
+            tracked.remove(signature);
+            if let Ok(body) = serde_json::to_string(&Some(status)) {
+                self.rpc_capture.record("getSignatureStatuses", signature, &body);
+            }
+        }
+    }
+
+    fn expire(&self, polled: &HashSet<String>) {
+        let replay = self.rpc_capture.is_replay();
+        let mut tracked = self.handle.tracked.lock().unwrap();
+        let expired: Vec<String> = tracked.iter()
+            .filter(|(signature, entry)| {
+                let elapsed = entry.first_seen.elapsed();
+                // A replayed signature still pending after being polled has no final status, it was dropped in the recording
+                (replay && polled.contains(*signature))
+                    || (entry.level < CommitmentLevel::Confirmed && elapsed > self.confirm_timeout)
+                    || elapsed > self.finalize_timeout
+            })
+            .map(|(signature, _)| signature.clone())
+            .collect();
+
+        for signature in expired {
+            if let Some(entry) = tracked.remove(&signature) {
+                println!("[[COMMITMENT TRACKER]] {} dropped after {:?} at {:?}", signature, entry.first_seen.elapsed(), entry.level);
+                let _ = self.handle.events.send(entry.event(&signature, LifecycleStage::Dropped));
+                self.rpc_capture.record("getSignatureStatuses", &signature, "null");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::http::rpc_pool::RpcPool;
+    use crate::subscriber::capture::RecordedResponses;
+
+    use super::*;
+
+    fn tracker(rpc_capture: RpcCapture) -> (CommitmentTracker<&'static str>, UnboundedReceiver<LifecycleEvent<&'static str>>) {
+        CommitmentTracker::new(SolanaRpcClient::with_pool(RpcPool::new(vec![])), rpc_capture)
+    }
+
+    fn status(slot: u64, confirmation_status: &str, err: Option<Value>) -> Option<SignatureStatus> {
+        Some(SignatureStatus { slot, confirmations: None, err, confirmation_status: Some(confirmation_status.to_string()) })
+    }
+
+    fn stages(events: &mut UnboundedReceiver<LifecycleEvent<&'static str>>) -> Vec<LifecycleStage> {
+        let mut stages = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            stages.push(event.stage);
+        }
+        stages
+    }
+
+    #[test]
+    fn emits_each_stage_once_and_in_order() {
+        let (tracker, mut events) = tracker(RpcCapture::Live(None));
+        let handle = tracker.handle();
+        assert!(handle.track("sig", CommitmentLevel::Processed, None, "whale"));
+        assert!(!handle.track("sig", CommitmentLevel::Confirmed, None, "whale"));
+        assert_eq!(stages(&mut events), vec![LifecycleStage::SeenProcessed]);
+
+        // Unknown to the node yet
+        tracker.apply("sig", None);
+        tracker.apply("sig", status(100, "processed", None));
+        assert!(stages(&mut events).is_empty());
+
+        // Confirmed was skipped between two polls, it is still emitted
+        tracker.apply("sig", status(100, "finalized", None));
+        assert_eq!(stages(&mut events), vec![LifecycleStage::Confirmed, LifecycleStage::Finalized]);
+        assert!(tracker.handle.tracked.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn starts_at_the_commitment_it_was_seen_at() {
+        let (tracker, mut events) = tracker(RpcCapture::Live(None));
+        let handle = tracker.handle();
+        handle.track("confirmed", CommitmentLevel::Confirmed, Some(100), "whale");
+        // Already final, not tracked
+        handle.track("finalized", CommitmentLevel::Finalized, Some(90), "whale");
+        assert_eq!(stages(&mut events), vec![LifecycleStage::Confirmed, LifecycleStage::Finalized]);
+        assert_eq!(tracker.handle.tracked.lock().unwrap().len(), 1);
+
+        // Failed transactions get finalized too
+        let err = serde_json::json!({"InstructionError": [0, {"Custom": 1}]});
+        tracker.apply("confirmed", status(101, "finalized", Some(err.clone())));
+        let event = events.try_recv().unwrap();
+        assert_eq!((event.stage, event.slot, event.err, event.context), (LifecycleStage::Finalized, Some(101), Some(err), "whale"));
+    }
+
+    #[test]
+    fn drops_signatures_past_their_timeouts() {
+        let (mut tracker, mut events) = tracker(RpcCapture::Live(None));
+        tracker.confirm_timeout = Duration::ZERO;
+        let handle = tracker.handle();
+        handle.track("processed", CommitmentLevel::Processed, None, "whale");
+        handle.track("confirmed", CommitmentLevel::Confirmed, Some(100), "whale");
+        stages(&mut events);
+
+        std::thread::sleep(Duration::from_millis(5));
+        tracker.expire(&HashSet::new());
+        let event = events.try_recv().unwrap();
+        assert_eq!((event.signature.as_str(), event.stage), ("processed", LifecycleStage::Dropped));
+        assert!(events.try_recv().is_err());
+
+        tracker.finalize_timeout = Duration::ZERO;
+        tracker.expire(&HashSet::new());
+        assert_eq!(stages(&mut events), vec![LifecycleStage::Dropped]);
+        assert!(tracker.handle.tracked.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn replayed_signatures_without_a_final_status_are_dropped() {
+        let (tracker, mut events) = tracker(RpcCapture::Replay(RecordedResponses::default()));
+        let handle = tracker.handle();
+        handle.track("polled", CommitmentLevel::Processed, None, "whale");
+        handle.track("tracked since the poll", CommitmentLevel::Processed, None, "whale");
+        stages(&mut events);
+
+        tracker.expire(&HashSet::from(["polled".to_string()]));
+        let event = events.try_recv().unwrap();
+        assert_eq!((event.signature.as_str(), event.stage), ("polled", LifecycleStage::Dropped));
+        assert!(events.try_recv().is_err());
+    }
+}
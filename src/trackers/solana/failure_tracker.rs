@@ -0,0 +1,147 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, RwLock};
+
+use serde::{Deserialize, Serialize};
+
+use crate::decoder::log_parser::parse_logs;
+use crate::decoder::transaction_error::{custom_error_program, decode_error, has_error_table};
+use crate::models::solana::failed_transaction::FailedTransaction;
+use crate::models::solana::program_logs::ProgramLogs;
+use crate::models::solana::solana_logs_notification::NotificationValue;
+use crate::trackers::solana::idl_registry::IdlRegistry;
+
+// Failed transactions kept for /failures
+const RECENT_FAILURES: usize = 1000;
+
+/// How many transactions failed in a program for a reason since startup
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FailureCount {
+    // None for transaction level errors
+    pub program_id: Option<String>,
+    pub reason: String,
+    pub count: u64,
+}
+
+// (program, reason) -> failures since startup
+type FailureCounts = Arc<RwLock<HashMap<(Option<String>, String), u64>>>;
+// Program id -> failures waiting for its IDL to be loaded
+type AwaitingIdl = Arc<Mutex<HashMap<String, Vec<(FailedTransaction, ProgramLogs)>>>>;
+
+/**
+Failed transactions of every log subscription, decoded into named causes. Keeps the last 1000 failures and the count of every
+(program, reason) since startup. Cheap to clone.
+
+Custom errors of programs the known tables don't cover are named with the program's Anchor IDL. Failures are recorded right away
+with what is already loaded: when the IDL isn't, it is loaded in the background (once per program, whatever the number of
+failures waiting for it) and the failures are renamed once it is.
+ */
+#[derive(Clone)]
+pub struct FailureTracker {
+    idl_registry: IdlRegistry,
+    recent: Arc<RwLock<VecDeque<FailedTransaction>>>,
+    counts: FailureCounts,
+    awaiting_idl: AwaitingIdl,
+}
+
+impl FailureTracker {
+    pub fn new(idl_registry: IdlRegistry) -> Self {
+        Self {
+            idl_registry,
+            recent: Arc::new(RwLock::new(VecDeque::new())),
+            counts: Arc::new(RwLock::new(HashMap::new())),
+            awaiting_idl: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Decodes and records the failure of a log notification, never waits for the network. None when the transaction succeeded.
+    /// Must be called within the tokio runtime: a missing IDL is loaded by a spawned task.
+    pub fn record(&self, slot: u64, value: &NotificationValue) -> Option<FailedTransaction> {
+        let err = value.err.as_ref()?;
+        let program_logs = parse_logs(&value.logs);
+
+        let idl_program = custom_error_program(err, &program_logs)
+            .map(|(program_id, _)| program_id)
+            .filter(|program_id| !has_error_table(program_id));
+        let idl = idl_program.as_deref().and_then(|program_id| self.idl_registry.get(program_id));
+        let failed = decode_error(&value.signature, slot, err, &program_logs, idl.as_deref());
+
+        *self.counts.write().unwrap().entry((failed.program_id.clone(), failed.reason())).or_insert(0) += 1;
+        {
+            let mut recent = self.recent.write().unwrap();
+            recent.push_back(failed.clone());
+            while recent.len() > RECENT_FAILURES {
+                recent.pop_front();
+            }
+        }
+
+        if let (Some(program_id), None) = (idl_program, idl) {
+            let mut awaiting_idl = self.awaiting_idl.lock().unwrap();
+            let waiting = awaiting_idl.entry(program_id.clone()).or_default();
+            waiting.push((failed.clone(), program_logs));
+            // The first failure waiting for the program loads its IDL
+            if waiting.len() == 1 {
+                tokio::spawn(self.clone().name_with_idl(program_id));
+            }
+        }
+        Some(failed)
+    }
+
+    /// Loads the IDL of the program and renames the failures that were waiting for it
+    async fn name_with_idl(self, program_id: String) {
+        let idl = match self.idl_registry.load(&program_id).await {
+            Ok(idl) => idl,
+            Err(e) => {
+                eprintln!("[[FAILURES]] Can't load the IDL of {}: {}", program_id, e);
+                None
+            }
+        };
+        let waiting = self.awaiting_idl.lock().unwrap().remove(&program_id).unwrap_or_default();
+        let Some(idl) = idl else {
+            return;
+        };
+        for (failed, program_logs) in waiting {
+            let named = decode_error(&failed.signature, failed.slot, &failed.err, &program_logs, Some(&idl));
+            if named != failed {
+                println!("[[FAILED]] {}", named);
+                self.replace(&failed, named);
+            }
+        }
+    }
+
+    fn replace(&self, failed: &FailedTransaction, named: FailedTransaction) {
+        {
+            let mut counts = self.counts.write().unwrap();
+            let key = (failed.program_id.clone(), failed.reason());
+            if let Some(count) = counts.get_mut(&key) {
+                *count = count.saturating_sub(1);
+                if *count == 0 {
+                    counts.remove(&key);
+                }
+            }
+            *counts.entry((named.program_id.clone(), named.reason())).or_insert(0) += 1;
+        }
+        let mut recent = self.recent.write().unwrap();
+        if let Some(recorded) = recent.iter_mut().find(|recorded| recorded.signature == failed.signature) {
+            *recorded = named;
+        }
+    }
+
+    /// Newest first, of the program when given
+    pub fn recent(&self, program_id: Option<&str>, limit: usize) -> Vec<FailedTransaction> {
+        self.recent.read().unwrap().iter()
+            .rev()
+            .filter(|failed| program_id.is_none_or(|program_id| failed.program_id.as_deref() == Some(program_id)))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// Most frequent first
+    pub fn counts(&self) -> Vec<FailureCount> {
+        let mut counts: Vec<FailureCount> = self.counts.read().unwrap().iter()
+            .map(|((program_id, reason), count)| FailureCount { program_id: program_id.clone(), reason: reason.clone(), count: *count })
+            .collect();
+        counts.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.reason.cmp(&b.reason)));
+        counts
+    }
+}
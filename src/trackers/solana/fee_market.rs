@@ -0,0 +1,268 @@
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::sync::{Arc, RwLock};
+
+use serde::{Deserialize, Serialize};
+
+use crate::decoder::compute_budget_instructions::{compute_budget, COMPUTE_BUDGET_PROGRAM_ID};
+use crate::models::solana::compute_budget::ComputeBudget;
+use crate::models::solana::solana_encoded_transaction::EncodedTransactionWithMeta;
+
+/// Slots of history the estimates are computed over, ~1 minute
+const DEFAULT_WINDOW_SLOTS: u64 = 150;
+// Vote transactions pay no priority fee and would drag every percentile to 0
+const VOTE_PROGRAM_ID: &str = "Vote111111111111111111111111111111111111111";
+
+/// Unit prices (micro-lamports per CU) paid over the window, nearest-rank percentiles
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FeeEstimate {
+    pub samples: usize,
+    pub first_slot: u64,
+    pub last_slot: u64,
+    pub min: u64,
+    pub p25: u64,
+    pub p50: u64,
+    pub p75: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub max: u64,
+}
+
+impl FeeEstimate {
+    /// None without samples. `samples` are (slot, unit price).
+    fn from_samples(mut samples: Vec<(u64, u64)>) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        let first_slot = samples.iter().map(|(slot, _)| *slot).min().unwrap_or_default();
+        let last_slot = samples.iter().map(|(slot, _)| *slot).max().unwrap_or_default();
+        samples.sort_by_key(|(_, price)| *price);
+        let prices: Vec<u64> = samples.into_iter().map(|(_, price)| price).collect();
+        Some(FeeEstimate {
+            samples: prices.len(),
+            first_slot,
+            last_slot,
+            min: prices[0],
+            p25: percentile(&prices, 25),
+            p50: percentile(&prices, 50),
+            p75: percentile(&prices, 75),
+            p90: percentile(&prices, 90),
+            p99: percentile(&prices, 99),
+            max: prices[prices.len() - 1],
+        })
+    }
+
+    /// The percentile of the ones kept, rounded up to the next kept one (ie. 80 -> p90)
+    pub fn at(&self, percentile: u8) -> u64 {
+        match percentile {
+            0 => self.min,
+            1..=25 => self.p25,
+            26..=50 => self.p50,
+            51..=75 => self.p75,
+            76..=90 => self.p90,
+            91..=99 => self.p99,
+            _ => self.max,
+        }
+    }
+}
+
+/// One transaction seen by the market: its unit price, the programs of its outer instructions and its writable accounts
+#[derive(Debug, Clone)]
+struct FeeSample {
+    unit_price: u64,
+    programs: Vec<String>,
+    writable_accounts: Vec<String>,
+}
+
+/// Samples of each slot of the window
+type SlotSamples = VecDeque<(u64, Vec<FeeSample>)>;
+
+/**
+Rolling priority fee market over the last `window` slots, fed with every transaction observed (blocks of the `blockSubscribe`
+subscriptions, fetched whale transactions). Estimates are unit price percentiles of the transactions that invoked a program, or
+that write-locked an account: transactions competing for a hot pool compete for its write lock, the account estimate is the
+one to price a send against it. Cheap to clone.
+ */
+#[derive(Clone)]
+pub struct FeeMarket {
+    window: u64,
+    // Oldest slot first
+    slots: Arc<RwLock<SlotSamples>>,
+}
+
+impl FeeMarket {
+    pub fn new(window: u64) -> Self {
+        Self {
+            window: window.max(1),
+            slots: Arc::new(RwLock::new(VecDeque::new())),
+        }
+    }
+
+    /// FEE_MARKET_SLOTS slots of history, 150 by default
+    pub fn from_env() -> Self {
+        let window = env::var("FEE_MARKET_SLOTS").ok()
+            .and_then(|window| window.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_WINDOW_SLOTS);
+        Self::new(window)
+    }
+
+    /// Records the transaction and returns its compute budget. None for vote transactions and transactions that can't be decoded.
+    pub fn observe(&self, transaction: &EncodedTransactionWithMeta) -> Option<ComputeBudget> {
+        let instructions = transaction.instructions().ok()?;
+        if instructions.iter().any(|instruction| instruction.program_id == VOTE_PROGRAM_ID) {
+            return None;
+        }
+        let budget = compute_budget(transaction).ok()?;
+
+        let mut programs: Vec<String> = instructions.iter()
+            .filter(|instruction| instruction.inner_index.is_none() && instruction.program_id != COMPUTE_BUDGET_PROGRAM_ID)
+            .map(|instruction| instruction.program_id.clone())
+            .collect();
+        programs.sort();
+        programs.dedup();
+        // Signers are the senders' own accounts, nobody competes for them
+        let writable_accounts = transaction.account_keys().keys.into_iter()
+            .filter(|key| key.writable && !key.signer)
+            .map(|key| key.pubkey)
+            .collect();
+
+        self.record(transaction.slot, FeeSample { unit_price: budget.unit_price, programs, writable_accounts });
+        Some(budget)
+    }
+
+    fn record(&self, slot: u64, sample: FeeSample) {
+        let mut slots = self.slots.write().unwrap();
+        let newest = slots.back().map_or(slot, |(newest, _)| (*newest).max(slot));
+        if slot + self.window <= newest {
+            return;
+        }
+        // Blocks mostly arrive in order, a late one goes before the newer slots
+        let position = slots.iter().rposition(|(known, _)| *known <= slot);
+        match position {
+            Some(position) if slots[position].0 == slot => slots[position].1.push(sample),
+            Some(position) => slots.insert(position + 1, (slot, vec![sample])),
+            None => slots.push_front((slot, vec![sample])),
+        }
+        while slots.front().is_some_and(|(oldest, _)| oldest + self.window <= newest) {
+            slots.pop_front();
+        }
+    }
+
+    fn estimate_where(&self, matches: impl Fn(&FeeSample) -> bool) -> Option<FeeEstimate> {
+        let slots = self.slots.read().unwrap();
+        let samples = slots.iter()
+            .flat_map(|(slot, samples)| samples.iter().filter(|sample| matches(sample)).map(move |sample| (*slot, sample.unit_price)))
+            .collect();
+        FeeEstimate::from_samples(samples)
+    }
+
+    /// Every non-vote transaction of the window
+    pub fn estimate(&self) -> Option<FeeEstimate> {
+        self.estimate_where(|_| true)
+    }
+
+    /// Transactions with an outer instruction of the program
+    pub fn estimate_program(&self, program_id: &str) -> Option<FeeEstimate> {
+        self.estimate_where(|sample| sample.programs.iter().any(|program| program == program_id))
+    }
+
+    /// Transactions that write-locked the account
+    pub fn estimate_account(&self, account: &str) -> Option<FeeEstimate> {
+        self.estimate_where(|sample| sample.writable_accounts.iter().any(|writable| writable == account))
+    }
+
+    /// The accounts write-locked by the most transactions of the window, with their estimate
+    pub fn hottest_accounts(&self, limit: usize) -> Vec<(String, FeeEstimate)> {
+        let counts: HashMap<String, usize> = {
+            let slots = self.slots.read().unwrap();
+            let mut counts = HashMap::new();
+            for sample in slots.iter().flat_map(|(_, samples)| samples.iter()) {
+                for account in &sample.writable_accounts {
+                    *counts.entry(account.clone()).or_insert(0) += 1;
+                }
+            }
+            counts
+        };
+        let mut hottest: Vec<(String, usize)> = counts.into_iter().collect();
+        hottest.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        hottest.into_iter()
+            .take(limit)
+            .filter_map(|(account, _)| self.estimate_account(&account).map(|estimate| (account, estimate)))
+            .collect()
+    }
+}
+
+/// Nearest-rank percentile of sorted, non empty `values`
+fn percentile(values: &[u64], percentile: usize) -> u64 {
+    let rank = (percentile * values.len()).div_ceil(100);
+    values[rank.clamp(1, values.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(unit_price: u64, programs: &[&str], writable_accounts: &[&str]) -> FeeSample {
+        FeeSample {
+            unit_price,
+            programs: programs.iter().map(|program| program.to_string()).collect(),
+            writable_accounts: writable_accounts.iter().map(|account| account.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn nearest_rank_percentiles() {
+        let values: Vec<u64> = (1..=10).collect();
+        assert_eq!(percentile(&values, 25), 3);
+        assert_eq!(percentile(&values, 50), 5);
+        assert_eq!(percentile(&values, 90), 9);
+        assert_eq!(percentile(&values, 99), 10);
+        assert_eq!(percentile(&values, 0), 1);
+        assert_eq!(percentile(&[7], 50), 7);
+    }
+
+    #[test]
+    fn estimates_by_program_and_account() {
+        let market = FeeMarket::new(150);
+        for unit_price in 1..=100 {
+            market.record(1000 + unit_price % 3, sample(unit_price * 1000, &["whirlpool"], &["pool"]));
+        }
+        market.record(1001, sample(5, &["token"], &["user"]));
+
+        let estimate = market.estimate().unwrap();
+        assert_eq!((estimate.samples, estimate.first_slot, estimate.last_slot), (101, 1000, 1002));
+        assert_eq!((estimate.min, estimate.max), (5, 100_000));
+
+        let pool = market.estimate_account("pool").unwrap();
+        assert_eq!((pool.p25, pool.p50, pool.p90, pool.p99), (25_000, 50_000, 90_000, 99_000));
+        assert_eq!(pool.at(80), pool.p90);
+        assert_eq!(market.estimate_program("whirlpool"), Some(pool));
+        assert_eq!(market.estimate_program("token").unwrap().samples, 1);
+        assert!(market.estimate_account("unknown").is_none());
+
+        let hottest = market.hottest_accounts(1);
+        assert_eq!(hottest.len(), 1);
+        assert_eq!(hottest[0].0, "pool");
+    }
+
+    #[test]
+    fn evicts_slots_out_of_the_window() {
+        let market = FeeMarket::new(10);
+        market.record(100, sample(1, &[], &["a"]));
+        market.record(105, sample(2, &[], &["a"]));
+        // Late block, still in the window
+        market.record(103, sample(3, &[], &["a"]));
+        assert_eq!(market.slots.read().unwrap().iter().map(|(slot, _)| *slot).collect::<Vec<u64>>(), vec![100, 103, 105]);
+
+        market.record(110, sample(4, &[], &["a"]));
+        let estimate = market.estimate().unwrap();
+        assert_eq!((estimate.samples, estimate.first_slot, estimate.min), (3, 103, 2));
+
+        // Older than the window
+        market.record(100, sample(0, &[], &["a"]));
+        assert_eq!(market.estimate().unwrap().samples, 3);
+        // Same slot, one more sample
+        market.record(110, sample(5, &[], &["a"]));
+        assert_eq!(market.slots.read().unwrap().back().unwrap().1.len(), 2);
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::decoder::account_decoder::decode_payload;
+use crate::decoder::anchor_idl::{AnchorIdl, AnchorInstructionDecoder};
+use crate::decoder::instruction_decoder::{DecodedInstruction, INSTRUCTION_DECODERS};
+use crate::http::rpc_cache::IDL_ACCOUNT;
+use crate::http::solana_rpc_client::SolanaRpcClient;
+use crate::models::solana::program_logs::{ProgramInvocation, ProgramLogs};
+use crate::models::solana::solana_encoded_transaction::ResolvedInstruction;
+use crate::subscriber::capture::RpcCapture;
+
+// How long a program without a readable IDL isn't fetched again
+const MISSING_TTL: Duration = Duration::from_secs(10 * 60);
+
+/**
+Anchor IDLs by program id, read from the local IDL directory at startup and fetched from the `anchor:idl` account of the program
+the first time one of its instructions has to be decoded.
+
+Every IDL loaded registers an `AnchorInstructionDecoder` on `INSTRUCTION_DECODERS`, so the transaction summaries decode the
+instructions of these programs too. Programs without an on-chain IDL, or whose IDL couldn't be fetched or read, are remembered
+and not fetched again for 10 minutes. Cheap to clone.
+ */
+#[derive(Clone)]
+pub struct IdlRegistry {
+    rpc: SolanaRpcClient,
+    rpc_capture: RpcCapture,
+    idls: Arc<RwLock<HashMap<String, Arc<AnchorIdl>>>>,
+    // Program id -> when its load failed or found no IDL
+    missing: Arc<RwLock<HashMap<String, Instant>>>,
+}
+
+impl IdlRegistry {
+    pub fn new(rpc: SolanaRpcClient, rpc_capture: RpcCapture) -> Self {
+        Self {
+            rpc,
+            rpc_capture,
+            idls: Arc::new(RwLock::new(HashMap::new())),
+            missing: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// Local IDLs from IDL_DIR, public/idls by default
+    pub fn from_env(rpc: SolanaRpcClient, rpc_capture: RpcCapture) -> Self {
+        let dir = env::var("IDL_DIR").unwrap_or_else(|_| "public/idls".to_string());
+        Self::new(rpc, rpc_capture).with_dir(PathBuf::from(dir))
+    }
+
+    /// Loads every json IDL of `dir` that names its program (`address`, or `metadata.address` for legacy IDLs).
+    pub fn with_dir(self, dir: PathBuf) -> Self {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                eprintln!("[[IDL REGISTRY]] Can't read IDL directory {:?}: {}", dir, e);
+                return self;
+            }
+        };
+        for path in entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+            if path.extension().is_some_and(|extension| extension == "json") {
+                if let Err(e) = self.load_file(&path) {
+                    eprintln!("[[IDL REGISTRY]] Ignoring {:?}: {}", path, e);
+                }
+            }
+        }
+        self
+    }
+
+    fn load_file(&self, path: &Path) -> Result<(), String> {
+        let contents = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let idl = AnchorIdl::from_json(&contents).map_err(|e| e.to_string())?;
+        let program_id = idl.address.clone().ok_or_else(|| "the IDL has no program address".to_string())?;
+        println!("[[IDL REGISTRY]] Loaded {} ({}) from {:?}", idl.name, program_id, path);
+        self.insert(&program_id, idl);
+        Ok(())
+    }
+
+    /// Replaces any IDL already loaded for the program, and its instruction decoder.
+    pub fn insert(&self, program_id: &str, idl: AnchorIdl) -> Arc<AnchorIdl> {
+        let idl = Arc::new(idl);
+        INSTRUCTION_DECODERS.write().unwrap().register(AnchorInstructionDecoder::new(program_id, idl.clone()));
+        self.idls.write().unwrap().insert(program_id.to_string(), idl.clone());
+        self.missing.write().unwrap().remove(program_id);
+        idl
+    }
+
+    /// Only what is already loaded, never fetches
+    pub fn get(&self, program_id: &str) -> Option<Arc<AnchorIdl>> {
+        self.idls.read().unwrap().get(program_id).cloned()
+    }
+
+    /// The IDL of the program, fetched on-chain when not loaded yet. Ok(None) when the program has no on-chain IDL, or when the
+    /// last attempt failed less than 10 minutes ago.
+    pub async fn load(&self, program_id: &str) -> Result<Option<Arc<AnchorIdl>>, String> {
+        if let Some(idl) = self.get(program_id) {
+            return Ok(Some(idl));
+        }
+        if self.missing.read().unwrap().get(program_id).is_some_and(|since| since.elapsed() < MISSING_TTL) {
+            return Ok(None);
+        }
+
+        match self.fetch(program_id).await {
+            Ok(Some(idl)) => {
+                println!("[[IDL REGISTRY]] Fetched the on-chain IDL of {} ({})", idl.name, program_id);
+                Ok(Some(self.insert(program_id, idl)))
+            }
+            Ok(None) => {
+                self.missing.write().unwrap().insert(program_id.to_string(), Instant::now());
+                Ok(None)
+            }
+            Err(e) => {
+                self.missing.write().unwrap().insert(program_id.to_string(), Instant::now());
+                Err(e)
+            }
+        }
+    }
+
+    async fn fetch(&self, program_id: &str) -> Result<Option<AnchorIdl>, String> {
+        let address = AnchorIdl::address_of(program_id).map_err(|e| e.to_string())?.to_string();
+        let account = self.rpc_capture.get_account_infos(&self.rpc, IDL_ACCOUNT, std::slice::from_ref(&address)).await.remove(0)?;
+        let Some(account) = account else {
+            return Ok(None);
+        };
+
+        let payload = account.data.first().ok_or_else(|| format!("IDL account {} without data", address))?;
+        let data = decode_payload(payload, "base64").map_err(|e| e.to_string())?;
+        AnchorIdl::from_account_data(&data)
+            .map(Some)
+            .map_err(|e| format!("unreadable IDL account {}: {}", address, e))
+    }
+
+    /// Same order as `instructions`. Decoded by `INSTRUCTION_DECODERS`, after loading the IDL of the programs without a decoder.
+    pub async fn decode_instructions(&self, instructions: &[ResolvedInstruction]) -> Vec<Result<Option<DecodedInstruction>, String>> {
+        let mut undecodable: HashMap<String, String> = HashMap::new();
+        let mut programs: Vec<String> = {
+            let decoders = INSTRUCTION_DECODERS.read().unwrap();
+            instructions.iter()
+                .filter(|instruction| decoders.decoder_for(&instruction.program_id).is_none())
+                .map(|instruction| instruction.program_id.clone())
+                .collect()
+        };
+        programs.sort();
+        programs.dedup();
+        for program_id in programs {
+            if let Err(e) = self.load(&program_id).await {
+                undecodable.insert(program_id, e);
+            }
+        }
+
+        let decoders = INSTRUCTION_DECODERS.read().unwrap();
+        instructions.iter()
+            .map(|instruction| match undecodable.get(&instruction.program_id) {
+                Some(e) => Err(format!("no IDL for {}: {}", instruction.program_id, e)),
+                None => decoders.decode(instruction).map_err(|e| e.to_string()),
+            })
+            .collect()
+    }
+
+    /// Decodes the `Program data:` logs of the tree into Anchor events, loading the IDL of the programs that emitted them.
+    /// Logs of programs without an IDL, or that don't match an event of it, are left undecoded.
+    pub async fn decode_events(&self, logs: &mut ProgramLogs) {
+        let mut programs: Vec<String> = logs.flatten().into_iter()
+            .filter(|invocation| !invocation.data.is_empty())
+            .map(|invocation| invocation.program_id.clone())
+            .collect();
+        programs.sort();
+        programs.dedup();
+
+        let mut idls = HashMap::new();
+        for program_id in programs {
+            match self.load(&program_id).await {
+                Ok(Some(idl)) => {
+                    idls.insert(program_id, idl);
+                }
+                Ok(None) => {}
+                Err(e) => eprintln!("[[IDL REGISTRY]] No IDL to decode the events of {}: {}", program_id, e),
+            }
+        }
+        for invocation in logs.invocations.iter_mut() {
+            decode_invocation_events(invocation, &idls);
+        }
+    }
+}
+
+fn decode_invocation_events(invocation: &mut ProgramInvocation, idls: &HashMap<String, Arc<AnchorIdl>>) {
+    if let Some(idl) = idls.get(&invocation.program_id) {
+        for data in invocation.data.iter_mut() {
+            // An event is logged as a single chunk
+            let Some(Ok(bytes)) = data.data.first().map(|chunk| decode_payload(chunk, "base64")) else {
+                continue;
+            };
+            match idl.decode_event(&bytes) {
+                Ok(event) => data.event = event,
+                Err(e) => eprintln!("[[IDL REGISTRY]] Could not decode an event of {}: {}", invocation.program_id, e),
+            }
+        }
+    }
+    for nested in invocation.invocations.iter_mut() {
+        decode_invocation_events(nested, idls);
+    }
+}
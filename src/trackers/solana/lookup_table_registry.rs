@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use crate::decoder::account_decoder::decode_payload;
+use crate::decoder::address_lookup_table::AddressLookupTable;
+use crate::http::rpc_cache::LOOKUP_TABLE_ACCOUNT;
+use crate::http::solana_rpc_client::SolanaRpcClient;
+use crate::models::solana::solana_encoded_transaction::EncodedTransactionWithMeta;
+use crate::subscriber::capture::RpcCapture;
+
+/**
+Address lookup tables by address, fetched the first time a v0 transaction points into them without the node having loaded
+its addresses (`meta.loadedAddresses`, missing from some providers and from older transactions).
+
+Tables are only ever extended, a table is refetched when a transaction points past the addresses we have. Cheap to clone.
+ */
+#[derive(Clone)]
+pub struct LookupTableRegistry {
+    rpc: SolanaRpcClient,
+    rpc_capture: RpcCapture,
+    tables: Arc<RwLock<HashMap<String, Arc<AddressLookupTable>>>>,
+}
+
+impl LookupTableRegistry {
+    pub fn new(rpc: SolanaRpcClient, rpc_capture: RpcCapture) -> Self {
+        Self {
+            rpc,
+            rpc_capture,
+            tables: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// Only what is already loaded, never fetches
+    pub fn get(&self, address: &str) -> Option<Arc<AddressLookupTable>> {
+        self.tables.read().unwrap().get(address).cloned()
+    }
+
+    /// Fills `meta.loadedAddresses` from the lookup tables of the message when the node did not, so that `account_keys` and
+    /// `instructions` see every account. Nothing to do for legacy messages and transactions the node resolved.
+    pub async fn resolve(&self, transaction: &mut EncodedTransactionWithMeta) -> Result<(), String> {
+        let addresses = transaction.unresolved_lookup_tables();
+        if addresses.is_empty() {
+            return Ok(());
+        }
+
+        let missing: Vec<String> = addresses.iter().filter(|address| self.get(address).is_none()).cloned().collect();
+        self.fetch(&missing, false).await?;
+
+        // Extended since we read them: refetched around the lookup table cache, which would serve the same addresses again
+        let mut highest_index: HashMap<&str, u8> = HashMap::new();
+        for lookup in transaction.transaction.message.address_table_lookups.iter().flatten() {
+            let highest = lookup.writable_indexes.iter().chain(lookup.readonly_indexes.iter()).max().copied().unwrap_or_default();
+            let entry = highest_index.entry(lookup.account_key.as_str()).or_default();
+            *entry = (*entry).max(highest);
+        }
+        let stale: Vec<String> = highest_index.iter()
+            .filter(|(address, highest)| self.get(address).is_some_and(|table| table.addresses.len() <= **highest as usize))
+            .map(|(address, _)| address.to_string())
+            .collect();
+        self.fetch(&stale, true).await?;
+
+        let tables: HashMap<String, Arc<AddressLookupTable>> = addresses.iter()
+            .filter_map(|address| self.get(address).map(|table| (address.clone(), table)))
+            .collect();
+        transaction.load_addresses(&tables).map_err(|e| format!("can't load the addresses of {}: {}", transaction.signature().unwrap_or_default(), e))
+    }
+
+    /// One batch for every table of `addresses`, cached under `LOOKUP_TABLE_ACCOUNT`. `refresh` skips the cached tables and
+    /// replaces them with the fetched ones.
+    async fn fetch(&self, addresses: &[String], refresh: bool) -> Result<(), String> {
+        if addresses.is_empty() {
+            return Ok(());
+        }
+        let accounts = if refresh {
+            self.rpc_capture.refresh_account_infos(&self.rpc, LOOKUP_TABLE_ACCOUNT, addresses).await
+        } else {
+            self.rpc_capture.get_account_infos(&self.rpc, LOOKUP_TABLE_ACCOUNT, addresses).await
+        };
+        for (address, account) in addresses.iter().zip(accounts) {
+            let account = account.map_err(|e| format!("can't fetch lookup table {}: {}", address, e))?
+                .ok_or_else(|| format!("lookup table {} does not exist (closed?)", address))?;
+            let payload = account.data.first().ok_or_else(|| format!("lookup table {} without data", address))?;
+            let data = decode_payload(payload, "base64").map_err(|e| e.to_string())?;
+            let table = AddressLookupTable::unpack(&data).map_err(|e| format!("unreadable lookup table {}: {}", address, e))?;
+            println!("[[LOOKUP TABLES]] Loaded {} ({} addresses)", address, table.addresses.len());
+            self.tables.write().unwrap().insert(address.clone(), Arc::new(table));
+        }
+        Ok(())
+    }
+}
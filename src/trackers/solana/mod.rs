@@ -0,0 +1,6 @@
+pub mod commitment_tracker;
+pub mod token_registry;
+pub mod idl_registry;
+pub mod lookup_table_registry;
+pub mod fee_market;
+pub mod failure_tracker;
@@ -0,0 +1,306 @@
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+
+use mpl_token_metadata::accounts::Metadata;
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::decoder::account_decoder::decode_payload;
+use crate::decoder::spl_token_accounts::{Mint, SPL_TOKEN_PROGRAM_ID};
+use crate::decoder::token_2022::{Token2022Mint, TokenMetadata, EXTENSION_TOKEN_METADATA, TOKEN_2022_PROGRAM_ID};
+use crate::http::rpc_cache::METADATA_ACCOUNT;
+use crate::http::solana_rpc_client::SolanaRpcClient;
+use crate::models::solana::solana_rpc::RpcAccount;
+use crate::models::solana::token_info::{MetadataSource, TokenInfo, TokenProgram};
+use crate::subscriber::capture::RpcCapture;
+
+/// Bundled with the binary, resolved without any RPC call
+const KNOWN_TOKENS: &str = include_str!("../../../config/known_tokens.json");
+
+/// One entry of config/known_tokens.json
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct KnownToken {
+    mint: String,
+    name: String,
+    symbol: String,
+    decimals: u8,
+    token_program: TokenProgram,
+    // SOL and stables, the payment side of most transfers
+    quote: bool,
+}
+
+/**
+Resolves mints to their `TokenInfo`: decimals and supply from the mint account, name, symbol and uri from the Token-2022
+`TokenMetadata` extension when the mint has one, from its Metaplex metadata PDA otherwise. The other extensions of Token-2022
+mints are decoded and flagged when they give the mint's authorities power over the holders.
+
+Seeded from the bundled known token list, and every mint resolved is kept in memory and persisted to a json file (when the registry
+has a path) so it is only fetched once. Account lookups go through the `RpcCapture`, recorded as `getAccountInfo`. Cheap to clone.
+ */
+#[derive(Clone)]
+pub struct TokenRegistry {
+    rpc: SolanaRpcClient,
+    rpc_capture: RpcCapture,
+    tokens: Arc<RwLock<HashMap<String, TokenInfo>>>,
+    quote_mints: Arc<HashSet<String>>,
+    path: Option<PathBuf>,
+}
+
+impl TokenRegistry {
+    /// Seeded from the known token list only, nothing persisted
+    pub fn new(rpc: SolanaRpcClient, rpc_capture: RpcCapture) -> Self {
+        let known: Vec<KnownToken> = serde_json::from_str(KNOWN_TOKENS).expect("config/known_tokens.json must be valid");
+        let quote_mints = known.iter().filter(|token| token.quote).map(|token| token.mint.clone()).collect();
+        let tokens = known.into_iter()
+            .map(|token| (token.mint.clone(), TokenInfo {
+                mint: token.mint,
+                name: Some(token.name),
+                symbol: Some(token.symbol),
+                decimals: token.decimals,
+                uri: None,
+                update_authority: None,
+                is_mutable: None,
+                supply: None,
+                token_program: token.token_program,
+                metadata_source: MetadataSource::KnownList,
+                extensions: None,
+                risk_flags: Vec::new(),
+            }))
+            .collect();
+
+        Self {
+            rpc,
+            rpc_capture,
+            tokens: Arc::new(RwLock::new(tokens)),
+            quote_mints: Arc::new(quote_mints),
+            path: None,
+        }
+    }
+
+    /// Persisted to TOKEN_REGISTRY_PATH, data/token_registry.json by default
+    pub fn from_env(rpc: SolanaRpcClient, rpc_capture: RpcCapture) -> Self {
+        let path = env::var("TOKEN_REGISTRY_PATH").unwrap_or_else(|_| "data/token_registry.json".to_string());
+        Self::new(rpc, rpc_capture).with_path(PathBuf::from(path))
+    }
+
+    /// Loads the tokens persisted at `path` (the known list wins over them) and persists there from now on.
+    pub fn with_path(mut self, path: PathBuf) -> Self {
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => match serde_json::from_str::<Vec<TokenInfo>>(&contents) {
+                Ok(persisted) => {
+                    let mut tokens = self.tokens.write().unwrap();
+                    println!("[[TOKEN REGISTRY]] Loaded {} tokens from {:?}", persisted.len(), path);
+                    for token in persisted {
+                        if !tokens.contains_key(&token.mint) {
+                            tokens.insert(token.mint.clone(), token);
+                        }
+                    }
+                }
+                Err(e) => eprintln!("[[TOKEN REGISTRY]] Ignoring unreadable {:?}: {}", path, e),
+            },
+            Err(_) => println!("[[TOKEN REGISTRY]] Nothing persisted at {:?} yet", path),
+        }
+        self.path = Some(path);
+        self
+    }
+
+    /// Only what is already resolved, never fetches
+    pub fn get(&self, mint: &str) -> Option<TokenInfo> {
+        self.tokens.read().unwrap().get(mint).cloned()
+    }
+
+    /// SOL and stables of the known list
+    pub fn is_quote(&self, mint: &str) -> bool {
+        self.quote_mints.contains(mint)
+    }
+
+    pub async fn resolve(&self, mint: &str) -> Result<TokenInfo, String> {
+        self.resolve_many(&[mint.to_string()]).await.remove(0)
+    }
+
+    /// Same order as `mints`. Mints not resolved yet are fetched in two batches: the mint accounts, then the Metaplex PDAs of
+    /// the mints without a Token-2022 metadata extension. Token-2022 mints of the known list are fetched once too, for their
+    /// extensions.
+    pub async fn resolve_many(&self, mints: &[String]) -> Vec<Result<TokenInfo, String>> {
+        let mut missing: Vec<String> = {
+            let tokens = self.tokens.read().unwrap();
+            mints.iter()
+                .filter(|mint| tokens.get(*mint).is_none_or(|token| token.token_program == TokenProgram::Token2022 && token.extensions.is_none()))
+                .cloned()
+                .collect()
+        };
+        missing.sort();
+        missing.dedup();
+
+        let mut errors: HashMap<String, String> = HashMap::new();
+        if !missing.is_empty() {
+            let mut resolved = Vec::new();
+            let mint_accounts = self.rpc_capture.get_account_infos(&self.rpc, "getAccountInfo", &missing).await;
+            for (mint, account) in missing.iter().zip(mint_accounts) {
+                match account.and_then(|account| unpack_mint(mint, account)) {
+                    Ok(token) => resolved.push(token),
+                    Err(e) => {
+                        errors.insert(mint.clone(), e);
+                    }
+                }
+            }
+
+            self.apply_metaplex_metadata(&mut resolved).await;
+
+            if !resolved.is_empty() {
+                let mut tokens = self.tokens.write().unwrap();
+                for mut token in resolved {
+                    // The known list keeps its names, only the on-chain state is taken
+                    if let Some(known) = tokens.get(&token.mint).filter(|known| known.metadata_source == MetadataSource::KnownList) {
+                        token = TokenInfo {
+                            supply: token.supply,
+                            extensions: token.extensions,
+                            risk_flags: token.risk_flags,
+                            ..known.clone()
+                        };
+                    }
+                    println!("[[TOKEN REGISTRY]] Resolved {} ({}) from {:?}", token.mint, token.display_symbol(), token.metadata_source);
+                    for flag in &token.risk_flags {
+                        println!("[[RUG FLAG]] {} ({}): {}", token.mint, token.display_symbol(), flag);
+                    }
+                    tokens.insert(token.mint.clone(), token);
+                }
+            }
+            self.persist().await;
+        }
+
+        mints.iter()
+            .map(|mint| match self.get(mint) {
+                Some(token) => Ok(token),
+                None => Err(errors.get(mint).cloned().unwrap_or_else(|| format!("mint {} not resolved", mint))),
+            })
+            .collect()
+    }
+
+    /// Name, symbol, uri, update authority and mutability of the tokens still `Missing` their metadata, from their Metaplex PDA
+    async fn apply_metaplex_metadata(&self, tokens: &mut [TokenInfo]) {
+        // (index in tokens, metadata PDA)
+        let pending: Vec<(usize, String)> = tokens.iter().enumerate()
+            .filter(|(_, token)| token.metadata_source == MetadataSource::Missing)
+            .filter_map(|(index, token)| metaplex_metadata_address(&token.mint).map(|address| (index, address)))
+            .collect();
+        if pending.is_empty() {
+            return;
+        }
+        let addresses: Vec<String> = pending.iter().map(|(_, address)| address.clone()).collect();
+
+        let accounts = self.rpc_capture.get_account_infos(&self.rpc, METADATA_ACCOUNT, &addresses).await;
+        for ((index, _), account) in pending.into_iter().zip(accounts) {
+            let token = &mut tokens[index];
+            let metadata = account
+                .and_then(|account| account.ok_or_else(|| "no Metaplex metadata account".to_string()))
+                .and_then(|account| account_data(&account))
+                .and_then(|data| Metadata::from_bytes(&data).map_err(|e| e.to_string()));
+            match metadata {
+                Ok(metadata) => {
+                    token.name = Some(trim_padding(&metadata.name));
+                    token.symbol = Some(trim_padding(&metadata.symbol));
+                    token.uri = Some(trim_padding(&metadata.uri));
+                    token.update_authority = Some(metadata.update_authority.to_string());
+                    token.is_mutable = Some(metadata.is_mutable);
+                    token.metadata_source = MetadataSource::Metaplex;
+                }
+                Err(e) => eprintln!("[[TOKEN REGISTRY]] No metadata for {}: {}", token.mint, e),
+            }
+        }
+    }
+
+    async fn persist(&self) {
+        let Some(ref path) = self.path else {
+            return;
+        };
+        let body = {
+            let tokens = self.tokens.read().unwrap();
+            let resolved: Vec<&TokenInfo> = tokens.values()
+                .filter(|token| token.metadata_source != MetadataSource::KnownList)
+                .collect();
+            serde_json::to_vec_pretty(&resolved)
+        };
+        match body {
+            Ok(body) => {
+                if let Some(parent) = path.parent() {
+                    let _ = tokio::fs::create_dir_all(parent).await;
+                }
+                if let Err(e) = tokio::fs::write(path, body).await {
+                    eprintln!("[[TOKEN REGISTRY]] Failed to persist to {:?}: {}", path, e);
+                }
+            }
+            Err(e) => eprintln!("[[TOKEN REGISTRY]] Failed to serialize the registry: {}", e),
+        }
+    }
+}
+
+/// Decimals, supply and token program of a mint account, plus its metadata when it carries a Token-2022 `TokenMetadata` extension
+fn unpack_mint(mint: &str, account: Option<RpcAccount<Vec<String>>>) -> Result<TokenInfo, String> {
+    let account = account.ok_or_else(|| format!("mint {} not found", mint))?;
+    let data = account_data(&account)?;
+    let mut token = TokenInfo {
+        mint: mint.to_string(),
+        name: None,
+        symbol: None,
+        decimals: 0,
+        uri: None,
+        update_authority: None,
+        is_mutable: None,
+        supply: None,
+        token_program: TokenProgram::SplToken,
+        metadata_source: MetadataSource::Missing,
+        extensions: None,
+        risk_flags: Vec::new(),
+    };
+
+    match account.owner.as_str() {
+        SPL_TOKEN_PROGRAM_ID => {
+            let unpacked = Mint::unpack(&data).map_err(|e| e.to_string())?;
+            token.decimals = unpacked.decimals;
+            token.supply = Some(unpacked.supply);
+        }
+        TOKEN_2022_PROGRAM_ID => {
+            let unpacked = Token2022Mint::unpack(&data).map_err(|e| e.to_string())?;
+            token.decimals = unpacked.mint.decimals;
+            token.supply = Some(unpacked.mint.supply);
+            token.token_program = TokenProgram::Token2022;
+            let extensions = unpacked.decode_extensions();
+            token.risk_flags = extensions.risk_flags();
+            token.extensions = Some(extensions);
+            if let Some(extension) = unpacked.extension(EXTENSION_TOKEN_METADATA) {
+                let metadata = TokenMetadata::unpack(extension).map_err(|e| e.to_string())?;
+                token.is_mutable = Some(metadata.update_authority.is_some());
+                token.name = Some(metadata.name);
+                token.symbol = Some(metadata.symbol);
+                token.uri = Some(metadata.uri);
+                token.update_authority = metadata.update_authority;
+                token.metadata_source = MetadataSource::Token2022Extension;
+            }
+        }
+        owner => return Err(format!("{} is owned by {}, not a token program", mint, owner)),
+    }
+    Ok(token)
+}
+
+fn account_data(account: &RpcAccount<Vec<String>>) -> Result<Vec<u8>, String> {
+    let payload = account.data.first().map(String::as_str).unwrap_or_default();
+    let encoding = account.data.get(1).map(String::as_str).unwrap_or("base64");
+    decode_payload(payload, encoding).map_err(|e| e.to_string())
+}
+
+// https://developers.metaplex.com/token-metadata
+fn metaplex_metadata_address(mint: &str) -> Option<String> {
+    let mint = Pubkey::from_str(mint).ok()?;
+    let program_id = mpl_token_metadata::ID;
+    let (address, _) = Pubkey::find_program_address(&[b"metadata", program_id.as_ref(), mint.as_ref()], &program_id);
+    Some(address.to_string())
+}
+
+/// Metaplex pads name, symbol and uri with null bytes up to their max length
+fn trim_padding(value: &str) -> String {
+    value.trim_end_matches('\0').trim().to_string()
+}